@@ -2,7 +2,9 @@ use criterion::*;
 use mimalloc::MiMalloc;
 
 use rand::Rng;
-use scalable_cuckoo_filter::ScalableCuckooFilter;
+use scalable_cuckoo_filter::{
+    AltIndexScheme, ScalableCuckooFilter, ScalableCuckooFilterBuilder, StorageBackend,
+};
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -24,6 +26,25 @@ fn insert(c: &mut Criterion) {
     }
 }
 
+fn buffered_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("buffered_insert");
+
+    for precision in [0.1, 0.001, 0.0001, 0.00001] {
+        let mut filter = ScalableCuckooFilter::new(1_000_000, precision);
+        let mut i = 0;
+
+        group.bench_function(BenchmarkId::new("precision", precision), |b| {
+            b.iter(|| {
+                let mut inserter = filter.buffered_inserter(1024);
+                for _ in 0..1024 {
+                    inserter.insert(&i);
+                    i += 1;
+                }
+            })
+        });
+    }
+}
+
 fn contains(c: &mut Criterion) {
     let mut group = c.benchmark_group("contains");
 
@@ -48,5 +69,124 @@ fn contains(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, insert, contains);
+// Guards the lookup hot path: misses are where branch mispredictions and cache misses hurt.
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    const ITEMS: u64 = 1_000_000;
+
+    for backend in [
+        StorageBackend::Buckets,
+        StorageBackend::Blocked,
+        StorageBackend::Morton,
+    ] {
+        // Large enough for a single layer with any backend.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(4 * ITEMS as usize)
+            .false_positive_probability(0.001)
+            .storage_backend(backend)
+            .finish();
+        for i in 0..ITEMS {
+            filter.insert(&i);
+        }
+
+        let mut i = 0;
+        group.bench_function(BenchmarkId::new("hit", format!("{backend:?}")), |b| {
+            b.iter(|| {
+                i = (i + 1) % ITEMS;
+                black_box(filter.contains(&i))
+            })
+        });
+        let mut i = ITEMS;
+        group.bench_function(BenchmarkId::new("miss", format!("{backend:?}")), |b| {
+            b.iter(|| {
+                i += 1;
+                black_box(filter.contains(&i))
+            })
+        });
+    }
+}
+
+// Lookups of the `Hashed` scheme search the primary bucket before hashing the fingerprint,
+// which speeds up hits at the cost of misses. The other schemes search both buckets without
+// branches, so `Mixed` (the default) shouldn't change.
+fn alt_index_scheme(c: &mut Criterion) {
+    let mut group = c.benchmark_group("alt_index_scheme");
+    const ITEMS: u64 = 1_000_000;
+
+    for scheme in [AltIndexScheme::Hashed, AltIndexScheme::Mixed] {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(2 * ITEMS as usize)
+            .false_positive_probability(0.001)
+            .alt_index_scheme(scheme)
+            .finish();
+        for i in 0..ITEMS {
+            filter.insert(&i);
+        }
+
+        let mut i = 0;
+        group.bench_function(BenchmarkId::new("hit", format!("{scheme:?}")), |b| {
+            b.iter(|| {
+                i = (i + 1) % ITEMS;
+                black_box(filter.contains(&i))
+            })
+        });
+        let mut i = ITEMS;
+        group.bench_function(BenchmarkId::new("miss", format!("{scheme:?}")), |b| {
+            b.iter(|| {
+                i += 1;
+                black_box(filter.contains(&i))
+            })
+        });
+    }
+}
+
+// Compares the bucket scans: the `simd` feature replaces the SWAR comparison of a bucket with
+// a vector comparison. The filters fit in the CPU caches so that the scans dominate.
+// Run `cargo bench --bench scalability -- bucket_scan --save-baseline swar`, and then
+// the same with `--features simd` and `--baseline swar` to see the difference.
+fn bucket_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bucket_scan");
+    let path = if cfg!(feature = "simd") {
+        "simd"
+    } else {
+        "swar"
+    };
+    const ITEMS: u64 = 10_000;
+
+    for precision in [0.1, 0.001, 0.00001] {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(2 * ITEMS as usize)
+            .false_positive_probability(precision)
+            .finish();
+        for i in 0..ITEMS {
+            filter.insert(&i);
+        }
+
+        let id = format!("{path}/{}bits", filter.layers()[0].fingerprint_bitwidth());
+        let mut i = 0;
+        group.bench_function(BenchmarkId::new("hit", &id), |b| {
+            b.iter(|| {
+                i = (i + 1) % ITEMS;
+                black_box(filter.contains(&i))
+            })
+        });
+        let mut i = ITEMS;
+        group.bench_function(BenchmarkId::new("miss", &id), |b| {
+            b.iter(|| {
+                i += 1;
+                black_box(filter.contains(&i))
+            })
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    insert,
+    buffered_insert,
+    contains,
+    lookup,
+    alt_index_scheme,
+    bucket_scan
+);
 criterion_main!(benches);
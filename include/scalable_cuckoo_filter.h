@@ -0,0 +1,85 @@
+#ifndef SCALABLE_CUCKOO_FILTER_H
+#define SCALABLE_CUCKOO_FILTER_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Opaque handle of a filter.
+typedef struct ScfFilter ScfFilter;
+
+// Makes a new filter, or returns `NULL` if the parameters are invalid.
+//
+// The returned filter must be freed by [`scf_free`].
+struct ScfFilter *scf_new(size_t initial_capacity, double false_positive_probability);
+
+// Frees a filter returned by [`scf_new`] or [`scf_deserialize`].
+//
+// # Safety
+//
+// `filter` must be `NULL` (which does nothing) or a filter that hasn't been freed.
+void scf_free(struct ScfFilter *filter);
+
+// Inserts the `len` bytes at `item` into a filter.
+//
+// # Safety
+//
+// `filter` must be a valid filter, and `item` must point to `len` readable bytes
+// (it may be `NULL` if `len` is zero).
+void scf_insert(struct ScfFilter *filter, const uint8_t *item, size_t len);
+
+// Returns `true` if a filter may contain the `len` bytes at `item`.
+//
+// # Safety
+//
+// `filter` must be a valid filter, and `item` must point to `len` readable bytes
+// (it may be `NULL` if `len` is zero).
+bool scf_contains(const struct ScfFilter *filter, const uint8_t *item, size_t len);
+
+// Removes the `len` bytes at `item` from a filter, returning `true` if an entry has been removed.
+//
+// # Safety
+//
+// `filter` must be a valid filter, and `item` must point to `len` readable bytes
+// (it may be `NULL` if `len` is zero).
+bool scf_remove(struct ScfFilter *filter, const uint8_t *item, size_t len);
+
+// Returns the approximate number of items in a filter.
+//
+// # Safety
+//
+// `filter` must be a valid filter.
+uint64_t scf_len(const struct ScfFilter *filter);
+
+// Serializes a filter (as `ScalableCuckooFilter::to_bytes` does), storing the bytes and their
+// length in `*bytes` and `*len`.
+//
+// Returns `false` if the filter can't be serialized, leaving `*bytes` and `*len` as they are.
+// Otherwise the bytes must be freed by [`scf_free_bytes`].
+//
+// # Safety
+//
+// `filter` must be a valid filter, and `bytes` and `len` must be writable.
+bool scf_serialize(const struct ScfFilter *filter, uint8_t **bytes, size_t *len);
+
+// Frees the bytes returned by [`scf_serialize`].
+//
+// # Safety
+//
+// `bytes` must be `NULL` (which does nothing) or bytes returned by [`scf_serialize`] that
+// haven't been freed, and `len` must be their length.
+void scf_free_bytes(uint8_t *bytes, size_t len);
+
+// Deserializes a filter from the `len` bytes at `bytes` written by [`scf_serialize`]
+// (or `ScalableCuckooFilter::to_bytes`), or returns `NULL` if they are malformed.
+//
+// The returned filter must be freed by [`scf_free`].
+//
+// # Safety
+//
+// `bytes` must point to `len` readable bytes (it may be `NULL` if `len` is zero).
+struct ScfFilter *scf_deserialize(const uint8_t *bytes, size_t len);
+
+#endif  /* SCALABLE_CUCKOO_FILTER_H */
@@ -0,0 +1,187 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// Allocator of the bit arrays that hold the fingerprints of a filter.
+///
+/// Any [`GlobalAlloc`] that is `Send + Sync` implements this trait, so an allocator serving
+/// hugepages or tracking memory usage can be plugged in via
+/// [`ScalableCuckooFilterBuilder::allocator`].
+/// Only the large bit arrays are allocated with it; small bookkeeping data
+/// (e.g., stashes and layer lists) still comes from the global allocator.
+///
+/// # Safety
+///
+/// `alloc_zeroed` must return either null or a pointer to `layout.size()` zeroed bytes aligned
+/// to `layout.align()`, which stay valid until passed to `dealloc` of the same allocator.
+///
+/// [`ScalableCuckooFilterBuilder::allocator`]: crate::ScalableCuckooFilterBuilder::allocator
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+/// use std::alloc::{GlobalAlloc, Layout, System};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Default)]
+/// struct Tracked(AtomicUsize);
+/// unsafe impl GlobalAlloc for Tracked {
+///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+///         self.0.fetch_add(layout.size(), Ordering::SeqCst);
+///         System.alloc(layout)
+///     }
+///
+///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+///         self.0.fetch_sub(layout.size(), Ordering::SeqCst);
+///         System.dealloc(ptr, layout)
+///     }
+/// }
+///
+/// let tracked = Arc::new(Tracked::default());
+/// let mut filter: ScalableCuckooFilter<usize> = ScalableCuckooFilterBuilder::new()
+///     .initial_capacity(1000)
+///     .allocator(tracked.clone())
+///     .finish();
+/// filter.insert(&42);
+/// assert!(tracked.0.load(Ordering::SeqCst) > 0);
+///
+/// drop(filter);
+/// assert_eq!(tracked.0.load(Ordering::SeqCst), 0);
+/// ```
+pub unsafe trait AllocBytes: Send + Sync {
+    /// Allocates zeroed memory as described by `layout`, returning null on failure.
+    ///
+    /// # Safety
+    ///
+    /// `layout` must have a non-zero size.
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8;
+
+    /// Deallocates the memory at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc_zeroed` of this allocator with the same `layout`.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout);
+}
+unsafe impl<A> AllocBytes for A
+where
+    A: GlobalAlloc + Send + Sync,
+{
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        GlobalAlloc::alloc_zeroed(self, layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        GlobalAlloc::dealloc(self, ptr, layout)
+    }
+}
+
+/// Allocator of a filter and how it prepares the pages of new bit arrays.
+#[derive(Clone, Default)]
+pub(crate) struct Allocator {
+    // `None` means the global allocator.
+    alloc: Option<Arc<dyn AllocBytes>>,
+    pre_touch_pages: bool,
+    transparent_hugepages: bool,
+}
+impl Allocator {
+    pub fn new(alloc: Arc<dyn AllocBytes>) -> Self {
+        Allocator {
+            alloc: Some(alloc),
+            ..Allocator::default()
+        }
+    }
+
+    pub fn with_pages(mut self, pre_touch_pages: bool, transparent_hugepages: bool) -> Self {
+        self.pre_touch_pages = pre_touch_pages;
+        self.transparent_hugepages = transparent_hugepages;
+        self
+    }
+
+    /// Allocates zeroed memory for `len` words, which must be positive.
+    ///
+    /// The words start at a cache line boundary, so that the blocks of
+    /// `StorageBackend::Blocked` each occupy exactly one cache line.
+    pub fn alloc_words(&self, len: usize) -> NonNull<u64> {
+        let layout = words_layout(len);
+        // SAFETY: `len` is positive, so the layout isn't zero-sized.
+        let ptr = unsafe {
+            match &self.alloc {
+                Some(alloc) => alloc.alloc_zeroed(layout),
+                None => std::alloc::alloc_zeroed(layout),
+            }
+        };
+        let Some(ptr) = NonNull::new(ptr.cast::<u64>()) else {
+            std::alloc::handle_alloc_error(layout);
+        };
+        if self.transparent_hugepages {
+            advise_hugepages(ptr.as_ptr().cast(), layout.size());
+        }
+        if self.pre_touch_pages {
+            // Zeroed memory may be mapped lazily, so a page is only committed once written.
+            // 4 KiB is the smallest page size of the supported platforms.
+            for i in (0..len).step_by(4096 / 8) {
+                // SAFETY: `i < len`, and the words are zero, so writing zero changes nothing.
+                unsafe { ptr.as_ptr().add(i).write_volatile(0) };
+            }
+        }
+        ptr
+    }
+
+    /// Deallocates `len` words allocated by `alloc_words`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc_words(len)` of this allocator.
+    pub unsafe fn dealloc_words(&self, ptr: NonNull<u64>, len: usize) {
+        let layout = words_layout(len);
+        match &self.alloc {
+            Some(alloc) => alloc.dealloc(ptr.as_ptr().cast(), layout),
+            None => std::alloc::dealloc(ptr.as_ptr().cast(), layout),
+        }
+    }
+}
+
+/// Size of the cache lines that the words are aligned to.
+const CACHE_LINE_BYTES: usize = 64;
+
+fn words_layout(len: usize) -> Layout {
+    Layout::array::<u64>(len)
+        .and_then(|layout| layout.align_to(CACHE_LINE_BYTES))
+        .expect("too many words")
+}
+
+impl fmt::Debug for Allocator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let alloc = if self.alloc.is_some() { ".." } else { "Global" };
+        f.debug_struct("Allocator")
+            .field("alloc", &format_args!("{alloc}"))
+            .field("pre_touch_pages", &self.pre_touch_pages)
+            .field("transparent_hugepages", &self.transparent_hugepages)
+            .finish()
+    }
+}
+
+/// Asks the kernel to back the whole pages in `len` bytes from `ptr` with transparent hugepages.
+///
+/// This is only advice, so failures are ignored.
+#[cfg(target_os = "linux")]
+fn advise_hugepages(ptr: *mut u8, len: usize) {
+    // SAFETY: `sysconf` has no preconditions.
+    let page_size = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        n if n > 0 => n as usize,
+        _ => return,
+    };
+    let start = (ptr as usize).next_multiple_of(page_size);
+    let end = (ptr as usize + len) / page_size * page_size;
+    if start < end {
+        // SAFETY: The pages are within the allocation, and the advice doesn't change their contents.
+        unsafe { libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_HUGEPAGE) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_hugepages(_ptr: *mut u8, _len: usize) {}
@@ -0,0 +1,274 @@
+use rand::Rng;
+use std::hash::{Hash, Hasher};
+
+#[cfg(feature = "mmap")]
+use crate::MmapCuckooFilter;
+#[cfg(feature = "xor_filter")]
+use crate::XorFilter;
+#[cfg(feature = "concurrent")]
+use crate::{AtomicScalableCuckooFilter, ConcurrentScalableCuckooFilter};
+use crate::{
+    CapacityError, CuckooFilter, FixedCuckooFilter, FrozenScalableCuckooFilter,
+    ScalableCuckooFilter, ScalableCuckooFilterView,
+};
+
+/// Set of items that answers membership queries approximately.
+///
+/// There are no false negatives: `contains` returns `true` for every inserted item
+/// (unless it has been removed). It may also return `true` for items that haven't been inserted,
+/// with a probability depending on the implementation and its parameters.
+///
+/// This is implemented by the filters of this crate, so that code can be generic over them.
+/// Filters that can be modified implement [`ApproximateSetMut`] as well.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{ApproximateSet, ScalableCuckooFilter};
+///
+/// fn count_known<S: ApproximateSet<str>>(set: &S, items: &[&str]) -> usize {
+///     items.iter().filter(|&&item| set.contains(item)).count()
+/// }
+///
+/// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+/// filter.insert("foo");
+/// assert_eq!(count_known(&filter, &["foo", "bar"]), 1);
+/// assert_eq!(count_known(&filter.freeze(), &["foo", "bar"]), 1);
+/// ```
+pub trait ApproximateSet<T: ?Sized> {
+    /// Returns `true` if this set may contain `item`, otherwise `false`.
+    fn contains(&self, item: &T) -> bool;
+
+    /// Returns the approximate number of items inserted in this set.
+    fn len(&self) -> u64;
+
+    /// Returns `true` if this set contains no items, otherwise `false`.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// [`ApproximateSet`] that items can be inserted into and removed from.
+///
+/// This is object safe, so filters can be switched at runtime behind a
+/// `Box<dyn ApproximateSetMut<T>>`.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{ApproximateSetMut, CuckooFilter, ScalableCuckooFilter};
+///
+/// let mut sets: Vec<Box<dyn ApproximateSetMut<str>>> = vec![
+///     Box::new(ScalableCuckooFilter::new(1000, 0.001)),
+///     Box::new(CuckooFilter::new(1000, 0.001)),
+/// ];
+/// for set in &mut sets {
+///     set.insert("foo").unwrap();
+///     assert!(set.contains("foo"));
+///     assert!(set.remove("foo"));
+///     assert!(set.is_empty());
+/// }
+/// ```
+pub trait ApproximateSetMut<T: ?Sized>: ApproximateSet<T> {
+    /// Inserts `item` into this set.
+    ///
+    /// Sets that grow as necessary always succeed. Those of a fixed capacity return
+    /// `Err(CapacityError)` if they are full, leaving themselves unchanged.
+    fn insert(&mut self, item: &T) -> Result<(), CapacityError>;
+
+    /// Removes `item` from this set, returning `true` if an entry matching it has been removed.
+    ///
+    /// Only inserted items should be removed, otherwise an entry of another item
+    /// that happens to match may be removed, which causes a false negative.
+    fn remove(&mut self, item: &T) -> bool;
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ApproximateSet<T>
+    for ScalableCuckooFilter<T, H, R>
+{
+    fn contains(&self, item: &T) -> bool {
+        ScalableCuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        ScalableCuckooFilter::len(self)
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ApproximateSetMut<T>
+    for ScalableCuckooFilter<T, H, R>
+{
+    fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        ScalableCuckooFilter::insert(self, item);
+        Ok(())
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        ScalableCuckooFilter::remove(self, item)
+    }
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ApproximateSet<T> for CuckooFilter<T, H, R> {
+    fn contains(&self, item: &T) -> bool {
+        CuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        CuckooFilter::len(self)
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ApproximateSetMut<T> for CuckooFilter<T, H, R> {
+    fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        CuckooFilter::insert(self, item)
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        CuckooFilter::remove(self, item)
+    }
+}
+
+impl<const BYTES: usize, T: Hash + ?Sized, H: Hasher + Clone> ApproximateSet<T>
+    for FixedCuckooFilter<BYTES, T, H>
+{
+    fn contains(&self, item: &T) -> bool {
+        FixedCuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        FixedCuckooFilter::len(self)
+    }
+}
+impl<const BYTES: usize, T: Hash + ?Sized, H: Hasher + Clone> ApproximateSetMut<T>
+    for FixedCuckooFilter<BYTES, T, H>
+{
+    fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        FixedCuckooFilter::insert(self, item)
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        FixedCuckooFilter::remove(self, item)
+    }
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone> ApproximateSet<T> for FrozenScalableCuckooFilter<T, H> {
+    fn contains(&self, item: &T) -> bool {
+        FrozenScalableCuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        FrozenScalableCuckooFilter::len(self)
+    }
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone> ApproximateSet<T> for ScalableCuckooFilterView<'_, T, H> {
+    fn contains(&self, item: &T) -> bool {
+        ScalableCuckooFilterView::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        ScalableCuckooFilterView::len(self)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T: Hash + ?Sized, H: Hasher + Clone> ApproximateSet<T> for MmapCuckooFilter<T, H> {
+    fn contains(&self, item: &T) -> bool {
+        MmapCuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        MmapCuckooFilter::len(self)
+    }
+}
+
+#[cfg(feature = "xor_filter")]
+impl<T: Hash + ?Sized, H: Hasher + Clone> ApproximateSet<T> for XorFilter<T, H> {
+    fn contains(&self, item: &T) -> bool {
+        XorFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        XorFilter::len(self)
+    }
+}
+
+#[cfg(feature = "concurrent")]
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ApproximateSet<T>
+    for ConcurrentScalableCuckooFilter<T, H, R>
+{
+    fn contains(&self, item: &T) -> bool {
+        ConcurrentScalableCuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        ConcurrentScalableCuckooFilter::len(self)
+    }
+}
+#[cfg(feature = "concurrent")]
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ApproximateSetMut<T>
+    for ConcurrentScalableCuckooFilter<T, H, R>
+{
+    fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        ConcurrentScalableCuckooFilter::insert(self, item);
+        Ok(())
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        ConcurrentScalableCuckooFilter::remove(self, item)
+    }
+}
+
+#[cfg(feature = "concurrent")]
+impl<T: Hash + ?Sized, H: Hasher + Clone> ApproximateSet<T> for AtomicScalableCuckooFilter<T, H> {
+    fn contains(&self, item: &T) -> bool {
+        AtomicScalableCuckooFilter::contains(self, item)
+    }
+
+    fn len(&self) -> u64 {
+        AtomicScalableCuckooFilter::len(self)
+    }
+}
+#[cfg(feature = "concurrent")]
+impl<T: Hash + ?Sized, H: Hasher + Clone> ApproximateSetMut<T>
+    for AtomicScalableCuckooFilter<T, H>
+{
+    fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        AtomicScalableCuckooFilter::insert(self, item);
+        Ok(())
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        AtomicScalableCuckooFilter::remove(self, item)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check<S: ApproximateSetMut<u64>>(mut set: S) {
+        assert!(set.is_empty());
+        for i in 0..100 {
+            set.insert(&i).unwrap();
+        }
+        assert_eq!(set.len(), 100);
+        assert!((0..100).all(|i| set.contains(&i)));
+        assert!(set.remove(&42));
+        assert!(!set.contains(&42));
+        assert_eq!(set.len(), 99);
+    }
+
+    #[test]
+    fn it_works() {
+        check(ScalableCuckooFilter::new(10, 0.001));
+        check(CuckooFilter::new(1000, 0.001));
+        check(FixedCuckooFilter::<4096, u64>::new(0.001));
+        #[cfg(feature = "concurrent")]
+        check(ConcurrentScalableCuckooFilter::new(10, 0.001));
+        #[cfg(feature = "concurrent")]
+        check(AtomicScalableCuckooFilter::new(10, 0.001));
+
+        let mut filter = FixedCuckooFilter::<64, u64>::new(0.5);
+        let inserted = (0..1000).take_while(|i| ApproximateSetMut::insert(&mut filter, i).is_ok());
+        assert!(inserted.count() < 1000);
+    }
+}
@@ -0,0 +1,160 @@
+use std::hash::{Hash, Hasher};
+use std::io;
+
+use rand::Rng;
+use rkyv::rancor::{Fallible, Source};
+use rkyv::ser::{Allocator, Writer};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::{Archive, Deserialize, Place, Portable, Serialize};
+
+use crate::{HasherKeys, ScalableCuckooFilter, ScalableCuckooFilterView};
+
+/// Archived form of [`ScalableCuckooFilter`] made by [rkyv].
+///
+/// This holds the bytes of [`ScalableCuckooFilter::to_bytes`], so the filter can be looked up
+/// in place via [`view`] without deserializing (and thus copying) it.
+///
+/// [rkyv]: https://docs.rs/rkyv
+/// [`view`]: ArchivedScalableCuckooFilter::view
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{
+///     ArchivedScalableCuckooFilter, DefaultHasher, ScalableCuckooFilter,
+///     ScalableCuckooFilterBuilder,
+/// };
+///
+/// let mut filter = ScalableCuckooFilterBuilder::new()
+///     .for_type::<str>()
+///     .finish();
+/// filter.insert("foo");
+///
+/// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&filter).unwrap();
+/// let archived =
+///     rkyv::access::<ArchivedScalableCuckooFilter, rkyv::rancor::Error>(&bytes).unwrap();
+/// let view = archived.view::<str, DefaultHasher>().unwrap();
+/// assert!(view.contains("foo"));
+///
+/// let filter: ScalableCuckooFilter<str> =
+///     rkyv::deserialize::<_, rkyv::rancor::Error>(archived).unwrap();
+/// assert!(filter.contains("foo"));
+/// ```
+#[derive(Portable, rkyv::bytecheck::CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[rkyv(crate = rkyv)]
+#[repr(transparent)]
+pub struct ArchivedScalableCuckooFilter {
+    bytes: ArchivedVec<u8>,
+}
+impl ArchivedScalableCuckooFilter {
+    /// Returns the bytes of the filter in the format of [`ScalableCuckooFilter::to_bytes`].
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    /// Makes a view of the archived filter, hashing items as by [`ScalableCuckooFilterView::new`].
+    ///
+    /// Use [`ScalableCuckooFilterView::with_hasher`] with [`as_bytes`] for other hashers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archived bytes are malformed.
+    ///
+    /// [`as_bytes`]: ArchivedScalableCuckooFilter::as_bytes
+    pub fn view<T, H>(&self) -> io::Result<ScalableCuckooFilterView<'_, T, H>>
+    where
+        T: Hash + ?Sized,
+        H: Hasher + Clone + Default + HasherKeys,
+    {
+        ScalableCuckooFilterView::new(self.as_bytes())
+    }
+}
+
+/// Resolver of [`ArchivedScalableCuckooFilter`].
+pub struct ScalableCuckooFilterResolver {
+    bytes: VecResolver,
+    len: usize,
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> Archive for ScalableCuckooFilter<T, H, R> {
+    type Archived = ArchivedScalableCuckooFilter;
+    type Resolver = ScalableCuckooFilterResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        rkyv::munge::munge!(let ArchivedScalableCuckooFilter { bytes } = out);
+        ArchivedVec::<u8>::resolve_from_len(resolver.len, resolver.bytes, bytes);
+    }
+}
+
+impl<T, H, R, S> Serialize<S> for ScalableCuckooFilter<T, H, R>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + HasherKeys,
+    R: Rng,
+    S: Fallible + Allocator + Writer + ?Sized,
+    S::Error: Source,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let bytes = self.to_bytes().map_err(S::Error::new)?;
+        Ok(ScalableCuckooFilterResolver {
+            bytes: ArchivedVec::serialize_from_slice(&bytes, serializer)?,
+            len: bytes.len(),
+        })
+    }
+}
+
+impl<T, H, R, D> Deserialize<ScalableCuckooFilter<T, H, R>, D> for ArchivedScalableCuckooFilter
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + Default + HasherKeys,
+    R: Rng + Default,
+    D: Fallible + ?Sized,
+    D::Error: Source,
+{
+    fn deserialize(&self, _: &mut D) -> Result<ScalableCuckooFilter<T, H, R>, D::Error> {
+        ScalableCuckooFilter::from_bytes(self.as_bytes()).map_err(D::Error::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rkyv::rancor::Error;
+
+    use super::*;
+    use crate::{DefaultHasher, ScalableCuckooFilterBuilder};
+
+    #[test]
+    fn it_works() {
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        #[rkyv(crate = rkyv)]
+        struct Asset {
+            name: String,
+            filter: ScalableCuckooFilter<u64>,
+        }
+
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .for_type::<u64>()
+            .finish();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        let asset = Asset {
+            name: "foo".to_owned(),
+            filter,
+        };
+        let bytes = rkyv::to_bytes::<Error>(&asset).unwrap();
+
+        let archived = rkyv::access::<ArchivedAsset, Error>(&bytes).unwrap();
+        assert_eq!(archived.name, "foo");
+        assert_eq!(archived.filter.as_bytes(), asset.filter.to_bytes().unwrap());
+        let view = archived.filter.view::<u64, DefaultHasher>().unwrap();
+        assert_eq!(view.len(), 1000);
+        assert!((0..1000).all(|i| view.contains(&i)));
+
+        let asset: Asset = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert_eq!(asset.filter.len(), 1000);
+        assert!((0..1000).all(|i| asset.filter.contains(&i)));
+    }
+}
@@ -0,0 +1,243 @@
+//! Asynchronous writing and reading of filters in the format of `write_to` (see `codec`).
+//!
+//! The bit arrays, which are most of a large filter, are converted and transferred in chunks
+//! of `CHUNK_WORDS` words, and the task yields to the runtime after each chunk.
+//! The other parts are parsed by the synchronous functions of `write_to` and `read_from`
+//! from bytes buffered by `Source`.
+use std::hash::Hasher;
+use std::io::{self, Read};
+
+use rand::Rng;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::bits::Bits;
+use crate::codec::{self, BitsEncoding};
+use crate::cuckoo_filter::CuckooFilter;
+use crate::hashers::HasherKeys;
+use crate::raw::ScalableCuckooFilter;
+
+/// Number of words of the bit arrays converted at once (1 MiB).
+const CHUNK_WORDS: usize = 1 << 17;
+
+pub async fn write<W, H, R>(filter: &ScalableCuckooFilter<H, R>, mut writer: W) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    H: Hasher + Clone + HasherKeys,
+    R: Rng,
+{
+    let mut w = codec::ChecksumWriter::new(Vec::new());
+    filter.write_params_to(&mut w, BitsEncoding::Raw)?;
+    for layer in &filter.filters {
+        layer.write_head_to(&mut w)?;
+        let bits = layer.bit_array();
+        bits.write_len_to(&mut w)?;
+        for start in (0..bits.words_len()).step_by(CHUNK_WORDS) {
+            bits.write_words_to(&mut w, start..(start + CHUNK_WORDS).min(bits.words_len()))?;
+            let bytes = w.get_mut();
+            writer.write_all(bytes).await?;
+            bytes.clear();
+            tokio::task::yield_now().await;
+        }
+    }
+    w.finish()?;
+    writer.write_all(w.get_mut()).await?;
+    writer.flush().await
+}
+
+pub async fn read<Rd, H, R>(
+    reader: Rd,
+    make_hasher: impl FnOnce(Option<(u64, u64)>) -> H,
+    rng: R,
+) -> io::Result<ScalableCuckooFilter<H, R>>
+where
+    Rd: AsyncRead + Unpin,
+    H: Hasher + Clone,
+    R: Rng,
+{
+    let mut source = Source {
+        reader,
+        buf: Vec::new(),
+        checksum: crc32fast::Hasher::new(),
+    };
+    let params = source
+        .parse(|r| ScalableCuckooFilter::<H, R>::read_params(r))
+        .await?;
+    let mut filters = Vec::new();
+    for _ in 0..params.layers {
+        let mut layer = source
+            .parse(|r| CuckooFilter::read_with(r, Bits::read_len_from))
+            .await?;
+        let bits = layer.bit_array_mut();
+        let mut i = 0;
+        while i < bits.words_len() {
+            let run = source
+                .parse(|r| bits.read_run_from(r, i, params.encoding))
+                .await?;
+            for start in run.clone().step_by(CHUNK_WORDS) {
+                let words = start..(start + CHUNK_WORDS).min(run.end);
+                source.fill(bits.words_byte_len(words.clone())).await?;
+                source
+                    .parse(|r| bits.read_words_from(r, words.clone()))
+                    .await?;
+                tokio::task::yield_now().await;
+            }
+            i = run.end;
+        }
+        filters.push(layer);
+    }
+    let actual = source.checksum.clone().finalize();
+    codec::verify_checksum(source.parse(|r| codec::read_u32(r)).await?, actual)?;
+    ScalableCuckooFilter::from_params(params, filters, make_hasher, rng)
+}
+
+/// Buffer of the bytes read from an `AsyncRead`, which are parsed by synchronous functions.
+///
+/// No more bytes than parsed are read, so the reader is left right after the filter.
+struct Source<Rd> {
+    reader: Rd,
+    buf: Vec<u8>,
+    // Checksum of the parsed bytes.
+    checksum: crc32fast::Hasher,
+}
+impl<Rd: AsyncRead + Unpin> Source<Rd> {
+    /// Parses the buffered bytes by `parse`, reading the bytes it misses until it succeeds.
+    ///
+    /// The parsed bytes are removed from the buffer.
+    async fn parse<T, F>(&mut self, mut parse: F) -> io::Result<T>
+    where
+        F: FnMut(&mut Probe) -> io::Result<T>,
+    {
+        loop {
+            let mut probe = Probe {
+                bytes: &self.buf,
+                missing: 0,
+            };
+            match parse(&mut probe) {
+                Ok(value) => {
+                    let parsed = self.buf.len() - probe.bytes.len();
+                    self.checksum.update(&self.buf[..parsed]);
+                    self.buf.drain(..parsed);
+                    return Ok(value);
+                }
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && probe.missing > 0 => {
+                    let missing = probe.missing;
+                    self.fill(self.buf.len() + missing).await?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Reads bytes until `len` bytes are buffered.
+    async fn fill(&mut self, len: usize) -> io::Result<()> {
+        let buffered = self.buf.len();
+        if len > buffered {
+            self.buf.resize(len, 0);
+            if let Err(e) = self.reader.read_exact(&mut self.buf[buffered..]).await {
+                self.buf.truncate(buffered);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reader of buffered bytes that records the number of bytes requested after running out.
+struct Probe<'a> {
+    bytes: &'a [u8],
+    missing: usize,
+}
+impl Read for Probe<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.bytes.is_empty() {
+            self.missing = buf.len();
+        }
+        Read::read(&mut self.bytes, buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::ReadBuf;
+
+    use super::*;
+    use crate::{DefaultHasher, ScalableCuckooFilterBuilder};
+
+    /// Reader that reads at most seven bytes at once.
+    struct Trickle<'a>(&'a [u8]);
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let n = self.0.len().min(buf.remaining()).min(7);
+            buf.put_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn it_works() {
+        // The first layer has more bits than a chunk.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1_000_000)
+            .finish_raw();
+        for i in 0..1000u64 {
+            filter.insert(i);
+        }
+        filter.reserve(2_000_000);
+        filter.insert(1000);
+        assert!(filter.filters[0].bit_array().words_len() > CHUNK_WORDS);
+
+        let mut bytes = Vec::new();
+        block_on(filter.write_to_async(&mut bytes)).unwrap();
+        assert_eq!(bytes, filter.to_bytes().unwrap());
+
+        let mut reader = &bytes[..];
+        let read = block_on(ScalableCuckooFilter::<DefaultHasher>::read_from_async(
+            &mut reader,
+        ))
+        .unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+        assert!((0..=1000).all(|i| read.contains(i)));
+
+        // Compressed bytes are read as well, and readers may return fewer bytes than requested.
+        let compressed = filter.to_compressed_bytes().unwrap();
+        let read = block_on(ScalableCuckooFilter::read_from_with_async(
+            Trickle(&compressed),
+            filter.hasher.clone(),
+            rand::thread_rng(),
+        ))
+        .unwrap();
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+
+        let mut corrupted = bytes.clone();
+        corrupted[bytes.len() / 2] ^= 1;
+        let e = block_on(ScalableCuckooFilter::<DefaultHasher>::read_from_async(
+            &corrupted[..],
+        ))
+        .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        for len in [3, 100, bytes.len() / 2, bytes.len() - 1] {
+            let e = block_on(ScalableCuckooFilter::<DefaultHasher>::read_from_async(
+                Trickle(&bytes[..len]),
+            ))
+            .unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
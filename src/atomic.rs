@@ -0,0 +1,468 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{self, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::scalable_cuckoo_filter::default_hasher;
+use crate::DefaultHasher;
+
+const MAX_LAYERS: usize = 48;
+const MAX_KICKS: usize = 512;
+
+/// Scalable Cuckoo Filter whose lookups never take a lock.
+///
+/// Fingerprints are stored in `AtomicU64` words, one word per bucket, and the layers are
+/// published through `OnceLock`s. Thus, `contains` runs concurrently with writers without any
+/// locking, while `insert` and `remove` are serialized by an internal mutex.
+///
+/// This is available only if the `concurrent` feature is enabled.
+///
+/// # Consistency
+///
+/// - An item becomes visible to readers when `insert` stores its fingerprint into a bucket
+///   (`Release`), and a reader that observes it (`Acquire`) also observes every layer
+///   published before.
+/// - A `contains` that happens after `insert` returned always reports the item
+///   (i.e., there are no false negatives even while other items are being inserted).
+/// - Relocating fingerprints between buckets is guarded by a per-layer sequence counter
+///   (seqlock). A reader that doesn't find an item re-validates the counter and retries the
+///   lookup if a relocation overlapped with it. Readers may therefore spin while a writer
+///   is relocating entries, but they never block on the writer's mutex.
+/// - A `contains` that overlaps with `remove` of the same item may return either result.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::AtomicScalableCuckooFilter;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let filter = Arc::new(AtomicScalableCuckooFilter::new(1000, 0.001));
+/// let writer = {
+///     let filter = Arc::clone(&filter);
+///     thread::spawn(move || {
+///         for i in 0..10_000 {
+///             filter.insert(&i);
+///         }
+///     })
+/// };
+/// let _ = filter.contains(&42); // Doesn't wait for the writer.
+/// writer.join().unwrap();
+///
+/// assert_eq!(filter.len(), 10_000);
+/// assert!(filter.contains(&42));
+/// ```
+#[derive(Debug)]
+pub struct AtomicScalableCuckooFilter<T: ?Sized, H = DefaultHasher> {
+    hasher: H,
+    initial_capacity: usize,
+    false_positive_probability: f64,
+    layers: Box<[OnceLock<AtomicLayer>]>,
+    writer: Mutex<()>,
+    _item: PhantomData<fn(&T)>,
+}
+impl<T: Hash + ?Sized> AtomicScalableCuckooFilter<T> {
+    /// Makes a new `AtomicScalableCuckooFilter` instance whose hasher is keyed randomly
+    /// (see [`DefaultHasher`]).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `false_positive_probability` is not in the range `(0.0, 1.0]`
+    /// or is too small for 64-bit fingerprints (see [`insert`]).
+    ///
+    /// [`insert`]: AtomicScalableCuckooFilter::insert
+    pub fn new(initial_capacity_hint: usize, false_positive_probability: f64) -> Self {
+        Self::with_hasher(
+            initial_capacity_hint,
+            false_positive_probability,
+            default_hasher(&mut rand::thread_rng()),
+        )
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> AtomicScalableCuckooFilter<T, H> {
+    /// Makes a new `AtomicScalableCuckooFilter` instance that uses `hasher` for hashing items.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `false_positive_probability` is not in the range `(0.0, 1.0]`
+    /// or is too small for 64-bit fingerprints (see [`insert`]).
+    ///
+    /// [`insert`]: AtomicScalableCuckooFilter::insert
+    pub fn with_hasher(
+        initial_capacity_hint: usize,
+        false_positive_probability: f64,
+        hasher: H,
+    ) -> Self {
+        assert!(0.0 < false_positive_probability && false_positive_probability <= 1.0);
+        let filter = AtomicScalableCuckooFilter {
+            hasher,
+            initial_capacity: initial_capacity_hint.max(1),
+            false_positive_probability,
+            layers: (0..MAX_LAYERS).map(|_| OnceLock::new()).collect(),
+            writer: Mutex::new(()),
+            _item: PhantomData,
+        };
+        filter.grow(0);
+        filter
+    }
+
+    /// Returns the approximate number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.iter_layers()
+            .map(|l| l.len.load(Ordering::Relaxed) as u64)
+            .sum()
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity (approximate number of items that can be stored) of this filter.
+    pub fn capacity(&self) -> u64 {
+        self.iter_layers().map(|l| l.capacity as u64).sum()
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.iter_layers().map(|l| l.words.len() as u64 * 64).sum()
+    }
+
+    /// Returns the number of layers of this filter.
+    pub fn number_of_layers(&self) -> usize {
+        self.iter_layers().count()
+    }
+
+    /// Returns the expected upper bound of the false positive probability of this filter.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    ///
+    /// This method never takes a lock.
+    pub fn contains(&self, item: &T) -> bool {
+        let item_hash = crate::hash(&self.hasher, item);
+        self.iter_layers()
+            .any(|l| l.contains(&self.hasher, item_hash))
+    }
+
+    /// Inserts `item` into this filter.
+    ///
+    /// If the current filter becomes full, it will be expanded automatically.
+    ///
+    /// # Panics
+    ///
+    /// Each layer halves the false positive probability of the previous one, and its
+    /// fingerprints are 8, 16, 32, or 64 bits long. This method panics if a new layer
+    /// would need a probability that 64-bit fingerprints can't achieve (about `1e-19`),
+    /// or more than 48 layers.
+    pub fn insert(&self, item: &T) {
+        let item_hash = crate::hash(&self.hasher, item);
+        let _guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let last = self.number_of_layers() - 1;
+        let layer = self.layers[last].get().expect("unreachable");
+        if !layer.insert(&self.hasher, item_hash) {
+            let layer = self.grow(last + 1);
+            let inserted = layer.insert(&self.hasher, item_hash);
+            debug_assert!(inserted);
+        }
+    }
+
+    /// Removes `item` from this filter.
+    ///
+    /// Returns `true` if a fingerprint of `item` was removed, otherwise `false`.
+    pub fn remove(&self, item: &T) -> bool {
+        let item_hash = crate::hash(&self.hasher, item);
+        let _guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        self.iter_layers()
+            .any(|l| l.remove(&self.hasher, item_hash))
+    }
+
+    fn iter_layers(&self) -> impl Iterator<Item = &AtomicLayer> {
+        self.layers.iter().map_while(|l| l.get())
+    }
+
+    fn grow(&self, index: usize) -> &AtomicLayer {
+        assert!(index < MAX_LAYERS, "too many layers");
+
+        // Same as the default growth policy with the tightening ratio `0.5`.
+        let capacity = self
+            .initial_capacity
+            .saturating_mul(1usize.checked_shl(index as u32).unwrap_or(usize::MAX));
+        let probability = self.false_positive_probability * 0.5f64.powi(index as i32 + 1);
+        let layer = AtomicLayer::new(capacity, probability)
+            .expect("false positive probability too small for 64-bit fingerprints");
+        if self.layers[index].set(layer).is_err() {
+            unreachable!();
+        }
+        self.layers[index].get().expect("unreachable")
+    }
+}
+
+#[derive(Debug)]
+struct AtomicLayer {
+    words: Vec<AtomicU64>,
+    fingerprint_bitwidth: u32,
+    capacity: usize,
+    len: AtomicUsize,
+
+    // Odd while entries are being relocated.
+    version: AtomicU64,
+}
+impl AtomicLayer {
+    /// Makes a layer, or returns `None` if no fingerprint width achieves
+    /// `false_positive_probability`.
+    fn new(capacity: usize, false_positive_probability: f64) -> Option<Self> {
+        // Each bucket is a single word, so a lookup of a bucket is a single atomic load.
+        let fingerprint_bitwidth = [8, 16, 32, 64].into_iter().find(|&f| {
+            2.0 * f64::from(64 / f) / 2f64.powi(f as i32) <= false_positive_probability
+        })?;
+        let entries_per_bucket = (64 / fingerprint_bitwidth) as usize;
+        let buckets = capacity.div_ceil(entries_per_bucket).next_power_of_two();
+        Some(AtomicLayer {
+            words: (0..buckets).map(|_| AtomicU64::new(0)).collect(),
+            fingerprint_bitwidth,
+            capacity: buckets * entries_per_bucket,
+            len: AtomicUsize::new(0),
+            version: AtomicU64::new(0),
+        })
+    }
+
+    fn entries_per_bucket(&self) -> usize {
+        (64 / self.fingerprint_bitwidth) as usize
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.words.len() - 1)
+    }
+
+    fn fingerprint(&self, hash: u64) -> u64 {
+        // Zero marks a free entry, so it is folded into one.
+        (hash >> (64 - self.fingerprint_bitwidth)).max(1)
+    }
+
+    fn alt_index<H: Hasher + Clone>(&self, hasher: &H, i: usize, fingerprint: u64) -> usize {
+        self.index(i as u64 ^ crate::hash(hasher, &fingerprint))
+    }
+
+    fn get(&self, word: u64, entry: usize) -> u64 {
+        let f = self.fingerprint_bitwidth;
+        (word >> (entry as u32 * f)) & (u64::MAX >> (64 - f))
+    }
+
+    fn set(&self, bucket: usize, entry: usize, fingerprint: u64) {
+        // Only the writer holding the mutex modifies words, so load-then-store is enough.
+        let f = self.fingerprint_bitwidth;
+        let shift = entry as u32 * f;
+        let mask = (u64::MAX >> (64 - f)) << shift;
+        let word = self.words[bucket].load(Ordering::Relaxed);
+        self.words[bucket].store((word & !mask) | (fingerprint << shift), Ordering::Release);
+    }
+
+    fn find(&self, bucket: usize, fingerprint: u64) -> Option<usize> {
+        let word = self.words[bucket].load(Ordering::Acquire);
+        (0..self.entries_per_bucket()).find(|&e| self.get(word, e) == fingerprint)
+    }
+
+    fn contains<H: Hasher + Clone>(&self, hasher: &H, item_hash: u64) -> bool {
+        let fingerprint = self.fingerprint(item_hash);
+        let i0 = self.index(item_hash);
+        let i1 = self.alt_index(hasher, i0, fingerprint);
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+            if version % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            if self.find(i0, fingerprint).is_some() || self.find(i1, fingerprint).is_some() {
+                return true;
+            }
+            atomic::fence(Ordering::Acquire);
+            if self.version.load(Ordering::Relaxed) == version {
+                return false;
+            }
+        }
+    }
+
+    fn insert<H: Hasher + Clone>(&self, hasher: &H, item_hash: u64) -> bool {
+        let fingerprint = self.fingerprint(item_hash);
+        let i0 = self.index(item_hash);
+        let i1 = self.alt_index(hasher, i0, fingerprint);
+        let inserted = self.try_insert(i0, fingerprint)
+            || self.try_insert(i1, fingerprint)
+            || self.breadth_first(hasher, i0, i1, fingerprint);
+        if inserted {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        inserted
+    }
+
+    fn try_insert(&self, bucket: usize, fingerprint: u64) -> bool {
+        if let Some(entry) = self.find(bucket, 0) {
+            self.set(bucket, entry, fingerprint);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn remove<H: Hasher + Clone>(&self, hasher: &H, item_hash: u64) -> bool {
+        let fingerprint = self.fingerprint(item_hash);
+        let i0 = self.index(item_hash);
+        let i1 = self.alt_index(hasher, i0, fingerprint);
+        for i in [i0, i1] {
+            if let Some(entry) = self.find(i, fingerprint) {
+                self.set(i, entry, 0);
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn breadth_first<H: Hasher + Clone>(
+        &self,
+        hasher: &H,
+        i0: usize,
+        i1: usize,
+        fingerprint: u64,
+    ) -> bool {
+        // Each node is `(bucket_index, parent_node, entry_index_in_parent_bucket)`.
+        let mut nodes = vec![(i0, usize::MAX, 0), (i1, usize::MAX, 0)];
+        let mut visited = HashSet::new();
+        visited.insert(i0);
+        visited.insert(i1);
+
+        let mut current = 0;
+        while current < nodes.len() {
+            let (i, _, _) = nodes[current];
+            let word = self.words[i].load(Ordering::Relaxed);
+            for entry in 0..self.entries_per_bucket() {
+                let f = self.get(word, entry);
+                let alt_i = self.alt_index(hasher, i, f);
+                if let Some(free_entry) = self.find(alt_i, 0) {
+                    self.shift_path(&nodes, current, entry, (alt_i, free_entry), fingerprint);
+                    return true;
+                }
+                if nodes.len() < MAX_KICKS && visited.insert(alt_i) {
+                    nodes.push((alt_i, current, entry));
+                }
+            }
+            current += 1;
+        }
+        false
+    }
+
+    fn shift_path(
+        &self,
+        nodes: &[(usize, usize, usize)],
+        mut node: usize,
+        mut entry: usize,
+        (free_bucket, free_entry): (usize, usize),
+        fingerprint: u64,
+    ) {
+        let version = self.version.load(Ordering::Relaxed);
+        self.version.store(version + 1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+
+        // Each fingerprint is copied before its old entry is overwritten.
+        let (i, _, _) = nodes[node];
+        let f = self.get(self.words[i].load(Ordering::Relaxed), entry);
+        self.set(free_bucket, free_entry, f);
+        loop {
+            let (i, parent, parent_entry) = nodes[node];
+            if parent == usize::MAX {
+                self.set(i, entry, fingerprint);
+                break;
+            }
+            let (parent_i, _, _) = nodes[parent];
+            let f = self.get(self.words[parent_i].load(Ordering::Relaxed), parent_entry);
+            self.set(i, entry, f);
+            node = parent;
+            entry = parent_entry;
+        }
+
+        self.version.store(version + 2, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn it_works() {
+        let filter = AtomicScalableCuckooFilter::new(100, 0.001);
+        assert!(filter.is_empty());
+        assert_eq!(filter.number_of_layers(), 1);
+
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.len(), 10_000);
+        assert!(filter.number_of_layers() > 1);
+        assert!(filter.capacity() >= filter.len());
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+
+        let false_positives = (10_000..110_000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 100, "{}", false_positives);
+
+        let removed = (0..5_000).filter(|i| filter.remove(i)).count();
+        assert!(removed > 4_990);
+        assert_eq!(filter.len(), 10_000 - removed as u64);
+    }
+
+    #[test]
+    fn long_fingerprints_work() {
+        let filter = AtomicScalableCuckooFilter::new(100, 1e-12);
+        let layer = filter.layers[0].get().unwrap();
+        assert_eq!(layer.fingerprint_bitwidth, 64);
+        assert_eq!(layer.entries_per_bucket(), 1);
+
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        assert!((0..1000).all(|i| filter.contains(&i)));
+        assert!((1000..101_000).all(|i| !filter.contains(&i)));
+        assert!((0..1000).all(|i| filter.remove(&i)));
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_small_false_positive_probability_panics() {
+        AtomicScalableCuckooFilter::<u64>::new(100, 1e-20);
+    }
+
+    #[test]
+    fn no_false_negatives_while_writing() {
+        let filter = Arc::new(AtomicScalableCuckooFilter::new(100, 0.001));
+        let inserted = Arc::new(AtomicUsize::new(0));
+        let readers = (0..4)
+            .map(|_| {
+                let filter = Arc::clone(&filter);
+                let inserted = Arc::clone(&inserted);
+                thread::spawn(move || loop {
+                    let n = inserted.load(Ordering::Acquire);
+                    for i in 0..n {
+                        assert!(filter.contains(&i), "{}", i);
+                    }
+                    if n == 20_000 {
+                        break;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..20_000 {
+            filter.insert(&i);
+            inserted.store(i + 1, Ordering::Release);
+        }
+        for h in readers {
+            h.join().unwrap();
+        }
+    }
+}
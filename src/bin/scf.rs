@@ -0,0 +1,344 @@
+//! Command-line tool for building, inspecting, and querying filters.
+//!
+//! Keys are byte strings (one per line, without the trailing `\n` or `\r\n`), and filters are
+//! `ScalableCuckooFilter<[u8]>`s built with `DefaultHasher::default()` and saved by `to_bytes`,
+//! so they can be loaded by `ScalableCuckooFilter::<[u8]>::load_from_path` in Rust as well.
+//! The hasher keys are fixed rather than random so that separately built filters can be merged.
+//!
+//! This is available only if the `cli` feature is enabled
+//! (e.g., `cargo install scalable_cuckoo_filter --features cli`).
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::process;
+
+use scalable_cuckoo_filter::{DefaultHasher, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+const USAGE: &str = "\
+Usage: scf <COMMAND> [ARGS]
+
+Commands:
+  build <OUTPUT> [--capacity <N>] [--fpp <P>] [--compressed]
+      Builds a filter from the keys read from stdin and saves it to OUTPUT.
+  stats <FILTER>
+      Prints the statistics of a filter.
+  contains <FILTER> [<KEY>...]
+      Prints whether a filter may contain each KEY (or each key read from stdin).
+      Exits with 1 if any of them is absent.
+  merge <OUTPUT> <FILTER>... [--compressed]
+      Saves the union of filters built with the same parameters to OUTPUT.
+  convert <INPUT> <OUTPUT> [--compressed]
+      Rewrites a filter, compactly if --compressed is given.
+";
+
+type Filter = ScalableCuckooFilter<[u8]>;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdout = BufWriter::new(stdout.lock());
+    let result = run(&args, &mut stdin.lock(), &mut stdout);
+    let flushed = stdout.flush();
+    match result.and_then(|ok| flushed.map(|()| ok).map_err(Into::into)) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("scf: {e}");
+            if e.is::<UsageError>() {
+                eprint!("\n{USAGE}");
+            }
+            process::exit(2);
+        }
+    }
+}
+
+/// Runs the command in `args`, returning `false` if it has completed but found a key absent.
+fn run<I: BufRead, O: Write>(args: &[String], stdin: &mut I, stdout: &mut O) -> Result<bool> {
+    let mut args = Args::parse(args);
+    let command = args.positional(0, "COMMAND")?.to_owned();
+    match command.as_str() {
+        "build" => {
+            let capacity = args.value("--capacity")?.unwrap_or(100_000);
+            let fpp = args.value("--fpp")?.unwrap_or(0.001);
+            let compressed = args.flag("--compressed");
+            let output = args.positional(1, "OUTPUT")?;
+            args.finish(2)?;
+
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .hasher(DefaultHasher::default())
+                .for_type::<[u8]>()
+                .initial_capacity(capacity)
+                .false_positive_probability(fpp)
+                .build()?;
+            for_each_key(stdin, |key| filter.insert(key))?;
+            save(&filter, output, compressed)?;
+        }
+        "stats" => {
+            let filter = load(args.positional(1, "FILTER")?)?;
+            args.finish(2)?;
+            write_stats(&filter, stdout)?;
+        }
+        "contains" => {
+            args.finish(usize::MAX)?;
+            let filter = load(args.positional(1, "FILTER")?)?;
+            let mut all = true;
+            let mut query = |key: &[u8]| -> io::Result<()> {
+                let contained = filter.contains(key);
+                all &= contained;
+                stdout.write_all(key)?;
+                writeln!(stdout, "\t{contained}")
+            };
+            if args.positionals.len() > 2 {
+                for key in &args.positionals[2..] {
+                    query(key.as_bytes())?;
+                }
+            } else {
+                let mut result = Ok(());
+                for_each_key(stdin, |key| {
+                    if result.is_ok() {
+                        result = query(key);
+                    }
+                })?;
+                result?;
+            }
+            return Ok(all);
+        }
+        "merge" => {
+            let compressed = args.flag("--compressed");
+            args.finish(usize::MAX)?;
+            let output = args.positional(1, "OUTPUT")?;
+            let mut filter = load(args.positional(2, "FILTER")?)?;
+            for path in &args.positionals[3..] {
+                let other = load(path)?;
+                let entries = other.entries_missing_from(&filter.reconciliation_summary()?)?;
+                filter
+                    .merge_entries(&entries)
+                    .map_err(|e| format!("{path}: {e}"))?;
+            }
+            save(&filter, output, compressed)?;
+        }
+        "convert" => {
+            let compressed = args.flag("--compressed");
+            let filter = load(args.positional(1, "INPUT")?)?;
+            let output = args.positional(2, "OUTPUT")?;
+            args.finish(3)?;
+            save(&filter, output, compressed)?;
+        }
+        "help" | "--help" | "-h" => write!(stdout, "{USAGE}")?,
+        _ => return Err(UsageError(format!("unknown command {command:?}")).into()),
+    }
+    Ok(true)
+}
+
+fn load(path: &str) -> Result<Filter> {
+    Filter::load_from_path(path).map_err(|e| format!("{path}: {e}").into())
+}
+
+fn save(filter: &Filter, path: &str, compressed: bool) -> Result<()> {
+    let result = if compressed {
+        File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            filter.write_compressed_to(&mut writer)?;
+            writer.into_inner()?.sync_all()
+        })
+    } else {
+        filter.save_to_path(path)
+    };
+    result.map_err(|e| format!("{path}: {e}").into())
+}
+
+fn for_each_key<I: BufRead, F: FnMut(&[u8])>(stdin: &mut I, mut f: F) -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if stdin.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let key = line.strip_suffix(b"\n").unwrap_or(&line);
+        f(key.strip_suffix(b"\r").unwrap_or(key));
+    }
+}
+
+fn write_stats<O: Write>(filter: &Filter, stdout: &mut O) -> io::Result<()> {
+    let stats = filter.stats();
+    writeln!(stdout, "len: {}", stats.len)?;
+    writeln!(stdout, "capacity: {}", stats.capacity)?;
+    writeln!(stdout, "bits: {}", stats.bits)?;
+    writeln!(stdout, "memory_usage: {}", filter.memory_usage())?;
+    writeln!(stdout, "fill_ratio: {:.6}", filter.fill_ratio())?;
+    writeln!(
+        stdout,
+        "false_positive_probability: {}",
+        filter.false_positive_probability()
+    )?;
+    writeln!(
+        stdout,
+        "false_positive_probability_bound: {:.6e}",
+        filter.false_positive_probability_bound()
+    )?;
+    writeln!(
+        stdout,
+        "estimated_false_positive_probability: {:.6e}",
+        filter.estimated_false_positive_probability()
+    )?;
+    writeln!(
+        stdout,
+        "entries_per_bucket: {}",
+        filter.entries_per_bucket()
+    )?;
+    writeln!(stdout, "stash_len: {}", stats.stash_len)?;
+    writeln!(stdout, "layers: {}", stats.number_of_layers)?;
+    for (i, layer) in stats.layers.iter().enumerate() {
+        writeln!(
+            stdout,
+            "  {i}: len={} capacity={} bits={} fingerprint_bitwidth={} fill_ratio={:.6}",
+            layer.len(),
+            layer.capacity(),
+            layer.bits(),
+            layer.fingerprint_bitwidth(),
+            layer.fill_ratio()
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct UsageError(String);
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+impl Error for UsageError {}
+
+/// Command-line arguments whose options are taken out as they are looked up.
+#[derive(Debug)]
+struct Args {
+    positionals: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+impl Args {
+    fn parse(args: &[String]) -> Self {
+        let mut positionals = Vec::new();
+        let mut options = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positionals.extend(args.by_ref().cloned());
+            } else if let Some((name, value)) = arg.split_once('=').filter(|_| is_option(arg)) {
+                options.push((name.to_owned(), Some(value.to_owned())));
+            } else if is_option(arg) {
+                // Options taking a value are followed by it unless written as `--name=value`.
+                let value = matches!(arg.as_str(), "--capacity" | "--fpp")
+                    .then(|| args.next().cloned())
+                    .flatten();
+                options.push((arg.clone(), value));
+            } else {
+                positionals.push(arg.clone());
+            }
+        }
+        Args {
+            positionals,
+            options,
+        }
+    }
+
+    fn positional(&self, i: usize, name: &str) -> Result<&str> {
+        match self.positionals.get(i) {
+            Some(arg) => Ok(arg),
+            None => Err(UsageError(format!("missing {name}")).into()),
+        }
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        self.take(name).is_some()
+    }
+
+    fn value<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>> {
+        match self.take(name) {
+            None => Ok(None),
+            Some(Some(value)) => match value.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(UsageError(format!("invalid value of {name}: {value:?}")).into()),
+            },
+            Some(None) => Err(UsageError(format!("missing value of {name}")).into()),
+        }
+    }
+
+    fn take(&mut self, name: &str) -> Option<Option<String>> {
+        let i = self.options.iter().position(|(n, _)| n == name)?;
+        Some(self.options.remove(i).1)
+    }
+
+    /// Fails if any option hasn't been taken, or if there are more than `positionals` arguments.
+    fn finish(&self, positionals: usize) -> Result<()> {
+        if let Some((name, _)) = self.options.first() {
+            return Err(UsageError(format!("unexpected option {name}")).into());
+        }
+        if let Some(arg) = self.positionals.get(positionals) {
+            return Err(UsageError(format!("unexpected argument {arg:?}")).into());
+        }
+        Ok(())
+    }
+}
+
+fn is_option(arg: &str) -> bool {
+    arg.starts_with("--") && arg.len() > 2
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scf(args: &[&str], stdin: &str) -> Result<(bool, String)> {
+        let args = args.iter().map(|&a| a.to_owned()).collect::<Vec<_>>();
+        let mut stdout = Vec::new();
+        let ok = run(&args, &mut stdin.as_bytes(), &mut stdout)?;
+        Ok((ok, String::from_utf8(stdout).unwrap()))
+    }
+
+    #[test]
+    fn it_works() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+        let (a, b, merged, compressed) = (path("a"), path("b"), path("m"), path("c"));
+
+        scf(&["build", &a, "--capacity", "100"], "foo\nbar\r\n").unwrap();
+        scf(&["build", &b, "--capacity=100"], "baz\n").unwrap();
+        assert_eq!(
+            scf(&["contains", &a, "foo", "baz"], "").unwrap(),
+            (false, "foo\ttrue\nbaz\tfalse\n".to_owned())
+        );
+        assert_eq!(
+            scf(&["contains", &a], "foo\nbar").unwrap(),
+            (true, "foo\ttrue\nbar\ttrue\n".to_owned())
+        );
+
+        scf(&["merge", &merged, &a, &b, "--compressed"], "").unwrap();
+        let (ok, out) = scf(&["contains", &merged, "foo", "bar", "baz"], "").unwrap();
+        assert!(ok, "{out}");
+        assert!(scf(&["stats", &merged], "").unwrap().1.contains("len: 3\n"));
+
+        scf(&["convert", &merged, &compressed], "").unwrap();
+        let filter = Filter::load_from_path(&compressed).unwrap();
+        assert!(filter.contains(b"baz"));
+        assert_eq!(filter.len(), 3);
+
+        scf(&["build", &b, "--capacity", "100000"], "").unwrap();
+        assert!(scf(&["merge", &merged, &a, &b], "").is_err());
+        assert!(scf(&["build", &a, "--fpp", "2"], "").is_err());
+        assert!(scf(&["contains", &path("none"), "foo"], "").is_err());
+
+        let usage = |args: &[&str]| scf(args, "").unwrap_err().is::<UsageError>();
+        assert!(usage(&[]));
+        assert!(usage(&["foo"]));
+        assert!(usage(&["build"]));
+        assert!(usage(&["build", &a, "--capacity"]));
+        assert!(usage(&["build", &a, "--capacity", "x"]));
+        assert!(usage(&["build", &a, "--verbose"]));
+        assert!(usage(&["stats", &a, &b]));
+    }
+}
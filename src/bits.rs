@@ -1,50 +1,560 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::ptr::NonNull;
+
+use crate::alloc::Allocator;
+use crate::codec::{self, BitsEncoding};
+
 #[cfg(feature = "serde_support")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
-pub struct Bits(#[cfg_attr(feature = "serde_support", serde(with = "serde_bytes"))] Vec<u8>);
+/// Fixed-size bit array.
+///
+/// Bits are stored in 64-bit words, so a value is read or written with at most
+/// two word operations. The size is rounded up to a multiple of eight bits, and the array is
+/// serialized as a little-endian byte string of that size.
+///
+/// The words are allocated with the allocator of the filter, which isn't serialized.
+pub struct Bits {
+    // Points to `words_len()` words, or dangling if there are none.
+    ptr: NonNull<u64>,
+    len: usize,
+    alloc: Allocator,
+    // Version of the filter that writes are stamped with (see `set_version`).
+    version: u64,
+    // Version at which the bits were made; no page has an older version.
+    base_version: u64,
+    // Version of the last write to each page.
+    page_versions: Vec<u64>,
+}
+// SAFETY: `Bits` owns its words like a `Vec<u64>`, and allocators are `Send + Sync`.
+unsafe impl Send for Bits {}
+// SAFETY: See above.
+unsafe impl Sync for Bits {}
 impl Bits {
-    pub fn new(size_hint: usize) -> Self {
-        Bits(vec![0; (size_hint + 7) / 8])
+    pub fn new(size_hint: usize, alloc: Allocator) -> Self {
+        let len = size_hint.next_multiple_of(8);
+        let words_len = len.div_ceil(64);
+        let ptr = if words_len == 0 {
+            NonNull::dangling()
+        } else {
+            alloc.alloc_words(words_len)
+        };
+        Bits {
+            ptr,
+            len,
+            alloc,
+            version: 0,
+            base_version: 0,
+            page_versions: vec![0; (len / 8).div_ceil(Self::PAGE_BYTES)],
+        }
     }
 
+    /// Returns the number of bytes allocated for the words.
     #[inline]
-    pub fn len(&self) -> usize {
-        self.0.len() * 8
+    pub fn heap_bytes(&self) -> usize {
+        self.words_len() * 8
     }
 
     #[inline]
-    pub fn get_uint(&self, position: usize, size: usize) -> u64 {
-        let mut value = 0;
-        let start = position / 8;
-        let end = (position + size + 7) / 8;
-        for (i, &b) in self.0[start..end].iter().enumerate() {
-            value |= u64::from(b) << (i * 8);
-        }
+    pub fn allocator(&self) -> &Allocator {
+        &self.alloc
+    }
+
+    #[inline]
+    pub fn set_uint(&mut self, position: usize, size: usize, value: u64) {
+        set_uint(self.words_mut(), position, size, value);
+        self.stamp(position);
+        self.stamp(position + size - 1);
+    }
+
+    #[inline]
+    fn words(&self) -> &[u64] {
+        // SAFETY: `ptr` points to `words_len()` initialized words (or is dangling if there are none).
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.words_len()) }
+    }
+
+    #[inline]
+    fn words_mut(&mut self) -> &mut [u64] {
+        // SAFETY: Same as `words`, and `&mut self` guarantees exclusive access.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.words_len()) }
+    }
+
+    #[cfg(test)]
+    pub fn as_ptr(&self) -> *const u64 {
+        self.ptr.as_ptr()
+    }
 
-        let offset = position % 8;
-        let mask = (1 << size) - 1;
-        (value >> offset) & mask
+    #[inline]
+    pub fn words_len(&self) -> usize {
+        self.len.div_ceil(64)
+    }
+
+    /// Splits the bits into mutable word slices of `bits_per_chunk` bits each.
+    ///
+    /// All pages are stamped as written.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn chunks_mut(&mut self, bits_per_chunk: usize) -> std::slice::ChunksMut<'_, u64> {
+        debug_assert_eq!(bits_per_chunk % 64, 0);
+        self.page_versions.fill(self.version);
+        self.words_mut().chunks_mut(bits_per_chunk / 64)
+    }
+}
+impl Bits {
+    /// Number of bytes of a page whose version is tracked.
+    pub const PAGE_BYTES: usize = 4096;
+
+    const PAGE_BITS: usize = Self::PAGE_BYTES * 8;
+    const PAGE_WORDS: usize = Self::PAGE_BYTES / 8;
+
+    /// Sets the version of the filter that the following writes are stamped with.
+    #[inline]
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    /// Returns the version that writes are stamped with.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Makes `version` the version of all pages, as if the bits were made at `version`.
+    pub fn reset_versions(&mut self, version: u64) {
+        self.version = version;
+        self.base_version = version;
+        self.page_versions.fill(version);
+    }
+
+    /// Returns the version at which the bits were made (or `reset_versions` was called).
+    #[inline]
+    pub fn base_version(&self) -> u64 {
+        self.base_version
+    }
+
+    #[inline]
+    fn stamp(&mut self, position: usize) {
+        self.page_versions[position / Self::PAGE_BITS] = self.version;
+    }
+
+    /// Returns the indices of the pages written after `version`.
+    pub fn pages_changed_since(&self, version: u64) -> impl Iterator<Item = usize> + '_ {
+        self.page_versions
+            .iter()
+            .enumerate()
+            .filter(move |&(_, &v)| v > version)
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the number of bytes of the serialized bits.
+    #[inline]
+    pub fn byte_len(&self) -> usize {
+        self.len / 8
     }
 
+    /// Returns the number of bytes of the given page, which is less than `PAGE_BYTES` for
+    /// the last page.
     #[inline]
-    pub fn set_uint(&mut self, position: usize, mut size: usize, mut value: u64) {
-        let mut offset = position % 8;
-        for b in &mut self.0[position / 8..] {
-            let high = ((u64::from(*b) >> (size + offset)) << (size + offset)) as u8;
-            let middle = (value << offset) as u8;
-            let low = *b & ((1 << offset) - 1);
-            *b = high | middle | low;
+    pub fn page_byte_len(&self, page: usize) -> usize {
+        (self.byte_len() - page * Self::PAGE_BYTES).min(Self::PAGE_BYTES)
+    }
+
+    /// Writes the little-endian bytes of the given page into the beginning of `buf`,
+    /// returning their number (see `page_byte_len`).
+    pub fn page_to_le_bytes(&self, page: usize, buf: &mut [u8; Self::PAGE_BYTES]) -> usize {
+        let start = page * Self::PAGE_WORDS;
+        let end = (start + Self::PAGE_WORDS).min(self.words_len());
+        for (word, bytes) in self.words()[start..end].iter().zip(buf.chunks_exact_mut(8)) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        self.page_byte_len(page)
+    }
+
+    /// Overwrites the given page with `bytes` (of `page_byte_len(page)` bytes) and stamps it.
+    pub fn set_page_from_le_bytes(&mut self, page: usize, bytes: &[u8]) {
+        debug_assert_eq!(bytes.len(), self.page_byte_len(page));
+        let start = page * Self::PAGE_WORDS;
+        let end = (start + Self::PAGE_WORDS).min(self.words_len());
+        for (word, chunk) in self.words_mut()[start..end].iter_mut().zip(bytes.chunks(8)) {
+            let mut le_bytes = [0; 8];
+            le_bytes[..chunk.len()].copy_from_slice(chunk);
+            *word = u64::from_le_bytes(le_bytes);
+        }
+        self.page_versions[page] = self.version;
+    }
+}
+impl Bits {
+    /// Number of words written or read at once by `write_to` and `read_from`.
+    const IO_CHUNK_WORDS: usize = 1024;
+
+    /// Minimum number of zero words replaced by a run in `BitsEncoding::ZeroRuns`.
+    ///
+    /// A run takes 16 bytes, so shorter runs are written as they are.
+    const MIN_ZERO_RUN_WORDS: usize = 4;
+
+    /// Writes the number of bits followed by the bits encoded by `encoding`.
+    ///
+    /// With `BitsEncoding::Raw`, the bits are written as little-endian bytes
+    /// (the same bytes as the serde representation).
+    /// With `BitsEncoding::ZeroRuns`, they are written as pairs of the number of zero words
+    /// to skip and the number of words that follow as little-endian bytes, until all words
+    /// are covered.
+    pub fn write_to<W: Write>(&self, writer: &mut W, encoding: BitsEncoding) -> io::Result<()> {
+        self.write_len_to(writer)?;
+        let words = self.words();
+        if encoding == BitsEncoding::Raw {
+            return write_words(writer, words, self.len / 8);
+        }
 
-            let drop_bits = 8 - offset;
-            if size <= drop_bits {
-                break;
+        let mut i = 0;
+        while i < words.len() {
+            let start = i + words[i..].iter().take_while(|&&w| w == 0).count();
+            let mut end = start;
+            while end < words.len() {
+                let zeros = words[end..]
+                    .iter()
+                    .take(Self::MIN_ZERO_RUN_WORDS)
+                    .take_while(|&&w| w == 0)
+                    .count();
+                if zeros == Self::MIN_ZERO_RUN_WORDS {
+                    break;
+                }
+                end = (end + zeros + 1).min(words.len());
             }
-            size -= drop_bits;
-            value >>= drop_bits;
-            offset = 0;
+            codec::write_usize(writer, start - i)?;
+            codec::write_usize(writer, end - start)?;
+            self.write_words_to(writer, start..end)?;
+            i = end;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R, encoding: BitsEncoding) -> io::Result<Self> {
+        let mut bits = Self::read_len_from(reader)?;
+        let mut i = 0;
+        while i < bits.words_len() {
+            let words = bits.read_run_from(reader, i, encoding)?;
+            bits.read_words_from(reader, words.clone())?;
+            i = words.end;
         }
+        Ok(bits)
+    }
+
+    /// Writes the number of bits, which `write_to` starts with.
+    pub fn write_len_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        codec::write_usize(writer, self.len)
+    }
+
+    /// Reads the number of bits written by `write_len_to`, returning zeroed bits of that size.
+    pub fn read_len_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let len = codec::read_usize(reader)?;
+        if len % 8 != 0 {
+            return Err(codec::invalid_data("bit count isn't a multiple of eight"));
+        }
+        Ok(Bits::new(len, Allocator::default()))
+    }
+
+    /// Reads the next run of the words encoded by `encoding` following the `i`-th word,
+    /// returning the range of the words that follow as little-endian bytes.
+    ///
+    /// The words skipped by the run are left as they are (i.e., zero).
+    pub fn read_run_from<R: Read>(
+        &self,
+        reader: &mut R,
+        i: usize,
+        encoding: BitsEncoding,
+    ) -> io::Result<Range<usize>> {
+        if encoding == BitsEncoding::Raw {
+            return Ok(i..self.words_len());
+        }
+
+        let zeros = codec::read_usize(reader)?;
+        let literals = codec::read_usize(reader)?;
+        let start = i.checked_add(zeros);
+        let end = start.and_then(|start| start.checked_add(literals));
+        let (Some(start), Some(end)) = (start, end) else {
+            return Err(codec::invalid_data("zero run exceeds the bit array"));
+        };
+        if end == i || end > self.words_len() {
+            return Err(codec::invalid_data("zero run exceeds the bit array"));
+        }
+        Ok(start..end)
+    }
+
+    /// Returns the number of the little-endian bytes of the given words that are serialized.
+    #[inline]
+    pub fn words_byte_len(&self, words: Range<usize>) -> usize {
+        self.byte_len()
+            .min(words.end * 8)
+            .saturating_sub(words.start * 8)
+    }
+
+    /// Writes the serialized little-endian bytes of the given words (see `words_byte_len`).
+    pub fn write_words_to<W: Write>(&self, writer: &mut W, words: Range<usize>) -> io::Result<()> {
+        let byte_len = self.words_byte_len(words.clone());
+        write_words(writer, &self.words()[words], byte_len)
+    }
+
+    /// Reads the words written by `write_words_to`.
+    ///
+    /// The pages aren't stamped, as the bits are being read rather than modified.
+    pub fn read_words_from<R: Read>(
+        &mut self,
+        reader: &mut R,
+        words: Range<usize>,
+    ) -> io::Result<()> {
+        let byte_len = self.words_byte_len(words.clone());
+        read_words(reader, &mut self.words_mut()[words], byte_len)
+    }
+}
+
+/// Writes the first `byte_len` bytes of `words` in little-endian order.
+fn write_words<W: Write>(writer: &mut W, words: &[u64], mut byte_len: usize) -> io::Result<()> {
+    let mut buf = [0; Bits::IO_CHUNK_WORDS * 8];
+    for chunk in words.chunks(Bits::IO_CHUNK_WORDS) {
+        for (word, bytes) in chunk.iter().zip(buf.chunks_exact_mut(8)) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        let n = byte_len.min(chunk.len() * 8);
+        writer.write_all(&buf[..n])?;
+        byte_len -= n;
+    }
+    Ok(())
+}
+
+/// Reads `byte_len` little-endian bytes into `words`, zero-filling the rest.
+fn read_words<R: Read>(reader: &mut R, words: &mut [u64], mut byte_len: usize) -> io::Result<()> {
+    let mut buf = [0; Bits::IO_CHUNK_WORDS * 8];
+    for chunk in words.chunks_mut(Bits::IO_CHUNK_WORDS) {
+        let n = byte_len.min(chunk.len() * 8);
+        reader.read_exact(&mut buf[..n])?;
+        buf[n..].fill(0);
+        for (word, bytes) in chunk.iter_mut().zip(buf.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().expect("eight bytes"));
+        }
+        byte_len -= n;
+    }
+    Ok(())
+}
+
+impl Drop for Bits {
+    fn drop(&mut self) {
+        if self.words_len() != 0 {
+            // SAFETY: `ptr` was allocated by `alloc_words(words_len())` of `alloc`.
+            unsafe { self.alloc.dealloc_words(self.ptr, self.words_len()) };
+        }
+    }
+}
+impl Clone for Bits {
+    fn clone(&self) -> Self {
+        let mut bits = Bits::new(self.len, self.alloc.clone());
+        bits.words_mut().copy_from_slice(self.words());
+        bits.version = self.version;
+        bits.base_version = self.base_version;
+        bits.page_versions.clone_from(&self.page_versions);
+        bits
+    }
+}
+/// Bits are equal if they have the same size and values, regardless of their versions.
+impl PartialEq for Bits {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.words() == other.words()
+    }
+}
+impl Eq for Bits {}
+impl fmt::Debug for Bits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Bits")
+            .field("words", &self.words())
+            .field("len", &self.len)
+            .field("alloc", &self.alloc)
+            .finish()
+    }
+}
+#[cfg(feature = "serde_support")]
+impl Serialize for Bits {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self
+            .words()
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .take(self.len / 8)
+            .collect::<Vec<_>>();
+        serde_bytes::serialize(&bytes, serializer)
+    }
+}
+#[cfg(feature = "serde_support")]
+impl<'de> Deserialize<'de> for Bits {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes: serde_bytes::ByteBuf = serde_bytes::deserialize(deserializer)?;
+        let mut bits = Bits::new(bytes.len() * 8, Allocator::default());
+        for (word, chunk) in bits.words_mut().iter_mut().zip(bytes.chunks(8)) {
+            let mut le_bytes = [0; 8];
+            le_bytes[..chunk.len()].copy_from_slice(chunk);
+            *word = u64::from_le_bytes(le_bytes);
+        }
+        Ok(bits)
+    }
+}
+
+/// Read access to a bit array, which is either owned ([`Bits`]) or borrowed ([`BitsRef`]).
+pub trait BitArray {
+    fn len(&self) -> usize;
+    fn get_uint(&self, position: usize, size: usize) -> u64;
+    fn prefetch(&self, position: usize);
+}
+impl BitArray for Bits {
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn get_uint(&self, position: usize, size: usize) -> u64 {
+        get_uint(self.words(), position, size)
+    }
+
+    /// Hints the CPU to fetch the cache line holding the bit at `position`.
+    ///
+    /// This is a no-op on architectures without a stable prefetch instruction.
+    #[inline]
+    fn prefetch(&self, position: usize) {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if let Some(word) = self.words().get(position / 64) {
+            #[cfg(target_arch = "x86")]
+            use std::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+            #[cfg(target_arch = "x86_64")]
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+            // SAFETY: Prefetching is only a hint and never faults, and the address is in bounds anyway.
+            unsafe { _mm_prefetch::<_MM_HINT_T0>((word as *const u64).cast()) };
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        let _ = position;
+    }
+}
+
+/// Bit array borrowed from the little-endian bytes written by [`Bits::write_to`].
+///
+/// The bytes need no alignment, and the last word may be incomplete.
+#[derive(Debug, Clone, Copy)]
+pub struct BitsRef<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> BitsRef<'a> {
+    /// Returns the little-endian bytes of the bits.
+    #[cfg(feature = "mmap")]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Takes the bits written by [`Bits::write_to`] from the front of `bytes`.
+    pub fn read_from(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        let len = codec::read_usize(bytes)?;
+        if len % 8 != 0 {
+            return Err(codec::invalid_data("bit count isn't a multiple of eight"));
+        }
+        if bytes.len() < len / 8 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (bits, rest) = bytes.split_at(len / 8);
+        *bytes = rest;
+        Ok(BitsRef { bytes: bits })
+    }
+}
+impl BitArray for BitsRef<'_> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.bytes.len() * 8
+    }
+
+    #[inline]
+    fn get_uint(&self, position: usize, size: usize) -> u64 {
+        get_uint(self, position, size)
+    }
+
+    #[inline]
+    fn prefetch(&self, _position: usize) {}
+}
+impl Words for BitsRef<'_> {
+    #[inline]
+    fn word(&self, i: usize) -> u64 {
+        let mut bytes = [0; 8];
+        let word = &self.bytes[i * 8..];
+        let n = word.len().min(8);
+        bytes[..n].copy_from_slice(&word[..n]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+/// Storage of the 64-bit words that [`get_uint`] operates on.
+pub trait Words {
+    fn word(&self, i: usize) -> u64;
+}
+/// Storage of the 64-bit words that [`set_uint`] operates on.
+pub trait WordsMut: Words {
+    fn set_word(&mut self, i: usize, word: u64);
+}
+impl Words for [u64] {
+    #[inline]
+    fn word(&self, i: usize) -> u64 {
+        self[i]
+    }
+}
+impl WordsMut for [u64] {
+    #[inline]
+    fn set_word(&mut self, i: usize, word: u64) {
+        self[i] = word;
+    }
+}
+/// Words stored as little-endian bytes, which need no alignment (see `FixedCuckooFilter`).
+impl Words for [u8] {
+    #[inline]
+    fn word(&self, i: usize) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self[i * 8..][..8]);
+        u64::from_le_bytes(bytes)
+    }
+}
+impl WordsMut for [u8] {
+    #[inline]
+    fn set_word(&mut self, i: usize, word: u64) {
+        self[i * 8..][..8].copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+/// Reads the `size`-bit unsigned integer starting at the bit `position`.
+///
+/// `size` must be in the range `1..=64`.
+#[inline]
+pub fn get_uint<W: Words + ?Sized>(words: &W, position: usize, size: usize) -> u64 {
+    debug_assert!((1..=64).contains(&size));
+    let i = position / 64;
+    let offset = position % 64;
+    let mut value = words.word(i) >> offset;
+    if offset + size > 64 {
+        value |= words.word(i + 1) << (64 - offset);
+    }
+    value & (u64::MAX >> (64 - size))
+}
+
+/// Writes the lower `size` bits of `value` starting at the bit `position`.
+///
+/// `size` must be in the range `1..=64`. The other bits are left unchanged.
+#[inline]
+pub fn set_uint<W: WordsMut + ?Sized>(words: &mut W, position: usize, size: usize, value: u64) {
+    debug_assert!((1..=64).contains(&size));
+    let i = position / 64;
+    let offset = position % 64;
+    let mask = u64::MAX >> (64 - size);
+    let value = value & mask;
+    words.set_word(i, (words.word(i) & !(mask << offset)) | (value << offset));
+    if offset + size > 64 {
+        let shift = 64 - offset;
+        let next = words.word(i + 1);
+        words.set_word(i + 1, (next & !(mask >> shift)) | (value >> shift));
     }
 }
 
@@ -54,7 +564,7 @@ mod test {
 
     #[test]
     fn it_works() {
-        let mut bits = Bits::new(12345);
+        let mut bits = Bits::new(12345, Allocator::default());
         assert_eq!(bits.len(), 12352);
 
         assert_eq!(bits.get_uint(0, 1), 0);
@@ -73,7 +583,7 @@ mod test {
 
     #[test]
     fn test_high_bits() {
-        let mut bits = Bits::new(320);
+        let mut bits = Bits::new(320, Allocator::default());
         assert_eq!(bits.len(), 320);
 
         assert_eq!(bits.get_uint(290, 5), 0);
@@ -82,4 +592,74 @@ mod test {
         bits.set_uint(290, 5, 21);
         assert_eq!(bits.get_uint(290, 5), 21);
     }
+
+    #[test]
+    fn wide_values_work() {
+        let mut bits = Bits::new(256, Allocator::default());
+        for size in [56, 57, 63, 64] {
+            for position in 0..16 {
+                let value = 0xDEAD_BEEF_CAFE_BABE & (u64::MAX >> (64 - size));
+                bits.set_uint(position + 100, 1, 1);
+                bits.set_uint(position + 101, size, value);
+                bits.set_uint(position + 101 + size, 1, 1);
+                assert_eq!(bits.get_uint(position + 101, size), value);
+                assert_eq!(bits.get_uint(position + 100, 1), 1);
+                assert_eq!(bits.get_uint(position + 101 + size, 1), 1);
+
+                bits.set_uint(position + 101, size, 0);
+                assert_eq!(bits.get_uint(position + 100, 1), 1);
+                assert_eq!(bits.get_uint(position + 101 + size, 1), 1);
+                bits.set_uint(position + 100, 1, 0);
+                bits.set_uint(position + 101 + size, 1, 0);
+                assert!(bits.words().iter().all(|&w| w == 0));
+            }
+        }
+
+        bits.set_uint(192, 64, u64::MAX);
+        assert_eq!(bits.get_uint(192, 64), u64::MAX);
+        assert_eq!(bits.get_uint(191, 1), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_support")]
+    fn serialized_as_bytes() {
+        let mut bits = Bits::new(20, Allocator::default());
+        bits.set_uint(0, 16, 0xABCD);
+        bits.set_uint(16, 4, 0xE);
+        let json = serde_json::to_string(&bits).unwrap();
+        assert_eq!(json, "[205,171,14]");
+
+        let bits: Bits = serde_json::from_str(&json).unwrap();
+        assert_eq!(bits.len(), 24);
+        assert_eq!(bits.get_uint(0, 20), 0xEABCD);
+    }
+
+    #[test]
+    fn zero_runs_work() {
+        let patterns: [&[usize]; 5] = [&[], &[0], &[1, 2, 3, 9, 100], &[4, 30, 31], &[31]];
+        for len in [0, 8, 1000, 2040] {
+            for set_words in patterns {
+                let mut bits = Bits::new(len, Allocator::default());
+                for &i in set_words.iter().filter(|&&i| i * 64 < len) {
+                    bits.set_uint(i * 64, 8, 0xA5);
+                }
+                let mut raw = Vec::new();
+                bits.write_to(&mut raw, BitsEncoding::Raw).unwrap();
+                let mut encoded = Vec::new();
+                bits.write_to(&mut encoded, BitsEncoding::ZeroRuns).unwrap();
+                if len == 2040 && set_words.len() < 3 {
+                    assert!(encoded.len() < raw.len() / 4);
+                }
+
+                let mut reader = &encoded[..];
+                let read = Bits::read_from(&mut reader, BitsEncoding::ZeroRuns).unwrap();
+                assert!(reader.is_empty());
+                assert_eq!(read.len(), bits.len());
+                assert_eq!(read.words(), bits.words());
+
+                let truncated = &encoded[..encoded.len() - 1];
+                assert!(Bits::read_from(&mut &truncated[..], BitsEncoding::ZeroRuns).is_err());
+            }
+        }
+    }
 }
@@ -0,0 +1,152 @@
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::cuckoo_filter::{CuckooFilter, LayerLayout};
+use crate::hashers::fmix64;
+use crate::{DefaultHasher, HashWidth};
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Classic Bloom filter made by [`ScalableCuckooFilter::to_bloom`].
+///
+/// The Bloom filter is derived from the entries of the cuckoo filter rather than from the items,
+/// so it contains exactly the items the cuckoo filter contains (plus its own false positives).
+/// Each entry is keyed by the index of its layer, the smaller of its two candidate bucket
+/// indices and its fingerprint (the canonical entry of [`LayerDump`]), and a lookup probes the
+/// canonical entry of the item in every layer.
+///
+/// The bitset is laid out so that other systems can reproduce lookups given the canonical
+/// entries: the key of the entry `(bucket_index, fingerprint)` of the `layer`-th layer is
+/// `h = fmix64(fmix64((layer << 48) ^ bucket_index) ^ fingerprint)` where `fmix64` is
+/// the finalizer of MurmurHash3, and
+/// the `i`-th probed bit (`0 <= i < number_of_hashes`) is `(h1 + i * h2) % number_of_bits`
+/// where `h1` and `h2` are the lower and upper 32 bits of `h` respectively
+/// (computed in 64-bit arithmetic).
+/// Bit `j` is stored in `as_bytes()[j / 8]` at position `j % 8` (least significant bit first).
+///
+/// [`ScalableCuckooFilter::to_bloom`]: crate::ScalableCuckooFilter::to_bloom
+/// [`LayerDump`]: crate::LayerDump
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(bound(
+        serialize = "H: crate::HasherKeys",
+        deserialize = "H: Default + crate::HasherKeys"
+    ))
+)]
+pub struct BloomFilter<T: ?Sized, H = DefaultHasher> {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, with = "crate::hashers::serde_keys")
+    )]
+    hasher: H,
+    hash_width: HashWidth,
+    layers: Vec<LayerLayout>,
+    number_of_bits: u64,
+    number_of_hashes: u32,
+    #[cfg_attr(feature = "serde_support", serde(with = "serde_bytes"))]
+    bits: Vec<u8>,
+    _item: PhantomData<fn(&T)>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> BloomFilter<T, H> {
+    /// Builds a `BloomFilter` containing the canonical entries of `layers`,
+    /// using `bits_per_item` bits per entry.
+    ///
+    /// The number of hash functions is chosen to minimize the false positive probability.
+    pub(crate) fn new(
+        hasher: H,
+        hash_width: HashWidth,
+        bits_per_item: usize,
+        layers: &[CuckooFilter],
+    ) -> Self {
+        assert_ne!(bits_per_item, 0);
+        let entries = layers.iter().map(CuckooFilter::len).sum::<usize>();
+        let number_of_bits = (entries.max(1) * bits_per_item).next_multiple_of(8) as u64;
+        let number_of_hashes =
+            ((bits_per_item as f64 * std::f64::consts::LN_2).round() as u32).max(1);
+        let mut filter = BloomFilter {
+            hasher,
+            hash_width,
+            layers: layers.iter().map(CuckooFilter::layout).collect(),
+            number_of_bits,
+            number_of_hashes,
+            bits: vec![0; number_of_bits as usize / 8],
+            _item: PhantomData,
+        };
+        for (layer_index, layer) in layers.iter().enumerate() {
+            for (bucket_index, fingerprint) in layer.canonical_entries(&filter.hasher) {
+                let h = entry_key(layer_index, bucket_index, fingerprint);
+                for i in 0..filter.number_of_hashes {
+                    let position = filter.position(h, i);
+                    filter.bits[position / 8] |= 1 << (position % 8);
+                }
+            }
+        }
+        filter
+    }
+
+    /// Returns the number of bits of the bitset.
+    pub fn number_of_bits(&self) -> u64 {
+        self.number_of_bits
+    }
+
+    /// Returns the number of bits probed for each entry.
+    pub fn number_of_hashes(&self) -> u32 {
+        self.number_of_hashes
+    }
+
+    /// Returns the bitset.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    pub fn contains(&self, item: &T) -> bool {
+        let item_hash = crate::item_hash(&self.hasher, item, self.hash_width);
+        self.layers.iter().enumerate().any(|(layer_index, layer)| {
+            let (bucket_index, fingerprint) = layer.canonical_entry(&self.hasher, item_hash);
+            let h = entry_key(layer_index, bucket_index, fingerprint);
+            (0..self.number_of_hashes).all(|i| {
+                let position = self.position(h, i);
+                self.bits[position / 8] & (1 << (position % 8)) != 0
+            })
+        })
+    }
+
+    fn position(&self, hash: u64, i: u32) -> usize {
+        let h1 = hash & 0xFFFF_FFFF;
+        let h2 = hash >> 32;
+        (h1.wrapping_add(u64::from(i).wrapping_mul(h2)) % self.number_of_bits) as usize
+    }
+}
+
+fn entry_key(layer_index: usize, bucket_index: usize, fingerprint: u64) -> u64 {
+    fmix64(fmix64(((layer_index as u64) << 48) ^ bucket_index as u64) ^ fingerprint)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ScalableCuckooFilter;
+
+    #[test]
+    fn it_works() {
+        let mut filter = ScalableCuckooFilter::<usize>::new(1000, 0.0001);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert!(filter.layers().len() > 1);
+
+        let bloom = filter.to_bloom(10);
+        assert_eq!(bloom.number_of_bits(), 100_000);
+        assert_eq!(bloom.number_of_hashes(), 7);
+        assert_eq!(bloom.as_bytes().len(), 12_500);
+        assert!((0..10_000).all(|i| bloom.contains(&i)));
+
+        // Each layer is probed, so the false positive probability is about 0.8% per layer.
+        let layers = filter.layers().len();
+        let false_positives = (10_000..110_000).filter(|i| bloom.contains(i)).count();
+        assert!(false_positives < 1200 * layers, "{}", false_positives);
+    }
+}
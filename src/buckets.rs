@@ -1,149 +1,629 @@
 use rand::Rng;
 
-use crate::bits::Bits;
+use std::io::{self, Read, Write};
+
+use crate::alloc::Allocator;
+use crate::bits::{BitArray, Bits};
+use crate::codec;
+
+#[cfg(feature = "simd")]
+use wide::{i64x4, CmpEq};
 
 #[cfg(feature = "serde_support")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
-pub struct Buckets {
-    fingerprint_bitwidth: usize, // fingerprint length in bits
-    entries_per_bucket: usize,   // number of entries per bucket
-    bucket_bitwidth: usize,
-    bucket_index_bitwidth: usize,
-    bits: Bits,
+/// Fixed-size buckets of a layer.
+///
+/// The layout parameters are packed into a small `Copy` header next to the bits,
+/// and serialized as separate fields (see `BucketsRepr`).
+/// Lookups also work on borrowed bits (see `ScalableCuckooFilterView`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buckets<B = Bits> {
+    header: Header,
+    bits: B,
 }
 impl Buckets {
     pub fn new(
         fingerprint_bitwidth: usize,
         entries_per_bucket: usize,
         number_of_buckets_hint: usize,
+        power_of_two: bool,
+        alloc: Allocator,
     ) -> Self {
-        let bucket_index_bitwidth =
-            number_of_buckets_hint.next_power_of_two().trailing_zeros() as usize;
-        let bucket_bitwidth = fingerprint_bitwidth * entries_per_bucket;
-        let bits = Bits::new(bucket_bitwidth << bucket_index_bitwidth);
-        Buckets {
+        let len = Self::required_number_of_buckets(number_of_buckets_hint, power_of_two);
+        let header = Header::new(
             fingerprint_bitwidth,
             entries_per_bucket,
-            bucket_bitwidth,
-            bucket_index_bitwidth,
-            bits,
+            len,
+            power_of_two,
+            None,
+        );
+        Buckets {
+            header,
+            bits: Bits::new(header.bucket_bitwidth * len, alloc),
         }
     }
 
+    /// Makes buckets grouped into blocks that occupy whole cache lines.
+    ///
+    /// The alternative index of a bucket is always in the same block, so looking up
+    /// an item touches a single cache line.
+    /// In the exact mode, the number of buckets is rounded up to a multiple of the block size.
+    pub fn new_blocked(
+        fingerprint_bitwidth: usize,
+        entries_per_bucket: usize,
+        number_of_buckets_hint: usize,
+        power_of_two: bool,
+        alloc: Allocator,
+    ) -> Self {
+        let mut len = Self::required_number_of_buckets(number_of_buckets_hint, power_of_two);
+        let bucket_bitwidth = fingerprint_bitwidth * entries_per_bucket;
+
+        // At least two buckets per block, or items would have no alternative bucket.
+        let fitting_buckets = (CACHE_LINE_BITS / bucket_bitwidth).max(2);
+        let buckets_per_block = 1 << fitting_buckets.ilog2();
+        if power_of_two {
+            len = len.max(buckets_per_block);
+        } else {
+            len = len.next_multiple_of(buckets_per_block);
+        }
+        let block = BlockLayout {
+            buckets_per_block,
+            block_bitwidth: (bucket_bitwidth * buckets_per_block).next_multiple_of(CACHE_LINE_BITS),
+        };
+        Buckets {
+            header: Header::new(
+                fingerprint_bitwidth,
+                entries_per_bucket,
+                len,
+                power_of_two,
+                Some(block),
+            ),
+            bits: Bits::new(block.block_bitwidth * (len / buckets_per_block), alloc),
+        }
+    }
+
+    #[inline]
+    pub fn required_number_of_buckets(number_of_buckets_hint: usize, power_of_two: bool) -> usize {
+        if power_of_two {
+            number_of_buckets_hint.next_power_of_two()
+        } else {
+            number_of_buckets_hint.max(1)
+        }
+    }
+
+    #[inline]
+    pub fn heap_bytes(&self) -> usize {
+        self.bits.heap_bytes()
+    }
+
     #[inline]
-    pub fn required_number_of_buckets(number_of_buckets_hint: usize) -> usize {
-        number_of_buckets_hint.next_power_of_two()
+    pub fn allocator(&self) -> &Allocator {
+        self.bits.allocator()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(self)
+    }
+
+    #[inline]
+    pub fn try_insert(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+        debug_assert_ne!(fingerprint, 0);
+        if let Some(i) = self.find_entry(bucket_index, 0) {
+            self.set_fingerprint(bucket_index, i, fingerprint);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    pub fn random_swap<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        bucket_index: usize,
+        fingerprint: u64,
+    ) -> (usize, u64) {
+        let i = rng.gen_range(0..self.entries_per_bucket());
+        let f = self.get_fingerprint(bucket_index, i);
+        self.set_fingerprint(bucket_index, i, fingerprint);
+
+        debug_assert_ne!(fingerprint, 0);
+        debug_assert_eq!(fingerprint, self.get_fingerprint(bucket_index, i));
+        debug_assert_ne!(f, 0);
+        (i, f)
+    }
+
+    #[inline]
+    pub fn remove_fingerprint(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+        debug_assert_ne!(fingerprint, 0);
+        if let Some(i) = self.find_entry(bucket_index, fingerprint) {
+            self.set_fingerprint(bucket_index, i, 0);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    pub fn set_fingerprint(&mut self, bucket_index: usize, entry_index: usize, fingerprint: u64) {
+        let offset = self.bucket_offset(bucket_index) + self.fingerprint_bitwidth() * entry_index;
+        self.bits
+            .set_uint(offset, self.fingerprint_bitwidth(), fingerprint);
+    }
+
+    /// Splits the buckets into disjoint chunks that can be modified in parallel.
+    ///
+    /// `buckets_per_chunk` must be a multiple of 64 and of `buckets_per_block()` so that
+    /// every chunk starts at a word boundary.
+    #[cfg(feature = "rayon")]
+    pub fn chunks_mut(&mut self, buckets_per_chunk: usize) -> Vec<ChunkMut<'_>> {
+        debug_assert_eq!(buckets_per_chunk % 64, 0);
+        debug_assert_eq!(buckets_per_chunk % self.buckets_per_block(), 0);
+        let header = self.header;
+        self.bits
+            .chunks_mut(self.bucket_offset(buckets_per_chunk))
+            .enumerate()
+            .map(|(i, words)| ChunkMut {
+                header,
+                first_bucket_index: i * buckets_per_chunk,
+                words,
+            })
+            .collect()
+    }
+}
+impl Buckets<()> {
+    /// Makes the layout of `len` (a power of two) unblocked buckets, which indexes and
+    /// fingerprints items the same way as `MortonBuckets` of the same size.
+    pub fn power_of_two_layout(
+        fingerprint_bitwidth: usize,
+        entries_per_bucket: usize,
+        len: usize,
+    ) -> Self {
+        debug_assert!(len.is_power_of_two());
+        Buckets {
+            header: Header::new(fingerprint_bitwidth, entries_per_bucket, len, true, None),
+            bits: (),
+        }
+    }
+}
+impl<B: BitArray> Buckets<B> {
+    #[inline]
+    pub fn is_power_of_two(&self) -> bool {
+        self.header.exact_len == 0
+    }
+
+    #[inline]
+    pub fn is_blocked(&self) -> bool {
+        self.header.block_shift != 0
+    }
+
+    /// Returns the number of buckets of each block (`1` if the buckets aren't blocked).
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn buckets_per_block(&self) -> usize {
+        1 << self.header.block_shift
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        1 << self.bucket_index_bitwidth
+        self.header.len()
     }
 
     #[inline]
     pub fn entries(&self) -> usize {
-        self.len() * self.entries_per_bucket
+        self.len() * self.entries_per_bucket()
     }
 
     #[inline]
     pub fn bits(&self) -> u64 {
         self.bits.len() as u64
     }
+}
+impl<B> Buckets<B> {
+    /// Returns the layout of these buckets without the bits, which indexes and
+    /// fingerprints items the same way.
+    #[inline]
+    pub fn layout(&self) -> Buckets<()> {
+        Buckets {
+            header: self.header,
+            bits: (),
+        }
+    }
 
     #[inline]
     pub fn index(&self, hash: u64) -> usize {
-        (hash & ((1 << self.bucket_index_bitwidth) - 1)) as usize
+        match self.header.exact_len {
+            0 => (hash & ((1 << self.header.bucket_index_bitwidth) - 1)) as usize,
+            // Not the multiply-shift reduction, which would take the upper bits
+            // the fingerprints are taken from in `HashWidth::Bits64` mode.
+            len => (hash % len as u64) as usize,
+        }
     }
 
+    /// Returns the alternative index of the bucket `i` for an entry whose offset is `offset`.
+    ///
+    /// Applying this twice with the same offset yields `i` again.
     #[inline]
-    pub fn fingerprint(&self, hash: u64) -> u64 {
-        hash >> (64 - self.fingerprint_bitwidth)
+    pub fn alt_index(&self, i: usize, offset: u64) -> usize {
+        match (self.header.exact_len, self.header.block_shift) {
+            (_, 1..) => {
+                // The distance is never zero, or a fraction `1 / buckets_per_block` of the items
+                // would have a single candidate bucket and overflow it long before the block is full.
+                let n = (1u64 << self.header.block_shift) - 1;
+                let distance = (((offset & 0xFFFF_FFFF) * n) >> 32) + 1;
+                i ^ distance as usize
+            }
+            (0, 0) => self.index(i as u64 ^ offset),
+            (len, 0) => {
+                let o = self.index(offset);
+                if o >= i {
+                    o - i
+                } else {
+                    o + len - i
+                }
+            }
+        }
     }
 
+    #[inline]
+    pub fn fingerprint(&self, hash: u64) -> u64 {
+        hash >> (64 - self.header.fingerprint_bitwidth)
+    }
+}
+impl<B: BitArray> Buckets<B> {
     #[inline]
     pub fn entries_per_bucket(&self) -> usize {
-        self.entries_per_bucket
+        self.header.entries_per_bucket as usize
     }
 
     #[inline]
     pub fn fingerprint_bitwidth(&self) -> usize {
-        self.fingerprint_bitwidth
+        self.header.fingerprint_bitwidth as usize
     }
 
     #[inline]
-    pub fn iter(&self) -> Iter {
-        Iter::new(self)
+    pub fn contains(&self, bucket_index: usize, fingerprint: u64) -> bool {
+        debug_assert_ne!(fingerprint, 0);
+        self.find_entry(bucket_index, fingerprint).is_some()
     }
 
+    /// Returns `true` if either of the buckets `i0` and `i1` contains `fingerprint`.
+    ///
+    /// Both buckets are always examined and the results are combined without branches,
+    /// which avoids mispredictions on lookups of absent items.
+    /// If `fingerprint` is zero, this tells whether either bucket has a free entry.
     #[inline]
-    pub fn contains(&self, bucket_index: usize, fingerprint: u64) -> bool {
-        debug_assert_ne!(fingerprint, 0);
-        for i in 0..self.entries_per_bucket {
-            let f = self.get_fingerprint(bucket_index, i);
-            if f == fingerprint {
-                return true;
-            }
+    pub fn contains_either(&self, i0: usize, i1: usize, fingerprint: u64) -> bool {
+        #[cfg(feature = "simd")]
+        if self.entries_per_bucket() <= SIMD_LANES {
+            let needle = i64x4::splat(fingerprint as i64);
+            let found = self.load_lanes(i0, fingerprint).cmp_eq(needle)
+                | self.load_lanes(i1, fingerprint).cmp_eq(needle);
+            return found.any();
+        }
+        if self.header.bucket_bitwidth <= 64 {
+            (self.swar_matches(i0, fingerprint) | self.swar_matches(i1, fingerprint)) != 0
+        } else {
+            (0..self.entries_per_bucket()).fold(false, |found, i| {
+                found
+                    | (self.get_fingerprint(i0, i) == fingerprint)
+                    | (self.get_fingerprint(i1, i) == fingerprint)
+            })
         }
-        false
     }
 
+    /// Returns the index of the first entry of the bucket that equals `fingerprint`.
     #[inline]
-    pub fn try_insert(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
-        debug_assert_ne!(fingerprint, 0);
-        for i in 0..self.entries_per_bucket {
-            let f = self.get_fingerprint(bucket_index, i);
-            if f == 0 {
-                self.set_fingerprint(bucket_index, i, fingerprint);
-                return true;
+    fn find_entry(&self, bucket_index: usize, fingerprint: u64) -> Option<usize> {
+        #[cfg(feature = "simd")]
+        if self.entries_per_bucket() <= SIMD_LANES {
+            let needle = i64x4::splat(fingerprint as i64);
+            let mask = self
+                .load_lanes(bucket_index, fingerprint)
+                .cmp_eq(needle)
+                .move_mask();
+            return (mask != 0).then(|| mask.trailing_zeros() as usize);
+        }
+        if self.header.bucket_bitwidth <= 64 {
+            // The lowest set bit of the matches is exactly at the first equal entry.
+            let matches = self.swar_matches(bucket_index, fingerprint);
+            (matches != 0).then(|| matches.trailing_zeros() as usize / self.fingerprint_bitwidth())
+        } else {
+            (0..self.entries_per_bucket())
+                .find(|&i| self.get_fingerprint(bucket_index, i) == fingerprint)
+        }
+    }
+
+    /// Reads the whole bucket at once and compares all the entries in parallel (SWAR).
+    ///
+    /// The result is non-zero iff the bucket contains `fingerprint`, and its lowest set bit is
+    /// in the lane of the first equal entry (borrows may only set the bits of higher lanes).
+    /// The bucket must fit in a word.
+    #[inline]
+    fn swar_matches(&self, bucket_index: usize, fingerprint: u64) -> u64 {
+        debug_assert!(self.header.bucket_bitwidth <= 64);
+        let bucket = self.bits.get_uint(
+            self.bucket_offset(bucket_index),
+            self.header.bucket_bitwidth,
+        );
+        let lows = LANE_LOW_BITS[self.fingerprint_bitwidth()]
+            & (u64::MAX >> (64 - self.header.bucket_bitwidth));
+        let highs = lows << (self.fingerprint_bitwidth() - 1);
+
+        // An entry is equal to `fingerprint` iff its lane in `x` is zero.
+        let x = bucket ^ fingerprint.wrapping_mul(lows);
+        x.wrapping_sub(lows) & !x & highs
+    }
+
+    /// Loads the entries of the bucket into the lanes of a vector.
+    ///
+    /// The unused lanes are filled with a value that differs from `fingerprint`.
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn load_lanes(&self, bucket_index: usize, fingerprint: u64) -> i64x4 {
+        let mut lanes = [!fingerprint as i64; SIMD_LANES];
+        if self.header.bucket_bitwidth <= 64 {
+            let bucket = self.bits.get_uint(
+                self.bucket_offset(bucket_index),
+                self.header.bucket_bitwidth,
+            );
+            let mask = u64::MAX >> (64 - self.fingerprint_bitwidth());
+            for (i, lane) in lanes.iter_mut().enumerate().take(self.entries_per_bucket()) {
+                *lane = ((bucket >> (i * self.fingerprint_bitwidth())) & mask) as i64;
+            }
+        } else {
+            for (i, lane) in lanes.iter_mut().enumerate().take(self.entries_per_bucket()) {
+                *lane = self.get_fingerprint(bucket_index, i) as i64;
             }
         }
-        false
+        i64x4::from(lanes)
     }
 
     #[inline]
-    pub fn random_swap<R: Rng>(
-        &mut self,
-        rng: &mut R,
-        bucket_index: usize,
-        fingerprint: u64,
-    ) -> u64 {
-        let i = rng.gen_range(0..self.entries_per_bucket);
-        let f = self.get_fingerprint(bucket_index, i);
-        self.set_fingerprint(bucket_index, i, fingerprint);
+    fn bucket_offset(&self, bucket_index: usize) -> usize {
+        self.header.bucket_offset(bucket_index)
+    }
 
-        debug_assert_ne!(fingerprint, 0);
-        debug_assert_eq!(fingerprint, self.get_fingerprint(bucket_index, i));
-        debug_assert_ne!(f, 0);
-        f
+    #[inline]
+    pub fn prefetch(&self, bucket_index: usize) {
+        self.bits.prefetch(self.bucket_offset(bucket_index));
     }
 
     #[inline]
-    pub fn remove_fingerprint(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+    pub fn get_fingerprint(&self, bucket_index: usize, entry_index: usize) -> u64 {
+        let offset = self.bucket_offset(bucket_index) + self.fingerprint_bitwidth() * entry_index;
+        self.bits.get_uint(offset, self.fingerprint_bitwidth())
+    }
+}
+
+impl Buckets {
+    /// Writes the header, which precedes the bits in a serialized layer.
+    pub fn write_head_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let h = &self.header;
+        codec::write_u8(writer, h.fingerprint_bitwidth)?;
+        codec::write_u32(writer, h.entries_per_bucket)?;
+        codec::write_u8(writer, h.bucket_index_bitwidth)?;
+        codec::write_usize(writer, h.exact_len)?;
+        codec::write_u8(writer, h.block_shift)?;
+        codec::write_usize(writer, h.block_bitwidth)
+    }
+}
+impl<B: BitArray> Buckets<B> {
+    /// Returns the bit array holding the buckets.
+    #[inline]
+    pub fn bit_array(&self) -> &B {
+        &self.bits
+    }
+
+    #[inline]
+    pub fn bit_array_mut(&mut self) -> &mut B {
+        &mut self.bits
+    }
+
+    /// Reads the layout written by `write_to` and then the bits with `read_bits`.
+    pub fn read_with<R: Read>(
+        reader: &mut R,
+        read_bits: impl FnOnce(&mut R) -> io::Result<B>,
+    ) -> io::Result<Self> {
+        let fingerprint_bitwidth = usize::from(codec::read_u8(reader)?);
+        let entries_per_bucket = codec::read_u32(reader)? as usize;
+        let bucket_index_bitwidth = u32::from(codec::read_u8(reader)?);
+        let exact_len = codec::read_usize(reader)?;
+        let block_shift = u32::from(codec::read_u8(reader)?);
+        let block_bitwidth = codec::read_usize(reader)?;
+        if !(1..=64).contains(&fingerprint_bitwidth)
+            || entries_per_bucket == 0
+            || bucket_index_bitwidth >= usize::BITS
+            || block_shift >= usize::BITS
+        {
+            return Err(codec::invalid_data("invalid bucket layout"));
+        }
+        let block = (block_shift != 0).then_some(BlockLayout {
+            buckets_per_block: 1 << block_shift,
+            block_bitwidth,
+        });
+        let len = if exact_len == 0 {
+            1 << bucket_index_bitwidth
+        } else {
+            exact_len
+        };
+        let header = Header::new(
+            fingerprint_bitwidth,
+            entries_per_bucket,
+            len,
+            exact_len == 0,
+            block,
+        );
+        let bits = read_bits(reader)?;
+        if header.bucket_index_bitwidth as u32 != bucket_index_bitwidth
+            || bits.len() < header.bucket_offset(len)
+        {
+            return Err(codec::invalid_data("invalid bucket layout"));
+        }
+        Ok(Buckets { header, bits })
+    }
+}
+
+/// Mutable view of a contiguous range of buckets.
+#[cfg(feature = "rayon")]
+#[derive(Debug)]
+pub struct ChunkMut<'a> {
+    header: Header,
+    first_bucket_index: usize,
+    words: &'a mut [u64],
+}
+#[cfg(feature = "rayon")]
+impl ChunkMut<'_> {
+    /// Inserts `fingerprint` into the bucket `bucket_index` (not relative to the chunk)
+    /// if it has a free entry.
+    #[inline]
+    pub fn try_insert(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
         debug_assert_ne!(fingerprint, 0);
-        for i in 0..self.entries_per_bucket {
-            let f = self.get_fingerprint(bucket_index, i);
-            if f == fingerprint {
-                self.set_fingerprint(bucket_index, i, 0);
+        let fingerprint_bitwidth = self.header.fingerprint_bitwidth as usize;
+        let bucket_offset = self.header.bucket_offset(bucket_index)
+            - self.header.bucket_offset(self.first_bucket_index);
+        for i in 0..self.header.entries_per_bucket as usize {
+            let offset = bucket_offset + fingerprint_bitwidth * i;
+            if crate::bits::get_uint(self.words, offset, fingerprint_bitwidth) == 0 {
+                crate::bits::set_uint(self.words, offset, fingerprint_bitwidth, fingerprint);
                 return true;
             }
         }
         false
     }
+}
+
+/// Layout parameters of `Buckets`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    bucket_bitwidth: usize,
+
+    // A multiple of `CACHE_LINE_BITS`, or zero unless the buckets are blocked.
+    block_bitwidth: usize,
+
+    // Zero if the number of buckets is `2^bucket_index_bitwidth`.
+    exact_len: usize,
+
+    entries_per_bucket: u32,
+    fingerprint_bitwidth: u8,
+    bucket_index_bitwidth: u8,
+
+    // `log2(buckets_per_block)`, or zero unless the buckets are blocked.
+    block_shift: u8,
+}
+impl Header {
+    fn new(
+        fingerprint_bitwidth: usize,
+        entries_per_bucket: usize,
+        len: usize,
+        power_of_two: bool,
+        block: Option<BlockLayout>,
+    ) -> Self {
+        debug_assert!((1..=64).contains(&fingerprint_bitwidth));
+        Header {
+            bucket_bitwidth: fingerprint_bitwidth * entries_per_bucket,
+            block_bitwidth: block.map_or(0, |b| b.block_bitwidth),
+            exact_len: if power_of_two { 0 } else { len },
+            entries_per_bucket: u32::try_from(entries_per_bucket)
+                .expect("too many entries per bucket"),
+            fingerprint_bitwidth: fingerprint_bitwidth as u8,
+            bucket_index_bitwidth: len.next_power_of_two().trailing_zeros() as u8,
+            block_shift: block.map_or(0, |b| b.buckets_per_block.trailing_zeros() as u8),
+        }
+    }
 
     #[inline]
-    fn set_fingerprint(&mut self, bucket_index: usize, entry_index: usize, fingerprint: u64) {
-        let offset = self.bucket_bitwidth * bucket_index + self.fingerprint_bitwidth * entry_index;
-        self.bits
-            .set_uint(offset, self.fingerprint_bitwidth, fingerprint);
+    fn len(&self) -> usize {
+        match self.exact_len {
+            0 => 1 << self.bucket_index_bitwidth,
+            len => len,
+        }
     }
 
+    /// Returns the position of the first bit of the bucket `bucket_index`.
     #[inline]
-    fn get_fingerprint(&self, bucket_index: usize, entry_index: usize) -> u64 {
-        let offset = self.bucket_bitwidth * bucket_index + self.fingerprint_bitwidth * entry_index;
-        self.bits.get_uint(offset, self.fingerprint_bitwidth)
+    fn bucket_offset(&self, bucket_index: usize) -> usize {
+        if self.block_shift == 0 {
+            self.bucket_bitwidth * bucket_index
+        } else {
+            let in_block = bucket_index & ((1 << self.block_shift) - 1);
+            self.block_bitwidth * (bucket_index >> self.block_shift)
+                + self.bucket_bitwidth * in_block
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+struct BlockLayout {
+    buckets_per_block: usize, // always a power of two
+    block_bitwidth: usize,    // a multiple of `CACHE_LINE_BITS`
+}
+
+const CACHE_LINE_BITS: usize = 512;
+
+/// Serialized form of `Buckets`, which keeps the fields of the unpacked layout.
+#[cfg(feature = "serde_support")]
+#[derive(Serialize, Deserialize)]
+struct BucketsRepr<B> {
+    fingerprint_bitwidth: usize,
+    entries_per_bucket: usize,
+    bucket_bitwidth: usize,
+    bucket_index_bitwidth: usize,
+    #[serde(default)]
+    exact_len: Option<usize>,
+    #[serde(default)]
+    block: Option<BlockLayout>,
+    bits: B,
+}
+#[cfg(feature = "serde_support")]
+impl<B: Serialize> Serialize for Buckets<B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let h = &self.header;
+        BucketsRepr {
+            fingerprint_bitwidth: h.fingerprint_bitwidth as usize,
+            entries_per_bucket: h.entries_per_bucket as usize,
+            bucket_bitwidth: h.bucket_bitwidth,
+            bucket_index_bitwidth: h.bucket_index_bitwidth as usize,
+            exact_len: (h.exact_len != 0).then_some(h.exact_len),
+            block: (h.block_shift != 0).then_some(BlockLayout {
+                buckets_per_block: 1 << h.block_shift,
+                block_bitwidth: h.block_bitwidth,
+            }),
+            bits: &self.bits,
+        }
+        .serialize(serializer)
+    }
+}
+#[cfg(feature = "serde_support")]
+impl<'de, B: Deserialize<'de>> Deserialize<'de> for Buckets<B> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let repr = BucketsRepr::<B>::deserialize(deserializer)?;
+        if !(1..=64).contains(&repr.fingerprint_bitwidth)
+            || u32::try_from(repr.entries_per_bucket).is_err()
+            || repr.bucket_bitwidth != repr.fingerprint_bitwidth * repr.entries_per_bucket
+            || repr.bucket_index_bitwidth >= usize::BITS as usize
+            || repr
+                .block
+                .is_some_and(|b| !b.buckets_per_block.is_power_of_two())
+        {
+            return Err(D::Error::custom("invalid bucket layout"));
+        }
+        let len = repr.exact_len.unwrap_or(1 << repr.bucket_index_bitwidth);
+        Ok(Buckets {
+            header: Header::new(
+                repr.fingerprint_bitwidth,
+                repr.entries_per_bucket,
+                len,
+                repr.exact_len.is_none(),
+                repr.block,
+            ),
+            bits: repr.bits,
+        })
     }
 }
 
@@ -168,16 +648,13 @@ impl<'a> Iterator for Iter<'a> {
         loop {
             if self.bucket_i == self.buckets.len() {
                 return None;
-            } else if self.entry_i == self.buckets.entries_per_bucket {
+            } else if self.entry_i == self.buckets.entries_per_bucket() {
                 self.bucket_i += 1;
                 self.entry_i = 0;
             } else {
                 let f = self.buckets.get_fingerprint(self.bucket_i, self.entry_i);
-                if f == 0 {
-                    self.bucket_i += 1;
-                    self.entry_i = 0;
-                } else {
-                    self.entry_i += 1;
+                self.entry_i += 1;
+                if f != 0 {
                     return Some((self.bucket_i, f));
                 }
             }
@@ -185,13 +662,31 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+#[cfg(feature = "simd")]
+const SIMD_LANES: usize = 4;
+
+/// `LANE_LOW_BITS[w]` has the lowest bit of every `w`-bit lane of a word set.
+const LANE_LOW_BITS: [u64; 65] = {
+    let mut table = [0; 65];
+    let mut w = 1;
+    while w <= 64 {
+        let mut i = 0;
+        while i < 64 {
+            table[w] |= 1 << i;
+            i += w;
+        }
+        w += 1;
+    }
+    table
+};
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn it_works() {
-        let mut buckets = Buckets::new(8, 4, 1000);
+        let mut buckets = Buckets::new(8, 4, 1000, true, Allocator::default());
         assert_eq!(buckets.len(), 1024);
         assert_eq!(buckets.bits(), 1024 * 8 * 4);
 
@@ -202,8 +697,140 @@ mod test {
         }
         assert!(!buckets.try_insert(333, 104)); // full
 
-        let old = buckets.random_swap(&mut rand::thread_rng(), 333, 104);
+        let (i, old) = buckets.random_swap(&mut rand::thread_rng(), 333, 104);
         assert!(buckets.contains(333, 104));
         assert!(!buckets.contains(333, old));
+        assert_eq!(buckets.get_fingerprint(333, i), 104);
+    }
+
+    #[test]
+    fn exact_len_works() {
+        let buckets = Buckets::new(8, 4, 1000, false, Allocator::default());
+        assert_eq!(buckets.len(), 1000);
+        assert_eq!(buckets.bits(), 1000 * 8 * 4);
+        assert!((0..10_000).all(|h| buckets.index(h * 0x9E37_79B9) < 1000));
+        for i in [0, 1, 500, 999] {
+            for offset in [0, 1, 999, 1000, u64::MAX] {
+                let alt = buckets.alt_index(i, offset);
+                assert!(alt < 1000);
+                assert_eq!(buckets.alt_index(alt, offset), i);
+            }
+        }
+    }
+
+    #[test]
+    fn blocked_layout_works() {
+        for (power_of_two, len) in [(true, 1024), (false, 1008)] {
+            let mut buckets = Buckets::new_blocked(8, 4, 1000, power_of_two, Allocator::default());
+            assert_eq!(buckets.bits.as_ptr() as usize % (CACHE_LINE_BITS / 8), 0);
+            assert_eq!(buckets.len(), len);
+            assert_eq!(buckets.bits(), len as u64 * 8 * 4);
+            for i in [0, 1, 15, 16, 500, len - 1] {
+                for offset in [0, 1, 15, 999, 1000, u64::MAX] {
+                    let alt = buckets.alt_index(i, offset);
+                    assert_ne!(alt, i);
+                    assert_eq!(alt / 16, i / 16);
+                    assert_eq!(buckets.alt_index(alt, offset), i);
+                }
+            }
+
+            for i in 0..len {
+                assert!(buckets.try_insert(i, i as u64 % 255 + 1));
+            }
+            let entries = buckets.iter().collect::<Vec<_>>();
+            assert_eq!(entries.len(), len);
+            assert!(entries.iter().all(|&(i, f)| f == i as u64 % 255 + 1));
+        }
+
+        // Buckets wider than a cache line are paired in blocks of three lines.
+        let alloc = Allocator::new(std::sync::Arc::new(std::alloc::System));
+        let buckets = Buckets::new_blocked(64, 10, 100, true, alloc);
+        assert_eq!(buckets.bits.as_ptr() as usize % (CACHE_LINE_BITS / 8), 0);
+        assert_eq!(buckets.bits(), 64 * 1536);
+        assert_eq!(buckets.alt_index(6, 1), 7);
+    }
+
+    #[test]
+    fn swar_lookups_work() {
+        for (fingerprint_bitwidth, entries_per_bucket) in [
+            (1, 4),
+            (3, 5),
+            (7, 9),
+            (8, 8),
+            (16, 4),
+            (21, 3),
+            (32, 2),
+            (64, 1),
+            (17, 4),
+        ] {
+            let mut buckets = Buckets::new(
+                fingerprint_bitwidth,
+                entries_per_bucket,
+                8,
+                true,
+                Allocator::default(),
+            );
+            let max = u64::MAX >> (64 - fingerprint_bitwidth);
+            let fingerprints = [1, max, max / 2 + 1, max.saturating_sub(1).max(1)];
+            for &f in &fingerprints {
+                assert!(!buckets.contains(3, f));
+            }
+            for &f in fingerprints.iter().take(entries_per_bucket) {
+                assert!(buckets.try_insert(3, f));
+                assert_eq!(
+                    buckets.find_entry(3, f),
+                    fingerprints.iter().position(|&g| g == f)
+                );
+                assert!(buckets.contains(3, f));
+                assert!(!buckets.contains(2, f) && !buckets.contains(4, f));
+                assert!(buckets.contains_either(2, 3, f) && buckets.contains_either(3, 4, f));
+                assert!(!buckets.contains_either(2, 4, f));
+            }
+            for &f in fingerprints.iter().take(entries_per_bucket) {
+                assert!(buckets.remove_fingerprint(3, f));
+            }
+            assert_eq!(buckets.iter().count(), 0);
+
+            for _ in 0..entries_per_bucket {
+                assert!(buckets.try_insert(5, max));
+            }
+            assert!(!buckets.try_insert(5, max));
+            assert_eq!(
+                buckets.iter().filter(|&(i, _)| i == 5).count(),
+                entries_per_bucket
+            );
+        }
+    }
+
+    #[test]
+    fn iter_skips_removed_entries() {
+        let mut buckets = Buckets::new(8, 4, 16, true, Allocator::default());
+        for i in 0..4 {
+            assert!(buckets.try_insert(3, 100 + i));
+        }
+        assert!(buckets.try_insert(5, 200));
+        assert!(buckets.remove_fingerprint(3, 101));
+
+        let entries = buckets.iter().collect::<Vec<_>>();
+        assert_eq!(entries, [(3, 100), (3, 102), (3, 103), (5, 200)]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_support")]
+    fn serialized_with_unpacked_layout() {
+        let mut buckets = Buckets::new_blocked(8, 4, 1000, false, Allocator::default());
+        assert!(buckets.try_insert(500, 42));
+        let json = serde_json::to_value(&buckets).unwrap();
+        assert_eq!(json["fingerprint_bitwidth"], 8);
+        assert_eq!(json["bucket_bitwidth"], 32);
+        assert_eq!(json["bucket_index_bitwidth"], 10);
+        assert_eq!(json["exact_len"], 1008);
+        assert_eq!(json["block"]["buckets_per_block"], 16);
+        assert_eq!(json["block"]["block_bitwidth"], 512);
+
+        let buckets: Buckets = serde_json::from_value(json).unwrap();
+        assert_eq!(buckets.len(), 1008);
+        assert!(buckets.is_blocked() && !buckets.is_power_of_two());
+        assert_eq!(buckets.iter().collect::<Vec<_>>(), [(500, 42)]);
     }
 }
@@ -0,0 +1,86 @@
+use rand::Rng;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::ScalableCuckooFilter;
+
+/// Inserter that buffers items and flushes them into a filter in bucket order.
+///
+/// Inserting items in random order causes a random memory access per item.
+/// This inserter accumulates the hashes of the items and, when the buffer becomes full,
+/// sorts them by bucket index before inserting, so that adjacent insertions touch
+/// adjacent memory.
+///
+/// The buffered items are flushed when the buffer becomes full, when [`flush`] is called,
+/// or when the inserter is dropped.
+///
+/// This is created by [`ScalableCuckooFilter::buffered_inserter`].
+///
+/// [`flush`]: BufferedInserter::flush
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilter;
+///
+/// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+/// {
+///     let mut inserter = filter.buffered_inserter(256);
+///     for i in 0..10_000 {
+///         inserter.insert(&i);
+///     }
+/// }
+/// assert_eq!(filter.len(), 10_000);
+/// assert!(filter.contains(&42));
+/// ```
+pub struct BufferedInserter<'a, T: Hash + ?Sized, H: Hasher + Clone, R: Rng> {
+    filter: &'a mut ScalableCuckooFilter<T, H, R>,
+    buffer: Vec<u128>,
+    buffer_size: usize,
+}
+impl<'a, T: Hash + ?Sized, H: Hasher + Clone, R: Rng> BufferedInserter<'a, T, H, R> {
+    pub(crate) fn new(filter: &'a mut ScalableCuckooFilter<T, H, R>, buffer_size: usize) -> Self {
+        let buffer_size = buffer_size.max(1);
+        BufferedInserter {
+            filter,
+            buffer: Vec::with_capacity(buffer_size),
+            buffer_size,
+        }
+    }
+
+    /// Buffers `item` to be inserted into the filter.
+    pub fn insert(&mut self, item: &T) {
+        self.buffer.push(self.filter.item_hash(item));
+        if self.buffer.len() >= self.buffer_size {
+            self.flush();
+        }
+    }
+
+    /// Returns the number of buffered items.
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Inserts all the buffered items into the filter.
+    pub fn flush(&mut self) {
+        let filter = &mut *self.filter;
+        self.buffer
+            .sort_unstable_by_key(|&item_hash| filter.bucket_index(item_hash));
+        for item_hash in self.buffer.drain(..) {
+            filter.raw.insert128(item_hash);
+        }
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> Drop for BufferedInserter<'_, T, H, R> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> fmt::Debug for BufferedInserter<'_, T, H, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferedInserter")
+            .field("buffered_len", &self.buffer.len())
+            .field("buffer_size", &self.buffer_size)
+            .finish()
+    }
+}
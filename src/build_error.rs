@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::fmt;
+
+/// Error returned by [`ScalableCuckooFilterBuilder::build`] when the configuration is invalid.
+///
+/// [`ScalableCuckooFilterBuilder::build`]: crate::ScalableCuckooFilterBuilder::build
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The initial capacity is zero.
+    ZeroInitialCapacity,
+
+    /// The false positive probability is not in the range `(0.0, 1.0]`.
+    InvalidFalsePositiveProbability {
+        /// Configured probability.
+        probability: f64,
+    },
+
+    /// The tightening ratio is not in the range `(0.0, 1.0)`.
+    InvalidTighteningRatio {
+        /// Configured ratio.
+        ratio: f64,
+    },
+
+    /// The number of entries per bucket is zero.
+    ZeroEntriesPerBucket,
+
+    /// One of the bucket counts is zero.
+    ZeroBucketCount {
+        /// Index of the layer whose bucket count is zero.
+        layer: usize,
+    },
+
+    /// The false positive probability of the first layer requires fingerprints longer than
+    /// the hash width allows (64 bits, or 32 bits with `HashWidth::Bits32`).
+    FingerprintTooWide {
+        /// Required fingerprint bitwidth.
+        bitwidth: usize,
+
+        /// Maximum fingerprint bitwidth of the hash width.
+        max_bitwidth: usize,
+    },
+
+    /// The load factor at which the filter grows is not in the range `(0.0, 1.0]`.
+    InvalidLoadFactor {
+        /// Configured load factor.
+        load_factor: f64,
+    },
+}
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::ZeroInitialCapacity => write!(f, "initial capacity must be positive"),
+            BuildError::InvalidFalsePositiveProbability { probability } => write!(
+                f,
+                "false positive probability {probability} is not in the range (0.0, 1.0]"
+            ),
+            BuildError::InvalidTighteningRatio { ratio } => {
+                write!(f, "tightening ratio {ratio} is not in the range (0.0, 1.0)")
+            }
+            BuildError::ZeroEntriesPerBucket => {
+                write!(f, "number of entries per bucket must be positive")
+            }
+            BuildError::ZeroBucketCount { layer } => {
+                write!(f, "bucket count of layer {layer} must be positive")
+            }
+            BuildError::FingerprintTooWide {
+                bitwidth,
+                max_bitwidth,
+            } => write!(
+                f,
+                "false positive probability requires {bitwidth}-bit fingerprints (at most {max_bitwidth})"
+            ),
+            BuildError::InvalidLoadFactor { load_factor } => {
+                write!(
+                    f,
+                    "load factor {load_factor} is not in the range (0.0, 1.0]"
+                )
+            }
+        }
+    }
+}
+impl Error for BuildError {}
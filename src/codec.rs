@@ -0,0 +1,316 @@
+//! Primitives of the binary format written by `write_to` and `to_bytes`.
+//!
+//! A filter starts with `MAGIC` and the format version (`u16`), which are followed by
+//! the `BitsEncoding` tag (`u8`) of the bit arrays, the keys of the hasher (a `bool` telling
+//! whether the hasher is keyed, and two `u64` keys that are zero if it isn't),
+//! the version of the filter (`u64`), which counts its modifications (see `delta`),
+//! the parameters of the filter, and then by the layers, each of which holds its parameters,
+//! its stash, and its bit array.
+//! The CRC-32 (IEEE) of all the preceding bytes follows as a `u32`,
+//! so corrupted or truncated input is detected when read.
+//! All integers are little-endian, `usize` values are encoded as `u64`,
+//! and `f64` values as their IEEE 754 bits.
+//!
+//! Once released, a format version is never changed. Changes of the layout bump
+//! `FORMAT_VERSION`, and the readers keep accepting every earlier version.
+use std::io::{self, Read, Write};
+
+/// Bytes that every serialized filter starts with.
+pub const MAGIC: [u8; 4] = *b"SCKF";
+
+/// Version of the format written by this crate.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Offset of the version of the filter.
+#[cfg(feature = "mmap")]
+pub const FILTER_VERSION_OFFSET: usize = MAGIC.len() + 2 + 1 + 17;
+
+/// Encoding of the bit arrays of a serialized filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitsEncoding {
+    /// Little-endian bytes as they are.
+    Raw,
+
+    /// Little-endian bytes, in which long runs of zero words are replaced by their lengths.
+    ZeroRuns,
+}
+impl BitsEncoding {
+    pub fn tag(self) -> u8 {
+        match self {
+            BitsEncoding::Raw => 0,
+            BitsEncoding::ZeroRuns => 1,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(BitsEncoding::Raw),
+            1 => Some(BitsEncoding::ZeroRuns),
+            _ => None,
+        }
+    }
+
+    /// Reads the encoding written after the header.
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        BitsEncoding::from_tag(read_u8(reader)?)
+            .ok_or_else(|| invalid_data("unknown bit array encoding"))
+    }
+}
+
+/// Writes the keys of the hasher, if any (see `HasherKeys`).
+pub fn write_hasher_keys<W: Write>(writer: &mut W, keys: Option<(u64, u64)>) -> io::Result<()> {
+    let (key0, key1) = keys.unwrap_or_default();
+    write_bool(writer, keys.is_some())?;
+    write_u64(writer, key0)?;
+    write_u64(writer, key1)
+}
+
+/// Reads the keys of the hasher written by `write_hasher_keys`.
+pub fn read_hasher_keys<R: Read>(reader: &mut R) -> io::Result<Option<(u64, u64)>> {
+    let has_keys = read_bool(reader)?;
+    let key0 = read_u64(reader)?;
+    let key1 = read_u64(reader)?;
+    Ok(has_keys.then_some((key0, key1)))
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn write_header<W: Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())
+}
+
+/// Reads the magic and the format version.
+pub fn read_header<R: Read>(reader: &mut R) -> io::Result<()> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("not a serialized scalable cuckoo filter"));
+    }
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    if u16::from_le_bytes(version) != FORMAT_VERSION {
+        return Err(invalid_data("unsupported format version"));
+    }
+    Ok(())
+}
+
+/// Returns the checksum of `bytes`.
+pub fn checksum(bytes: &[u8]) -> u32 {
+    crc32fast::hash(bytes)
+}
+
+/// Checks the checksum read after the bytes whose checksum is `actual`.
+pub fn verify_checksum(expected: u32, actual: u32) -> io::Result<()> {
+    if expected != actual {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "checksum mismatch (expected {expected:08x}, but got {actual:08x}): \
+                 the filter is corrupted or truncated"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Writer that computes the checksum of the bytes written through it.
+#[derive(Debug)]
+pub struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Returns the inner writer, which the bytes are written to.
+    #[cfg(feature = "tokio")]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes the checksum of the bytes written so far and flushes the inner writer.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let checksum = self.hasher.clone().finalize();
+        write_u32(&mut self.inner, checksum)?;
+        self.inner.flush()
+    }
+}
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that computes the checksum of the bytes read through it.
+#[derive(Debug)]
+pub struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> Self {
+        ChecksumReader {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Reads the checksum and compares it with that of the bytes read so far.
+    pub fn verify(&mut self) -> io::Result<()> {
+        let actual = self.hasher.clone().finalize();
+        verify_checksum(read_u32(&mut self.inner)?, actual)
+    }
+}
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+pub fn write_u8<W: Write>(writer: &mut W, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+pub fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_usize<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    write_u64(writer, value as u64)
+}
+
+pub fn write_f64<W: Write>(writer: &mut W, value: f64) -> io::Result<()> {
+    write_u64(writer, value.to_bits())
+}
+
+pub fn write_bool<W: Write>(writer: &mut W, value: bool) -> io::Result<()> {
+    write_u8(writer, u8::from(value))
+}
+
+pub fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn read_usize<R: Read>(reader: &mut R) -> io::Result<usize> {
+    usize::try_from(read_u64(reader)?).map_err(|_| invalid_data("value exceeds usize"))
+}
+
+pub fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    read_u64(reader).map(f64::from_bits)
+}
+
+pub fn read_bool<R: Read>(reader: &mut R) -> io::Result<bool> {
+    match read_u8(reader)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(invalid_data("invalid boolean")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use siphasher::sip::SipHasher13;
+
+    use crate::{HashWidth, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+    /// `golden_filter()` serialized on x86_64.
+    ///
+    /// Every platform must produce and read exactly these bytes.
+    const GOLDEN_V1: &[u8] = include_bytes!("../testdata/golden_v1.bin");
+
+    /// `golden_filter()` serialized compactly on x86_64.
+    const GOLDEN_V1_COMPRESSED: &[u8] = include_bytes!("../testdata/golden_v1_compressed.bin");
+
+    fn golden_filter() -> ScalableCuckooFilter<u64, SipHasher13, StdRng> {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(16)
+            .false_positive_probability(0.01)
+            .hasher(SipHasher13::new())
+            .rng(StdRng::seed_from_u64(7))
+            .for_type::<u64>()
+            .finish();
+        for i in 0..64 {
+            filter.insert(&i);
+        }
+        filter
+    }
+
+    #[test]
+    fn item_hashes_are_portable() {
+        let hasher = SipHasher13::new_with_keys(1, 2);
+        assert_eq!(crate::hash(&hasher, &42u64), 0xe6f8_c611_bbf3_6c67);
+        assert_eq!(crate::hash(&hasher, &42usize), 0xe6f8_c611_bbf3_6c67);
+        assert_eq!(crate::hash(&hasher, &42u32), 0x0a1a_bad7_2dd5_03ee);
+        assert_eq!(crate::hash(&hasher, "foo"), 0x8b0a_338f_5824_92fd);
+        assert_eq!(
+            crate::hash(&hasher, &[1u16, 2, 3][..]),
+            0x27f7_aab6_b865_877c
+        );
+        assert_eq!(
+            crate::item_hash(&hasher, &42u64, HashWidth::Bits128),
+            0x622e_5f15_b9ae_d661_e6f8_c611_bbf3_6c67
+        );
+    }
+
+    #[test]
+    fn golden_vector_works() {
+        assert_eq!(golden_filter().to_bytes().unwrap(), GOLDEN_V1);
+        assert_eq!(
+            golden_filter().to_compressed_bytes().unwrap(),
+            GOLDEN_V1_COMPRESSED
+        );
+
+        for golden in [GOLDEN_V1, GOLDEN_V1_COMPRESSED] {
+            let filter = ScalableCuckooFilter::<u64, SipHasher13>::from_bytes(golden).unwrap();
+            assert_eq!(filter.len(), 64);
+            assert!((0..64).all(|i| filter.contains(&i)));
+            assert_eq!(filter.version(), golden_filter().version());
+            assert_eq!(filter.to_bytes().unwrap(), GOLDEN_V1);
+        }
+        // `SipHasher13::new()` is keyed with zeros.
+        assert_eq!(
+            GOLDEN_V1[7..24],
+            [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let mut unknown_version = GOLDEN_V1.to_vec();
+        unknown_version[4] = 2;
+        assert!(ScalableCuckooFilter::<u64, SipHasher13>::from_bytes(&unknown_version).is_err());
+    }
+}
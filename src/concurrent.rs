@@ -0,0 +1,234 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hash::{Hash, Hasher};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::scalable_cuckoo_filter::default_hasher;
+use crate::{DefaultHasher, HashWidth, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+/// Scalable Cuckoo Filter that can be shared between threads.
+///
+/// Items are distributed over independently locked shards, each of which is a
+/// `ScalableCuckooFilter`, so threads operating on different shards don't block each other.
+/// Lookups only take a shared (read) lock of a shard.
+///
+/// Because an item is looked up in exactly one shard, the false positive probability of
+/// the whole filter is the same as that of each shard.
+///
+/// This is available only if the `concurrent` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ConcurrentScalableCuckooFilter;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let filter = Arc::new(ConcurrentScalableCuckooFilter::new(10_000, 0.001));
+/// let handles = (0..4)
+///     .map(|t| {
+///         let filter = Arc::clone(&filter);
+///         thread::spawn(move || {
+///             for i in 0..1000 {
+///                 filter.insert(&(t * 1000 + i));
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// for h in handles {
+///     h.join().unwrap();
+/// }
+/// assert_eq!(filter.len(), 4000);
+/// assert!(filter.contains(&1234));
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentScalableCuckooFilter<T: ?Sized, H = DefaultHasher, R = StdRng> {
+    hasher: H,
+    hash_width: HashWidth,
+    shards: Vec<RwLock<ScalableCuckooFilter<T, H, R>>>,
+}
+impl<T: Hash + ?Sized> ConcurrentScalableCuckooFilter<T> {
+    /// Makes a new `ConcurrentScalableCuckooFilter` instance.
+    ///
+    /// The number of shards is derived from the available parallelism.
+    pub fn new(initial_capacity_hint: usize, false_positive_probability: f64) -> Self {
+        let parallelism = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(
+            initial_capacity_hint,
+            false_positive_probability,
+            (parallelism * 4).next_power_of_two(),
+        )
+    }
+
+    /// Makes a new `ConcurrentScalableCuckooFilter` instance that has `shards` shards.
+    ///
+    /// Each shard is created with the initial capacity `initial_capacity_hint / shards`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `shards` is zero.
+    pub fn with_shards(
+        initial_capacity_hint: usize,
+        false_positive_probability: f64,
+        shards: usize,
+    ) -> Self {
+        assert_ne!(shards, 0);
+        let hasher = default_hasher(&mut rand::thread_rng());
+        let shards = (0..shards)
+            .map(|_| {
+                let rng = StdRng::from_rng(rand::thread_rng()).expect("unreachable");
+                ScalableCuckooFilterBuilder::new()
+                    .initial_capacity(initial_capacity_hint.div_ceil(shards))
+                    .false_positive_probability(false_positive_probability)
+                    .hasher(hasher.clone())
+                    .rng(rng)
+                    .finish()
+            })
+            .collect();
+        Self::from_shards(hasher, shards)
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ConcurrentScalableCuckooFilter<T, H, R> {
+    /// Makes a new `ConcurrentScalableCuckooFilter` instance from the given shards.
+    ///
+    /// All shards must have been built with hashers that behave the same as `hasher`,
+    /// and with the same hash width.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `shards` is empty.
+    pub fn from_shards(hasher: H, shards: Vec<ScalableCuckooFilter<T, H, R>>) -> Self {
+        assert!(!shards.is_empty());
+        ConcurrentScalableCuckooFilter {
+            hasher,
+            hash_width: shards[0].hash_width(),
+            shards: shards.into_iter().map(RwLock::new).collect(),
+        }
+    }
+
+    /// Returns the number of shards of this filter.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the approximate number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.shards.iter().map(|s| read(s).len()).sum()
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| read(s).is_empty())
+    }
+
+    /// Returns the capacity of this filter.
+    pub fn capacity(&self) -> u64 {
+        self.shards.iter().map(|s| read(s).capacity()).sum()
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.shards.iter().map(|s| read(s).bits()).sum()
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    pub fn contains(&self, item: &T) -> bool {
+        let item_hash = self.item_hash(item);
+        read(self.shard(item_hash)).raw.contains128(item_hash)
+    }
+
+    /// Inserts `item` into this filter.
+    ///
+    /// See [`ScalableCuckooFilter::insert`] for details.
+    pub fn insert(&self, item: &T) {
+        let item_hash = self.item_hash(item);
+        write(self.shard(item_hash)).raw.insert128(item_hash);
+    }
+
+    /// Removes `item` from this filter.
+    ///
+    /// See [`ScalableCuckooFilter::remove`] for details.
+    pub fn remove(&self, item: &T) -> bool {
+        let item_hash = self.item_hash(item);
+        write(self.shard(item_hash)).raw.remove128(item_hash)
+    }
+
+    /// Shrinks the capacity of each shard as much as possible.
+    pub fn shrink_to_fit(&self) {
+        for s in &self.shards {
+            write(s).shrink_to_fit();
+        }
+    }
+
+    /// Consumes this filter and returns the shards.
+    pub fn into_shards(self) -> Vec<ScalableCuckooFilter<T, H, R>> {
+        self.shards
+            .into_iter()
+            .map(|s| s.into_inner().unwrap_or_else(|e| e.into_inner()))
+            .collect()
+    }
+
+    fn item_hash(&self, item: &T) -> u128 {
+        crate::item_hash(&self.hasher, item, self.hash_width)
+    }
+
+    fn shard(&self, item_hash: u128) -> &RwLock<ScalableCuckooFilter<T, H, R>> {
+        // The bucket indices are taken from the lower bits of the hash, so the hash is mixed
+        // before choosing a shard to avoid biasing the bucket indices within each shard.
+        let mixed = (item_hash as u64)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            .rotate_left(32);
+        let i = ((u128::from(mixed) * self.shards.len() as u128) >> 64) as usize;
+        &self.shards[i]
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    // A panic while holding the lock can't leave a filter in a state unsafe to read.
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn it_works() {
+        let filter = Arc::new(ConcurrentScalableCuckooFilter::with_shards(1000, 0.001, 8));
+        assert_eq!(filter.shards(), 8);
+        assert!(filter.is_empty());
+
+        let handles = (0..8)
+            .map(|t| {
+                let filter = Arc::clone(&filter);
+                thread::spawn(move || {
+                    for i in t * 10_000..(t + 1) * 10_000 {
+                        filter.insert(&i);
+                        assert!(filter.contains(&i));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(filter.len(), 80_000);
+        for i in 0..80_000 {
+            assert!(filter.contains(&i));
+        }
+
+        let removed = (0..40_000).filter(|i| filter.remove(i)).count();
+        assert!(removed > 39_900);
+        assert_eq!(filter.len(), 80_000 - removed as u64);
+
+        let filter = Arc::try_unwrap(filter).unwrap();
+        let shards = filter.into_shards();
+        assert!(shards.iter().all(|s| s.verify_integrity().is_ok()));
+    }
+}
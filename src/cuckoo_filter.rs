@@ -1,40 +1,326 @@
 use rand::Rng;
 use std::cmp;
+use std::collections::HashSet;
 use std::hash::Hasher;
+use std::io::{self, Read, Write};
 use std::mem;
 
+use crate::alloc::Allocator;
+use crate::bits::Bits;
 use crate::buckets::Buckets;
+use crate::codec::{self, BitsEncoding};
+use crate::integrity::IntegrityError;
+use crate::stats::FilterCounters;
+use crate::storage::Storage;
 
-#[cfg(feature = "serde")]
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "serde_support")]
 use serde::{Deserialize, Serialize};
 
+/// Strategy for finding a free entry when both candidate buckets of an item are full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum InsertionStrategy {
+    /// Kicks out a randomly chosen entry repeatedly until a free entry is found
+    /// or `max_kicks` relocations are performed.
+    #[default]
+    RandomWalk,
+
+    /// Searches for the shortest relocation path in breadth-first order, visiting at most
+    /// `max_kicks` buckets, and then moves only the entries on that path.
+    ///
+    /// This achieves higher load factors with fewer displaced entries than `RandomWalk`.
+    BreadthFirst,
+}
+impl InsertionStrategy {
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            InsertionStrategy::RandomWalk => 0,
+            InsertionStrategy::BreadthFirst => 1,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(InsertionStrategy::RandomWalk),
+            1 => Some(InsertionStrategy::BreadthFirst),
+            _ => None,
+        }
+    }
+}
+
+/// Width of the item hashes the bucket indices and the fingerprints are derived from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum HashWidth {
+    /// A 64-bit hash whose lower bits are used as the bucket index and
+    /// upper bits as the fingerprint.
+    ///
+    /// The two overlap if `fingerprint_bitwidth + log2(number_of_buckets) > 64`.
+    ///
+    /// This is the layout of filters serialized before `HashWidth` was introduced.
+    #[default]
+    Bits64,
+
+    /// A 128-bit hash whose lower 64 bits are used for the bucket index and
+    /// upper 64 bits for the fingerprint, so that they never overlap.
+    ///
+    /// The upper 64 bits are computed by hashing the item once more with the same hasher
+    /// after writing a fixed salt, so insertions and lookups hash each item twice.
+    /// This is the default of [`ScalableCuckooFilterBuilder`].
+    ///
+    /// [`ScalableCuckooFilterBuilder`]: crate::ScalableCuckooFilterBuilder
+    Bits128,
+
+    /// The 64-bit hash folded into 32 bits, whose lower bits are used as the bucket index
+    /// and upper bits as the fingerprint.
+    ///
+    /// The two overlap if `fingerprint_bitwidth + log2(number_of_buckets) > 32`, so this
+    /// suits filters of up to about a hundred million entries with short fingerprints.
+    /// Fingerprints are at most 32 bits long, which keeps the stash entries compact.
+    Bits32,
+}
+impl HashWidth {
+    /// Returns the maximum length of the fingerprints taken from hashes of this width.
+    #[inline]
+    pub(crate) fn max_fingerprint_bitwidth(self) -> usize {
+        match self {
+            HashWidth::Bits32 => 32,
+            HashWidth::Bits64 | HashWidth::Bits128 => 64,
+        }
+    }
+
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            HashWidth::Bits64 => 0,
+            HashWidth::Bits128 => 1,
+            HashWidth::Bits32 => 2,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(HashWidth::Bits64),
+            1 => Some(HashWidth::Bits128),
+            2 => Some(HashWidth::Bits32),
+            _ => None,
+        }
+    }
+}
+
+/// Scheme for deriving the alternative bucket index of an entry from its fingerprint.
+///
+/// The alternative index is `index ^ offset(fingerprint)` reduced to the number of buckets
+/// (or `(offset(fingerprint) - index) mod number_of_buckets` if the number of buckets
+/// isn't a power of two), which is an involution whatever the offset is, so an entry can
+/// always be moved back to the bucket it came from.
+/// The schemes differ only in how the offset is computed.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum AltIndexScheme {
+    /// The offset is the hash of the fingerprint computed by the hasher of the filter.
+    ///
+    /// Every kick during an insertion hashes the kicked fingerprint, which is costly with
+    /// a slow hasher such as SipHash.
+    /// This is the scheme of filters serialized before `AltIndexScheme` was introduced.
+    #[default]
+    Hashed,
+
+    /// The offset is the fingerprint mixed by the finalizer of MurmurHash3.
+    ///
+    /// This is as cheap as a few multiplications and spreads the offsets as evenly as hashing.
+    /// This is the default of [`ScalableCuckooFilterBuilder`].
+    ///
+    /// [`ScalableCuckooFilterBuilder`]: crate::ScalableCuckooFilterBuilder
+    Mixed,
+
+    /// The offset is `fingerprint * 0x5bd1e995` (wrapping at 64 bits).
+    ///
+    /// This is the same derivation as the [reference implementation] of cuckoo filters
+    /// and is cheaper than hashing.
+    ///
+    /// [reference implementation]: https://github.com/efficient/cuckoofilter
+    Multiplicative,
+
+    /// The offset is computed by the given function.
+    ///
+    /// Filters using this scheme can't be serialized because functions can't.
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    Custom(fn(u64) -> u64),
+}
+impl AltIndexScheme {
+    #[inline]
+    pub(crate) fn offset<H: Hasher + Clone>(self, hasher: &H, fingerprint: u64) -> u64 {
+        match self {
+            AltIndexScheme::Hashed => crate::hash(hasher, &fingerprint),
+            AltIndexScheme::Mixed => crate::hashers::fmix64(fingerprint),
+            AltIndexScheme::Multiplicative => fingerprint.wrapping_mul(0x5bd1_e995),
+            AltIndexScheme::Custom(f) => f(fingerprint),
+        }
+    }
+
+    /// Returns the tag of the scheme, or an error for `Custom` which can't be written.
+    pub(crate) fn tag(self) -> io::Result<u8> {
+        match self {
+            AltIndexScheme::Hashed => Ok(0),
+            AltIndexScheme::Mixed => Ok(1),
+            AltIndexScheme::Multiplicative => Ok(2),
+            AltIndexScheme::Custom(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "custom alternative index schemes can't be written",
+            )),
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(AltIndexScheme::Hashed),
+            1 => Some(AltIndexScheme::Mixed),
+            2 => Some(AltIndexScheme::Multiplicative),
+            _ => None,
+        }
+    }
+}
+/// `Custom` schemes are equal if their functions have the same address.
+impl PartialEq for AltIndexScheme {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AltIndexScheme::Custom(f), AltIndexScheme::Custom(g)) => std::ptr::fn_addr_eq(*f, *g),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+impl Eq for AltIndexScheme {}
+
+/// Layout of the fingerprints of each layer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum StorageBackend {
+    /// Fixed-size buckets of `entries_per_bucket` entries.
+    #[default]
+    Buckets,
+
+    /// Morton filter style blocks.
+    ///
+    /// Each block of 64 buckets shares `16 * entries_per_bucket` entries, and a small counter per
+    /// bucket records how many of them the bucket uses. Buckets hold at most `entries_per_bucket`
+    /// entries as usual, but the free entries of a block are available to any of its buckets.
+    /// This makes insertions succeed without kicks more often and lets layers get fuller before
+    /// they grow, at the cost of the counters (`log2(entries_per_bucket + 1)` bits per bucket)
+    /// and of slower lookups (the counters preceding a bucket are summed up).
+    ///
+    /// The `BreadthFirst` insertion strategy doesn't use the shared entries of full blocks,
+    /// so `RandomWalk` is recommended.
+    ///
+    /// # References
+    ///
+    /// - [Morton Filters: Faster, Space-Efficient Cuckoo Filters via Biasing, Compression, and Decoupled Logical Sparsity](https://www.vldb.org/pvldb/vol11/p1041-breslow.pdf)
+    Morton,
+
+    /// Fixed-size buckets grouped into blocks of one cache line (64 bytes).
+    ///
+    /// Both candidate buckets of an item are in the same block, so a lookup touches a single
+    /// cache line instead of two, which speeds up lookups in filters much larger than the CPU
+    /// caches. In exchange, the items of a block can't be moved to other blocks, and
+    /// blocks fill unevenly, so layers grow when they are between a quarter and
+    /// a half full (the longer the fingerprints, the fewer buckets a block holds and the
+    /// earlier it overflows). Setting `initial_capacity` to two to four times the expected
+    /// number of items keeps the filter in a single layer.
+    ///
+    /// The buckets are allocated at a 64-byte boundary and blocks are placed at multiples of
+    /// 64 bytes from their start, so each block is aligned to a cache line.
+    /// The number of buckets is rounded up to a multiple of the block size.
+    Blocked,
+}
+impl StorageBackend {
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            StorageBackend::Buckets => 0,
+            StorageBackend::Morton => 1,
+            StorageBackend::Blocked => 2,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(StorageBackend::Buckets),
+            1 => Some(StorageBackend::Morton),
+            2 => Some(StorageBackend::Blocked),
+            _ => None,
+        }
+    }
+}
+
+/// Parameters of a layer that determine the canonical entries of items
+/// (see `CuckooFilter::canonical_entries`), without the entries themselves.
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct LayerLayout {
+    buckets: Buckets<()>,
+    alt_index_scheme: AltIndexScheme,
+}
+impl LayerLayout {
+    /// Returns the smaller candidate bucket index and the fingerprint of an item.
+    #[inline]
+    pub fn canonical_entry<H: Hasher + Clone>(&self, hasher: &H, item_hash: u128) -> (usize, u64) {
+        let i0 = self.buckets.index(item_hash as u64);
+        let fingerprint = self.buckets.fingerprint((item_hash >> 64) as u64);
+        let offset = self.alt_index_scheme.offset(hasher, fingerprint);
+        (i0.min(self.buckets.alt_index(i0, offset)), fingerprint)
+    }
+}
+
+/// Parts of a layer written by `CuckooFilter::write_counts_to`.
+#[derive(Debug)]
+pub struct LayerCounts {
+    item_count: usize,
+    exceptional_items: ExceptionalItems,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct CuckooFilter {
-    buckets: Buckets,
+    buckets: Storage,
     max_kicks: usize,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    insertion_strategy: InsertionStrategy,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    alt_index_scheme: AltIndexScheme,
     exceptional_items: ExceptionalItems,
     item_count: usize,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    counters: FilterCounters,
 }
 impl CuckooFilter {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         fingerprint_bitwidth: usize,
         entries_per_bucket: usize,
         number_of_items_hint: usize,
         max_kicks: usize,
+        insertion_strategy: InsertionStrategy,
+        storage_backend: StorageBackend,
+        alt_index_scheme: AltIndexScheme,
+        power_of_two_buckets: bool,
+        alloc: Allocator,
     ) -> Self {
-        let number_of_buckets_hint =
-            (number_of_items_hint + entries_per_bucket - 1) / entries_per_bucket;
-        let buckets = Buckets::new(
+        let buckets = Storage::new(
+            storage_backend,
             fingerprint_bitwidth,
             entries_per_bucket,
-            number_of_buckets_hint,
+            number_of_items_hint,
+            power_of_two_buckets,
+            alloc,
         );
         CuckooFilter {
             buckets,
             max_kicks,
+            insertion_strategy,
+            alt_index_scheme,
             exceptional_items: ExceptionalItems::new(),
             item_count: 0,
+            counters: FilterCounters::default(),
         }
     }
 
@@ -43,6 +329,104 @@ impl CuckooFilter {
         self.buckets.bits() + self.exceptional_items.bits()
     }
 
+    /// Returns `true` if this layer has the same parameters and entries as `other`.
+    ///
+    /// The counters aren't compared.
+    pub fn same_contents(&self, other: &Self) -> bool {
+        self.max_kicks == other.max_kicks
+            && self.insertion_strategy == other.insertion_strategy
+            && self.alt_index_scheme == other.alt_index_scheme
+            && self.item_count == other.item_count
+            && self.exceptional_items == other.exceptional_items
+            && self.buckets == other.buckets
+    }
+
+    /// Returns the number of bytes of the heap memory owned by this layer.
+    #[inline]
+    pub fn heap_bytes(&self) -> usize {
+        self.buckets.heap_bytes() + self.exceptional_items.heap_bytes()
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W, encoding: BitsEncoding) -> io::Result<()> {
+        self.write_head_to(writer)?;
+        self.bit_array().write_to(writer, encoding)
+    }
+
+    /// Writes the part of `write_to` preceding the bit array of the buckets.
+    pub fn write_head_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        codec::write_usize(writer, self.max_kicks)?;
+        codec::write_u8(writer, self.insertion_strategy.tag())?;
+        codec::write_u8(writer, self.alt_index_scheme.tag()?)?;
+        self.write_counts_to(writer)?;
+        self.buckets.write_head_to(writer)
+    }
+
+    /// Writes the parts of `write_to` changed by insertions and removals,
+    /// other than the buckets: the number of items and the stash.
+    pub fn write_counts_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        codec::write_usize(writer, self.item_count)?;
+        self.exceptional_items.write_to(writer)
+    }
+
+    /// Reads the parts written by `write_counts_to`.
+    pub fn read_counts_from<R: Read>(reader: &mut R) -> io::Result<LayerCounts> {
+        Ok(LayerCounts {
+            item_count: codec::read_usize(reader)?,
+            exceptional_items: ExceptionalItems::read_from(reader)?,
+        })
+    }
+
+    /// Replaces the parts written by `write_counts_to`.
+    pub fn set_counts(&mut self, counts: LayerCounts) {
+        self.item_count = counts.item_count;
+        self.exceptional_items = counts.exceptional_items;
+    }
+
+    /// Sets the version of the scalable filter that the following writes to the buckets
+    /// are stamped with (see `Bits::set_version`).
+    #[inline]
+    pub fn set_version(&mut self, version: u64) {
+        self.buckets.bit_array_mut().set_version(version);
+    }
+
+    #[inline]
+    pub fn bit_array(&self) -> &crate::bits::Bits {
+        self.buckets.bit_array()
+    }
+
+    #[inline]
+    pub fn bit_array_mut(&mut self) -> &mut crate::bits::Bits {
+        self.buckets.bit_array_mut()
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R, encoding: BitsEncoding) -> io::Result<Self> {
+        Self::read_with(reader, |reader| Bits::read_from(reader, encoding))
+    }
+
+    /// Reads a layer like `read_from`, but reads the bit array by `read_bits`.
+    pub fn read_with<R: Read>(
+        reader: &mut R,
+        read_bits: impl FnOnce(&mut R) -> io::Result<Bits>,
+    ) -> io::Result<Self> {
+        let max_kicks = codec::read_usize(reader)?;
+        let insertion_strategy = InsertionStrategy::from_tag(codec::read_u8(reader)?)
+            .ok_or_else(|| codec::invalid_data("unknown insertion strategy"))?;
+        let alt_index_scheme = AltIndexScheme::from_tag(codec::read_u8(reader)?)
+            .ok_or_else(|| codec::invalid_data("unknown alternative index scheme"))?;
+        let item_count = codec::read_usize(reader)?;
+        let exceptional_items = ExceptionalItems::read_from(reader)?;
+        let buckets = Storage::read_with(reader, read_bits)?;
+        Ok(CuckooFilter {
+            buckets,
+            max_kicks,
+            insertion_strategy,
+            alt_index_scheme,
+            exceptional_items,
+            item_count,
+            counters: FilterCounters::default(),
+        })
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.item_count
@@ -54,37 +438,260 @@ impl CuckooFilter {
     }
 
     #[inline]
-    pub fn is_nearly_full(&self) -> bool {
-        self.exceptional_items.contains_kicked_out_entries()
+    pub fn free_entries(&self) -> usize {
+        self.buckets.entries() + self.exceptional_items.len() - self.item_count
+    }
+
+    #[inline]
+    pub fn fingerprint_bitwidth(&self) -> usize {
+        self.buckets.fingerprint_bitwidth()
+    }
+
+    #[inline]
+    pub fn entries(&self) -> usize {
+        self.buckets.entries()
+    }
+
+    #[inline]
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
     }
 
     #[inline]
-    pub fn contains<H: Hasher + Clone>(&self, hasher: &H, item_hash: u64) -> bool {
-        let fingerprint = self.buckets.fingerprint(item_hash);
-        let i0 = self.buckets.index(item_hash);
-        let i1 = self
+    pub fn backend(&self) -> StorageBackend {
+        self.buckets.backend()
+    }
+
+    #[inline]
+    pub fn is_power_of_two(&self) -> bool {
+        self.buckets.is_power_of_two()
+    }
+
+    /// Returns the fingerprint in the `entry`-th entry of the `i`-th bucket (zero if it is empty).
+    #[inline]
+    pub fn get_entry(&self, i: usize, entry: usize) -> u64 {
+        self.buckets.get_fingerprint(i, entry)
+    }
+
+    /// Sets the fingerprint in the `entry`-th entry of the `i`-th bucket, keeping the number of
+    /// items up to date.
+    pub fn set_entry(&mut self, i: usize, entry: usize, fingerprint: u64) {
+        let old = self.buckets.get_fingerprint(i, entry);
+        self.buckets.set_fingerprint(i, entry, fingerprint);
+        self.item_count = self.item_count + usize::from(fingerprint != 0) - usize::from(old != 0);
+    }
+
+    /// Returns the entries of this layer (including the stashed ones) as pairs of the smaller
+    /// candidate bucket index and the fingerprint, which don't depend on where the entries
+    /// have been placed.
+    pub fn canonical_entries<'a, H: Hasher + Clone>(
+        &'a self,
+        hasher: &'a H,
+    ) -> impl Iterator<Item = (usize, u64)> + 'a {
+        let placed = self
             .buckets
-            .index(i0 as u64 ^ crate::hash(hasher, &fingerprint));
+            .iter()
+            .map(move |(i, f)| (i.min(self.alt_index(hasher, i, f)), f));
+        // Stashed entries are kept with the smaller index.
+        placed.chain(self.exceptional_items.iter().map(|(f, i)| (i, f)))
+    }
+
+    /// Returns the parameters of this layer that determine the canonical entries of items.
+    pub fn layout(&self) -> LayerLayout {
+        LayerLayout {
+            buckets: self.buckets.layout(),
+            alt_index_scheme: self.alt_index_scheme,
+        }
+    }
+
+    /// Inserts an entry taken from `canonical_entries` of a layer of the same shape.
+    ///
+    /// The entry is stashed if no free entry is found, so this never fails.
+    pub fn insert_canonical_entry<H: Hasher + Clone, R: Rng>(
+        &mut self,
+        hasher: &H,
+        rng: &mut R,
+        bucket_index: usize,
+        fingerprint: u64,
+    ) {
+        self.insert_fingerprint(hasher, rng, bucket_index, fingerprint, true);
+    }
+
+    #[inline]
+    pub fn occupied_entries(&self) -> usize {
+        self.item_count - self.exceptional_items.len()
+    }
+
+    #[inline]
+    pub fn fill_ratio(&self) -> f64 {
+        self.occupied_entries() as f64 / self.entries() as f64
+    }
+
+    #[inline]
+    pub fn false_positive_probability_bound(&self) -> f64 {
+        let compared_entries = (2 * self.buckets.entries_per_bucket()) as f64;
+        compared_entries / 2f64.powi(self.buckets.fingerprint_bitwidth() as i32)
+    }
+
+    pub fn estimated_false_positive_probability(&self) -> f64 {
+        let fingerprints = 2f64.powi(self.buckets.fingerprint_bitwidth() as i32);
+
+        // A lookup compares the fingerprint with every occupied entry in two buckets.
+        let entries_per_bucket = self.buckets.entries() as f64 / self.buckets.len() as f64;
+        let compared_entries = 2.0 * entries_per_bucket * self.fill_ratio();
+        let buckets_miss = (1.0 - 1.0 / fingerprints).powf(compared_entries);
+
+        // A stash entry matches if both the fingerprint and the smaller bucket index are equal.
+        let stash_hit = 2.0 / (fingerprints * self.buckets.len() as f64);
+        let stash_miss = (1.0 - stash_hit).powf(self.exceptional_items.len() as f64);
+
+        1.0 - buckets_miss * stash_miss
+    }
+
+    #[inline]
+    pub fn counters(&self) -> &FilterCounters {
+        &self.counters
+    }
+
+    #[inline]
+    pub fn stash_len(&self) -> usize {
+        self.exceptional_items.len()
+    }
+
+    /// Returns the `(bucket_index, fingerprint, stashed)` triples of the entries in this layer:
+    /// the buckets in order, followed by the stash (not necessarily in order).
+    pub fn stored_entries(&self) -> impl Iterator<Item = (usize, u64, bool)> + '_ {
+        let placed = self.buckets.iter().map(|(i, f)| (i, f, false));
+        placed.chain(self.exceptional_items.iter().map(|(f, i)| (i, f, true)))
+    }
+
+    #[inline]
+    pub fn kicked_out_entries(&self) -> usize {
+        self.exceptional_items.kicked_out_entries()
+    }
+
+    #[inline]
+    pub fn contains<H: Hasher + Clone>(&self, hasher: &H, item_hash: u128) -> bool {
+        let (i0, fingerprint) = self.split(item_hash);
+        self.buckets.prefetch(i0);
+        if matches!(self.alt_index_scheme, AltIndexScheme::Hashed) {
+            return self.contains_fingerprint_lazily(hasher, i0, fingerprint);
+        }
+        let i1 = self.alt_index(hasher, i0, fingerprint);
+        self.buckets.prefetch(i1);
         self.contains_fingerprint(i0, i1, fingerprint)
     }
 
     #[inline]
-    pub fn insert<H: Hasher + Clone, R: Rng>(&mut self, hasher: &H, rng: &mut R, item_hash: u64) {
-        let fingerprint = self.buckets.fingerprint(item_hash);
-        let i0 = self.buckets.index(item_hash);
-        self.insert_fingerprint(hasher, rng, i0, fingerprint);
+    pub fn bucket_index(&self, item_hash: u128) -> usize {
+        self.split(item_hash).0
+    }
+
+    /// Inserts items into their primary or alternative buckets in parallel.
+    ///
+    /// No entries are kicked out, so the items that couldn't be placed are returned
+    /// (their hashes) to be inserted in the usual way.
+    #[cfg(feature = "rayon")]
+    pub fn par_insert<H: Hasher + Clone + Sync>(
+        &mut self,
+        hasher: &H,
+        hashes: Vec<u128>,
+    ) -> Vec<u128> {
+        if !matches!(self.buckets, Storage::Buckets(_)) {
+            return hashes;
+        }
+
+        let primary = hashes
+            .into_par_iter()
+            .map(|h| (self.bucket_index(h), h))
+            .collect();
+        let rest = self.par_place(primary);
+
+        let alternative = rest
+            .into_par_iter()
+            .map(|(i0, h)| {
+                let fingerprint = self.split(h).1;
+                let i1 = self.alt_index(hasher, i0, fingerprint);
+                (i1, h)
+            })
+            .collect();
+        let rest = self.par_place(alternative);
+        rest.into_iter().map(|(_, h)| h).collect()
+    }
+
+    /// Places each `(bucket_index, item_hash)` into the bucket if it has a free entry,
+    /// and returns the rest.
+    #[cfg(feature = "rayon")]
+    fn par_place(&mut self, mut entries: Vec<(usize, u128)>) -> Vec<(usize, u128)> {
+        entries.par_sort_unstable_by_key(|&(i, _)| i);
+
+        let chunks = (rayon::current_num_threads() * 4).next_power_of_two();
+        let Storage::Buckets(buckets) = &self.buckets else {
+            unreachable!()
+        };
+        let buckets_per_chunk = buckets
+            .len()
+            .div_ceil(chunks)
+            .next_multiple_of(buckets.buckets_per_block().max(64));
+        let mut slices = Vec::new();
+        let mut rest = &entries[..];
+        for end in (1..).map(|c| c * buckets_per_chunk).take(chunks) {
+            let (slice, r) = rest.split_at(rest.partition_point(|&(i, _)| i < end));
+            slices.push(slice);
+            rest = r;
+        }
+        debug_assert!(rest.is_empty());
+
+        let Storage::Buckets(buckets) = &mut self.buckets else {
+            unreachable!()
+        };
+        let fingerprint_bitwidth = buckets.fingerprint_bitwidth();
+        let leftovers: Vec<_> = buckets
+            .chunks_mut(buckets_per_chunk)
+            .into_par_iter()
+            .zip(slices)
+            .flat_map_iter(|(mut chunk, entries)| {
+                entries
+                    .iter()
+                    .filter(|&&(i, h)| {
+                        // Zero fingerprints must be stashed, so they are left over as well.
+                        let fingerprint = (h >> 64) as u64 >> (64 - fingerprint_bitwidth);
+                        fingerprint == 0 || !chunk.try_insert(i, fingerprint)
+                    })
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        self.item_count += entries.len() - leftovers.len();
+        leftovers
+    }
+
+    /// Inserts an item into this filter.
+    ///
+    /// If `use_stash` is `false` and no free entry is found, this method returns `false`
+    /// leaving the filter unchanged.
+    #[inline]
+    pub fn insert<H: Hasher + Clone, R: Rng>(
+        &mut self,
+        hasher: &H,
+        rng: &mut R,
+        item_hash: u128,
+        use_stash: bool,
+    ) -> bool {
+        let (i0, fingerprint) = self.split(item_hash);
+        self.buckets.prefetch(i0);
+        self.insert_fingerprint(hasher, rng, i0, fingerprint, use_stash)
     }
 
     #[inline]
-    pub fn remove<H: Hasher + Clone>(&mut self, hasher: &H, item_hash: u64) -> bool {
-        let fingerprint = self.buckets.fingerprint(item_hash);
-        let i0 = self.buckets.index(item_hash);
-        let i1 = self
-            .buckets
-            .index(i0 as u64 ^ crate::hash(hasher, &fingerprint));
+    pub fn remove<H: Hasher + Clone>(&mut self, hasher: &H, item_hash: u128) -> bool {
+        let (i0, fingerprint) = self.split(item_hash);
+        let i1 = self.alt_index(hasher, i0, fingerprint);
 
         let removed = if self.exceptional_items.contains(i0, i1, fingerprint) {
             self.exceptional_items.remove(i0, i1, fingerprint)
+        } else if fingerprint == 0 {
+            false
         } else if self.buckets.contains(i0, fingerprint) {
             self.buckets.remove_fingerprint(i0, fingerprint)
         } else if self.buckets.contains(i1, fingerprint) {
@@ -101,36 +708,187 @@ impl CuckooFilter {
     }
 
     #[inline]
-    pub fn shrink_to_fit<H: Hasher + Clone, R: Rng>(&mut self, hasher: &H, rng: &mut R) {
+    pub fn defragment<H: Hasher + Clone>(&mut self, hasher: &H) {
+        let buckets = &mut self.buckets;
+        let scheme = self.alt_index_scheme;
+        self.exceptional_items.retain(|i0, fingerprint| {
+            if fingerprint == 0 {
+                return true;
+            }
+            let i1 = buckets.alt_index(i0, scheme.offset(hasher, fingerprint));
+            !(buckets.try_insert(i0, fingerprint) || buckets.try_insert(i1, fingerprint))
+        });
+    }
+
+    /// Shrinks this filter.
+    ///
+    /// If `use_stash` is `false` and the entries don't fit into the shrunk buckets,
+    /// the buckets are left as they are.
+    ///
+    /// The buckets of a filter whose number of buckets isn't a power of two are never shrunk,
+    /// because the bucket indices in a smaller filter can't be derived from the current ones.
+    #[inline]
+    pub fn shrink_to_fit<H: Hasher + Clone, R: Rng>(
+        &mut self,
+        hasher: &H,
+        rng: &mut R,
+        use_stash: bool,
+    ) {
+        let backend = self.buckets.backend();
         let entries_per_bucket = self.buckets.entries_per_bucket();
-        let shrunk_buckets_len = Buckets::required_number_of_buckets(
-            (self.item_count + entries_per_bucket - 1) / entries_per_bucket,
-        );
-        if shrunk_buckets_len < self.buckets.len() {
+        let shrunk_buckets_len =
+            Storage::required_number_of_buckets(backend, entries_per_bucket, self.item_count, true);
+        if self.buckets.is_power_of_two() && shrunk_buckets_len < self.buckets.len() {
             let mut shrunk_filter = CuckooFilter::new(
                 self.buckets.fingerprint_bitwidth(),
-                self.buckets.entries_per_bucket(),
+                entries_per_bucket,
                 self.item_count,
                 self.max_kicks,
+                self.insertion_strategy,
+                backend,
+                self.alt_index_scheme,
+                true,
+                self.buckets.allocator().clone(),
             );
-            for (i, fingerprint) in self.buckets.iter() {
-                let shrunk_i = shrunk_filter.buckets.index(i as u64);
-                shrunk_filter.insert_fingerprint(hasher, rng, shrunk_i, fingerprint);
+            let placed = self
+                .buckets
+                .iter()
+                .chain(self.exceptional_items.iter().map(|(f, i)| (i, f)))
+                .all(|(i, fingerprint)| {
+                    let shrunk_i = shrunk_filter.buckets.index(i as u64);
+                    shrunk_filter.insert_fingerprint(hasher, rng, shrunk_i, fingerprint, use_stash)
+                });
+            if placed {
+                shrunk_filter.counters.merge(&self.counters);
+                let version = self.buckets.bit_array().version();
+                shrunk_filter
+                    .buckets
+                    .bit_array_mut()
+                    .reset_versions(version);
+                *self = shrunk_filter;
             }
-            *self = shrunk_filter;
         }
         self.exceptional_items.shrink_to_fit();
     }
 
+    pub fn verify_integrity<H: Hasher + Clone>(
+        &self,
+        hasher: &H,
+        layer: usize,
+        entries_per_bucket: usize,
+        hash_width: HashWidth,
+    ) -> Result<(), IntegrityError> {
+        let fingerprint_bitwidth = self.buckets.fingerprint_bitwidth();
+        if self.buckets.entries_per_bucket() != entries_per_bucket
+            || !(1..=hash_width.max_fingerprint_bitwidth()).contains(&fingerprint_bitwidth)
+        {
+            return Err(IntegrityError::InvalidLayerParameters { layer });
+        }
+
+        let stored_entries = self.buckets.iter().count() + self.exceptional_items.len();
+        if stored_entries != self.item_count {
+            return Err(IntegrityError::ItemCountMismatch {
+                layer,
+                item_count: self.item_count,
+                stored_entries,
+            });
+        }
+
+        for (i, fingerprint) in self.buckets.iter() {
+            let alt = self.alt_index(hasher, i, fingerprint);
+            if !self.is_primary_index(hash_width, i, fingerprint)
+                && !self.is_primary_index(hash_width, alt, fingerprint)
+            {
+                return Err(IntegrityError::MisplacedEntry {
+                    layer,
+                    fingerprint,
+                    bucket_index: i,
+                });
+            }
+        }
+
+        let stash = &self.exceptional_items;
+        if !stash.is_ordered() {
+            return Err(IntegrityError::UnsortedStash { layer });
+        }
+        for (fingerprint, i0) in stash.iter() {
+            let i1 = self.alt_index(hasher, i0, fingerprint);
+            if i0 >= self.buckets.len() || i1 < i0 {
+                return Err(IntegrityError::MisplacedStashEntry {
+                    layer,
+                    fingerprint,
+                    bucket_index: i0,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if some item hash of `hash_width` has `i` as the primary bucket index
+    /// and `fingerprint` as the fingerprint.
+    ///
+    /// Both are taken from the same hash unless the hashes are 128 bits long, so the fingerprint
+    /// (the upper bits) narrows the range of the hash and thus of the index (the hash modulo
+    /// the number of buckets). Raw 64-bit hashes (e.g., of `insert_hash`) are split the same way
+    /// whatever `hash_width` is, so they are accepted for 32-bit hashes as well.
+    fn is_primary_index(&self, hash_width: HashWidth, i: usize, fingerprint: u64) -> bool {
+        let len = self.buckets.len() as u128;
+        let hash_bitwidths: &[usize] = match hash_width {
+            HashWidth::Bits64 => &[64],
+            HashWidth::Bits32 => &[32, 64],
+            HashWidth::Bits128 => return (i as u128) < len,
+        };
+        hash_bitwidths.iter().any(|&hash_bitwidth| {
+            let free_bitwidth = hash_bitwidth - self.buckets.fingerprint_bitwidth();
+            let lowest_hash = u128::from(fingerprint) << free_bitwidth;
+            let distance = (i as u128 + len - lowest_hash % len) % len;
+            (i as u128) < len && distance < 1 << free_bitwidth
+        })
+    }
+
+    #[inline]
+    fn alt_index<H: Hasher + Clone>(&self, hasher: &H, i: usize, fingerprint: u64) -> usize {
+        self.buckets
+            .alt_index(i, self.alt_index_scheme.offset(hasher, fingerprint))
+    }
+
+    /// Returns the primary bucket index and the fingerprint of an item.
+    ///
+    /// The index is taken from the lower 64 bits of `item_hash` and the fingerprint from
+    /// the upper 64 bits (see [`crate::item_hash`]).
+    #[inline]
+    fn split(&self, item_hash: u128) -> (usize, u64) {
+        let i0 = self.buckets.index(item_hash as u64);
+        let fingerprint = self.buckets.fingerprint((item_hash >> 64) as u64);
+        (i0, fingerprint)
+    }
+
     #[inline]
     fn contains_fingerprint(&self, i0: usize, i1: usize, fingerprint: u64) -> bool {
-        if self.exceptional_items.contains(i0, i1, fingerprint) {
-            true
-        } else if fingerprint == 0 {
-            false
-        } else {
-            self.buckets.contains(i0, fingerprint) || self.buckets.contains(i1, fingerprint)
+        // Both buckets are searched without data-dependent branches (`&` instead of `&&`).
+        // The stash is empty most of the time, so the branch skipping it is well predicted.
+        let in_buckets = (fingerprint != 0) & self.buckets.contains_either(i0, i1, fingerprint);
+        in_buckets
+            || (!self.exceptional_items.is_empty()
+                && self.exceptional_items.contains(i0, i1, fingerprint))
+    }
+
+    /// Searches the primary bucket before computing the alternative index, which is skipped
+    /// on hits. Hashing the fingerprint costs more than the branch.
+    #[inline]
+    fn contains_fingerprint_lazily<H: Hasher + Clone>(
+        &self,
+        hasher: &H,
+        i0: usize,
+        fingerprint: u64,
+    ) -> bool {
+        if fingerprint != 0 && self.buckets.contains(i0, fingerprint) {
+            return true;
         }
+        let i1 = self.alt_index(hasher, i0, fingerprint);
+        (fingerprint != 0 && self.buckets.contains(i1, fingerprint))
+            || (!self.exceptional_items.is_empty()
+                && self.exceptional_items.contains(i0, i1, fingerprint))
     }
 
     #[inline]
@@ -140,87 +898,518 @@ impl CuckooFilter {
         rng: &mut R,
         i0: usize,
         fingerprint: u64,
-    ) {
-        let i1 = self
-            .buckets
-            .index(i0 as u64 ^ crate::hash(hasher, &fingerprint));
-        self.item_count += 1;
+        use_stash: bool,
+    ) -> bool {
+        let i1 = self.alt_index(hasher, i0, fingerprint);
+        self.buckets.prefetch(i1);
 
-        if fingerprint == 0 {
+        let inserted = if fingerprint == 0 {
+            // Zero fingerprints can't be represented in buckets, so they are always stashed.
             self.exceptional_items.insert(i0, i1, 0);
-            return;
-        }
-        if self.buckets.try_insert(i0, fingerprint) || self.buckets.try_insert(i1, fingerprint) {
-            return;
+            self.counters.stash_insertions += 1;
+            true
+        } else if self.buckets.try_insert(i0, fingerprint)
+            || self.buckets.try_insert(i1, fingerprint)
+        {
+            true
+        } else {
+            match self.insertion_strategy {
+                InsertionStrategy::RandomWalk => {
+                    self.random_walk(hasher, rng, i0, i1, fingerprint, use_stash)
+                }
+                InsertionStrategy::BreadthFirst => {
+                    if self.breadth_first(hasher, i0, i1, fingerprint) {
+                        true
+                    } else if use_stash {
+                        self.exceptional_items.insert(i0, i1, fingerprint);
+                        self.counters.stash_insertions += 1;
+                        true
+                    } else {
+                        false
+                    }
+                }
+            }
+        };
+        if inserted {
+            self.item_count += 1;
         }
+        inserted
+    }
 
+    #[inline]
+    fn random_walk<H: Hasher + Clone, R: Rng>(
+        &mut self,
+        hasher: &H,
+        rng: &mut R,
+        i0: usize,
+        i1: usize,
+        fingerprint: u64,
+        use_stash: bool,
+    ) -> bool {
         let mut fingerprint = fingerprint;
-        let mut i = if rng.gen::<bool>() { i0 } else { i1 };
-        let mut prev_i = i;
+        let (mut prev_i, mut i) = if rng.gen::<bool>() {
+            (i1, i0)
+        } else {
+            (i0, i1)
+        };
+        let mut path = Vec::new();
         for _ in 0..self.max_kicks {
-            fingerprint = self.buckets.random_swap(rng, i, fingerprint);
-            prev_i = i;
-            i = self
-                .buckets
-                .index(i as u64 ^ crate::hash(hasher, &fingerprint));
+            let (kicked_i, kicked) = self.buckets.random_swap(rng, i, fingerprint);
+            self.counters.kicks += 1;
+            if !use_stash {
+                path.push((i, fingerprint, kicked_i));
+            }
+            fingerprint = kicked;
+            prev_i = kicked_i;
+            i = self.alt_index(hasher, kicked_i, fingerprint);
             if self.buckets.try_insert(i, fingerprint) {
+                return true;
+            }
+        }
+        if use_stash {
+            self.exceptional_items.insert(prev_i, i, fingerprint);
+            self.counters.stash_insertions += 1;
+            return true;
+        }
+
+        // Rolls back the relocations.
+        for (i, inserted, kicked_i) in path.into_iter().rev() {
+            let removed = self.buckets.remove_fingerprint(i, inserted);
+            let restored = self.buckets.try_insert(kicked_i, fingerprint);
+            debug_assert!(removed && restored);
+            fingerprint = inserted;
+        }
+        false
+    }
+
+    fn breadth_first<H: Hasher + Clone>(
+        &mut self,
+        hasher: &H,
+        i0: usize,
+        i1: usize,
+        fingerprint: u64,
+    ) -> bool {
+        // Each node is `(bucket_index, parent_node, entry_index_in_parent_bucket)`.
+        let mut nodes = vec![(i0, usize::MAX, 0), (i1, usize::MAX, 0)];
+        let mut visited = HashSet::new();
+        visited.insert(i0);
+        visited.insert(i1);
+
+        let mut current = 0;
+        while current < nodes.len() {
+            let (i, _, _) = nodes[current];
+            for entry in 0..self.buckets.entries_per_bucket() {
+                let f = self.buckets.get_fingerprint(i, entry);
+                if f == 0 {
+                    // Buckets of a full Morton block may have no entries to relocate.
+                    continue;
+                }
+                let alt_i = self.alt_index(hasher, i, f);
+                if self.buckets.try_insert(alt_i, f) {
+                    self.counters.kicks += 1;
+                    self.shift_path(&nodes, current, entry, fingerprint);
+                    return true;
+                }
+                if nodes.len() < self.max_kicks && visited.insert(alt_i) {
+                    nodes.push((alt_i, current, entry));
+                }
+            }
+            current += 1;
+        }
+        false
+    }
+
+    fn shift_path(
+        &mut self,
+        nodes: &[(usize, usize, usize)],
+        mut node: usize,
+        mut free_entry: usize,
+        fingerprint: u64,
+    ) {
+        loop {
+            let (i, parent, parent_entry) = nodes[node];
+            if parent == usize::MAX {
+                self.buckets.set_fingerprint(i, free_entry, fingerprint);
                 return;
             }
+            let (parent_i, _, _) = nodes[parent];
+            let f = self.buckets.get_fingerprint(parent_i, parent_entry);
+            self.buckets.set_fingerprint(i, free_entry, f);
+            self.counters.kicks += 1;
+            node = parent;
+            free_entry = parent_entry;
         }
-        self.exceptional_items.insert(prev_i, i, fingerprint);
     }
 }
 
+/// Stash of a layer, ordered by fingerprint and then by the smaller candidate bucket index.
+///
+/// Entries are stored as pairs of `u32` as long as they fit (i.e., fingerprints of up to
+/// 32 bits and bucket indices below `2^32`), which halves their size on 64-bit targets.
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-struct ExceptionalItems(Vec<(u64, usize)>);
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(from = "Vec<(u64, usize)>", into = "Vec<(u64, usize)>")
+)]
+enum ExceptionalItems {
+    Compact(StashEntries<(u32, u32)>),
+    Wide(StashEntries<(u64, usize)>),
+}
 impl ExceptionalItems {
     fn new() -> Self {
-        ExceptionalItems(Vec::new())
+        ExceptionalItems::Compact(StashEntries::default())
     }
 
     #[inline]
     fn len(&self) -> usize {
-        self.0.len()
+        match self {
+            ExceptionalItems::Compact(items) => items.len(),
+            ExceptionalItems::Wide(items) => items.len(),
+        }
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     #[inline]
     fn bits(&self) -> u64 {
-        (mem::size_of::<(u64, usize)>() * self.0.capacity()) as u64 * 8
+        self.heap_bytes() as u64 * 8
+    }
+
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        match self {
+            ExceptionalItems::Compact(items) => items.heap_bytes(),
+            ExceptionalItems::Wide(items) => items.heap_bytes(),
+        }
     }
 
     #[inline]
     fn shrink_to_fit(&mut self) {
-        self.0.shrink_to_fit();
+        match self {
+            ExceptionalItems::Compact(items) => items.shrink_to_fit(),
+            ExceptionalItems::Wide(items) => items.shrink_to_fit(),
+        }
     }
 
+    /// Returns the `(fingerprint, bucket_index)` pairs (not necessarily in order).
     #[inline]
-    fn contains_kicked_out_entries(&self) -> bool {
-        self.0
-            .last()
-            .map_or(false, |&(fingerprint, _)| fingerprint != 0)
+    fn iter(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        let (compact, wide) = match self {
+            ExceptionalItems::Compact(items) => (Some(items), None),
+            ExceptionalItems::Wide(items) => (None, Some(items)),
+        };
+        let compact = compact
+            .into_iter()
+            .flat_map(|items| items.items.iter())
+            .map(|&(f, i)| (u64::from(f), i as usize));
+        let wide = wide.into_iter().flat_map(|items| items.items.iter());
+        compact.chain(wide.copied())
+    }
+
+    #[inline]
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, u64) -> bool,
+    {
+        match self {
+            ExceptionalItems::Compact(items) => {
+                items.retain(|&(fingerprint, i)| f(i as usize, u64::from(fingerprint)))
+            }
+            ExceptionalItems::Wide(items) => items.retain(|&(fingerprint, i)| f(i, fingerprint)),
+        }
+    }
+
+    #[inline]
+    fn kicked_out_entries(&self) -> usize {
+        // Zero fingerprints are not kicked out entries.
+        match self {
+            ExceptionalItems::Compact(items) => items.len() - items.count_less_than(&(1, 0)),
+            ExceptionalItems::Wide(items) => items.len() - items.count_less_than(&(1, 0)),
+        }
+    }
+
+    /// Returns `true` if the sorted runs of the entries are actually in order.
+    fn is_ordered(&self) -> bool {
+        match self {
+            ExceptionalItems::Compact(items) => items.is_ordered(),
+            ExceptionalItems::Wide(items) => items.is_ordered(),
+        }
     }
 
     #[inline]
     fn contains(&self, i0: usize, i1: usize, fingerprint: u64) -> bool {
-        let item = (fingerprint, cmp::min(i0, i1));
-        self.0.binary_search(&item).is_ok()
+        let i = cmp::min(i0, i1);
+        match self {
+            ExceptionalItems::Compact(items) => {
+                compact_item(fingerprint, i).is_some_and(|item| items.position(&item).is_some())
+            }
+            ExceptionalItems::Wide(items) => items.position(&(fingerprint, i)).is_some(),
+        }
     }
 
     #[inline]
     fn insert(&mut self, i0: usize, i1: usize, fingerprint: u64) {
-        let item = (fingerprint, cmp::min(i0, i1));
-        let index = self.0.binary_search(&item).unwrap_or_else(|i| i);
-        self.0.insert(index, item);
+        let i = cmp::min(i0, i1);
+        if let ExceptionalItems::Compact(items) = self {
+            if let Some(item) = compact_item(fingerprint, i) {
+                items.insert(item);
+                return;
+            }
+            *self = ExceptionalItems::Wide(StashEntries::from(self.iter().collect::<Vec<_>>()));
+        }
+        if let ExceptionalItems::Wide(items) = self {
+            items.insert((fingerprint, i));
+        }
     }
 
     #[inline]
     fn remove(&mut self, i0: usize, i1: usize, fingerprint: u64) -> bool {
-        let item = (fingerprint, cmp::min(i0, i1));
-        if let Ok(index) = self.0.binary_search(&item) {
-            self.0.remove(index);
-            return true;
+        let i = cmp::min(i0, i1);
+        match self {
+            ExceptionalItems::Compact(items) => {
+                compact_item(fingerprint, i).is_some_and(|item| items.remove(&item))
+            }
+            ExceptionalItems::Wide(items) => items.remove(&(fingerprint, i)),
         }
-        false
+    }
+
+    /// Writes the entries in order, so that readers can search them in place.
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut items = self.iter().collect::<Vec<_>>();
+        items.sort_unstable();
+        codec::write_usize(writer, items.len())?;
+        for (fingerprint, i) in items {
+            codec::write_u64(writer, fingerprint)?;
+            codec::write_usize(writer, i)?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let len = codec::read_usize(reader)?;
+        // The length isn't trusted for preallocation, so a corrupt one fails at the end of the input.
+        let mut items = Vec::new();
+        for _ in 0..len {
+            let fingerprint = codec::read_u64(reader)?;
+            let i = codec::read_usize(reader)?;
+            items.push((fingerprint, i));
+        }
+        Ok(ExceptionalItems::from(items))
+    }
+}
+/// Stashes are equal if they hold the same entries, in whatever order.
+impl PartialEq for ExceptionalItems {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let mut items = self.iter().collect::<Vec<_>>();
+        let mut other_items = other.iter().collect::<Vec<_>>();
+        items.sort_unstable();
+        other_items.sort_unstable();
+        items == other_items
+    }
+}
+impl From<Vec<(u64, usize)>> for ExceptionalItems {
+    fn from(items: Vec<(u64, usize)>) -> Self {
+        items
+            .iter()
+            .map(|&(f, i)| compact_item(f, i))
+            .collect::<Option<Vec<_>>>()
+            .map_or_else(
+                || ExceptionalItems::Wide(StashEntries::from(items)),
+                |items| ExceptionalItems::Compact(StashEntries::from(items)),
+            )
+    }
+}
+impl From<ExceptionalItems> for Vec<(u64, usize)> {
+    fn from(items: ExceptionalItems) -> Self {
+        let mut items = items.iter().collect::<Vec<_>>();
+        items.sort_unstable();
+        items
+    }
+}
+
+/// Multiset of stash entries kept as a sorted run followed by a short unsorted tail.
+///
+/// Insertions append to the tail, which is merged into the run once it has `MERGE_LEN`
+/// entries, so inserting into a large stash (e.g., one holding many zero fingerprints)
+/// doesn't shift the whole stash every time.
+#[derive(Debug, Clone)]
+struct StashEntries<T> {
+    items: Vec<T>,
+    sorted_len: usize,
+}
+impl<T: Ord + Copy> StashEntries<T> {
+    const MERGE_LEN: usize = 32;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        mem::size_of::<T>() * self.items.capacity()
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.merge();
+        self.items.shrink_to_fit();
+    }
+
+    fn insert(&mut self, item: T) {
+        self.items.push(item);
+        if self.items.len() - self.sorted_len >= Self::MERGE_LEN {
+            self.merge();
+        }
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        let Some(index) = self.position(item) else {
+            return false;
+        };
+        if index < self.sorted_len {
+            self.items.remove(index);
+            self.sorted_len -= 1;
+        } else {
+            self.items.swap_remove(index);
+        }
+        true
+    }
+
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.items.retain(f);
+        self.sorted_len = 0;
+        self.merge();
+    }
+
+    #[inline]
+    fn position(&self, item: &T) -> Option<usize> {
+        let (sorted, tail) = self.items.split_at(self.sorted_len);
+        sorted.binary_search(item).ok().or_else(|| {
+            tail.iter()
+                .position(|x| x == item)
+                .map(|i| self.sorted_len + i)
+        })
+    }
+
+    /// Returns the number of entries less than `item`.
+    #[inline]
+    fn count_less_than(&self, item: &T) -> usize {
+        let (sorted, tail) = self.items.split_at(self.sorted_len);
+        sorted.partition_point(|x| x < item) + tail.iter().filter(|&x| x < item).count()
+    }
+
+    fn is_ordered(&self) -> bool {
+        self.items[..self.sorted_len]
+            .windows(2)
+            .all(|w| w[0] <= w[1])
+    }
+
+    fn merge(&mut self) {
+        // The stable sort detects the sorted run, so this is a linear-time merge.
+        self.items[self.sorted_len..].sort_unstable();
+        self.items.sort();
+        self.sorted_len = self.items.len();
+    }
+}
+impl<T> Default for StashEntries<T> {
+    fn default() -> Self {
+        StashEntries {
+            items: Vec::new(),
+            sorted_len: 0,
+        }
+    }
+}
+impl<T: Ord + Copy> From<Vec<T>> for StashEntries<T> {
+    fn from(items: Vec<T>) -> Self {
+        let mut entries = StashEntries {
+            items,
+            sorted_len: 0,
+        };
+        entries.merge();
+        entries
+    }
+}
+
+#[inline]
+fn compact_item(fingerprint: u64, i: usize) -> Option<(u32, u32)> {
+    Some((u32::try_from(fingerprint).ok()?, u32::try_from(i).ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exceptional_items_work() {
+        let mut items = ExceptionalItems::new();
+        items.insert(3, 7, 100);
+        items.insert(9, 5, 0);
+        assert!(matches!(items, ExceptionalItems::Compact(_)));
+        assert!(items.contains(7, 3, 100));
+        assert_eq!(items.kicked_out_entries(), 1);
+
+        // Entries that don't fit in `u32` make the stash wide.
+        items.insert(1, 2, 1 << 40);
+        assert!(matches!(items, ExceptionalItems::Wide(_)));
+        assert_eq!(Vec::from(items.clone()), [(0, 5), (100, 3), (1 << 40, 1)]);
+        assert!(items.contains(7, 3, 100));
+        assert!(items.remove(2, 1, 1 << 40));
+        assert!(!items.contains(2, 1, 1 << 40));
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn lazy_contains_works() {
+        let hasher = siphasher::sip::SipHasher13::new();
+        let mut rng = rand::thread_rng();
+        for scheme in [AltIndexScheme::Hashed, AltIndexScheme::Mixed] {
+            let mut filter = CuckooFilter::new(
+                12,
+                4,
+                64,
+                1,
+                InsertionStrategy::RandomWalk,
+                StorageBackend::Buckets,
+                scheme,
+                true,
+                Allocator::default(),
+            );
+            let hashes = (1..200u128)
+                .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15_F39C_C060_5CED_C835))
+                .collect::<Vec<_>>();
+            for &hash in &hashes {
+                assert!(filter.insert(&hasher, &mut rng, hash, true));
+            }
+            // Both the primary and the alternative buckets are full, and the rest is stashed.
+            assert!(filter.stash_len() > 0);
+            assert!(hashes.iter().all(|&hash| filter.contains(&hasher, hash)));
+        }
+    }
+
+    #[test]
+    fn stash_entries_work() {
+        let mut entries = StashEntries::default();
+        for i in (0..1000).rev() {
+            entries.insert((i % 7, i));
+        }
+        assert_eq!(entries.len(), 1000);
+        assert!(entries.sorted_len > 0 && entries.sorted_len < 1000);
+        assert!((0..1000).all(|i| entries.position(&(i % 7, i)).is_some()));
+        assert_eq!(entries.count_less_than(&(1, 0)), 143);
+
+        assert!(entries.remove(&(0, 0)));
+        assert!(entries.remove(&(6, 13)));
+        assert!(!entries.remove(&(6, 13)));
+        entries.retain(|&(f, _)| f != 3);
+        assert_eq!(entries.sorted_len, entries.len());
+        assert_eq!(entries.len(), 1000 - 2 - 143);
+        assert!(entries.items.windows(2).all(|w| w[0] <= w[1]));
     }
 }
@@ -0,0 +1,189 @@
+//! Format of the deltas made by `delta_since` and read by `apply_delta`.
+//!
+//! A delta starts with `MAGIC`, the format version of deltas (`u16`), the baseline version,
+//! and the version of the filter (`u64`s), which are followed by the number of layers and then
+//! by the layers. Each layer starts with a tag: a layer made after the baseline is written
+//! whole (as in `write_to`, with `BitsEncoding::ZeroRuns`); any other layer is written as its
+//! number of items and stash, the byte length of its bit array, and the pages written after
+//! the baseline, each of which is its index followed by its bytes.
+//! The CRC-32 of all the preceding bytes follows as a `u32`.
+//! The primitives are encoded as in `codec`.
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+
+use rand::Rng;
+
+use crate::bits::Bits;
+use crate::codec::{self, BitsEncoding};
+use crate::cuckoo_filter::{CuckooFilter, LayerCounts};
+use crate::raw::ScalableCuckooFilter;
+
+/// Bytes that every delta starts with.
+const MAGIC: [u8; 4] = *b"SCKD";
+
+/// Version of the format of deltas written by this crate.
+const DELTA_VERSION: u16 = 1;
+
+const PAGES: u8 = 0;
+const LAYER: u8 = 1;
+
+/// Changes of a layer read from a delta.
+enum LayerDelta {
+    Pages {
+        counts: LayerCounts,
+        byte_len: usize,
+        pages: Vec<(usize, Vec<u8>)>,
+    },
+    Layer(CuckooFilter),
+}
+
+pub fn write<H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<H, R>,
+    baseline_version: u64,
+) -> io::Result<Vec<u8>> {
+    if baseline_version > filter.version {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "baseline version is newer than the filter",
+        ));
+    }
+
+    let mut bytes = Vec::new();
+    let w = &mut codec::ChecksumWriter::new(&mut bytes);
+    w.write_all(&MAGIC)?;
+    w.write_all(&DELTA_VERSION.to_le_bytes())?;
+    codec::write_u64(w, baseline_version)?;
+    codec::write_u64(w, filter.version)?;
+    codec::write_usize(w, filter.filters.len())?;
+    let mut page = [0; Bits::PAGE_BYTES];
+    for layer in &filter.filters {
+        let bits = layer.bit_array();
+        if bits.base_version() > baseline_version {
+            codec::write_u8(w, LAYER)?;
+            layer.write_to(w, BitsEncoding::ZeroRuns)?;
+            continue;
+        }
+
+        codec::write_u8(w, PAGES)?;
+        layer.write_counts_to(w)?;
+        codec::write_usize(w, bits.byte_len())?;
+        let changed = bits
+            .pages_changed_since(baseline_version)
+            .collect::<Vec<_>>();
+        codec::write_usize(w, changed.len())?;
+        for i in changed {
+            let len = bits.page_to_le_bytes(i, &mut page);
+            codec::write_usize(w, i)?;
+            w.write_all(&page[..len])?;
+        }
+    }
+    w.finish()?;
+    Ok(bytes)
+}
+
+pub fn apply<H: Hasher + Clone, R: Rng>(
+    filter: &mut ScalableCuckooFilter<H, R>,
+    mut delta: &[u8],
+) -> io::Result<()> {
+    let r = &mut codec::ChecksumReader::new(&mut delta);
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(codec::invalid_data(
+            "not a delta of a scalable cuckoo filter",
+        ));
+    }
+    let mut delta_version = [0; 2];
+    r.read_exact(&mut delta_version)?;
+    if u16::from_le_bytes(delta_version) != DELTA_VERSION {
+        return Err(codec::invalid_data("unsupported delta format version"));
+    }
+    let baseline_version = codec::read_u64(r)?;
+    let version = codec::read_u64(r)?;
+    let layers = (0..codec::read_usize(r)?)
+        .map(|_| read_layer(r))
+        .collect::<io::Result<Vec<_>>>()?;
+    r.verify()?;
+    if !delta.is_empty() {
+        return Err(codec::invalid_data("trailing bytes after the delta"));
+    }
+
+    // Nothing is changed unless the whole delta applies.
+    if !(baseline_version..=version).contains(&filter.version) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "delta doesn't apply to the version of the filter",
+        ));
+    }
+    if layers.is_empty() {
+        return Err(codec::invalid_data("delta has no layers"));
+    }
+    for (i, layer) in layers.iter().enumerate() {
+        let LayerDelta::Pages { byte_len, .. } = layer else {
+            continue;
+        };
+        if filter.filters.get(i).map(|f| f.bit_array().byte_len()) != Some(*byte_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "delta doesn't match the layers of the filter",
+            ));
+        }
+    }
+
+    filter.filters.truncate(layers.len());
+    for (i, layer) in layers.into_iter().enumerate() {
+        match layer {
+            LayerDelta::Pages { counts, pages, .. } => {
+                let f = &mut filter.filters[i];
+                f.set_counts(counts);
+                let bits = f.bit_array_mut();
+                bits.set_version(version);
+                for (page, bytes) in pages {
+                    bits.set_page_from_le_bytes(page, &bytes);
+                }
+            }
+            LayerDelta::Layer(mut f) => {
+                f.bit_array_mut().reset_versions(version);
+                if i < filter.filters.len() {
+                    filter.filters[i] = f;
+                } else {
+                    filter.filters.push(f);
+                }
+            }
+        }
+    }
+    filter.version = version;
+    Ok(())
+}
+
+fn read_layer<Rd: Read>(reader: &mut Rd) -> io::Result<LayerDelta> {
+    match codec::read_u8(reader)? {
+        PAGES => {
+            let counts = CuckooFilter::read_counts_from(reader)?;
+            let byte_len = codec::read_usize(reader)?;
+            let page_count = byte_len.div_ceil(Bits::PAGE_BYTES);
+            let pages = (0..codec::read_usize(reader)?)
+                .map(|_| {
+                    let page = codec::read_usize(reader)?;
+                    if page >= page_count {
+                        return Err(codec::invalid_data("page exceeds the bit array"));
+                    }
+                    let len = (byte_len - page * Bits::PAGE_BYTES).min(Bits::PAGE_BYTES);
+                    let mut bytes = vec![0; len];
+                    reader.read_exact(&mut bytes)?;
+                    Ok((page, bytes))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            Ok(LayerDelta::Pages {
+                counts,
+                byte_len,
+                pages,
+            })
+        }
+        LAYER => Ok(LayerDelta::Layer(CuckooFilter::read_from(
+            reader,
+            BitsEncoding::ZeroRuns,
+        )?)),
+        _ => Err(codec::invalid_data("unknown layer delta")),
+    }
+}
@@ -0,0 +1,468 @@
+//! Dumps of the entries of filters made by `dump` and read by `restore`.
+//!
+//! Entries are dumped in the canonical form of reconciliation (see `reconcile`): the smaller
+//! candidate bucket index and the fingerprint, which don't depend on where the entries have been
+//! placed. So entries can be added to, removed from, or moved between dumps freely, and
+//! `restore` places them anew.
+use std::hash::Hasher;
+use std::io;
+use std::mem;
+
+use rand::Rng;
+
+use crate::bits;
+use crate::raw::ScalableCuckooFilter;
+use crate::reconcile;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Entries of a `ScalableCuckooFilter` with the parameters of its layers, made by
+/// `ScalableCuckooFilter::dump`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct FilterDump {
+    /// Hash of the hasher and of the configuration of the filter that determine
+    /// the entries of items.
+    ///
+    /// A dump can be restored only into filters with the same hash.
+    pub config: u64,
+
+    /// Layers of the filter, from the oldest one.
+    pub layers: Vec<LayerDump>,
+}
+
+/// Layer of a [`FilterDump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct LayerDump {
+    /// Number of buckets of the layer.
+    pub bucket_count: usize,
+
+    /// Number of bits of the fingerprints of the layer.
+    pub fingerprint_bitwidth: usize,
+
+    /// `(bucket_index, fingerprint)` pairs of the entries of the layer.
+    ///
+    /// `bucket_index` is the smaller of the two candidate bucket indices of the entry,
+    /// wherever the entry is placed. An item inserted more than once has as many entries.
+    pub entries: LayerEntries,
+}
+
+/// `(bucket_index, fingerprint)` pairs of the entries of a [`LayerDump`], each packed into
+/// `log2(bucket_count) + fingerprint_bitwidth` bits (with `log2` rounded up).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde_support", serde(try_from = "LayerEntriesRepr"))]
+pub struct LayerEntries {
+    index_bitwidth: usize,
+    fingerprint_bitwidth: usize,
+    len: usize,
+    words: Vec<u64>,
+}
+impl LayerEntries {
+    /// Makes an empty list of the entries of a layer of `bucket_count` buckets
+    /// and `fingerprint_bitwidth`-bit fingerprints.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `fingerprint_bitwidth` is not in the range `1..=64`.
+    pub fn new(bucket_count: usize, fingerprint_bitwidth: usize) -> Self {
+        assert!((1..=64).contains(&fingerprint_bitwidth));
+        LayerEntries {
+            index_bitwidth: bucket_count.next_power_of_two().trailing_zeros() as usize,
+            fingerprint_bitwidth,
+            len: 0,
+            words: Vec::new(),
+        }
+    }
+
+    /// Returns the number of the entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no entries, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the `i`-th entry, or `None` if `i` is out of range.
+    pub fn get(&self, i: usize) -> Option<(usize, u64)> {
+        if i >= self.len {
+            return None;
+        }
+        let position = i * self.entry_bitwidth();
+        let bucket_index = if self.index_bitwidth == 0 {
+            0
+        } else {
+            bits::get_uint(&self.words[..], position, self.index_bitwidth) as usize
+        };
+        let fingerprint = bits::get_uint(
+            &self.words[..],
+            position + self.index_bitwidth,
+            self.fingerprint_bitwidth,
+        );
+        Some((bucket_index, fingerprint))
+    }
+
+    /// Returns an iterator over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        (0..self.len).map(|i| self.get(i).expect("unreachable"))
+    }
+
+    /// Appends an entry.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `bucket_index` or `fingerprint` doesn't fit in its bits.
+    pub fn push(&mut self, bucket_index: usize, fingerprint: u64) {
+        assert!(
+            (bucket_index as u64)
+                .checked_shr(self.index_bitwidth as u32)
+                .unwrap_or(0)
+                == 0,
+            "bucket index out of range"
+        );
+        assert!(
+            fingerprint
+                .checked_shr(self.fingerprint_bitwidth as u32)
+                .unwrap_or(0)
+                == 0,
+            "fingerprint out of range"
+        );
+        let position = self.len * self.entry_bitwidth();
+        self.len += 1;
+        self.words
+            .resize((self.len * self.entry_bitwidth()).div_ceil(64), 0);
+        if self.index_bitwidth != 0 {
+            bits::set_uint(
+                &mut self.words[..],
+                position,
+                self.index_bitwidth,
+                bucket_index as u64,
+            );
+        }
+        bits::set_uint(
+            &mut self.words[..],
+            position + self.index_bitwidth,
+            self.fingerprint_bitwidth,
+            fingerprint,
+        );
+    }
+
+    /// Removes all the entries.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.words.clear();
+    }
+
+    fn entry_bitwidth(&self) -> usize {
+        self.index_bitwidth + self.fingerprint_bitwidth
+    }
+
+    /// Returns `true` if the entries are packed for a layer of the given parameters.
+    fn fits(&self, bucket_count: usize, fingerprint_bitwidth: usize) -> bool {
+        self.index_bitwidth == bucket_count.next_power_of_two().trailing_zeros() as usize
+            && self.fingerprint_bitwidth == fingerprint_bitwidth
+    }
+}
+
+/// Serialized form of `LayerEntries`, which is validated before use.
+#[cfg(feature = "serde_support")]
+#[derive(Deserialize)]
+struct LayerEntriesRepr {
+    index_bitwidth: usize,
+    fingerprint_bitwidth: usize,
+    len: usize,
+    words: Vec<u64>,
+}
+#[cfg(feature = "serde_support")]
+impl TryFrom<LayerEntriesRepr> for LayerEntries {
+    type Error = &'static str;
+
+    fn try_from(repr: LayerEntriesRepr) -> Result<Self, Self::Error> {
+        let bits = repr
+            .len
+            .checked_mul(repr.index_bitwidth + repr.fingerprint_bitwidth);
+        if repr.index_bitwidth >= usize::BITS as usize
+            || !(1..=64).contains(&repr.fingerprint_bitwidth)
+            || bits.map(|bits| bits.div_ceil(64)) != Some(repr.words.len())
+        {
+            return Err("invalid layer entries");
+        }
+        Ok(LayerEntries {
+            index_bitwidth: repr.index_bitwidth,
+            fingerprint_bitwidth: repr.fingerprint_bitwidth,
+            len: repr.len,
+            words: repr.words,
+        })
+    }
+}
+
+pub fn dump<H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<H, R>,
+) -> io::Result<FilterDump> {
+    let layers = filter
+        .filters
+        .iter()
+        .map(|layer| {
+            let mut entries = LayerEntries::new(layer.bucket_count(), layer.fingerprint_bitwidth());
+            for (bucket_index, fingerprint) in layer.canonical_entries(&filter.hasher) {
+                entries.push(bucket_index, fingerprint);
+            }
+            LayerDump {
+                bucket_count: layer.bucket_count(),
+                fingerprint_bitwidth: layer.fingerprint_bitwidth(),
+                entries,
+            }
+        })
+        .collect();
+    Ok(FilterDump {
+        config: reconcile::config_hash(filter)?,
+        layers,
+    })
+}
+
+pub fn restore<H: Hasher + Clone, R: Rng>(
+    filter: &mut ScalableCuckooFilter<H, R>,
+    dump: &FilterDump,
+) -> io::Result<()> {
+    // The layers are added the same way as the filter grows, so the dumped parameters are
+    // checked rather than trusted.
+    replace_layers(filter, dump, |filter, _| {
+        filter.grow();
+        Ok(())
+    })
+}
+
+pub fn rebuild<H: Hasher + Clone, R: Rng>(
+    filter: &mut ScalableCuckooFilter<H, R>,
+    dump: &FilterDump,
+) -> io::Result<()> {
+    replace_layers(filter, dump, |filter, layer_dump| {
+        let max_fingerprint_bitwidth = filter.hash_width.max_fingerprint_bitwidth();
+        if !(1..=max_fingerprint_bitwidth).contains(&layer_dump.fingerprint_bitwidth) {
+            return Err(invalid_input("the dump has an invalid fingerprint length"));
+        }
+        let layer = filter.new_layer(
+            layer_dump.fingerprint_bitwidth,
+            layer_dump
+                .bucket_count
+                .saturating_mul(filter.entries_per_bucket),
+            layer_dump.bucket_count.is_power_of_two(),
+        );
+        filter.filters.push(layer);
+        Ok(())
+    })
+}
+
+/// Replaces the layers of `filter` with those of `dump`, each added by `add_layer`.
+///
+/// The filter is left as it is on errors.
+fn replace_layers<H: Hasher + Clone, R: Rng, F>(
+    filter: &mut ScalableCuckooFilter<H, R>,
+    dump: &FilterDump,
+    add_layer: F,
+) -> io::Result<()>
+where
+    F: FnMut(&mut ScalableCuckooFilter<H, R>, &LayerDump) -> io::Result<()>,
+{
+    if reconcile::config_hash(filter)? != dump.config {
+        return Err(invalid_input(
+            "the dump was made by a filter with a different hasher or configuration",
+        ));
+    }
+    if dump.layers.is_empty() {
+        return Err(invalid_input("the dump has no layers"));
+    }
+
+    let filters = mem::take(&mut filter.filters);
+    let (version, counters) = (filter.version, filter.counters);
+    let replaced = add_layers(filter, dump, add_layer);
+    if replaced.is_err() {
+        filter.filters = filters;
+        filter.version = version;
+        filter.counters = counters;
+    }
+    replaced
+}
+
+fn add_layers<H: Hasher + Clone, R: Rng, F>(
+    filter: &mut ScalableCuckooFilter<H, R>,
+    dump: &FilterDump,
+    mut add_layer: F,
+) -> io::Result<()>
+where
+    F: FnMut(&mut ScalableCuckooFilter<H, R>, &LayerDump) -> io::Result<()>,
+{
+    // The new layers are made at a new version, so deltas since the old one cover them.
+    filter.next_version();
+    for layer_dump in &dump.layers {
+        add_layer(filter, layer_dump)?;
+        let layer = filter.filters.last_mut().expect("unreachable");
+        if layer.bucket_count() != layer_dump.bucket_count
+            || layer.fingerprint_bitwidth() != layer_dump.fingerprint_bitwidth
+        {
+            return Err(invalid_input(
+                "the layers of the dump don't match those of the filter",
+            ));
+        }
+        if !layer_dump
+            .entries
+            .fits(layer_dump.bucket_count, layer_dump.fingerprint_bitwidth)
+        {
+            return Err(invalid_input(
+                "the entries of the dump are packed for another layer",
+            ));
+        }
+
+        for (bucket_index, fingerprint) in layer_dump.entries.iter() {
+            if bucket_index >= layer_dump.bucket_count {
+                return Err(invalid_input("the dump has an invalid entry"));
+            }
+            layer.insert_canonical_entry(
+                &filter.hasher,
+                &mut filter.rng,
+                bucket_index,
+                fingerprint,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod test {
+    use siphasher::sip::SipHasher13;
+
+    use super::*;
+    use crate::ScalableCuckooFilterBuilder;
+
+    fn new_filter(key: u64) -> ScalableCuckooFilter<SipHasher13> {
+        ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .hasher(SipHasher13::new_with_keys(key, key))
+            .finish_raw()
+    }
+
+    fn sorted_entries(dump: &FilterDump) -> Vec<Vec<(usize, u64)>> {
+        dump.layers
+            .iter()
+            .map(|layer| {
+                let mut entries = layer.entries.iter().collect::<Vec<_>>();
+                entries.sort_unstable();
+                entries
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_works() {
+        let mut filter = new_filter(1);
+        let hashes = (0..1000u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect::<Vec<_>>();
+        for &hash in &hashes {
+            filter.insert(hash);
+        }
+        let dump = filter.dump().unwrap();
+        assert!(dump.layers.len() > 1);
+        assert_eq!(
+            dump.layers
+                .iter()
+                .map(|l| l.entries.len() as u64)
+                .sum::<u64>(),
+            filter.len()
+        );
+
+        let mut restored = new_filter(1);
+        restored.insert(12345);
+        restored.restore(&dump).unwrap();
+        assert_eq!(restored.len(), filter.len());
+        assert_eq!(restored.layers().len(), filter.layers().len());
+        assert!(hashes.iter().all(|&hash| restored.contains(hash)));
+        assert_eq!(
+            sorted_entries(&restored.dump().unwrap()),
+            sorted_entries(&dump)
+        );
+
+        // Invalid dumps leave the filter as it is.
+        let before = restored.clone();
+        assert!(new_filter(2).restore(&dump).is_err());
+        let mut invalid = dump.clone();
+        let layer = &mut invalid.layers[0];
+        layer.entries = LayerEntries::new(layer.bucket_count * 2, layer.fingerprint_bitwidth);
+        assert!(restored.restore(&invalid).is_err());
+        let mut invalid = dump.clone();
+        invalid.layers[1].fingerprint_bitwidth += 1;
+        assert!(restored.restore(&invalid).is_err());
+        let mut invalid = dump.clone();
+        invalid.layers.clear();
+        assert!(restored.restore(&invalid).is_err());
+        assert!(restored == before);
+    }
+
+    #[test]
+    fn build_from_dump_works() {
+        let mut filter = new_filter(1);
+        for i in 0..1000u64 {
+            filter.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        let dump = filter.dump().unwrap();
+
+        // The layers are made as dumped, whatever the initial capacity.
+        let builder = || {
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(10_000)
+                .hasher(SipHasher13::new_with_keys(1, 1))
+        };
+        let restored = builder().build_raw_from_dump(&dump).unwrap();
+        assert_eq!(restored.len(), filter.len());
+        assert_eq!(restored.layers().len(), filter.layers().len());
+        assert_eq!(
+            sorted_entries(&restored.dump().unwrap()),
+            sorted_entries(&dump)
+        );
+
+        let mut invalid = dump.clone();
+        invalid.layers[0].fingerprint_bitwidth = 65;
+        assert!(builder().build_raw_from_dump(&invalid).is_err());
+        assert!(ScalableCuckooFilterBuilder::new()
+            .hasher(SipHasher13::new_with_keys(2, 2))
+            .build_raw_from_dump(&dump)
+            .is_err());
+    }
+
+    #[test]
+    fn layer_entries_work() {
+        let mut entries = LayerEntries::new(1000, 13);
+        assert!(entries.is_empty());
+        for i in 0..100 {
+            entries.push(i * 10, (i as u64 * 77) % (1 << 13));
+        }
+        assert_eq!(entries.len(), 100);
+        assert_eq!(entries.words.len(), (100 * (10 + 13) as usize).div_ceil(64));
+        assert!(entries
+            .iter()
+            .enumerate()
+            .all(|(i, entry)| entry == (i * 10, (i as u64 * 77) % (1 << 13))));
+        assert_eq!(entries.get(100), None);
+
+        // A single bucket needs no index bits.
+        let mut entries = LayerEntries::new(1, 64);
+        entries.push(0, u64::MAX);
+        assert_eq!(entries.get(0), Some((0, u64::MAX)));
+        assert_eq!(entries.words.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn layer_entries_out_of_range_panics() {
+        LayerEntries::new(1000, 13).push(1024, 1);
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::growth::LayerInfo;
+
+/// Event emitted by `ScalableCuckooFilter`.
+#[derive(Debug, Clone)]
+pub enum FilterEvent<'a> {
+    /// A new layer has been added to the filter.
+    Grow(&'a LayerInfo),
+
+    /// An entry has been inserted into the stash of a layer.
+    Stash(&'a LayerInfo),
+
+    /// A layer has been added with the longest fingerprints the hash width allows
+    /// (64 bits, or 32 bits with `HashWidth::Bits32`), although its share of
+    /// the false positive probability requires longer ones.
+    ///
+    /// Such a layer (and thus the filter) exceeds the configured false positive probability,
+    /// as reported by [`false_positive_probability_bound`].
+    /// This happens once the tightening ratio has shrunk the share of later layers too much.
+    ///
+    /// [`false_positive_probability_bound`]: crate::ScalableCuckooFilter::false_positive_probability_bound
+    FingerprintClamped(&'a LayerInfo),
+}
+
+/// Listener of [`FilterEvent`]s.
+///
+/// Any `Fn(&FilterEvent)` closure that is `Send + Sync` implements this trait.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{FilterEvent, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let growths = Arc::new(AtomicUsize::new(0));
+/// let counter = Arc::clone(&growths);
+/// let mut filter: ScalableCuckooFilter<usize> = ScalableCuckooFilterBuilder::new()
+///     .initial_capacity(100)
+///     .event_listener(move |event: &FilterEvent| {
+///         if let FilterEvent::Grow(layer) = event {
+///             assert!(layer.capacity() > 100);
+///             counter.fetch_add(1, Ordering::SeqCst);
+///         }
+///     })
+///     .finish();
+/// for i in 0..1000 {
+///     filter.insert(&i);
+/// }
+/// assert_eq!(growths.load(Ordering::SeqCst), filter.layers().len() - 1);
+/// ```
+pub trait EventListener: Send + Sync {
+    /// Handles `event`.
+    fn on_event(&self, event: &FilterEvent);
+}
+impl<F> EventListener for F
+where
+    F: Fn(&FilterEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &FilterEvent) {
+        self(event)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Listener(Option<Arc<dyn EventListener>>);
+impl Listener {
+    pub fn new<L: EventListener + 'static>(listener: L) -> Self {
+        Listener(Some(Arc::new(listener)))
+    }
+
+    #[inline]
+    pub fn is_some(&self) -> bool {
+        self.0.is_some()
+    }
+
+    #[inline]
+    pub fn notify(&self, event: &FilterEvent) {
+        if let Some(listener) = &self.0 {
+            listener.on_event(event);
+        }
+    }
+}
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_some() {
+            write!(f, "Listener(..)")
+        } else {
+            write!(f, "Listener(None)")
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! C API over an opaque handle of a filter of byte strings.
+//!
+//! This is available only if the `ffi` feature is enabled.
+//! The declarations are in `include/scalable_cuckoo_filter.h`, which is generated by
+//! [cbindgen] (`cbindgen --config cbindgen.toml --output include/scalable_cuckoo_filter.h`).
+//! A shared or static library is built by
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! Items are byte strings, and filters are built with a randomly keyed `DefaultHasher` as their
+//! hasher, whose keys are written with the filter, so a `ScalableCuckooFilter<[u8]>` in Rust can
+//! read the bytes written by [`scf_serialize`] and vice versa.
+//!
+//! Functions taking a `const ScfFilter *` may be called from multiple threads at the same time,
+//! but those taking a `ScfFilter *` must not be called concurrently with any other function on
+//! the same filter.
+//!
+//! # Examples
+//!
+//! ```c
+//! #include "scalable_cuckoo_filter.h"
+//!
+//! ScfFilter *filter = scf_new(1000, 0.001);
+//! scf_insert(filter, (const uint8_t *)"foo", 3);
+//! assert(scf_contains(filter, (const uint8_t *)"foo", 3));
+//!
+//! uint8_t *bytes;
+//! size_t len;
+//! if (scf_serialize(filter, &bytes, &len)) {
+//!     ScfFilter *copy = scf_deserialize(bytes, len);
+//!     scf_free_bytes(bytes, len);
+//!     scf_free(copy);
+//! }
+//! scf_free(filter);
+//! ```
+//!
+//! [cbindgen]: https://github.com/mozilla/cbindgen
+use std::ptr;
+use std::slice;
+
+use crate::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+/// Opaque handle of a filter.
+#[derive(Debug)]
+pub struct ScfFilter(ScalableCuckooFilter<[u8]>);
+
+/// Makes a new filter, or returns `NULL` if the parameters are invalid.
+///
+/// The returned filter must be freed by [`scf_free`].
+#[no_mangle]
+pub extern "C" fn scf_new(
+    initial_capacity: usize,
+    false_positive_probability: f64,
+) -> *mut ScfFilter {
+    ScalableCuckooFilterBuilder::new()
+        .for_type::<[u8]>()
+        .initial_capacity(initial_capacity)
+        .false_positive_probability(false_positive_probability)
+        .build()
+        .map_or(ptr::null_mut(), |filter| {
+            Box::into_raw(Box::new(ScfFilter(filter)))
+        })
+}
+
+/// Frees a filter returned by [`scf_new`] or [`scf_deserialize`].
+///
+/// # Safety
+///
+/// `filter` must be `NULL` (which does nothing) or a filter that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn scf_free(filter: *mut ScfFilter) {
+    if !filter.is_null() {
+        drop(Box::from_raw(filter));
+    }
+}
+
+/// Inserts the `len` bytes at `item` into a filter.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter, and `item` must point to `len` readable bytes
+/// (it may be `NULL` if `len` is zero).
+#[no_mangle]
+pub unsafe extern "C" fn scf_insert(filter: *mut ScfFilter, item: *const u8, len: usize) {
+    (*filter).0.insert(bytes(item, len));
+}
+
+/// Returns `true` if a filter may contain the `len` bytes at `item`.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter, and `item` must point to `len` readable bytes
+/// (it may be `NULL` if `len` is zero).
+#[no_mangle]
+pub unsafe extern "C" fn scf_contains(
+    filter: *const ScfFilter,
+    item: *const u8,
+    len: usize,
+) -> bool {
+    (*filter).0.contains(bytes(item, len))
+}
+
+/// Removes the `len` bytes at `item` from a filter, returning `true` if an entry has been removed.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter, and `item` must point to `len` readable bytes
+/// (it may be `NULL` if `len` is zero).
+#[no_mangle]
+pub unsafe extern "C" fn scf_remove(filter: *mut ScfFilter, item: *const u8, len: usize) -> bool {
+    (*filter).0.remove(bytes(item, len))
+}
+
+/// Returns the approximate number of items in a filter.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter.
+#[no_mangle]
+pub unsafe extern "C" fn scf_len(filter: *const ScfFilter) -> u64 {
+    (*filter).0.len()
+}
+
+/// Serializes a filter (as `ScalableCuckooFilter::to_bytes` does), storing the bytes and their
+/// length in `*bytes` and `*len`.
+///
+/// Returns `false` if the filter can't be serialized, leaving `*bytes` and `*len` as they are.
+/// Otherwise the bytes must be freed by [`scf_free_bytes`].
+///
+/// # Safety
+///
+/// `filter` must be a valid filter, and `bytes` and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn scf_serialize(
+    filter: *const ScfFilter,
+    bytes: *mut *mut u8,
+    len: *mut usize,
+) -> bool {
+    let Ok(serialized) = (*filter).0.to_bytes() else {
+        return false;
+    };
+    let serialized = serialized.into_boxed_slice();
+    *len = serialized.len();
+    *bytes = Box::into_raw(serialized).cast();
+    true
+}
+
+/// Frees the bytes returned by [`scf_serialize`].
+///
+/// # Safety
+///
+/// `bytes` must be `NULL` (which does nothing) or bytes returned by [`scf_serialize`] that
+/// haven't been freed, and `len` must be their length.
+#[no_mangle]
+pub unsafe extern "C" fn scf_free_bytes(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+/// Deserializes a filter from the `len` bytes at `bytes` written by [`scf_serialize`]
+/// (or `ScalableCuckooFilter::to_bytes`), or returns `NULL` if they are malformed.
+///
+/// The returned filter must be freed by [`scf_free`].
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes (it may be `NULL` if `len` is zero).
+#[no_mangle]
+pub unsafe extern "C" fn scf_deserialize(bytes: *const u8, len: usize) -> *mut ScfFilter {
+    ScalableCuckooFilter::<[u8]>::from_bytes(self::bytes(bytes, len))
+        .map_or(ptr::null_mut(), |filter| {
+            Box::into_raw(Box::new(ScfFilter(filter)))
+        })
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        unsafe {
+            assert!(scf_new(0, 0.001).is_null());
+            let filter = scf_new(100, 0.001);
+            assert!(!filter.is_null());
+            scf_insert(filter, b"foo".as_ptr(), 3);
+            scf_insert(filter, ptr::null(), 0);
+            assert!(scf_contains(filter, b"foo".as_ptr(), 3));
+            assert!(scf_contains(filter, ptr::null(), 0));
+            assert!(!scf_contains(filter, b"bar".as_ptr(), 3));
+            assert_eq!(scf_len(filter), 2);
+
+            let mut bytes = ptr::null_mut();
+            let mut len = 0;
+            assert!(scf_serialize(filter, &mut bytes, &mut len));
+            let copy = scf_deserialize(bytes, len);
+            let rust = ScalableCuckooFilter::<[u8]>::from_bytes(slice::from_raw_parts(bytes, len));
+            assert!(rust.unwrap().contains(b"foo"));
+            scf_free_bytes(bytes, len);
+            assert!(scf_contains(copy, b"foo".as_ptr(), 3));
+            assert!(scf_remove(copy, b"foo".as_ptr(), 3));
+            assert!(!scf_contains(copy, b"foo".as_ptr(), 3));
+            scf_free(copy);
+
+            assert!(scf_deserialize(b"foo".as_ptr(), 3).is_null());
+            scf_free(filter);
+            scf_free(ptr::null_mut());
+            scf_free_bytes(ptr::null_mut(), 0);
+        }
+    }
+}
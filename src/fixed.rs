@@ -0,0 +1,235 @@
+use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use rand::Rng;
+
+use crate::alloc::Allocator;
+use crate::cuckoo_filter::{self, AltIndexScheme, InsertionStrategy, StorageBackend};
+use crate::scalable_cuckoo_filter::default_hasher;
+use crate::{DefaultHasher, DefaultRng};
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+const ENTRIES_PER_BUCKET: usize = 4;
+const MAX_KICKS: usize = 512;
+
+/// Cuckoo Filter whose capacity is fixed at construction.
+///
+/// Unlike [`ScalableCuckooFilter`], this filter never grows.
+/// Once no free entry can be found for an item, [`insert`] fails with [`CapacityError`].
+///
+/// [`ScalableCuckooFilter`]: crate::ScalableCuckooFilter
+/// [`insert`]: CuckooFilter::insert
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::CuckooFilter;
+///
+/// let mut filter = CuckooFilter::new(100, 0.001);
+/// assert_eq!(filter.capacity(), 128);
+///
+/// filter.insert("foo").unwrap();
+/// assert!(filter.contains("foo"));
+///
+/// for i in 0.. {
+///     if filter.insert(&i.to_string()).is_err() {
+///         break;
+///     }
+/// }
+/// assert!(filter.len() > 100);
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(bound(
+        serialize = "H: crate::HasherKeys",
+        deserialize = "H: Default + crate::HasherKeys, R: Default"
+    ))
+)]
+pub struct CuckooFilter<T: ?Sized, H = DefaultHasher, R = DefaultRng> {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, with = "crate::hashers::serde_keys")
+    )]
+    hasher: H,
+    filter: cuckoo_filter::CuckooFilter,
+    false_positive_probability: f64,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    rng: R,
+    _item: PhantomData<fn(&T)>,
+}
+impl<T: Hash + ?Sized> CuckooFilter<T> {
+    /// Makes a new `CuckooFilter` instance that can hold at least `capacity` items.
+    ///
+    /// The actual capacity is rounded up to a power of two, and the hasher is keyed randomly
+    /// (see [`DefaultHasher`]).
+    pub fn new(capacity: usize, false_positive_probability: f64) -> Self {
+        let mut rng = rand::thread_rng();
+        Self::with_hasher_and_rng(
+            capacity,
+            false_positive_probability,
+            default_hasher(&mut rng),
+            rng,
+        )
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> CuckooFilter<T, H, R> {
+    /// Makes a new `CuckooFilter` instance that uses the given hasher and random number generator.
+    pub fn with_hasher_and_rng(
+        capacity: usize,
+        false_positive_probability: f64,
+        hasher: H,
+        rng: R,
+    ) -> Self {
+        // Same bound as the layers of `ScalableCuckooFilter` (see `fingerprint_bitwidth` there).
+        let compared_entries = (2 * ENTRIES_PER_BUCKET) as f64;
+        let mut fingerprint_bitwidth = 1;
+        while compared_entries / 2f64.powi(fingerprint_bitwidth as i32) > false_positive_probability
+            && fingerprint_bitwidth < 64
+        {
+            fingerprint_bitwidth += 1;
+        }
+
+        let filter = cuckoo_filter::CuckooFilter::new(
+            fingerprint_bitwidth,
+            ENTRIES_PER_BUCKET,
+            capacity,
+            MAX_KICKS,
+            InsertionStrategy::RandomWalk,
+            StorageBackend::Buckets,
+            AltIndexScheme::Hashed,
+            true,
+            Allocator::default(),
+        );
+        CuckooFilter {
+            hasher,
+            filter,
+            false_positive_probability,
+            rng,
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.filter.len() as u64
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity (i.e., the number of entries) of this filter.
+    ///
+    /// Note that insertions usually start failing a little before the filter is completely full.
+    pub fn capacity(&self) -> u64 {
+        self.filter.capacity() as u64
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.filter.bits()
+    }
+
+    /// Returns the false positive probability given at construction.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let item_hash = crate::widen(crate::hash(&self.hasher, item));
+        self.filter.contains(&self.hasher, item_hash)
+    }
+
+    /// Inserts `item` into this filter.
+    ///
+    /// If no free entry is found for `item`, this method returns `Err(CapacityError)`
+    /// leaving the filter unchanged.
+    pub fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        let item_hash = crate::widen(crate::hash(&self.hasher, item));
+        if self
+            .filter
+            .insert(&self.hasher, &mut self.rng, item_hash, false)
+        {
+            Ok(())
+        } else {
+            Err(CapacityError)
+        }
+    }
+
+    /// Removes `item` from this filter.
+    ///
+    /// Returns `true` if a matching entry was removed, otherwise `false`.
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let item_hash = crate::widen(crate::hash(&self.hasher, item));
+        self.filter.remove(&self.hasher, item_hash)
+    }
+}
+
+/// Error returned by [`CuckooFilter::insert`] when the filter is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError;
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cuckoo filter is full")
+    }
+}
+impl Error for CapacityError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let mut filter = CuckooFilter::new(10_000, 0.001);
+        assert_eq!(filter.capacity(), 16384);
+        assert!(filter.is_empty());
+
+        let mut inserted = 0;
+        loop {
+            let bits = filter.bits();
+            if filter.insert(&inserted).is_err() {
+                // A failed insertion leaves the filter unchanged.
+                assert_eq!(filter.len(), inserted);
+                assert_eq!(filter.bits(), bits);
+                break;
+            }
+            inserted += 1;
+        }
+        assert!(inserted as f64 > filter.capacity() as f64 * 0.9);
+        assert!((0..inserted).all(|i| filter.contains(&i)));
+
+        // The removed entry frees a slot in one of its candidate buckets.
+        assert!(filter.remove(&0));
+        assert_eq!(filter.len(), inserted - 1);
+        assert!(filter.insert(&0).is_ok());
+        assert!(filter.contains(&0));
+    }
+
+    #[test]
+    fn false_positive_probability_works() {
+        let mut filter = CuckooFilter::new(10_000, 0.001);
+        for i in 0..10_000 {
+            filter.insert(&i).unwrap();
+        }
+        let false_positives = (10_000..1_010_000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 1000, "{}", false_positives);
+    }
+}
@@ -0,0 +1,172 @@
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::cuckoo_filter::{CuckooFilter, HashWidth};
+use crate::growth::LayerInfo;
+use crate::{DefaultHasher, FilterStats};
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Read-only Scalable Cuckoo Filter.
+///
+/// This is made by [`ScalableCuckooFilter::freeze`] and only supports lookups.
+/// Because it has neither a random number generator nor a mutation API, it is `Sync`
+/// (if the hasher is) and can be shared between threads freely.
+///
+/// [`ScalableCuckooFilter::freeze`]: crate::ScalableCuckooFilter::freeze
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilter;
+///
+/// let mut filter = ScalableCuckooFilter::new(100, 0.001);
+/// for i in 0..1000 {
+///     filter.insert(&i);
+/// }
+///
+/// let frozen = filter.freeze();
+/// assert_eq!(frozen.len(), 1000);
+/// assert!(frozen.contains(&10));
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(bound(
+        serialize = "H: crate::HasherKeys",
+        deserialize = "H: Default + crate::HasherKeys"
+    ))
+)]
+pub struct FrozenScalableCuckooFilter<T: ?Sized, H = DefaultHasher> {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, with = "crate::hashers::serde_keys")
+    )]
+    hasher: H,
+    filters: Vec<CuckooFilter>,
+    false_positive_probability: f64,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    hash_width: HashWidth,
+    _item: PhantomData<fn(&T)>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> FrozenScalableCuckooFilter<T, H> {
+    pub(crate) fn new(
+        hasher: H,
+        mut filters: Vec<CuckooFilter>,
+        false_positive_probability: f64,
+        hash_width: HashWidth,
+    ) -> Self {
+        // Positive lookups stop at the first matching layer, so the most populated layers
+        // are checked first.
+        filters.retain(|f| f.len() > 0);
+        filters.sort_by_key(|f| std::cmp::Reverse(f.len()));
+        FrozenScalableCuckooFilter {
+            hasher,
+            filters,
+            false_positive_probability,
+            hash_width,
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the approximate number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.filters.iter().map(|f| f.len() as u64).sum()
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.filters.iter().map(|f| f.bits()).sum()
+    }
+
+    /// Returns the number of bytes of memory used by this filter.
+    ///
+    /// See [`ScalableCuckooFilter::memory_usage`](crate::ScalableCuckooFilter::memory_usage).
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + std::mem::size_of::<CuckooFilter>() * self.filters.capacity()
+            + self.filters.iter().map(|f| f.heap_bytes()).sum::<usize>()
+    }
+
+    /// Returns the false positive probability configured for the original filter.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /// Returns information about the layers of this filter.
+    pub fn layers(&self) -> Vec<LayerInfo> {
+        self.filters.iter().map(LayerInfo::new).collect()
+    }
+
+    /// Returns the statistics of this filter.
+    pub fn stats(&self) -> FilterStats {
+        FilterStats::new(self.layers())
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let item_hash = crate::item_hash(&self.hasher, item, self.hash_width);
+        self.filters
+            .iter()
+            .any(|f| f.contains(&self.hasher, item_hash))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FrozenScalableCuckooFilter, ScalableCuckooFilter};
+
+    #[test]
+    fn it_works() {
+        let mut filter = ScalableCuckooFilter::new(100, 0.001);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        let layers = filter.layers().len();
+        let bits = filter.bits();
+
+        let frozen = filter.freeze();
+        assert_eq!(frozen.len(), 10_000);
+        assert_eq!(frozen.layers().len(), layers);
+        assert!(frozen.bits() <= bits);
+        assert!(frozen.layers().windows(2).all(|w| w[0].len() >= w[1].len()));
+        assert!((0..10_000).all(|i| frozen.contains(&i)));
+
+        fn assert_sync<T: Sync>(_: &T) {}
+        assert_sync(&frozen);
+        let _: &FrozenScalableCuckooFilter<usize> = &frozen;
+    }
+
+    #[test]
+    fn freeze_compacted_works() {
+        let mut filter = ScalableCuckooFilter::new(100, 0.001);
+        for i in 0..100_000 {
+            filter.insert(&i);
+        }
+        assert!(filter.layers().len() > 5);
+        let bits = filter.bits();
+
+        let frozen = filter.freeze_compacted(0..100_000);
+        assert_eq!(frozen.len(), 100_000);
+        assert_eq!(frozen.layers().len(), 1);
+        assert!(frozen.layers()[0].fill_ratio() > 0.7);
+        assert!(frozen.bits() < bits);
+        assert!((0..100_000).all(|i| frozen.contains(&i)));
+
+        let false_positives = (100_000..1_100_000).filter(|i| frozen.contains(i)).count();
+        assert!(false_positives < 1000, "{}", false_positives);
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::cuckoo_filter::CuckooFilter;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Growth strategy of `ScalableCuckooFilter`.
+///
+/// When the last layer of a filter becomes full, a new layer is appended.
+/// A `GrowthPolicy` decides the capacity and the false positive probability of that layer.
+///
+/// # Examples
+///
+/// Additive growth (every layer has the same capacity):
+///
+/// ```
+/// use scalable_cuckoo_filter::{
+///     GrowthConfig, GrowthPolicy, LayerInfo, LayerSpec, ScalableCuckooFilter,
+///     ScalableCuckooFilterBuilder,
+/// };
+///
+/// #[derive(Debug)]
+/// struct AdditiveGrowthPolicy;
+/// impl GrowthPolicy for AdditiveGrowthPolicy {
+///     fn next_layer(&self, layers: &[LayerInfo], _len: usize, config: &GrowthConfig) -> LayerSpec {
+///         LayerSpec {
+///             capacity: config.initial_capacity,
+///             false_positive_probability: config.layer_false_positive_probability(layers.len()),
+///         }
+///     }
+/// }
+///
+/// let mut filter: ScalableCuckooFilter<usize> = ScalableCuckooFilterBuilder::new()
+///     .initial_capacity(100)
+///     .growth_policy(AdditiveGrowthPolicy)
+///     .finish();
+/// for i in 0..1000 {
+///     filter.insert(&i);
+/// }
+/// assert!(filter.capacity() >= 1000);
+/// ```
+pub trait GrowthPolicy: fmt::Debug + Send + Sync {
+    /// Returns the specification of the layer to be appended next.
+    ///
+    /// `layers` are the current layers of the filter (empty when the first layer is created)
+    /// and `len` is the number of items in the filter.
+    fn next_layer(&self, layers: &[LayerInfo], len: usize, config: &GrowthConfig) -> LayerSpec;
+}
+
+/// Default growth policy.
+///
+/// The capacity of the `i`-th layer (zero origin) is `initial_capacity * 2^i` and
+/// its false positive probability is [`GrowthConfig::layer_false_positive_probability(i)`].
+///
+/// [`GrowthConfig::layer_false_positive_probability(i)`]: GrowthConfig::layer_false_positive_probability
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultGrowthPolicy;
+impl GrowthPolicy for DefaultGrowthPolicy {
+    fn next_layer(&self, layers: &[LayerInfo], _len: usize, config: &GrowthConfig) -> LayerSpec {
+        LayerSpec {
+            capacity: config.initial_capacity.saturating_mul(
+                1usize
+                    .checked_shl(layers.len() as u32)
+                    .unwrap_or(usize::MAX),
+            ),
+            false_positive_probability: config.layer_false_positive_probability(layers.len()),
+        }
+    }
+}
+
+/// Filter wide configuration passed to [`GrowthPolicy`].
+#[derive(Debug, Clone)]
+pub struct GrowthConfig {
+    /// Initial capacity of the filter.
+    pub initial_capacity: usize,
+
+    /// Expected upper bound of the false positive probability of the filter.
+    pub false_positive_probability: f64,
+
+    /// Tightening ratio of the false positive probability.
+    pub tightening_ratio: f64,
+}
+impl GrowthConfig {
+    /// Returns the false positive probability assigned to the `i`-th layer (zero origin).
+    ///
+    /// The result is `false_positive_probability * (1 - tightening_ratio) * tightening_ratio^i`.
+    pub fn layer_false_positive_probability(&self, i: usize) -> f64 {
+        self.false_positive_probability
+            * (1.0 - self.tightening_ratio)
+            * self.tightening_ratio.powi(i as i32)
+    }
+}
+
+/// Specification of a new layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerSpec {
+    /// Number of items the layer is expected to hold.
+    pub capacity: usize,
+
+    /// Expected upper bound of the false positive probability of the layer.
+    pub false_positive_probability: f64,
+}
+
+/// Information about a layer (i.e., an internal fixed size cuckoo filter) of `ScalableCuckooFilter`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct LayerInfo {
+    capacity: usize,
+    len: usize,
+    bits: u64,
+    fingerprint_bitwidth: usize,
+    stash_len: usize,
+    fill_ratio: f64,
+}
+impl LayerInfo {
+    pub(crate) fn new(filter: &CuckooFilter) -> Self {
+        LayerInfo {
+            capacity: filter.capacity(),
+            len: filter.len(),
+            bits: filter.bits(),
+            fingerprint_bitwidth: filter.fingerprint_bitwidth(),
+            stash_len: filter.stash_len(),
+            fill_ratio: filter.fill_ratio(),
+        }
+    }
+
+    /// Returns the capacity of the layer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the approximate number of items inserted in the layer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the layer contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits being used for representing the layer.
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    /// Returns the fingerprint length in bits of the layer.
+    pub fn fingerprint_bitwidth(&self) -> usize {
+        self.fingerprint_bitwidth
+    }
+
+    /// Returns the number of entries kept in the stash (i.e., outside of the buckets) of the layer.
+    ///
+    /// This includes the entries kicked out by relocations and the items whose fingerprint is zero.
+    pub fn stash_len(&self) -> usize {
+        self.stash_len
+    }
+
+    /// Returns the ratio of the occupied bucket entries to the total bucket entries of the layer.
+    pub fn fill_ratio(&self) -> f64 {
+        self.fill_ratio
+    }
+}
@@ -0,0 +1,534 @@
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use siphasher::sip::{SipHasher, SipHasher13, SipHasher24};
+
+/// Key whose 64-bit hash has already been computed.
+///
+/// Combined with [`IdentityHasher`], the typed API of the filters can be used for keys that
+/// are high-quality hashes (e.g., cryptographic digests) without hashing them again.
+///
+/// # Fingerprint derivation
+///
+/// The lower bits of the hash are used as the bucket index, so the hashes must be uniformly
+/// distributed over all 64 bits. In [`HashWidth::Bits64`] mode, the upper bits are used as
+/// the fingerprint as is. In the default [`HashWidth::Bits128`] mode, the fingerprint is
+/// derived from the hash mixed with a salt, which costs only a few multiplications.
+///
+/// The hasher of a filter is also used to hash fingerprints when computing the alternative
+/// bucket index of an entry. Because `IdentityHasher` doesn't mix them, the alternative
+/// index becomes `index ^ fingerprint` and relocations stay within `2^fingerprint_bitwidth`
+/// buckets. This is harmless for the false positive probability, but the load factor
+/// reached before growing may be slightly lower for filters with many more buckets than that.
+/// Use [`ScalableCuckooFilter::insert_hash`] with the default hasher if that matters.
+///
+/// [`ScalableCuckooFilter::insert_hash`]: crate::ScalableCuckooFilter::insert_hash
+/// [`HashWidth::Bits64`]: crate::HashWidth::Bits64
+/// [`HashWidth::Bits128`]: crate::HashWidth::Bits128
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{
+///     IdentityHasher, PreHashed, ScalableCuckooFilter, ScalableCuckooFilterBuilder,
+/// };
+///
+/// let mut filter: ScalableCuckooFilter<PreHashed, IdentityHasher> =
+///     ScalableCuckooFilterBuilder::new()
+///         .hasher(IdentityHasher::default())
+///         .finish();
+/// filter.insert(&PreHashed(0x3a7b_d3e2_360a_3d29));
+/// assert!(filter.contains(&PreHashed(0x3a7b_d3e2_360a_3d29)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PreHashed(pub u64);
+impl Hash for PreHashed {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.0);
+    }
+}
+impl From<u64> for PreHashed {
+    fn from(f: u64) -> Self {
+        PreHashed(f)
+    }
+}
+
+/// `Hasher` that returns the written `u64` value as is.
+///
+/// This is intended to be used with [`PreHashed`] keys (see its documentation for
+/// the implications). Only `u64` values can be written.
+///
+/// # Panics
+///
+/// Writing anything other than a `u64` value panics.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdentityHasher(u64);
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        panic!("IdentityHasher only supports `u64` values (e.g., `PreHashed`)");
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+}
+
+/// `Hasher` that mixes the output of another (weak) hasher.
+///
+/// Hashers such as [nohash-hasher] return integer keys as is. Since the upper bits of
+/// a hash are used as the fingerprint and the lower bits as the bucket index, such hashes
+/// would make fingerprints of small integers all zero and alternative bucket indices
+/// correlated with the primary ones. This hasher applies the finalizer of MurmurHash3
+/// to the output of the inner hasher, so that fingerprints and bucket indices are derived
+/// from well-distributed bits while keeping the speed of the inner hasher.
+///
+/// Fingerprints are hashed by the same hasher to derive alternative bucket indices,
+/// so they are mixed too.
+///
+/// Note that the default [`HashWidth::Bits128`] mode writes a salt before each item,
+/// which `NoHashHasher` rejects in debug builds. Use [`HashWidth::Bits64`] with it.
+///
+/// [nohash-hasher]: https://docs.rs/nohash-hasher
+/// [`HashWidth::Bits64`]: crate::HashWidth::Bits64
+/// [`HashWidth::Bits128`]: crate::HashWidth::Bits128
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{MixingHasher, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+/// use std::collections::hash_map::DefaultHasher;
+///
+/// // Any `Hasher + Clone` can be wrapped.
+/// let mut filter: ScalableCuckooFilter<u64, MixingHasher<DefaultHasher>> =
+///     ScalableCuckooFilterBuilder::new()
+///         .hasher(MixingHasher::new(DefaultHasher::new()))
+///         .finish();
+/// filter.insert(&42);
+/// assert!(filter.contains(&42));
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MixingHasher<H>(H);
+impl<H: Hasher> MixingHasher<H> {
+    /// Makes a new `MixingHasher` instance that wraps `inner`.
+    pub fn new(inner: H) -> Self {
+        MixingHasher(inner)
+    }
+
+    /// Returns the inner hasher.
+    pub fn into_inner(self) -> H {
+        self.0
+    }
+}
+impl<H: Hasher> Hasher for MixingHasher<H> {
+    fn finish(&self) -> u64 {
+        fmix64(self.0.finish())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.0.write_u8(i);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.0.write_u16(i);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0.write_u32(i);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0.write_u64(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.0.write_usize(i);
+    }
+}
+
+/// `Hasher` made by a [`BuildHasher`], so that the filters can be configured with one
+/// (e.g., `std::collections::hash_map::RandomState` or `ahash::RandomState`).
+///
+/// The filters hash an item by cloning a pristine hasher, which this adapter makes
+/// with [`BuildHasher::build_hasher`] once.
+///
+/// Note that a `BuildHasher` with random keys such as `RandomState` hashes differently after
+/// deserialization, because the hasher isn't serialized with the filter.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{BuildHasherAdapter, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+/// use std::collections::hash_map::RandomState;
+///
+/// let mut filter: ScalableCuckooFilter<str, BuildHasherAdapter<RandomState>> =
+///     ScalableCuckooFilterBuilder::new()
+///         .build_hasher(RandomState::new())
+///         .finish();
+/// filter.insert("foo");
+/// assert!(filter.contains("foo"));
+/// ```
+#[derive(Debug)]
+pub struct BuildHasherAdapter<S: BuildHasher> {
+    build_hasher: S,
+    hasher: S::Hasher,
+}
+impl<S: BuildHasher> BuildHasherAdapter<S> {
+    /// Makes a new `BuildHasherAdapter` instance from `build_hasher`.
+    pub fn new(build_hasher: S) -> Self {
+        let hasher = build_hasher.build_hasher();
+        BuildHasherAdapter {
+            build_hasher,
+            hasher,
+        }
+    }
+
+    /// Returns the inner `BuildHasher`.
+    pub fn into_inner(self) -> S {
+        self.build_hasher
+    }
+}
+impl<S: BuildHasher + Clone> Clone for BuildHasherAdapter<S>
+where
+    S::Hasher: Clone,
+{
+    fn clone(&self) -> Self {
+        BuildHasherAdapter {
+            build_hasher: self.build_hasher.clone(),
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+impl<S: BuildHasher + Default> Default for BuildHasherAdapter<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+impl<S: BuildHasher> Hasher for BuildHasherAdapter<S> {
+    fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.hasher.write_u8(i);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.hasher.write_u16(i);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.hasher.write_u32(i);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.hasher.write_u64(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.hasher.write_usize(i);
+    }
+}
+
+/// `Hasher` that writes integers to another hasher as if it were on a little-endian 64-bit target.
+///
+/// Hashers like SipHash hash integers as their native-endian bytes, and `usize` values as
+/// four or eight bytes, so the same items would be mapped to different buckets on other
+/// platforms. This adapter byte-swaps integers on big-endian targets and widens `usize`
+/// values to `u64`, which changes nothing on little-endian 64-bit targets.
+///
+/// Hashers that take integers as values (e.g., [`IdentityHasher`]) hash differently on
+/// big-endian targets through this adapter, but they are consistent on each platform.
+pub(crate) struct PortableHasher<'a, H>(pub &'a mut H);
+impl<H: Hasher> Hasher for PortableHasher<'_, H> {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.0.write_u8(i);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.0.write_u16(i.to_le());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0.write_u32(i.to_le());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0.write_u64(i.to_le());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.0.write_u128(i.to_le());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Hasher whose keys are saved with the filters that use it.
+///
+/// A filter records the keys of its hasher when it is serialized (by serde or
+/// `ScalableCuckooFilter::to_bytes`), and a filter read without a given hasher makes one
+/// keyed the same by [`from_keys`], so that items are hashed as before.
+/// Hashers that aren't keyed, or whose keys can't be exported, use the provided methods,
+/// and their read filters use `Default` instead.
+///
+/// [`from_keys`]: HasherKeys::from_keys
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{HasherKeys, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+/// use std::hash::Hasher;
+///
+/// #[derive(Debug, Default, Clone)]
+/// struct MyHasher(u64);
+/// impl Hasher for MyHasher {
+///     fn finish(&self) -> u64 {
+///         self.0
+///     }
+///
+///     fn write(&mut self, bytes: &[u8]) {
+///         for &b in bytes {
+///             self.0 = (self.0 ^ u64::from(b)).wrapping_mul(0x100_0000_01B3);
+///         }
+///     }
+/// }
+/// impl HasherKeys for MyHasher {
+///     fn keys(&self) -> Option<(u64, u64)> {
+///         Some((self.0, 0))
+///     }
+///
+///     fn from_keys((key0, _): (u64, u64)) -> Option<Self> {
+///         Some(MyHasher(key0))
+///     }
+/// }
+///
+/// let mut filter = ScalableCuckooFilterBuilder::new()
+///     .hasher(MyHasher(0xCBF2_9CE4_8422_2325))
+///     .finish();
+/// filter.insert("foo");
+///
+/// let bytes = filter.to_bytes().unwrap();
+/// let filter = ScalableCuckooFilter::<str, MyHasher>::from_bytes(&bytes).unwrap();
+/// assert!(filter.contains("foo"));
+/// ```
+pub trait HasherKeys {
+    /// Returns the keys of this hasher, or `None` if it isn't keyed.
+    fn keys(&self) -> Option<(u64, u64)> {
+        None
+    }
+
+    /// Makes a hasher keyed by `keys`, or returns `None` if this type of hasher isn't keyed.
+    fn from_keys(keys: (u64, u64)) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let _ = keys;
+        None
+    }
+}
+impl HasherKeys for SipHasher13 {
+    fn keys(&self) -> Option<(u64, u64)> {
+        Some(SipHasher13::keys(self))
+    }
+
+    fn from_keys((key0, key1): (u64, u64)) -> Option<Self> {
+        Some(SipHasher13::new_with_keys(key0, key1))
+    }
+}
+impl HasherKeys for SipHasher24 {
+    fn keys(&self) -> Option<(u64, u64)> {
+        Some(SipHasher24::keys(self))
+    }
+
+    fn from_keys((key0, key1): (u64, u64)) -> Option<Self> {
+        Some(SipHasher24::new_with_keys(key0, key1))
+    }
+}
+impl HasherKeys for SipHasher {
+    fn keys(&self) -> Option<(u64, u64)> {
+        Some(SipHasher::keys(self))
+    }
+
+    fn from_keys((key0, key1): (u64, u64)) -> Option<Self> {
+        Some(SipHasher::new_with_keys(key0, key1))
+    }
+}
+impl HasherKeys for std::collections::hash_map::DefaultHasher {}
+impl HasherKeys for IdentityHasher {}
+impl<H: HasherKeys> HasherKeys for MixingHasher<H> {
+    fn keys(&self) -> Option<(u64, u64)> {
+        self.0.keys()
+    }
+
+    fn from_keys(keys: (u64, u64)) -> Option<Self> {
+        H::from_keys(keys).map(MixingHasher)
+    }
+}
+impl<S: BuildHasher> HasherKeys for BuildHasherAdapter<S> {}
+#[cfg(feature = "ahash")]
+impl HasherKeys for ahash::AHasher {}
+#[cfg(feature = "xxhash")]
+impl HasherKeys for xxhash_rust::xxh3::Xxh3 {}
+
+/// Makes the hasher of a read filter from the recorded `keys` (see [`HasherKeys`]).
+pub(crate) fn keyed_hasher<H: HasherKeys + Default>(keys: Option<(u64, u64)>) -> H {
+    keys.and_then(H::from_keys).unwrap_or_default()
+}
+
+/// Serializes a hasher as its keys (see `HasherKeys`), for `#[serde(with)]`.
+#[cfg(feature = "serde_support")]
+pub(crate) mod serde_keys {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::HasherKeys;
+
+    pub fn serialize<H: HasherKeys, S: Serializer>(
+        hasher: &H,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        hasher.keys().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, H, D>(deserializer: D) -> Result<H, D::Error>
+    where
+        H: HasherKeys + Default,
+        D: Deserializer<'de>,
+    {
+        Option::deserialize(deserializer).map(super::keyed_hasher)
+    }
+}
+
+/// The finalizer (fmix64) of MurmurHash3.
+#[inline]
+pub(crate) fn fmix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 33)).wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    x = (x ^ (x >> 33)).wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    x ^ (x >> 33)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{HashWidth, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+    #[test]
+    fn it_works() {
+        assert_eq!(
+            crate::hash(&IdentityHasher::default(), &PreHashed(123)),
+            123
+        );
+
+        let mut filter: ScalableCuckooFilter<PreHashed, IdentityHasher> =
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .hash_width(HashWidth::Bits64)
+                .hasher(IdentityHasher::default())
+                .finish();
+        let keys = (0..10_000).map(key).collect::<Vec<_>>();
+        for k in &keys {
+            filter.insert(k);
+        }
+        assert!(keys.iter().all(|k| filter.contains(k)));
+        assert!(keys.iter().all(|k| filter.contains_hash(k.0)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let false_positives = (10_000..110_000)
+            .map(key)
+            .filter(|k| filter.contains(k))
+            .count();
+        assert!(false_positives < 100, "{}", false_positives);
+    }
+
+    #[test]
+    fn fingerprints_are_decorrelated_from_indices() {
+        // Sequential keys share their upper bits, so they would share fingerprints
+        // in 64-bit mode.
+        let mut filter: ScalableCuckooFilter<PreHashed, IdentityHasher> =
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .hasher(IdentityHasher::default())
+                .finish();
+        assert_eq!(filter.hash_width(), HashWidth::Bits128);
+        for i in 0..10_000 {
+            filter.insert(&PreHashed(i));
+        }
+        assert!((0..10_000).all(|i| filter.contains(&PreHashed(i))));
+
+        let false_positives = (10_000..110_000)
+            .filter(|&i| filter.contains(&PreHashed(i)))
+            .count();
+        assert!(false_positives < 200, "{}", false_positives);
+    }
+
+    #[test]
+    fn build_hasher_adapter_works() {
+        let state = std::collections::hash_map::RandomState::new();
+        let adapter = BuildHasherAdapter::new(state.clone());
+        assert_eq!(crate::hash(&adapter, "foo"), state.hash_one("foo"));
+
+        let mut filter = ScalableCuckooFilter::<u64>::builder()
+            .initial_capacity(100)
+            .build_hasher(state)
+            .finish();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        assert!((0..1000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+    }
+
+    fn key(i: u64) -> PreHashed {
+        // splitmix64
+        let mut x = i.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        PreHashed(x ^ (x >> 31))
+    }
+
+    #[test]
+    fn mixing_hasher_works() {
+        type NoHash = nohash_hasher::NoHashHasher<u64>;
+
+        let mut filter: ScalableCuckooFilter<u64, MixingHasher<NoHash>> =
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .hash_width(HashWidth::Bits64)
+                .hasher(MixingHasher::new(NoHash::default()))
+                .finish();
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let false_positives = (10_000..110_000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 100, "{}", false_positives);
+    }
+
+    #[test]
+    #[should_panic]
+    fn identity_hasher_rejects_bytes() {
+        crate::hash(&IdentityHasher::default(), "foo");
+    }
+}
@@ -0,0 +1,324 @@
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::bits;
+use crate::fixed::CapacityError;
+use crate::DefaultHasher;
+
+const ENTRIES_PER_BUCKET: usize = 4;
+const MAX_KICKS: usize = 512;
+
+/// Cuckoo Filter whose fingerprints are stored inline in `BYTES` bytes.
+///
+/// Unlike [`CuckooFilter`], this filter never allocates, so it can live on the stack or
+/// in a `static`, and its size is known at compile time.
+/// The number of buckets is the largest power of two whose buckets fit in `BYTES` bytes
+/// (rounded down to a multiple of eight), so the capacity depends on the length of
+/// the fingerprints derived from the false positive probability.
+///
+/// An entry that can't be placed within the relocation limit is kept in a single victim slot.
+/// While the slot is occupied, [`insert`] fails with [`CapacityError`].
+///
+/// Relocations choose entries pseudo-randomly from a fixed seed, so no entropy is needed.
+///
+/// [`CuckooFilter`]: crate::CuckooFilter
+/// [`insert`]: FixedCuckooFilter::insert
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::FixedCuckooFilter;
+///
+/// let mut filter = FixedCuckooFilter::<1024, str>::new(0.001);
+/// assert_eq!(filter.fingerprint_bitwidth(), 13);
+/// assert_eq!(filter.capacity(), 512);
+///
+/// filter.insert("foo").unwrap();
+/// assert!(filter.contains("foo"));
+/// assert!(filter.remove("foo"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FixedCuckooFilter<const BYTES: usize, T: ?Sized, H = DefaultHasher> {
+    hasher: H,
+    bits: [u8; BYTES],
+    fingerprint_bitwidth: usize,
+    bucket_index_mask: usize,
+    victim: Option<(usize, u64)>,
+    len: usize,
+    false_positive_probability: f64,
+    rng_state: u64,
+    _item: PhantomData<fn(&T)>,
+}
+impl<const BYTES: usize, T: Hash + ?Sized> FixedCuckooFilter<BYTES, T> {
+    /// Makes a new `FixedCuckooFilter` instance that uses `DefaultHasher::default()`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `false_positive_probability` is not in the range `(0.0, 1.0]`
+    /// or `BYTES` can't hold a single bucket.
+    pub fn new(false_positive_probability: f64) -> Self {
+        Self::with_hasher(false_positive_probability, DefaultHasher::default())
+    }
+}
+impl<const BYTES: usize, T: Hash + ?Sized, H: Hasher + Clone> FixedCuckooFilter<BYTES, T, H> {
+    /// Makes a new `FixedCuckooFilter` instance that uses `hasher` for hashing items.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `false_positive_probability` is not in the range `(0.0, 1.0]`
+    /// or `BYTES` can't hold a single bucket.
+    pub fn with_hasher(false_positive_probability: f64, hasher: H) -> Self {
+        assert!(0.0 < false_positive_probability && false_positive_probability <= 1.0);
+
+        // Same bound as the layers of `ScalableCuckooFilter`.
+        let fingerprint_bitwidth = crate::raw::required_fingerprint_bitwidth(
+            ENTRIES_PER_BUCKET,
+            false_positive_probability,
+        )
+        .min(64);
+        let usable_bits = BYTES / 8 * 64;
+        let fitting_buckets = usable_bits / (fingerprint_bitwidth * ENTRIES_PER_BUCKET);
+        assert_ne!(
+            fitting_buckets, 0,
+            "{BYTES} bytes can't hold a single bucket"
+        );
+        FixedCuckooFilter {
+            hasher,
+            bits: [0; BYTES],
+            fingerprint_bitwidth,
+            bucket_index_mask: (1 << fitting_buckets.ilog2()) - 1,
+            victim: None,
+            len: 0,
+            false_positive_probability,
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.len as u64
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the capacity (i.e., the number of entries) of this filter.
+    ///
+    /// Note that insertions usually start failing a little before the filter is completely full.
+    pub fn capacity(&self) -> u64 {
+        ((self.bucket_index_mask + 1) * ENTRIES_PER_BUCKET) as u64
+    }
+
+    /// Returns the number of bits of the inline storage of this filter (i.e., `BYTES * 8`).
+    pub fn bits(&self) -> u64 {
+        BYTES as u64 * 8
+    }
+
+    /// Returns the length of the fingerprints in bits.
+    pub fn fingerprint_bitwidth(&self) -> usize {
+        self.fingerprint_bitwidth
+    }
+
+    /// Returns the false positive probability given at construction.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (i0, fingerprint) = self.index_and_fingerprint(item);
+        let i1 = self.alt_index(i0, fingerprint);
+        self.find_entry(i0, fingerprint).is_some()
+            || self.find_entry(i1, fingerprint).is_some()
+            || self.victim_matches(i0, i1, fingerprint)
+    }
+
+    /// Inserts `item` into this filter.
+    ///
+    /// If the victim slot is occupied, this method returns `Err(CapacityError)`
+    /// leaving the filter unchanged.
+    pub fn insert(&mut self, item: &T) -> Result<(), CapacityError> {
+        if self.victim.is_some() {
+            return Err(CapacityError);
+        }
+        let (i0, fingerprint) = self.index_and_fingerprint(item);
+        let i1 = self.alt_index(i0, fingerprint);
+        self.len += 1;
+        if self.try_insert(i0, fingerprint) || self.try_insert(i1, fingerprint) {
+            return Ok(());
+        }
+
+        let i = if self.next_random() & 1 == 0 { i0 } else { i1 };
+        self.victim = self.relocate(i, fingerprint);
+        Ok(())
+    }
+
+    /// Removes `item` from this filter.
+    ///
+    /// Returns `true` if a matching entry was removed, otherwise `false`.
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (i0, fingerprint) = self.index_and_fingerprint(item);
+        let i1 = self.alt_index(i0, fingerprint);
+        if self.victim_matches(i0, i1, fingerprint) {
+            self.victim = None;
+            self.len -= 1;
+            return true;
+        }
+        for i in [i0, i1] {
+            if let Some(entry) = self.find_entry(i, fingerprint) {
+                let offset = self.entry_offset(i, entry);
+                bits::set_uint(&mut self.bits[..], offset, self.fingerprint_bitwidth, 0);
+                self.len -= 1;
+
+                // The freed entry may make room for the victim.
+                if let Some((v, f)) = self.victim.take() {
+                    if !self.try_insert(v, f) && !self.try_insert(self.alt_index(v, f), f) {
+                        self.victim = self.relocate(v, f);
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Places `fingerprint` into the bucket `i` by kicking out existing entries.
+    ///
+    /// Returns the entry left over after `MAX_KICKS` relocations, if any.
+    fn relocate(&mut self, mut i: usize, mut fingerprint: u64) -> Option<(usize, u64)> {
+        for _ in 0..MAX_KICKS {
+            let entry = self.next_random() as usize % ENTRIES_PER_BUCKET;
+            let offset = self.entry_offset(i, entry);
+            let kicked = bits::get_uint(&self.bits[..], offset, self.fingerprint_bitwidth);
+            bits::set_uint(
+                &mut self.bits[..],
+                offset,
+                self.fingerprint_bitwidth,
+                fingerprint,
+            );
+            fingerprint = kicked;
+            i = self.alt_index(i, fingerprint);
+            if self.try_insert(i, fingerprint) {
+                return None;
+            }
+        }
+        Some((i, fingerprint))
+    }
+
+    fn index_and_fingerprint<Q: Hash + ?Sized>(&self, item: &Q) -> (usize, u64) {
+        let hash = crate::hash(&self.hasher, item);
+        let index = hash as usize & self.bucket_index_mask;
+        // Zero marks free entries.
+        let fingerprint = (hash >> (64 - self.fingerprint_bitwidth)).max(1);
+        (index, fingerprint)
+    }
+
+    fn alt_index(&self, i: usize, fingerprint: u64) -> usize {
+        (i ^ crate::hashers::fmix64(fingerprint) as usize) & self.bucket_index_mask
+    }
+
+    fn entry_offset(&self, i: usize, entry: usize) -> usize {
+        (i * ENTRIES_PER_BUCKET + entry) * self.fingerprint_bitwidth
+    }
+
+    fn find_entry(&self, i: usize, fingerprint: u64) -> Option<usize> {
+        (0..ENTRIES_PER_BUCKET).find(|&entry| {
+            let offset = self.entry_offset(i, entry);
+            bits::get_uint(&self.bits[..], offset, self.fingerprint_bitwidth) == fingerprint
+        })
+    }
+
+    fn try_insert(&mut self, i: usize, fingerprint: u64) -> bool {
+        let Some(entry) = self.find_entry(i, 0) else {
+            return false;
+        };
+        let offset = self.entry_offset(i, entry);
+        bits::set_uint(
+            &mut self.bits[..],
+            offset,
+            self.fingerprint_bitwidth,
+            fingerprint,
+        );
+        true
+    }
+
+    fn victim_matches(&self, i0: usize, i1: usize, fingerprint: u64) -> bool {
+        self.victim
+            .is_some_and(|(i, f)| f == fingerprint && (i == i0 || i == i1))
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64*
+        let mut x = self.rng_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let mut filter = FixedCuckooFilter::<4096, usize>::new(0.001);
+        assert_eq!(filter.fingerprint_bitwidth(), 13);
+        assert_eq!(filter.capacity(), 2048);
+        assert_eq!(filter.bits(), 4096 * 8);
+        assert!(filter.is_empty());
+
+        let mut inserted = 0;
+        while filter.insert(&inserted).is_ok() {
+            inserted += 1;
+        }
+        assert_eq!(filter.len(), inserted as u64);
+        assert!(inserted > 1900, "inserted={inserted}");
+        assert!((0..inserted).all(|i| filter.contains(&i)));
+
+        // The victim slot is occupied, so the filter stays unchanged.
+        assert!(filter.insert(&inserted).is_err());
+        assert_eq!(filter.len(), inserted as u64);
+
+        // Removing items makes room again.
+        for i in 0..inserted / 2 {
+            assert!(filter.remove(&i));
+        }
+        assert!(filter.insert(&inserted).is_ok());
+        for i in inserted / 2..=inserted {
+            assert!(filter.remove(&i));
+        }
+        assert!(filter.is_empty());
+        assert!(filter.bits.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn false_positive_probability_works() {
+        let mut filter = FixedCuckooFilter::<4096, usize>::new(0.01);
+        for i in 0..1500 {
+            filter.insert(&i).unwrap();
+        }
+        let false_positives = (1500..101_500).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 1000, "false_positives={false_positives}");
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_small_storage_panics() {
+        let _ = FixedCuckooFilter::<4, usize>::new(0.001);
+    }
+}
@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fmt;
+
+/// Error returned by [`ScalableCuckooFilter::verify_integrity`].
+///
+/// [`ScalableCuckooFilter::verify_integrity`]: crate::ScalableCuckooFilter::verify_integrity
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IntegrityError {
+    /// The filter has no layers.
+    NoLayers,
+
+    /// The parameters of a layer are invalid or inconsistent with the filter.
+    InvalidLayerParameters {
+        /// Index of the layer.
+        layer: usize,
+    },
+
+    /// The item count of a layer differs from the number of the stored entries.
+    ItemCountMismatch {
+        /// Index of the layer.
+        layer: usize,
+
+        /// Number of items recorded by the layer.
+        item_count: usize,
+
+        /// Number of entries actually stored in the buckets and the stash of the layer.
+        stored_entries: usize,
+    },
+
+    /// A bucket entry is in neither of the candidate buckets of the items it may belong to.
+    MisplacedEntry {
+        /// Index of the layer.
+        layer: usize,
+
+        /// Fingerprint of the entry.
+        fingerprint: u64,
+
+        /// Index of the bucket holding the entry.
+        bucket_index: usize,
+    },
+
+    /// The stash of a layer is not sorted.
+    UnsortedStash {
+        /// Index of the layer.
+        layer: usize,
+    },
+
+    /// A stash entry is not keyed by the smaller of its two candidate bucket indices.
+    MisplacedStashEntry {
+        /// Index of the layer.
+        layer: usize,
+
+        /// Fingerprint of the entry.
+        fingerprint: u64,
+
+        /// Bucket index recorded for the entry.
+        bucket_index: usize,
+    },
+}
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntegrityError::NoLayers => write!(f, "filter has no layers"),
+            IntegrityError::InvalidLayerParameters { layer } => {
+                write!(f, "layer {layer} has invalid parameters")
+            }
+            IntegrityError::ItemCountMismatch {
+                layer,
+                item_count,
+                stored_entries,
+            } => write!(
+                f,
+                "layer {layer} records {item_count} items but stores {stored_entries} entries"
+            ),
+            IntegrityError::MisplacedEntry {
+                layer,
+                fingerprint,
+                bucket_index,
+            } => write!(
+                f,
+                "entry (fingerprint={fingerprint}) in bucket {bucket_index} of layer {layer} is misplaced"
+            ),
+            IntegrityError::UnsortedStash { layer } => {
+                write!(f, "stash of layer {layer} is not sorted")
+            }
+            IntegrityError::MisplacedStashEntry {
+                layer,
+                fingerprint,
+                bucket_index,
+            } => write!(
+                f,
+                "stash entry (fingerprint={fingerprint}, bucket_index={bucket_index}) of layer {layer} is misplaced"
+            ),
+        }
+    }
+}
+impl Error for IntegrityError {}
@@ -16,9 +16,10 @@
 //! Filter grows automatically:
 //!
 //! ```
-//! use scalable_cuckoo_filter::ScalableCuckooFilter;
+//! use scalable_cuckoo_filter::{DefaultHasher, ScalableCuckooFilter};
 //!
-//! let mut filter = ScalableCuckooFilter::new(100, 0.001);
+//! // Fixed hasher keys make the growth reproducible (the default keys are random).
+//! let mut filter = ScalableCuckooFilter::with_hasher(100, 0.001, DefaultHasher::default());
 //! assert_eq!(filter.capacity(), 128);
 //!
 //! for i in 0..1000 {
@@ -30,9 +31,9 @@
 //! Filter shrinking:
 //!
 //! ```
-//! use scalable_cuckoo_filter::ScalableCuckooFilter;
+//! use scalable_cuckoo_filter::{DefaultHasher, ScalableCuckooFilter};
 //!
-//! let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+//! let mut filter = ScalableCuckooFilter::with_hasher(1000, 0.001, DefaultHasher::default());
 //! for i in 0..100 {
 //!     filter.insert(&i);
 //! }
@@ -47,6 +48,79 @@
 //! assert_eq!(filter.bits(), 1792);
 //! ```
 //!
+//! # Features
+//!
+//! - `serde_support` (or its alias `serde`): Implements `Serialize` and `Deserialize` of [serde]
+//!   for the filter.
+//!   The derived representation follows the internal layout and may change between versions;
+//!   the `versioned` module provides one that later versions can always read.
+//!   Neither includes the hasher except for its keys (see `HasherKeys`);
+//!   `ScalableCuckooFilterSeed` deserializes a filter with a given (e.g., keyed) hasher.
+//! - `metrics`: Emits the following counters and gauges via the [metrics] facade:
+//!   - `scalable_cuckoo_filter_inserts_total`
+//!   - `scalable_cuckoo_filter_kicks_total`
+//!   - `scalable_cuckoo_filter_removes_total`
+//!   - `scalable_cuckoo_filter_failed_removes_total`
+//!   - `scalable_cuckoo_filter_growths_total`
+//!   - `scalable_cuckoo_filter_bits` (gauge; the size of each filter labelled by
+//!     `ScalableCuckooFilterBuilder::metrics_label`, with the label as `filter`)
+//! - `ahash`: Makes [ahash]'s `AHasher` the `DefaultHasher` (see its documentation for
+//!   the trade-offs).
+//! - `arbitrary`: Implements `Arbitrary` of [arbitrary] for the filter, for structured fuzzing.
+//! - `cli`: Builds the `scf` command-line tool, which builds filters from keys read from stdin,
+//!   prints their statistics, queries them, and merges and converts saved filters
+//!   (run `scf help` for the usage).
+//! - `concurrent`: Provides filters that can be shared between threads:
+//!   - `ConcurrentScalableCuckooFilter` (sharded and locked)
+//!   - `AtomicScalableCuckooFilter` (lookups never take a lock)
+//!   - `SnapshotWriter`/`SnapshotReader` (a writer publishes snapshots to readers)
+//! - `rkyv`: Implements `Archive`, `Serialize`, and `Deserialize` of [rkyv] for the filter.
+//!   The archived filter (`ArchivedScalableCuckooFilter`) can be looked up in place.
+//! - `ffi`: Provides the `ffi` module, a C API (declared in `include/scalable_cuckoo_filter.h`)
+//!   for using filters from other languages.
+//! - `mmap`: Provides `MmapCuckooFilter`, which looks up a filter saved to a file in place
+//!   via memory mapping (using [memmap2]), for filters larger than the available memory,
+//!   and `PersistentCuckooFilter`, which keeps a filter in a file and flushes only the
+//!   changed pages of it.
+//! - `python`: Provides `PyScalableCuckooFilter`, which is exposed to Python (using [pyo3])
+//!   as the `ScalableCuckooFilter` class of the `scalable_cuckoo_filter` module.
+//! - `rayon`: Provides `ScalableCuckooFilter::par_extend` for building large filters
+//!   using multiple threads.
+//! - `shm`: Provides `SharedCuckooFilter` on Linux, which a process publishes to a named
+//!   shared memory segment for other processes to query without copying.
+//! - `simd`: Compares the entries of buckets with vector instructions (using [wide])
+//!   when looking them up.
+//! - `small_rng`: Provides `ScalableCuckooFilterBuilder::small_rng` that uses the seedable
+//!   `rand::rngs::SmallRng` as the random number generator.
+//! - `xor_filter`: Provides `XorFilter` and `ScalableCuckooFilter::to_xor_filter` for serving
+//!   static sets more compactly.
+//! - `xxhash`: Makes [xxhash-rust]'s `Xxh3` the `DefaultHasher` unless `ahash` is enabled.
+//! - `wasm_js`: Makes the default random number generator (and thus `ScalableCuckooFilter::new`)
+//!   work on `wasm32-unknown-unknown` by obtaining entropy from JavaScript's
+//!   `crypto.getRandomValues` (via the `js` feature of [getrandom]).
+//!   Without it, that target can't build this crate unless another `getrandom` backend is
+//!   registered. This has no effect on other targets.
+//! - `tokio`: Provides `write_to_async` and `read_from_async` (and `read_from_with_async`)
+//!   of the filter, which write and read it over [tokio]'s `AsyncWrite` and `AsyncRead`
+//!   without blocking the runtime for long.
+//! - `stream`: Provides `ScalableCuckooFilter::extend_from_stream` for inserting the items of
+//!   a [futures-core] `Stream` in batches.
+//! - `test_utils`: Provides the `test_utils` module for measuring false positive rates and
+//!   load factors empirically.
+//!
+//! [serde]: https://docs.rs/serde
+//! [metrics]: https://docs.rs/metrics
+//! [ahash]: https://docs.rs/ahash
+//! [arbitrary]: https://docs.rs/arbitrary
+//! [xxhash-rust]: https://docs.rs/xxhash-rust
+//! [wide]: https://docs.rs/wide
+//! [rkyv]: https://docs.rs/rkyv
+//! [getrandom]: https://docs.rs/getrandom
+//! [memmap2]: https://docs.rs/memmap2
+//! [pyo3]: https://docs.rs/pyo3
+//! [tokio]: https://docs.rs/tokio
+//! [futures-core]: https://docs.rs/futures-core
+//!
 //! # References
 //!
 //! - [Cuckoo Filter: Practically Better Than Bloom][cuckoo filter]
@@ -56,18 +130,164 @@
 //! [scalable bloom filters]: http://haslab.uminho.pt/cbm/files/dbloom.pdf
 #![warn(missing_docs)]
 
+pub use crate::alloc::AllocBytes;
+pub use crate::approximate_set::{ApproximateSet, ApproximateSetMut};
+#[cfg(feature = "rkyv")]
+pub use crate::archive::{ArchivedScalableCuckooFilter, ScalableCuckooFilterResolver};
+#[cfg(feature = "concurrent")]
+pub use crate::atomic::AtomicScalableCuckooFilter;
+pub use crate::bloom::BloomFilter;
+pub use crate::buffered::BufferedInserter;
+pub use crate::build_error::BuildError;
+#[cfg(feature = "concurrent")]
+pub use crate::concurrent::ConcurrentScalableCuckooFilter;
+pub use crate::cuckoo_filter::{AltIndexScheme, HashWidth, InsertionStrategy, StorageBackend};
+pub use crate::dump::{FilterDump, LayerDump, LayerEntries};
+pub use crate::events::{EventListener, FilterEvent};
+pub use crate::fixed::{CapacityError, CuckooFilter};
+pub use crate::frozen::FrozenScalableCuckooFilter;
+pub use crate::growth::{DefaultGrowthPolicy, GrowthConfig, GrowthPolicy, LayerInfo, LayerSpec};
+pub use crate::hashers::{BuildHasherAdapter, HasherKeys, IdentityHasher, MixingHasher, PreHashed};
+pub use crate::heapless::FixedCuckooFilter;
+pub use crate::integrity::IntegrityError;
+#[cfg(feature = "mmap")]
+pub use crate::mmap::MmapCuckooFilter;
+#[cfg(feature = "mmap")]
+pub use crate::persistent::PersistentCuckooFilter;
+#[cfg(feature = "python")]
+pub use crate::python::PyScalableCuckooFilter;
 pub use crate::scalable_cuckoo_filter::{
-    DefaultHasher, DefaultRng, ScalableCuckooFilter, ScalableCuckooFilterBuilder,
+    DefaultHasher, DefaultRng, ScalableCuckooFilter, ScalableCuckooFilterBuilder, Untyped,
 };
+#[cfg(feature = "serde_support")]
+pub use crate::seed::ScalableCuckooFilterSeed;
+#[cfg(all(feature = "shm", target_os = "linux"))]
+pub use crate::shm::SharedCuckooFilter;
+#[cfg(feature = "concurrent")]
+pub use crate::snapshot::{SnapshotReader, SnapshotWriter};
+pub use crate::stats::{FilterCounters, FilterStats, StoredFingerprint};
+pub use crate::view::ScalableCuckooFilterView;
+pub use crate::wal::WalCuckooFilter;
+#[cfg(feature = "xor_filter")]
+pub use crate::xor_filter::XorFilter;
 
+mod alloc;
+mod approximate_set;
+#[cfg(feature = "rkyv")]
+mod archive;
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "concurrent")]
+mod atomic;
 mod bits;
+mod bloom;
 mod buckets;
+mod buffered;
+mod build_error;
+mod codec;
+#[cfg(feature = "concurrent")]
+mod concurrent;
 mod cuckoo_filter;
+mod delta;
+mod dump;
+mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fixed;
+mod frozen;
+mod growth;
+mod hashers;
+mod heapless;
+mod integrity;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod morton;
+mod persist;
+#[cfg(feature = "mmap")]
+mod persistent;
+#[cfg(feature = "python")]
+mod python;
+pub mod raw;
+mod reconcile;
+pub mod redis;
 mod scalable_cuckoo_filter;
+#[cfg(feature = "serde_support")]
+mod seed;
+#[cfg(all(feature = "shm", target_os = "linux"))]
+mod shm;
+#[cfg(feature = "concurrent")]
+mod snapshot;
+mod stats;
+mod storage;
+#[cfg(feature = "stream")]
+mod stream;
+mod telemetry;
+#[cfg(feature = "test_utils")]
+pub mod test_utils;
+#[cfg(feature = "serde_support")]
+pub mod versioned;
+mod view;
+mod wal;
+#[cfg(feature = "xor_filter")]
+mod xor_filter;
 
 #[inline]
 fn hash<T: ?Sized + std::hash::Hash, H: std::hash::Hasher + Clone>(hasher: &H, item: &T) -> u64 {
     let mut hasher = hasher.clone();
-    item.hash(&mut hasher);
+    item.hash(&mut hashers::PortableHasher(&mut hasher));
     hasher.finish()
 }
+
+/// Hashes `item` into the item hash whose lower 64 bits are used for the bucket index and
+/// upper 64 bits for the fingerprint.
+#[inline]
+fn item_hash<T: ?Sized + std::hash::Hash, H: std::hash::Hasher + Clone>(
+    hasher: &H,
+    item: &T,
+    width: HashWidth,
+) -> u128 {
+    let lower = hash(hasher, item);
+    match width {
+        HashWidth::Bits64 => widen(lower),
+        HashWidth::Bits128 => {
+            let upper = fingerprint_hash(hasher, |h| item.hash(h));
+            (u128::from(upper) << 64) | u128::from(lower)
+        }
+        HashWidth::Bits32 => narrow(lower),
+    }
+}
+
+/// Computes the hash the fingerprints are derived from in [`HashWidth::Bits128`] mode.
+///
+/// The hasher is keyed differently from the index hash by writing a salt before the item.
+/// The result is mixed once more because hashers like `IdentityHasher` only keep the last
+/// written value and would otherwise return the index hash as is.
+#[inline]
+fn fingerprint_hash<H: std::hash::Hasher + Clone>(
+    hasher: &H,
+    write: impl FnOnce(&mut hashers::PortableHasher<H>),
+) -> u64 {
+    let mut hasher = hasher.clone();
+    let mut portable = hashers::PortableHasher(&mut hasher);
+    std::hash::Hasher::write_u64(&mut portable, FINGERPRINT_SALT);
+    write(&mut portable);
+    hashers::fmix64(hasher.finish() ^ FINGERPRINT_SALT)
+}
+
+/// Salt written before an item when computing the upper 64 bits of a 128-bit item hash.
+const FINGERPRINT_SALT: u64 = 0x5CF1_F1A9_E2D7_B6C3;
+
+/// Converts a 64-bit hash into an item hash (the bucket index and the fingerprint are taken
+/// from the lower and upper bits of the same 64 bits).
+#[inline]
+fn widen(item_hash: u64) -> u128 {
+    (u128::from(item_hash) << 64) | u128::from(item_hash)
+}
+
+/// Folds a 64-bit hash into 32 bits and converts it into an item hash (the bucket index and
+/// the fingerprint are taken from the lower and upper bits of the same 32 bits).
+#[inline]
+fn narrow(hash: u64) -> u128 {
+    let folded = u64::from((hash ^ (hash >> 32)) as u32);
+    (u128::from(folded << 32) << 64) | u128::from(folded)
+}
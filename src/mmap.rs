@@ -0,0 +1,191 @@
+use std::borrow::Borrow;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::{DefaultHasher, HasherKeys, ScalableCuckooFilterView};
+
+/// Read-only filter that looks up a serialized filter in a memory-mapped file.
+///
+/// The file is in the format of [`ScalableCuckooFilter::save_to_path`] (or `write_to`)
+/// and is never read into memory as a whole: the OS pages in the buckets touched by lookups,
+/// and can evict them again under memory pressure because they are backed by the file.
+/// So filters larger than the available memory can be queried.
+///
+/// The file must not be modified or truncated while it is mapped, otherwise lookups may give
+/// wrong answers or crash the process (e.g., by `SIGBUS`). Replace it atomically instead,
+/// as [`ScalableCuckooFilter::save_to_path`] does; the mapping keeps the old contents.
+///
+/// [`ScalableCuckooFilter::save_to_path`]: crate::ScalableCuckooFilter::save_to_path
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{MmapCuckooFilter, ScalableCuckooFilterBuilder};
+///
+/// let mut filter = ScalableCuckooFilterBuilder::new()
+///     .for_type::<str>()
+///     .finish();
+/// filter.insert("foo");
+/// let path = std::env::temp_dir().join(format!("mmap-filter-{}.bin", std::process::id()));
+/// filter.save_to_path(&path).unwrap();
+///
+/// let filter: MmapCuckooFilter<str> = MmapCuckooFilter::open(&path).unwrap();
+/// assert!(filter.contains("foo"));
+/// # drop(filter);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MmapCuckooFilter<T: ?Sized, H = DefaultHasher> {
+    // Borrows the bytes of `mmap`, so it is declared (and thus dropped) first.
+    view: ScalableCuckooFilterView<'static, T, H>,
+    mmap: Mmap,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone + Default + HasherKeys> MmapCuckooFilter<T, H> {
+    /// Maps the file at `path` and makes a filter from it, hashing items as
+    /// [`ScalableCuckooFilterView::new`] does.
+    ///
+    /// Only the parameters of the layers are read, so opening takes the same time however large
+    /// the file is. Call [`verify_checksum`] to detect a corrupted file.
+    ///
+    /// [`verify_checksum`]: MmapCuckooFilter::verify_checksum
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be mapped, or if its contents are malformed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::map(path.as_ref(), ScalableCuckooFilterView::new)
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> MmapCuckooFilter<T, H> {
+    /// Maps the file at `path` and makes a filter from it, hashing items with `hasher`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be mapped, or if its contents are malformed.
+    pub fn open_with_hasher<P: AsRef<Path>>(path: P, hasher: H) -> io::Result<Self> {
+        Self::map(path.as_ref(), |bytes| {
+            ScalableCuckooFilterView::with_hasher(bytes, hasher)
+        })
+    }
+
+    fn map<F>(path: &Path, make_view: F) -> io::Result<Self>
+    where
+        F: FnOnce(&'static [u8]) -> io::Result<ScalableCuckooFilterView<'static, T, H>>,
+    {
+        let file = File::open(path)?;
+        // SAFETY: The file isn't modified while mapped, as required by the documentation.
+        let mmap = unsafe { Mmap::map(&file)? };
+        // SAFETY: The mapped bytes don't move when `mmap` does, and outlive `view`,
+        // which is dropped before `mmap` and never hands out the `'static` lifetime.
+        let bytes = unsafe { std::slice::from_raw_parts(mmap.as_ptr(), mmap.len()) };
+        let view = make_view(bytes)?;
+
+        // Lookups touch pages at random.
+        #[cfg(unix)]
+        let _ = mmap.advise(memmap2::Advice::Random);
+        Ok(MmapCuckooFilter { view, mmap })
+    }
+
+    /// Returns the view of the mapped filter.
+    pub fn view(&self) -> &ScalableCuckooFilterView<'_, T, H> {
+        &self.view
+    }
+
+    /// Verifies the checksum of the mapped file (see [`ScalableCuckooFilterView::verify_checksum`]).
+    ///
+    /// This reads the whole file once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind `InvalidData` if the checksum doesn't match.
+    pub fn verify_checksum(&self) -> io::Result<()> {
+        self.view.verify_checksum()
+    }
+
+    /// Returns the size of the mapped file in bytes.
+    pub fn file_len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// Returns the approximate number of items inserted in the filter.
+    pub fn len(&self) -> u64 {
+        self.view.len()
+    }
+
+    /// Returns `true` if the filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.view.is_empty()
+    }
+
+    /// Returns the false positive probability configured for the filter.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.view.false_positive_probability()
+    }
+
+    /// Returns `true` if the filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.view.contains(item)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ScalableCuckooFilter, ScalableCuckooFilterBuilder, StorageBackend};
+
+    #[test]
+    fn it_works() {
+        let dir = tempfile::tempdir().unwrap();
+        for storage_backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .storage_backend(storage_backend)
+                .for_type::<usize>()
+                .finish();
+            for i in 0..1000 {
+                filter.insert(&i);
+            }
+            let path = dir.path().join("filter.bin");
+            filter.save_to_path(&path).unwrap();
+
+            let mapped = MmapCuckooFilter::<usize>::open(&path).unwrap();
+            assert_eq!(mapped.len(), filter.len());
+            assert_eq!(mapped.file_len(), filter.to_bytes().unwrap().len());
+            assert!((0..1000).all(|i| mapped.contains(&i)));
+            let false_positives = (1000..11000).filter(|i| mapped.contains(i)).count();
+            assert!(false_positives < 100);
+
+            // Replacing the file doesn't affect the mapping.
+            let empty: ScalableCuckooFilter<usize> = ScalableCuckooFilterBuilder::new()
+                .for_type::<usize>()
+                .finish();
+            empty.save_to_path(&path).unwrap();
+            assert!((0..1000).all(|i| mapped.contains(&i)));
+            assert!(MmapCuckooFilter::<usize>::open(&path).unwrap().is_empty());
+        }
+
+        // Opening doesn't read the buckets, so corruption is detected only when asked.
+        let path = dir.path().join("corrupted.bin");
+        let mut bytes = ScalableCuckooFilter::<usize>::new(1000, 0.001)
+            .to_bytes()
+            .unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+        let mapped = MmapCuckooFilter::<usize>::open(&path).unwrap();
+        assert!(mapped.verify_checksum().is_err());
+
+        let path = dir.path().join("malformed.bin");
+        std::fs::write(&path, b"SCKF").unwrap();
+        assert!(MmapCuckooFilter::<usize>::open(&path).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use rand::Rng;
+
+use std::io::{self, Read, Write};
+
+use crate::alloc::Allocator;
+use crate::bits::{BitArray, Bits};
+use crate::codec;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Morton filter style storage.
+///
+/// Logical buckets are grouped into blocks, and each block consists of
+/// a fullness counter array (the number of entries of each logical bucket) and
+/// a fingerprint storage array shared by the buckets of the block.
+/// Because the entries of a block are allocated on demand, sparsely and densely
+/// populated buckets balance each other out, and the fingerprint storage can be filled
+/// almost completely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct MortonBuckets<B = Bits> {
+    fingerprint_bitwidth: usize,
+    max_entries_per_bucket: usize,
+    counter_bitwidth: usize,
+    bucket_index_bitwidth: usize,
+    buckets_per_block: usize,
+    slots_per_block: usize,
+    bits: B,
+}
+impl MortonBuckets {
+    pub fn new(
+        fingerprint_bitwidth: usize,
+        max_entries_per_bucket: usize,
+        number_of_items_hint: usize,
+        alloc: Allocator,
+    ) -> Self {
+        let number_of_buckets =
+            Self::required_number_of_buckets(max_entries_per_bucket, number_of_items_hint);
+        let buckets_per_block = number_of_buckets.min(BUCKETS_PER_BLOCK);
+        let slots_per_block = Self::slots_per_block(max_entries_per_bucket, buckets_per_block);
+        let counter_bitwidth = (usize::BITS - max_entries_per_bucket.leading_zeros()) as usize;
+        let block_bitwidth =
+            counter_bitwidth * buckets_per_block + fingerprint_bitwidth * slots_per_block;
+        let blocks = number_of_buckets / buckets_per_block;
+        MortonBuckets {
+            fingerprint_bitwidth,
+            max_entries_per_bucket,
+            counter_bitwidth,
+            bucket_index_bitwidth: number_of_buckets.trailing_zeros() as usize,
+            buckets_per_block,
+            slots_per_block,
+            bits: Bits::new(block_bitwidth * blocks, alloc),
+        }
+    }
+
+    /// Writes the parameters the layout is derived from, which precede the bits in
+    /// a serialized layer.
+    pub fn write_head_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        codec::write_usize(writer, self.fingerprint_bitwidth)?;
+        codec::write_usize(writer, self.max_entries_per_bucket)?;
+        codec::write_usize(writer, self.bucket_index_bitwidth)
+    }
+
+    /// Returns the number of logical buckets required for storing `number_of_items_hint` items.
+    #[inline]
+    pub fn required_number_of_buckets(
+        max_entries_per_bucket: usize,
+        number_of_items_hint: usize,
+    ) -> usize {
+        let slots = Self::slots_per_block(max_entries_per_bucket, BUCKETS_PER_BLOCK);
+        let blocks = number_of_items_hint.div_ceil(slots).max(1);
+        if blocks == 1 {
+            // Small filters consist of a single block with fewer buckets.
+            let buckets = number_of_items_hint.div_ceil(max_entries_per_bucket) * 4;
+            buckets.clamp(1, BUCKETS_PER_BLOCK).next_power_of_two()
+        } else {
+            blocks.next_power_of_two() * BUCKETS_PER_BLOCK
+        }
+    }
+
+    fn slots_per_block(max_entries_per_bucket: usize, buckets_per_block: usize) -> usize {
+        // Same ratio as the original paper (46 slots for 64 buckets of three entries).
+        (max_entries_per_bucket * buckets_per_block / 4).max(1)
+    }
+
+    #[inline]
+    pub fn heap_bytes(&self) -> usize {
+        self.bits.heap_bytes()
+    }
+
+    #[inline]
+    pub fn allocator(&self) -> &Allocator {
+        self.bits.allocator()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        (0..self.len()).flat_map(move |i| {
+            let (start, count) = self.bucket_slots(i);
+            let block = i / self.buckets_per_block;
+            (start..start + count).map(move |s| (i, self.get_slot(block, s)))
+        })
+    }
+
+    #[inline]
+    pub fn try_insert(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+        debug_assert_ne!(fingerprint, 0);
+        let (start, count) = self.bucket_slots(bucket_index);
+        if count == self.max_entries_per_bucket || self.is_block_full(bucket_index) {
+            return false;
+        }
+        self.insert_slot(bucket_index, start + count, fingerprint);
+        true
+    }
+
+    /// Replaces a randomly chosen entry of the block of `bucket_index` with `fingerprint`
+    /// and returns the kicked out entry as `(its_bucket_index, fingerprint)`.
+    ///
+    /// If the bucket itself has room (i.e., only the block is full), the kicked out entry may
+    /// belong to another bucket of the block.
+    #[inline]
+    pub fn random_swap<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        bucket_index: usize,
+        fingerprint: u64,
+    ) -> (usize, u64) {
+        debug_assert_ne!(fingerprint, 0);
+        let (start, count) = self.bucket_slots(bucket_index);
+        let block = bucket_index / self.buckets_per_block;
+        if count == self.max_entries_per_bucket {
+            let slot = start + rng.gen_range(0..count);
+            let f = self.get_slot(block, slot);
+            self.set_slot(block, slot, fingerprint);
+            return (bucket_index, f);
+        }
+
+        debug_assert!(self.is_block_full(bucket_index));
+        let slot = rng.gen_range(0..self.slots_per_block);
+        let victim = self.bucket_of_slot(block, slot);
+        let f = self.get_slot(block, slot);
+        self.remove_slot(victim, slot);
+        let (start, count) = self.bucket_slots(bucket_index);
+        self.insert_slot(bucket_index, start + count, fingerprint);
+        debug_assert_ne!(f, 0);
+        (victim, f)
+    }
+
+    #[inline]
+    pub fn remove_fingerprint(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+        debug_assert_ne!(fingerprint, 0);
+        if let Some(slot) = self.find(bucket_index, fingerprint) {
+            self.remove_slot(bucket_index, slot);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Sets the `entry_index`-th entry of the bucket.
+    ///
+    /// Entries are kept packed, so setting zero removes the entry (shifting the following ones)
+    /// and setting an entry beyond the occupied ones appends a new entry.
+    #[inline]
+    pub fn set_fingerprint(&mut self, bucket_index: usize, entry_index: usize, fingerprint: u64) {
+        let (start, count) = self.bucket_slots(bucket_index);
+        let block = bucket_index / self.buckets_per_block;
+        if fingerprint == 0 {
+            if entry_index < count {
+                self.remove_slot(bucket_index, start + entry_index);
+            }
+        } else if entry_index < count {
+            self.set_slot(block, start + entry_index, fingerprint);
+        } else {
+            let inserted = self.try_insert(bucket_index, fingerprint);
+            debug_assert!(inserted);
+        }
+    }
+
+    fn set_counter(&mut self, bucket_index: usize, count: usize) {
+        let block = bucket_index / self.buckets_per_block;
+        let offset = self.block_offset(block)
+            + self.counter_bitwidth * (bucket_index % self.buckets_per_block);
+        self.bits
+            .set_uint(offset, self.counter_bitwidth, count as u64);
+    }
+
+    fn set_slot(&mut self, block: usize, slot: usize, fingerprint: u64) {
+        let offset = self.block_offset(block)
+            + self.counter_bitwidth * self.buckets_per_block
+            + self.fingerprint_bitwidth * slot;
+        self.bits
+            .set_uint(offset, self.fingerprint_bitwidth, fingerprint);
+    }
+
+    fn insert_slot(&mut self, bucket_index: usize, slot: usize, fingerprint: u64) {
+        let block = bucket_index / self.buckets_per_block;
+        let used = self.used_slots(block);
+        for s in (slot..used).rev() {
+            let f = self.get_slot(block, s);
+            self.set_slot(block, s + 1, f);
+        }
+        self.set_slot(block, slot, fingerprint);
+        let count = self.counter(bucket_index);
+        self.set_counter(bucket_index, count + 1);
+    }
+
+    fn remove_slot(&mut self, bucket_index: usize, slot: usize) {
+        let block = bucket_index / self.buckets_per_block;
+        let used = self.used_slots(block);
+        for s in slot + 1..used {
+            let f = self.get_slot(block, s);
+            self.set_slot(block, s - 1, f);
+        }
+        self.set_slot(block, used - 1, 0);
+        let count = self.counter(bucket_index);
+        self.set_counter(bucket_index, count - 1);
+    }
+}
+impl<B: BitArray> MortonBuckets<B> {
+    /// Returns the bit array holding the buckets.
+    #[inline]
+    pub fn bit_array(&self) -> &B {
+        &self.bits
+    }
+
+    #[inline]
+    pub fn bit_array_mut(&mut self) -> &mut B {
+        &mut self.bits
+    }
+
+    /// Reads the parameters written by `write_to` and then the bits with `read_bits`.
+    pub fn read_with<R: Read>(
+        reader: &mut R,
+        read_bits: impl FnOnce(&mut R) -> io::Result<B>,
+    ) -> io::Result<Self> {
+        let fingerprint_bitwidth = codec::read_usize(reader)?;
+        let max_entries_per_bucket = codec::read_usize(reader)?;
+        let bucket_index_bitwidth = codec::read_usize(reader)?;
+        if !(1..=64).contains(&fingerprint_bitwidth)
+            || max_entries_per_bucket == 0
+            || bucket_index_bitwidth >= usize::BITS as usize
+        {
+            return Err(codec::invalid_data("invalid Morton bucket layout"));
+        }
+        let number_of_buckets = 1usize << bucket_index_bitwidth;
+        let buckets_per_block = number_of_buckets.min(BUCKETS_PER_BLOCK);
+        let slots_per_block =
+            MortonBuckets::slots_per_block(max_entries_per_bucket, buckets_per_block);
+        let counter_bitwidth = (usize::BITS - max_entries_per_bucket.leading_zeros()) as usize;
+        let block_bitwidth =
+            counter_bitwidth * buckets_per_block + fingerprint_bitwidth * slots_per_block;
+        let bits = read_bits(reader)?;
+        if bits.len() < block_bitwidth * (number_of_buckets / buckets_per_block) {
+            return Err(codec::invalid_data("invalid Morton bucket layout"));
+        }
+        Ok(MortonBuckets {
+            fingerprint_bitwidth,
+            max_entries_per_bucket,
+            counter_bitwidth,
+            bucket_index_bitwidth,
+            buckets_per_block,
+            slots_per_block,
+            bits,
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        1 << self.bucket_index_bitwidth
+    }
+
+    #[inline]
+    pub fn entries(&self) -> usize {
+        self.len() / self.buckets_per_block * self.slots_per_block
+    }
+
+    #[inline]
+    pub fn bits(&self) -> u64 {
+        self.bits.len() as u64
+    }
+
+    #[inline]
+    pub fn index(&self, hash: u64) -> usize {
+        (hash & ((1 << self.bucket_index_bitwidth) - 1)) as usize
+    }
+
+    #[inline]
+    pub fn fingerprint(&self, hash: u64) -> u64 {
+        hash >> (64 - self.fingerprint_bitwidth)
+    }
+
+    #[inline]
+    pub fn entries_per_bucket(&self) -> usize {
+        self.max_entries_per_bucket
+    }
+
+    #[inline]
+    pub fn fingerprint_bitwidth(&self) -> usize {
+        self.fingerprint_bitwidth
+    }
+
+    #[inline]
+    pub fn contains(&self, bucket_index: usize, fingerprint: u64) -> bool {
+        debug_assert_ne!(fingerprint, 0);
+        self.find(bucket_index, fingerprint).is_some()
+    }
+
+    #[inline]
+    pub fn get_fingerprint(&self, bucket_index: usize, entry_index: usize) -> u64 {
+        let (start, count) = self.bucket_slots(bucket_index);
+        if entry_index < count {
+            self.get_slot(bucket_index / self.buckets_per_block, start + entry_index)
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    pub fn prefetch(&self, bucket_index: usize) {
+        let block = bucket_index / self.buckets_per_block;
+        self.bits.prefetch(self.block_offset(block));
+    }
+
+    fn find(&self, bucket_index: usize, fingerprint: u64) -> Option<usize> {
+        let (start, count) = self.bucket_slots(bucket_index);
+        let block = bucket_index / self.buckets_per_block;
+        (start..start + count).find(|&s| self.get_slot(block, s) == fingerprint)
+    }
+
+    fn block_offset(&self, block: usize) -> usize {
+        let block_bitwidth = self.counter_bitwidth * self.buckets_per_block
+            + self.fingerprint_bitwidth * self.slots_per_block;
+        block_bitwidth * block
+    }
+
+    fn counter(&self, bucket_index: usize) -> usize {
+        let block = bucket_index / self.buckets_per_block;
+        let offset = self.block_offset(block)
+            + self.counter_bitwidth * (bucket_index % self.buckets_per_block);
+        self.bits.get_uint(offset, self.counter_bitwidth) as usize
+    }
+
+    /// Returns the first slot (within the block) and the number of entries of the bucket.
+    fn bucket_slots(&self, bucket_index: usize) -> (usize, usize) {
+        let first = bucket_index - bucket_index % self.buckets_per_block;
+        let start = (first..bucket_index).map(|i| self.counter(i)).sum();
+        (start, self.counter(bucket_index))
+    }
+
+    fn used_slots(&self, block: usize) -> usize {
+        let first = block * self.buckets_per_block;
+        (first..first + self.buckets_per_block)
+            .map(|i| self.counter(i))
+            .sum()
+    }
+
+    fn is_block_full(&self, bucket_index: usize) -> bool {
+        self.used_slots(bucket_index / self.buckets_per_block) == self.slots_per_block
+    }
+
+    fn bucket_of_slot(&self, block: usize, slot: usize) -> usize {
+        let first = block * self.buckets_per_block;
+        let mut end = 0;
+        for i in first..first + self.buckets_per_block {
+            end += self.counter(i);
+            if slot < end {
+                return i;
+            }
+        }
+        unreachable!()
+    }
+
+    fn get_slot(&self, block: usize, slot: usize) -> u64 {
+        let offset = self.block_offset(block)
+            + self.counter_bitwidth * self.buckets_per_block
+            + self.fingerprint_bitwidth * slot;
+        self.bits.get_uint(offset, self.fingerprint_bitwidth)
+    }
+}
+
+const BUCKETS_PER_BLOCK: usize = 64;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn it_works() {
+        let mut buckets = MortonBuckets::new(8, 3, 1000, Allocator::default());
+        assert_eq!(buckets.len(), 2048);
+        assert_eq!(buckets.entries(), 1536);
+
+        for i in 0..3 {
+            assert!(!buckets.contains(333, 100 + i));
+            assert!(buckets.try_insert(333, 100 + i));
+            assert!(buckets.contains(333, 100 + i));
+        }
+        assert!(!buckets.try_insert(333, 103)); // the bucket is full
+        assert!(buckets.try_insert(332, 200));
+        assert!(buckets.try_insert(334, 201));
+        assert_eq!(buckets.get_fingerprint(333, 1), 101);
+        assert_eq!(buckets.get_fingerprint(333, 3), 0);
+
+        assert!(buckets.remove_fingerprint(333, 101));
+        assert!(!buckets.contains(333, 101));
+        assert!(buckets.contains(333, 100));
+        assert!(buckets.contains(333, 102));
+        assert!(buckets.contains(332, 200));
+        assert!(buckets.contains(334, 201));
+        assert_eq!(buckets.iter().count(), 4);
+    }
+
+    #[test]
+    fn block_sharing_works() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut buckets = MortonBuckets::new(8, 3, 1000, Allocator::default());
+
+        // Fills the first block (48 slots) through a few buckets.
+        for i in 0..48 {
+            assert!(buckets.try_insert(i % 16, 1 + i as u64));
+        }
+        assert!(!buckets.try_insert(20, 100)); // the block is full
+        assert!(buckets.try_insert(64, 100)); // the next block
+
+        let (victim, f) = buckets.random_swap(&mut rng, 20, 100);
+        assert!(victim < 16);
+        assert!(!buckets.contains(victim, f));
+        assert!(buckets.contains(20, 100));
+        assert_eq!(buckets.iter().filter(|&(i, _)| i < 64).count(), 48);
+    }
+}
@@ -0,0 +1,165 @@
+//! Helpers of `save_to_path` and `load_from_path`.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::codec;
+
+/// Shortest possible file: the magic and the format version.
+const MIN_FILE_LEN: u64 = 6;
+
+/// Writes a file at `path` by `write`, atomically replacing any existing file.
+///
+/// The contents are written to a temporary file in the same directory, synced to the disk,
+/// and then renamed to `path`, so readers never see a partially written file, and a crash
+/// leaves either the old or the new file (and possibly the temporary file).
+pub fn save_atomically<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> io::Result<()>,
+{
+    let temp_path = temp_path(path)?;
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)?;
+    let result = write_and_sync(&file, write).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // Makes the rename durable.
+    #[cfg(unix)]
+    File::open(parent_dir(path))?.sync_all()?;
+    Ok(())
+}
+
+/// Removes the file at `path` if it exists, and makes the removal durable.
+#[cfg(feature = "mmap")]
+pub fn remove_durably(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    #[cfg(unix)]
+    File::open(parent_dir(path))?.sync_all()?;
+    Ok(())
+}
+
+fn write_and_sync<F>(file: &File, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> io::Result<()>,
+{
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    writer.flush()?;
+    file.sync_all()
+}
+
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(parent_dir(path).join(temp_name))
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Reads the file at `path` by `read`, which must consume all of it.
+///
+/// Returns an error if the path isn't a regular file, if the file is too short to hold a filter,
+/// or if its size or modification time changes while it is read (e.g., because it is
+/// being written by a process that doesn't use `save_atomically`).
+pub fn load_checked<T, F>(path: &Path, read: F) -> io::Result<T>
+where
+    F: FnOnce(&mut BufReader<&File>) -> io::Result<T>,
+{
+    let file = File::open(path)?;
+    let before = file.metadata()?;
+    if !before.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a regular file",
+        ));
+    }
+    if before.len() < MIN_FILE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "file is too short to hold a filter",
+        ));
+    }
+
+    let mut reader = BufReader::new(&file);
+    let value = read(&mut reader)?;
+    if !reader.fill_buf()?.is_empty() {
+        return Err(codec::invalid_data("trailing bytes after the filter"));
+    }
+
+    let after = file.metadata()?;
+    if after.len() != before.len() || after.modified().ok() != before.modified().ok() {
+        return Err(codec::invalid_data("file was modified while being read"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filter.bin");
+
+        save_atomically(&path, |w| w.write_all(b"SCKF-old")).unwrap();
+        save_atomically(&path, |w| w.write_all(b"SCKF-new")).unwrap();
+        let contents = load_checked(&path, |r| {
+            let mut contents = Vec::new();
+            r.read_to_end(&mut contents)?;
+            Ok(contents)
+        })
+        .unwrap();
+        assert_eq!(contents, b"SCKF-new");
+        // No temporary files are left.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // A failed write leaves the old file as it is.
+        let error = save_atomically(&path, |w| {
+            w.write_all(b"SCKF-broken")?;
+            Err(io::Error::other("failed"))
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "failed");
+        assert_eq!(fs::read(&path).unwrap(), b"SCKF-new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let partial = load_checked(&path, |r| r.read_exact(&mut [0; 4]));
+        assert_eq!(partial.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::write(&path, b"SCKF").unwrap();
+        let short = load_checked(&path, |_| Ok(()));
+        assert_eq!(short.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let not_file = load_checked(dir.path(), |_| Ok(()));
+        assert!(not_file.is_err());
+    }
+}
@@ -0,0 +1,524 @@
+use std::borrow::Borrow;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use rand::Rng;
+
+use crate::bits::Bits;
+use crate::view::{self, LayerLayout};
+use crate::{codec, persist};
+use crate::{DefaultHasher, DefaultRng, HasherKeys, ScalableCuckooFilter};
+
+/// Number of bytes covered by each of the checksums combined into the checksum of the file.
+const CHECKSUM_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Bytes that every journal starts with.
+const JOURNAL_MAGIC: [u8; 4] = *b"SCKJ";
+const JOURNAL_VERSION: u16 = 1;
+
+/// Bytes written to the file at an offset.
+type FileWrite = (usize, Vec<u8>);
+
+/// Filter that persists itself in a file, which it updates in place.
+///
+/// The file is in the format of [`ScalableCuckooFilter::save_to_path`]. The filter records
+/// which pages of its buckets insertions and removals write, and [`flush`] writes only those
+/// pages (and the item counts and stashes) to the file and syncs it to the disk. So a large
+/// filter can be kept durable without writing all of it every time, and is ready right after
+/// restarts by [`open`] instead of being rebuilt. The file isn't kept mapped into memory,
+/// so the filter takes as much memory as a [`ScalableCuckooFilter`].
+///
+/// Growing the filter changes the layout of the file, so the first flush after a growth
+/// rewrites the whole file (atomically, as `save_to_path` does).
+///
+/// Changes are only written by [`flush`], and are lost if the filter is dropped without it.
+/// A flush first saves the bytes it writes to a journal next to the file (the path of the file
+/// followed by `.journal`), so if the process crashes during a flush, [`open`] completes
+/// the flush from the journal, or finds the file as the previous flush left it if the journal
+/// wasn't saved yet.
+/// The file must not be modified by others while it is open.
+///
+/// [`flush`]: PersistentCuckooFilter::flush
+/// [`open`]: PersistentCuckooFilter::open
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{PersistentCuckooFilter, ScalableCuckooFilterBuilder};
+///
+/// let path = std::env::temp_dir().join(format!("persistent-filter-{}.bin", std::process::id()));
+/// let filter = ScalableCuckooFilterBuilder::new()
+///     .for_type::<str>()
+///     .finish();
+/// let mut filter = PersistentCuckooFilter::create(&path, filter).unwrap();
+/// filter.insert("foo");
+/// filter.flush().unwrap();
+/// drop(filter);
+///
+/// let filter: PersistentCuckooFilter<str> = PersistentCuckooFilter::open(&path).unwrap();
+/// assert!(filter.contains("foo"));
+/// # drop(filter);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct PersistentCuckooFilter<T: ?Sized, H = DefaultHasher, R = DefaultRng> {
+    filter: ScalableCuckooFilter<T, H, R>,
+    path: PathBuf,
+    file: File,
+    file_len: usize,
+    layouts: Vec<LayerLayout>,
+    // CRC-32 of each chunk of the bytes covered by the checksum of the file.
+    chunk_checksums: Vec<u32>,
+    // Version of the filter that the file holds.
+    flushed_version: u64,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone + HasherKeys, R: Rng> PersistentCuckooFilter<T, H, R> {
+    /// Saves `filter` to the file at `path`, replacing any existing file, and makes a
+    /// persistent filter from them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written or read, or if the alternative index
+    /// scheme of `filter` is [`AltIndexScheme::Custom`].
+    ///
+    /// [`AltIndexScheme::Custom`]: crate::AltIndexScheme::Custom
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        filter: ScalableCuckooFilter<T, H, R>,
+    ) -> io::Result<Self> {
+        // A journal of an earlier file must not be replayed onto this one.
+        persist::remove_durably(&journal_path(path.as_ref()))?;
+        filter.save_to_path(path.as_ref())?;
+        Self::attach(filter, path.as_ref())
+    }
+
+    /// Opens the file at `path`, which holds `filter` as it is.
+    fn attach(filter: ScalableCuckooFilter<T, H, R>, path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let (layouts, chunk_checksums, file_len) = read_layout(&filter, &file)?;
+        Ok(PersistentCuckooFilter {
+            path: path.to_path_buf(),
+            file,
+            file_len,
+            layouts,
+            chunk_checksums,
+            flushed_version: filter.version(),
+            filter,
+        })
+    }
+
+    /// Returns `true` if the filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.filter.contains(item)
+    }
+
+    /// Inserts `item` into the filter.
+    ///
+    /// The insertion is persisted by the next [`flush`].
+    ///
+    /// [`flush`]: PersistentCuckooFilter::flush
+    pub fn insert(&mut self, item: &T) {
+        self.filter.insert(item);
+    }
+
+    /// Removes `item` from the filter, returning `true` if it was found.
+    ///
+    /// The removal is persisted by the next [`flush`].
+    ///
+    /// [`flush`]: PersistentCuckooFilter::flush
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.filter.remove(item)
+    }
+
+    /// Returns the approximate number of items inserted in the filter.
+    pub fn len(&self) -> u64 {
+        self.filter.len()
+    }
+
+    /// Returns `true` if the filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty()
+    }
+
+    /// Returns the filter.
+    pub fn filter(&self) -> &ScalableCuckooFilter<T, H, R> {
+        &self.filter
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the changes since the last flush to the file, and waits for them to reach the disk.
+    ///
+    /// Only the pages of the buckets written by insertions and removals are written,
+    /// unless the filter has grown since the last flush, which rewrites the whole file.
+    /// They are saved to the journal first, which is removed once they reach the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing or syncing the file or the journal fails.
+    /// The changes are kept and written by the next flush then.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let version = self.filter.version();
+        if version == self.flushed_version {
+            return Ok(());
+        }
+        if !self.has_same_layout() {
+            // A journal left by a failed flush must not be replayed onto the new file.
+            persist::remove_durably(&journal_path(&self.path))?;
+            self.filter.save_to_path(&self.path)?;
+            let file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+            (self.layouts, self.chunk_checksums, self.file_len) = read_layout(&self.filter, &file)?;
+            self.file = file;
+            self.flushed_version = version;
+            return Ok(());
+        }
+
+        let (writes, chunk_checksums) = self.write_journal(version)?;
+        apply_writes(&self.file, &writes)?;
+        persist::remove_durably(&journal_path(&self.path))?;
+        self.chunk_checksums = chunk_checksums;
+        self.flushed_version = version;
+        Ok(())
+    }
+
+    fn has_same_layout(&self) -> bool {
+        let layers = self.filter.raw.layers_ref();
+        layers.len() == self.layouts.len()
+            && layers.iter().zip(&self.layouts).all(|(layer, layout)| {
+                let mut counts = Vec::new();
+                layer.write_counts_to(&mut counts).is_ok()
+                    && counts.len() == layout.counts.len()
+                    && layer.bit_array().base_version() <= self.flushed_version
+            })
+    }
+
+    /// Saves the writes that bring the file to `version` to the journal, returning them and
+    /// the checksums of the chunks of the file after them.
+    fn write_journal(&self, version: u64) -> io::Result<(Vec<FileWrite>, Vec<u32>)> {
+        let mut writes = vec![(codec::FILTER_VERSION_OFFSET, version.to_le_bytes().to_vec())];
+        let mut page = [0; Bits::PAGE_BYTES];
+        for (layer, layout) in self.filter.raw.layers_ref().iter().zip(&self.layouts) {
+            let mut counts = Vec::new();
+            layer.write_counts_to(&mut counts)?;
+            writes.push((layout.counts.start, counts));
+
+            let bits = layer.bit_array();
+            for i in bits.pages_changed_since(self.flushed_version) {
+                let len = bits.page_to_le_bytes(i, &mut page);
+                writes.push((
+                    layout.bits.start + i * Bits::PAGE_BYTES,
+                    page[..len].to_vec(),
+                ));
+            }
+        }
+        let (chunk_checksums, checksum) = self.updated_checksums(&writes)?;
+        writes.push((self.file_len - 4, checksum.to_le_bytes().to_vec()));
+
+        persist::save_atomically(&journal_path(&self.path), |w| {
+            let w = &mut codec::ChecksumWriter::new(w);
+            w.write_all(&JOURNAL_MAGIC)?;
+            w.write_all(&JOURNAL_VERSION.to_le_bytes())?;
+            codec::write_usize(w, self.file_len)?;
+            codec::write_usize(w, writes.len())?;
+            for (offset, bytes) in &writes {
+                codec::write_usize(w, *offset)?;
+                codec::write_usize(w, bytes.len())?;
+                w.write_all(bytes)?;
+            }
+            w.finish()
+        })?;
+        Ok((writes, chunk_checksums))
+    }
+
+    /// Returns the checksums of the chunks of the file after `writes`, and the checksum of
+    /// the file combined from them.
+    ///
+    /// The chunks that `writes` change are read from the file.
+    fn updated_checksums(&self, writes: &[FileWrite]) -> io::Result<(Vec<u32>, u32)> {
+        let covered = self.file_len - 4;
+        let mut chunks = writes
+            .iter()
+            .flat_map(|(offset, bytes)| {
+                offset / CHECKSUM_CHUNK_BYTES..(offset + bytes.len()).div_ceil(CHECKSUM_CHUNK_BYTES)
+            })
+            .collect::<Vec<_>>();
+        chunks.sort_unstable();
+        chunks.dedup();
+
+        let mut chunk_checksums = self.chunk_checksums.clone();
+        let mut buf = vec![0; CHECKSUM_CHUNK_BYTES];
+        for i in chunks {
+            let start = i * CHECKSUM_CHUNK_BYTES;
+            let end = (start + CHECKSUM_CHUNK_BYTES).min(covered);
+            let chunk = &mut buf[..end - start];
+            let mut file = &self.file;
+            file.seek(SeekFrom::Start(start as u64))?;
+            file.read_exact(chunk)?;
+            for (offset, bytes) in writes {
+                let from = (*offset).max(start);
+                let to = (offset + bytes.len()).min(end);
+                if from < to {
+                    chunk[from - start..to - start]
+                        .copy_from_slice(&bytes[from - offset..to - offset]);
+                }
+            }
+            chunk_checksums[i] = codec::checksum(chunk);
+        }
+
+        let mut hasher = crc32fast::Hasher::new();
+        for (i, &checksum) in chunk_checksums.iter().enumerate() {
+            let len = (covered - i * CHECKSUM_CHUNK_BYTES).min(CHECKSUM_CHUNK_BYTES);
+            hasher.combine(&crc32fast::Hasher::new_with_initial_len(
+                checksum, len as u64,
+            ));
+        }
+        Ok((chunk_checksums, hasher.finalize()))
+    }
+}
+impl<T, H, R> PersistentCuckooFilter<T, H, R>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + Default + HasherKeys,
+    R: Rng + Default,
+{
+    /// Opens the file at `path` saved by [`create`] (or [`ScalableCuckooFilter::save_to_path`]).
+    ///
+    /// If a flush was interrupted after saving its journal, the writes in the journal are
+    /// completed first. A journal that wasn't completely saved is ignored, since the file isn't
+    /// written until the journal is saved. Either way the journal is removed.
+    ///
+    /// The filter is read into memory, and the hasher is made as by
+    /// [`ScalableCuckooFilter::read_from`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file or the journal can't be read or written, if the journal
+    /// doesn't match the file, or if the contents of the file are malformed.
+    ///
+    /// [`create`]: PersistentCuckooFilter::create
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        recover(path.as_ref())?;
+        let filter = ScalableCuckooFilter::load_from_path(path.as_ref())?;
+        Self::attach(filter, path.as_ref())
+    }
+}
+
+fn journal_path(path: &Path) -> PathBuf {
+    let mut journal_path = OsString::from(path.as_os_str());
+    journal_path.push(".journal");
+    PathBuf::from(journal_path)
+}
+
+/// Completes the writes of the journal of the file at `path`, if any, and removes it.
+fn recover(path: &Path) -> io::Result<()> {
+    let journal_path = journal_path(path);
+    let journal = match fs::read(&journal_path) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = usize::try_from(file.metadata()?.len())
+        .map_err(|_| codec::invalid_data("file is too large"))?;
+    if let Some((journal_file_len, writes)) = read_journal(&journal) {
+        if journal_file_len != file_len
+            || writes
+                .iter()
+                .any(|(offset, bytes)| offset.saturating_add(bytes.len()) > file_len)
+        {
+            return Err(codec::invalid_data("journal doesn't match the file"));
+        }
+        apply_writes(&file, &writes)?;
+    }
+    persist::remove_durably(&journal_path)
+}
+
+/// Reads the length of the file and the writes saved by `write_journal`, or returns `None` if
+/// the journal is incomplete.
+fn read_journal(mut bytes: &[u8]) -> Option<(usize, Vec<FileWrite>)> {
+    let r = &mut codec::ChecksumReader::new(&mut bytes);
+    let mut header = [0; 6];
+    r.read_exact(&mut header).ok()?;
+    if header[..4] != JOURNAL_MAGIC || header[4..] != JOURNAL_VERSION.to_le_bytes() {
+        return None;
+    }
+    let file_len = codec::read_usize(r).ok()?;
+    let mut writes = Vec::new();
+    for _ in 0..codec::read_usize(r).ok()? {
+        let offset = codec::read_usize(r).ok()?;
+        let len = codec::read_usize(r).ok()?;
+        let mut write = Vec::new();
+        r.by_ref().take(len as u64).read_to_end(&mut write).ok()?;
+        if write.len() != len {
+            return None;
+        }
+        writes.push((offset, write));
+    }
+    r.verify().ok()?;
+    Some((file_len, writes))
+}
+
+/// Writes `writes` to `file` and syncs it to the disk.
+fn apply_writes(mut file: &File, writes: &[FileWrite]) -> io::Result<()> {
+    for (offset, bytes) in writes {
+        file.seek(SeekFrom::Start(*offset as u64))?;
+        file.write_all(bytes)?;
+    }
+    file.sync_data()
+}
+
+/// Returns the layout of the layers in `file`, which holds `filter` as it is,
+/// the checksums of its chunks, and its length.
+fn read_layout<T: ?Sized, H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<T, H, R>,
+    file: &File,
+) -> io::Result<(Vec<LayerLayout>, Vec<u32>, usize)> {
+    // The file is mapped only while its layout is read, so its pages can be evicted afterwards.
+    // SAFETY: The file isn't modified by others while open, as required by the documentation.
+    let mmap = unsafe { Mmap::map(file)? };
+    let layouts = view::layer_layouts(&mmap)?;
+    if layouts.len() != filter.raw.layers_ref().len() {
+        return Err(codec::invalid_data("file doesn't hold the filter"));
+    }
+    let checksums = mmap[..mmap.len() - 4]
+        .chunks(CHECKSUM_CHUNK_BYTES)
+        .map(codec::checksum)
+        .collect();
+    Ok((layouts, checksums, mmap.len()))
+}
+
+impl<T: ?Sized, H, R> fmt::Debug for PersistentCuckooFilter<T, H, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PersistentCuckooFilter")
+            .field("path", &self.path)
+            .field("file_len", &self.file_len)
+            .field("flushed_version", &self.flushed_version)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ScalableCuckooFilterBuilder, StorageBackend};
+
+    #[test]
+    fn it_works() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filter.bin");
+        for storage_backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+            let filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100_000)
+                .storage_backend(storage_backend)
+                .for_type::<usize>()
+                .finish();
+            let mut filter = PersistentCuckooFilter::create(&path, filter).unwrap();
+            for i in 0..1000 {
+                filter.insert(&i);
+            }
+            filter.flush().unwrap();
+            assert_eq!(
+                std::fs::read(&path).unwrap(),
+                filter.filter().to_bytes().unwrap()
+            );
+
+            // Only a few pages are written.
+            for i in 0..1000 {
+                assert!(filter.remove(&i));
+            }
+            filter.insert(&1000);
+            filter.flush().unwrap();
+            assert_eq!(
+                std::fs::read(&path).unwrap(),
+                filter.filter().to_bytes().unwrap()
+            );
+            drop(filter);
+
+            let mut filter = PersistentCuckooFilter::<usize>::open(&path).unwrap();
+            assert_eq!(filter.len(), 1);
+            assert!(filter.contains(&1000));
+
+            // Growing rewrites the file.
+            for i in 0..300_000 {
+                filter.insert(&i);
+            }
+            assert!(filter.filter().layers().len() > 1);
+            filter.flush().unwrap();
+            for i in 300_000..301_000 {
+                filter.insert(&i);
+            }
+            filter.flush().unwrap();
+            drop(filter);
+
+            let filter = PersistentCuckooFilter::<usize>::open(&path).unwrap();
+            assert!((0..301_000).all(|i| filter.contains(&i)));
+        }
+    }
+    #[test]
+    fn interrupted_flush_is_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filter.bin");
+        let journal_path = journal_path(&path);
+        // With fixed keys, no items are stashed, which would change the layout of the file.
+        let filter = ScalableCuckooFilterBuilder::new()
+            .hasher(DefaultHasher::default())
+            .initial_capacity(100_000)
+            .for_type::<usize>()
+            .finish();
+        let mut filter = PersistentCuckooFilter::create(&path, filter).unwrap();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        filter.flush().unwrap();
+        let flushed = filter.filter().to_bytes().unwrap();
+        for i in 1000..2000 {
+            filter.insert(&i);
+        }
+        let unflushed = filter.filter().to_bytes().unwrap();
+        assert!(filter.has_same_layout());
+        let (writes, _) = filter.write_journal(filter.filter().version()).unwrap();
+        let journal = fs::read(&journal_path).unwrap();
+
+        // A crash while the journal is saved leaves the file as the last flush did.
+        fs::write(&journal_path, &journal[..journal.len() / 2]).unwrap();
+        let reopened = PersistentCuckooFilter::<usize>::open(&path).unwrap();
+        assert_eq!(reopened.filter().to_bytes().unwrap(), flushed);
+        assert!(!journal_path.exists());
+        drop(reopened);
+
+        // A crash while the file is written tears it, and the flush is completed from the journal.
+        fs::write(&journal_path, &journal).unwrap();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        let (torn, applied) = writes.split_last().unwrap();
+        apply_writes(&file, &applied[..applied.len() / 2]).unwrap();
+        apply_writes(&file, &[(torn.0, torn.1[..2].to_vec())]).unwrap();
+        drop(file);
+        assert!(ScalableCuckooFilter::<usize>::load_from_path(&path).is_err());
+
+        let mut reopened = PersistentCuckooFilter::<usize>::open(&path).unwrap();
+        assert_eq!(reopened.filter().to_bytes().unwrap(), unflushed);
+        assert!(!journal_path.exists());
+        assert!((0..2000).all(|i| reopened.contains(&i)));
+        reopened.insert(&2000);
+        reopened.flush().unwrap();
+        assert_eq!(
+            fs::read(&path).unwrap(),
+            reopened.filter().to_bytes().unwrap()
+        );
+    }
+}
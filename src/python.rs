@@ -0,0 +1,155 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::{raw, DefaultHasher, ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+/// Filter of byte strings exposed to Python as `scalable_cuckoo_filter.ScalableCuckooFilter`.
+///
+/// This is available only if the `python` feature is enabled.
+/// The extension module is built by [maturin] (e.g., `maturin build --features python`).
+///
+/// Filters are built with a randomly keyed `DefaultHasher` as their hasher and serialized by
+/// `to_bytes` in the same way as [`ScalableCuckooFilter::to_bytes`] (with the hasher keys),
+/// so a filter built in Python can be loaded by `ScalableCuckooFilter::<[u8]>::from_bytes`
+/// in Rust and vice versa.
+/// Filters are pickled as those bytes.
+///
+/// ```python
+/// import pickle
+/// from scalable_cuckoo_filter import ScalableCuckooFilter
+///
+/// filter = ScalableCuckooFilter(initial_capacity=1000, false_positive_probability=0.001)
+/// filter.insert(b"foo")
+/// assert b"foo" in filter
+/// assert pickle.loads(pickle.dumps(filter)).contains(b"foo")
+/// open("filter.bin", "wb").write(filter.to_bytes())
+/// ```
+///
+/// [maturin]: https://www.maturin.rs/
+#[pyclass(name = "ScalableCuckooFilter", module = "scalable_cuckoo_filter")]
+#[derive(Debug)]
+pub struct PyScalableCuckooFilter {
+    filter: ScalableCuckooFilter<[u8], DefaultHasher, StdRng>,
+}
+#[pymethods]
+impl PyScalableCuckooFilter {
+    #[new]
+    #[pyo3(signature = (initial_capacity = 100_000, false_positive_probability = 0.001))]
+    fn new(initial_capacity: usize, false_positive_probability: f64) -> PyResult<Self> {
+        let filter = ScalableCuckooFilterBuilder::new()
+            .rng(StdRng::from_entropy())
+            .for_type::<[u8]>()
+            .initial_capacity(initial_capacity)
+            .false_positive_probability(false_positive_probability)
+            .build()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyScalableCuckooFilter { filter })
+    }
+
+    /// Deserializes a filter from bytes made by `to_bytes`.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let raw = raw::ScalableCuckooFilter::<DefaultHasher>::from_bytes(bytes)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let hasher = raw.hasher.clone();
+        let filter = ScalableCuckooFilter::from_raw(raw.with_parts(hasher, StdRng::from_entropy()));
+        Ok(PyScalableCuckooFilter { filter })
+    }
+
+    /// Serializes this filter into bytes.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = self
+            .filter
+            .to_bytes()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    /// Inserts `item` into this filter.
+    fn insert(&mut self, item: &[u8]) {
+        self.filter.insert(item);
+    }
+
+    /// Returns `True` if this filter may contain `item`.
+    fn contains(&self, item: &[u8]) -> bool {
+        self.filter.contains(item)
+    }
+
+    /// Removes `item` from this filter, returning `True` if an entry has been removed.
+    fn remove(&mut self, item: &[u8]) -> bool {
+        self.filter.remove(item)
+    }
+
+    fn __contains__(&self, item: &[u8]) -> bool {
+        self.filter.contains(item)
+    }
+
+    fn __len__(&self) -> usize {
+        self.filter.len() as usize
+    }
+
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+        let from_bytes = slf.get_type().getattr("from_bytes")?;
+        Ok((from_bytes, (slf.borrow().to_bytes(slf.py())?,)))
+    }
+}
+
+#[pymodule]
+fn scalable_cuckoo_filter(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyScalableCuckooFilter>()
+}
+
+#[cfg(test)]
+mod test {
+    use pyo3::types::PyDict;
+
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "scalable_cuckoo_filter").unwrap();
+            scalable_cuckoo_filter(&module).unwrap();
+            let sys_modules = py.import("sys").unwrap().getattr("modules").unwrap();
+            sys_modules
+                .set_item("scalable_cuckoo_filter", &module)
+                .unwrap();
+
+            let locals = PyDict::new(py);
+            py.run(
+                cr#"
+import pickle
+from scalable_cuckoo_filter import ScalableCuckooFilter
+
+f = ScalableCuckooFilter(initial_capacity=100)
+f.insert(b"foo")
+assert f.contains(b"foo") and b"foo" in f and b"bar" not in f
+assert len(f) == 1
+g = pickle.loads(pickle.dumps(f))
+assert g.contains(b"foo")
+assert g.remove(b"foo") and not g.contains(b"foo")
+try:
+    ScalableCuckooFilter(initial_capacity=0)
+    assert False
+except ValueError:
+    pass
+bytes = f.to_bytes()
+"#,
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+
+            let bytes = locals.get_item("bytes").unwrap().unwrap();
+            let filter =
+                ScalableCuckooFilter::<[u8]>::from_bytes(bytes.extract().unwrap()).unwrap();
+            assert!(filter.contains(b"foo"));
+        });
+    }
+}
@@ -0,0 +1,1821 @@
+//! Filters operating directly on 64-bit item hashes.
+//!
+//! [`ScalableCuckooFilter`] in this module doesn't hash items by itself.
+//! Instead, the caller supplies a 64-bit hash of each item, which is useful for systems
+//! that already have high-quality hashes (e.g., content checksums or halves of UUIDs).
+//!
+//! The typed [`crate::ScalableCuckooFilter`] is a thin wrapper of this filter that hashes
+//! items with its hasher.
+//!
+//! The upper bits of a hash are used as the fingerprint and the lower bits as the bucket index,
+//! so the supplied hashes should be uniformly distributed over the whole 64 bits.
+//! The hasher of the filter is still used to derive the alternative bucket index
+//! from a fingerprint.
+//! Callers that have 128 bits of hash per item can use the `*128` methods instead,
+//! which take the bucket index and the fingerprint from separate halves.
+//!
+//! # Examples
+//!
+//! ```
+//! use scalable_cuckoo_filter::raw::ScalableCuckooFilter;
+//!
+//! let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+//! assert!(!filter.contains(0x9E37_79B9_7F4A_7C15));
+//! filter.insert(0x9E37_79B9_7F4A_7C15);
+//! assert!(filter.contains(0x9E37_79B9_7F4A_7C15));
+//! ```
+use rand::Rng;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::alloc::Allocator;
+use crate::codec::{self, BitsEncoding};
+use crate::cuckoo_filter::{
+    AltIndexScheme, CuckooFilter, HashWidth, InsertionStrategy, StorageBackend,
+};
+use crate::delta;
+use crate::dump::{self, FilterDump};
+use crate::events::{EventListener, FilterEvent, Listener};
+use crate::growth::{DefaultGrowthPolicy, GrowthConfig, GrowthPolicy, LayerInfo, LayerSpec};
+use crate::hashers::{self, HasherKeys};
+use crate::integrity::IntegrityError;
+use crate::persist;
+use crate::reconcile;
+use crate::stats::{FilterCounters, FilterStats, StoredFingerprint};
+use crate::telemetry;
+use crate::{DefaultHasher, DefaultRng, ScalableCuckooFilterBuilder};
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[cfg(feature = "tokio")]
+use crate::async_io;
+
+/// Scalable Cuckoo Filter whose items are 64-bit hashes.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(bound(
+        serialize = "H: crate::HasherKeys",
+        deserialize = "H: Default + crate::HasherKeys, R: Default"
+    ))
+)]
+pub struct ScalableCuckooFilter<H = DefaultHasher, R = DefaultRng> {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, with = "crate::hashers::serde_keys")
+    )]
+    pub(crate) hasher: H,
+    pub(crate) filters: Vec<CuckooFilter>,
+    pub(crate) initial_capacity: usize,
+    pub(crate) false_positive_probability: f64,
+    #[cfg_attr(feature = "serde_support", serde(default = "default_tightening_ratio"))]
+    pub(crate) tightening_ratio: f64,
+    pub(crate) entries_per_bucket: usize,
+    pub(crate) max_kicks: usize,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) insertion_strategy: InsertionStrategy,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) storage_backend: StorageBackend,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) hash_width: HashWidth,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) alt_index_scheme: AltIndexScheme,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default = "default_power_of_two_buckets")
+    )]
+    pub(crate) power_of_two_buckets: bool,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) bucket_counts: Vec<usize>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) stash_capacity: usize,
+    #[cfg_attr(feature = "serde_support", serde(default = "default_stash_enabled"))]
+    pub(crate) stash_enabled: bool,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) grow_at_load_factor: Option<f64>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) pre_touch_pages: bool,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub(crate) transparent_hugepages: bool,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip, default = "default_growth_policy")
+    )]
+    pub(crate) growth_policy: Arc<dyn GrowthPolicy>,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub(crate) listener: Listener,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub(crate) metrics_label: Option<Arc<str>>,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub(crate) allocator: Allocator,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub(crate) rng: R,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub(crate) counters: FilterCounters,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub(crate) version: u64,
+}
+impl ScalableCuckooFilter {
+    /// Makes a new `ScalableCuckooFilter` instance.
+    ///
+    /// This is equivalent to the following expression:
+    ///
+    /// ```
+    /// # use scalable_cuckoo_filter::ScalableCuckooFilterBuilder;
+    /// # let initial_capacity = 10;
+    /// # let false_positive_probability = 0.1;
+    /// # let _ =
+    /// ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(initial_capacity)
+    ///     .false_positive_probability(false_positive_probability)
+    ///     .finish_raw()
+    /// # ;
+    /// ```
+    pub fn new(initial_capacity_hint: usize, false_positive_probability: f64) -> Self {
+        ScalableCuckooFilterBuilder::new()
+            .initial_capacity(initial_capacity_hint)
+            .false_positive_probability(false_positive_probability)
+            .finish_raw()
+    }
+}
+impl<H: Hasher + Clone, R: Rng> ScalableCuckooFilter<H, R> {
+    /// Returns the approximate number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.filters.iter().map(|f| f.len() as u64).sum()
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity (i.e., the upper bound of acceptable items count) of this filter.
+    pub fn capacity(&self) -> u64 {
+        self.filters.iter().map(|f| f.capacity() as u64).sum()
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.filters.iter().map(|f| f.bits()).sum()
+    }
+
+    /// Returns the number of bytes of memory used by this filter.
+    ///
+    /// Unlike [`bits`], this counts the actual allocations: the bit arrays rounded up to
+    /// whole words, the spare capacity of the stashes and of the layer list,
+    /// the bookkeeping of each layer, and the filter struct itself.
+    /// The growth policy, the event listener, and the allocator aren't counted.
+    ///
+    /// [`bits`]: ScalableCuckooFilter::bits
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>()
+            + mem::size_of::<CuckooFilter>() * self.filters.capacity()
+            + self.filters.iter().map(|f| f.heap_bytes()).sum::<usize>()
+            + mem::size_of::<usize>() * self.bucket_counts.capacity()
+    }
+
+    /// Returns information about the layers (i.e., internal fixed size cuckoo filters) of this filter.
+    pub fn layers(&self) -> Vec<LayerInfo> {
+        self.filters.iter().map(LayerInfo::new).collect()
+    }
+
+    /// Returns an iterator over the entries stored in this filter.
+    ///
+    /// Layers are visited from the oldest one. The entries of a layer are yielded in the order of
+    /// their buckets, followed by the entries in the stash of the layer (not necessarily in order).
+    pub fn fingerprints(&self) -> impl Iterator<Item = StoredFingerprint> + '_ {
+        self.filters.iter().enumerate().flat_map(|(layer, f)| {
+            f.stored_entries().map(
+                move |(bucket_index, fingerprint, stashed)| StoredFingerprint {
+                    layer,
+                    bucket_index,
+                    fingerprint,
+                    stashed,
+                },
+            )
+        })
+    }
+
+    /// Returns the ratio of the occupied bucket entries to the total bucket entries of this filter.
+    pub fn fill_ratio(&self) -> f64 {
+        let occupied = self
+            .filters
+            .iter()
+            .map(|f| f.occupied_entries())
+            .sum::<usize>();
+        let entries = self.filters.iter().map(|f| f.entries()).sum::<usize>();
+        occupied as f64 / entries as f64
+    }
+
+    /// Returns the upper bound of the false positive probability guaranteed by the current layers.
+    ///
+    /// This exceeds the configured false positive probability once the fingerprints of
+    /// later layers are clamped (see [`FilterEvent::FingerprintClamped`]).
+    pub fn false_positive_probability_bound(&self) -> f64 {
+        self.filters
+            .iter()
+            .map(|f| f.false_positive_probability_bound())
+            .sum()
+    }
+
+    /// Returns the estimated false positive probability of this filter in its current state.
+    pub fn estimated_false_positive_probability(&self) -> f64 {
+        let true_negative = self
+            .filters
+            .iter()
+            .map(|f| 1.0 - f.estimated_false_positive_probability())
+            .product::<f64>();
+        1.0 - true_negative
+    }
+
+    /// Sets the listener notified of the growth and stash events of this filter.
+    pub fn set_event_listener<L: EventListener + 'static>(&mut self, listener: L) {
+        self.listener = Listener::new(listener);
+    }
+
+    /// Returns the version of this filter, which is incremented by every modification.
+    ///
+    /// See [`delta_since`] for its use.
+    ///
+    /// [`delta_since`]: ScalableCuckooFilter::delta_since
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the cumulative counters of the operations performed on this filter.
+    pub fn counters(&self) -> FilterCounters {
+        let mut counters = self.counters;
+        for f in &self.filters {
+            counters.merge(f.counters());
+        }
+        counters
+    }
+
+    /// Validates the internal invariants of this filter.
+    pub fn verify_integrity(&self) -> Result<(), IntegrityError> {
+        if self.filters.is_empty() {
+            return Err(IntegrityError::NoLayers);
+        }
+        for (layer, f) in self.filters.iter().enumerate() {
+            f.verify_integrity(
+                &self.hasher,
+                layer,
+                self.entries_per_bucket,
+                self.hash_width,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the statistics of this filter.
+    pub fn stats(&self) -> FilterStats {
+        FilterStats::new(self.layers())
+    }
+
+    /// Returns the false positive probability.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /// Returns the tightening ratio of the false positive probability.
+    pub fn tightening_ratio(&self) -> f64 {
+        self.tightening_ratio
+    }
+
+    /// Returns the number of elements in each buckets.
+    pub fn entries_per_bucket(&self) -> usize {
+        self.entries_per_bucket
+    }
+
+    /// Returns the number of kicks before the filter grows.
+    pub fn max_kicks(&self) -> usize {
+        self.max_kicks
+    }
+
+    /// Returns the strategy for relocating entries.
+    pub fn insertion_strategy(&self) -> InsertionStrategy {
+        self.insertion_strategy
+    }
+
+    /// Returns the layout of the fingerprints of each layer.
+    pub fn storage_backend(&self) -> StorageBackend {
+        self.storage_backend
+    }
+
+    /// Returns the width of the item hashes the typed filter derives from items.
+    ///
+    /// This filter itself takes item hashes from the caller, so it only affects
+    /// the typed wrapper and the `*128` methods are available regardless of it.
+    pub fn hash_width(&self) -> HashWidth {
+        self.hash_width
+    }
+
+    /// Returns the scheme for deriving alternative bucket indices.
+    pub fn alt_index_scheme(&self) -> AltIndexScheme {
+        self.alt_index_scheme
+    }
+
+    /// Returns `true` if the numbers of buckets of new layers are rounded up to powers of two,
+    /// otherwise `false`.
+    pub fn power_of_two_buckets(&self) -> bool {
+        self.power_of_two_buckets
+    }
+
+    /// Returns the numbers of buckets of the first layers given by
+    /// [`ScalableCuckooFilterBuilder::bucket_counts`].
+    pub fn bucket_counts(&self) -> &[usize] {
+        &self.bucket_counts
+    }
+
+    /// Returns the number of kicked out entries the last layer can hold without growing.
+    pub fn stash_capacity(&self) -> usize {
+        self.stash_capacity
+    }
+
+    /// Returns `true` if kicked out entries are kept in stashes, otherwise `false`.
+    pub fn is_stash_enabled(&self) -> bool {
+        self.stash_enabled
+    }
+
+    /// Returns the load factor at which this filter grows proactively, if configured.
+    pub fn grow_at_load_factor(&self) -> Option<f64> {
+        self.grow_at_load_factor
+    }
+
+    /// Returns a copy of this filter that uses `rng` as the random number generator.
+    pub fn clone_with_rng<R2: Rng>(&self, rng: R2) -> ScalableCuckooFilter<H, R2> {
+        ScalableCuckooFilter {
+            hasher: self.hasher.clone(),
+            filters: self.filters.clone(),
+            initial_capacity: self.initial_capacity,
+            false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
+            entries_per_bucket: self.entries_per_bucket,
+            max_kicks: self.max_kicks,
+            insertion_strategy: self.insertion_strategy,
+            storage_backend: self.storage_backend,
+            hash_width: self.hash_width,
+            alt_index_scheme: self.alt_index_scheme,
+            power_of_two_buckets: self.power_of_two_buckets,
+            bucket_counts: self.bucket_counts.clone(),
+            stash_capacity: self.stash_capacity,
+            stash_enabled: self.stash_enabled,
+            grow_at_load_factor: self.grow_at_load_factor,
+            pre_touch_pages: self.pre_touch_pages,
+            transparent_hugepages: self.transparent_hugepages,
+            growth_policy: Arc::clone(&self.growth_policy),
+            listener: self.listener.clone(),
+            metrics_label: self.metrics_label.clone(),
+            allocator: self.allocator.clone(),
+            rng,
+            counters: self.counters,
+            version: self.version,
+        }
+    }
+
+    /// Returns `true` if this filter may contain the item whose hash is `item_hash`,
+    /// otherwise `false`.
+    pub fn contains(&self, item_hash: u64) -> bool {
+        self.contains128(crate::widen(item_hash))
+    }
+
+    /// Returns `true` if this filter may contain the item whose 128-bit hash is `item_hash`,
+    /// otherwise `false`.
+    ///
+    /// See [`insert128`] for how `item_hash` is used.
+    ///
+    /// [`insert128`]: ScalableCuckooFilter::insert128
+    pub fn contains128(&self, item_hash: u128) -> bool {
+        self.filters
+            .iter()
+            .any(|f| f.contains(&self.hasher, item_hash))
+    }
+
+    /// Inserts the item whose hash is `item_hash` into this filter.
+    ///
+    /// If the current filter becomes full, it will be expanded automatically.
+    pub fn insert(&mut self, item_hash: u64) {
+        self.insert128(crate::widen(item_hash));
+    }
+
+    /// Inserts the item whose 128-bit hash is `item_hash` into this filter.
+    ///
+    /// The lower 64 bits of `item_hash` are used for the bucket index and the upper 64 bits
+    /// for the fingerprint, so that they don't overlap however large the filter grows.
+    /// `insert(h)` is the same as `insert128` with `h` in both halves.
+    pub fn insert128(&mut self, item_hash: u128) {
+        let version = self.next_version();
+        let mut last = self.filters.len() - 1;
+        self.filters[last].set_version(version);
+        let mut kicks = 0;
+        loop {
+            let before = *self.filters[last].counters();
+            let inserted = self.filters[last].insert(
+                &self.hasher,
+                &mut self.rng,
+                item_hash,
+                self.stash_enabled,
+            );
+            let after = self.filters[last].counters();
+            kicks += after.kicks - before.kicks;
+            if self.listener.is_some() && after.stash_insertions != before.stash_insertions {
+                let layer = LayerInfo::new(&self.filters[last]);
+                self.listener.notify(&FilterEvent::Stash(&layer));
+            }
+            if inserted {
+                break;
+            }
+            self.grow();
+            last += 1;
+        }
+        telemetry::record_insert(kicks);
+        if self.is_last_layer_full() {
+            self.grow();
+        }
+    }
+
+    /// Removes the item whose hash is `item_hash` from this filter.
+    ///
+    /// This method returns `true` if an entry with the same fingerprint has been removed,
+    /// otherwise it returns `false`.
+    pub fn remove(&mut self, item_hash: u64) -> bool {
+        self.remove128(crate::widen(item_hash))
+    }
+
+    /// Removes the item whose 128-bit hash is `item_hash` from this filter.
+    ///
+    /// See [`insert128`] for how `item_hash` is used.
+    ///
+    /// [`insert128`]: ScalableCuckooFilter::insert128
+    pub fn remove128(&mut self, item_hash: u128) -> bool {
+        for filter in &mut self.filters {
+            filter.set_version(self.version + 1);
+            let removed = filter.remove(&self.hasher, item_hash);
+            if removed {
+                self.version += 1;
+                telemetry::record_remove(true);
+                return true;
+            }
+        }
+        self.counters.failed_removes += 1;
+        telemetry::record_remove(false);
+        false
+    }
+
+    /// Inserts the items whose hashes are `hashes` using multiple threads.
+    ///
+    /// This is available only if the `rayon` feature is enabled.
+    #[cfg(feature = "rayon")]
+    pub fn par_extend(&mut self, hashes: Vec<u64>)
+    where
+        H: Sync,
+    {
+        self.par_extend128(hashes.into_iter().map(crate::widen).collect());
+    }
+
+    /// Inserts the items whose 128-bit hashes are `hashes` using multiple threads.
+    ///
+    /// This is available only if the `rayon` feature is enabled.
+    #[cfg(feature = "rayon")]
+    pub fn par_extend128(&mut self, hashes: Vec<u128>)
+    where
+        H: Sync,
+    {
+        let count = hashes.len();
+        let version = self.next_version();
+        self.reserve(count);
+
+        let last = self.filters.len() - 1;
+        self.filters[last].set_version(version);
+        let leftovers = self.filters[last].par_insert(&self.hasher, hashes);
+        telemetry::record_inserts((count - leftovers.len()) as u64);
+        for item_hash in leftovers {
+            self.insert128(item_hash);
+        }
+        if self.is_last_layer_full() {
+            self.grow();
+        }
+    }
+
+    /// Shrinks the capacity of this filter as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        let version = self.next_version();
+        for f in &mut self.filters {
+            f.set_version(version);
+            f.shrink_to_fit(&self.hasher, &mut self.rng, self.stash_enabled);
+        }
+        telemetry::record_bits(self.metrics_label.as_deref(), self.bits());
+    }
+
+    /// Reserves capacity for at least `additional` more items to be inserted without growing
+    /// more than once.
+    pub fn reserve(&mut self, additional: usize) {
+        let last = &self.filters[self.filters.len() - 1];
+        if additional <= last.free_entries() {
+            return;
+        }
+
+        let mut spec = self.next_layer_spec();
+        spec.capacity = spec.capacity.max(additional);
+        self.push_layer(spec);
+    }
+
+    /// Moves the entries kicked out to the stashes back into buckets if there are free entries.
+    pub fn defragment(&mut self) {
+        let version = self.next_version();
+        for f in &mut self.filters {
+            f.set_version(version);
+            f.defragment(&self.hasher);
+        }
+    }
+
+    /// Writes this filter to `writer` without building an intermediate buffer or using serde.
+    ///
+    /// The output is in the same versioned format as [`to_bytes`].
+    /// The bit arrays are written in fixed-size chunks as they are, so `writer` should be
+    /// buffered (e.g., by [`BufWriter`]) if it is a file or a socket.
+    /// As with serde, the RNG, the growth policy, the event listener, the allocator,
+    /// and the counters aren't written. Neither is the hasher, except for its keys
+    /// (see [`HasherKeys`]), which are restored by [`read_from`] so that a randomly keyed filter
+    /// stays queryable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` fails, or if the alternative index scheme is
+    /// [`AltIndexScheme::Custom`] (whose functions can't be written).
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    /// [`BufWriter`]: std::io::BufWriter
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        self.write_encoded_to(writer, BitsEncoding::Raw)
+    }
+
+    /// Writes this filter to `writer` like [`write_to`], but compactly if it is sparse.
+    ///
+    /// Long runs of empty buckets (e.g., in a layer just added by a growth) are written as their
+    /// lengths, so the output of a mostly empty filter is much smaller than that of
+    /// [`write_to`]. It is read by [`read_from`] and [`from_bytes`] as usual,
+    /// but can't be viewed in place by [`ScalableCuckooFilterView`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` fails, or if the alternative index scheme is
+    /// [`AltIndexScheme::Custom`].
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    /// [`from_bytes`]: ScalableCuckooFilter::from_bytes
+    /// [`ScalableCuckooFilterView`]: crate::ScalableCuckooFilterView
+    pub fn write_compressed_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        self.write_encoded_to(writer, BitsEncoding::ZeroRuns)
+    }
+
+    /// Writes this filter to `writer` like [`write_to`], but asynchronously.
+    ///
+    /// The bit arrays are converted and written in chunks of 1 MiB, and the task yields to
+    /// the runtime after each chunk, so writing a large filter neither blocks the runtime for
+    /// long nor needs [`spawn_blocking`] or an intermediate buffer of the whole filter.
+    /// `writer` is flushed at the end.
+    ///
+    /// This is available only if the `tokio` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` fails, or if the alternative index scheme is
+    /// [`AltIndexScheme::Custom`].
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    /// [`spawn_blocking`]: https://docs.rs/tokio/latest/tokio/task/fn.spawn_blocking.html
+    #[cfg(feature = "tokio")]
+    pub async fn write_to_async<W: AsyncWrite + Unpin>(&self, writer: W) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        async_io::write(self, writer).await
+    }
+
+    fn write_encoded_to<W: Write>(&self, mut writer: W, encoding: BitsEncoding) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        let w = &mut codec::ChecksumWriter::new(&mut writer);
+        self.write_params_to(w, encoding)?;
+        for f in &self.filters {
+            f.write_to(w, encoding)?;
+        }
+        w.finish()
+    }
+
+    /// Writes the part of `write_to` preceding the layers.
+    pub(crate) fn write_params_to<W: Write>(
+        &self,
+        w: &mut W,
+        encoding: BitsEncoding,
+    ) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        let alt_index_scheme = self.alt_index_scheme.tag()?;
+        codec::write_header(w)?;
+        codec::write_u8(w, encoding.tag())?;
+        codec::write_hasher_keys(w, self.hasher.keys())?;
+        codec::write_u64(w, self.version)?;
+        codec::write_usize(w, self.initial_capacity)?;
+        codec::write_f64(w, self.false_positive_probability)?;
+        codec::write_f64(w, self.tightening_ratio)?;
+        codec::write_usize(w, self.entries_per_bucket)?;
+        codec::write_usize(w, self.max_kicks)?;
+        codec::write_u8(w, self.insertion_strategy.tag())?;
+        codec::write_u8(w, self.storage_backend.tag())?;
+        codec::write_u8(w, self.hash_width.tag())?;
+        codec::write_u8(w, alt_index_scheme)?;
+        codec::write_bool(w, self.power_of_two_buckets)?;
+        codec::write_usize(w, self.bucket_counts.len())?;
+        for &count in &self.bucket_counts {
+            codec::write_usize(w, count)?;
+        }
+        codec::write_usize(w, self.stash_capacity)?;
+        codec::write_bool(w, self.stash_enabled)?;
+        codec::write_bool(w, self.grow_at_load_factor.is_some())?;
+        codec::write_f64(w, self.grow_at_load_factor.unwrap_or(0.0))?;
+        codec::write_bool(w, self.pre_touch_pages)?;
+        codec::write_bool(w, self.transparent_hugepages)?;
+        codec::write_usize(w, self.filters.len())
+    }
+
+    /// Serializes this filter into a versioned binary format without using serde.
+    ///
+    /// The bytes start with the magic `b"SCKF"` and a format version, followed by the parameters
+    /// of the filter, the contents of each layer, and a CRC-32 checksum of all of them. Bytes written by this version of the crate
+    /// can be read by [`from_bytes`] of any later version; changes of the format only ever
+    /// add new versions. The hasher isn't included except for its keys, as with [`write_to`].
+    ///
+    /// The format is the same on every platform (all integers are little-endian), and so is
+    /// the mapping from items to buckets: integers written by `Hash` implementations are
+    /// passed to the hasher as on a little-endian 64-bit target. So a filter built on x86_64
+    /// can be used on aarch64 or big-endian targets as long as the hasher is keyed the same way
+    /// there and hashes bytes portably, as SipHash (the default without `ahash` or `xxhash`) does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`from_bytes`]: ScalableCuckooFilter::from_bytes
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>>
+    where
+        H: HasherKeys,
+    {
+        let mut bytes = Vec::with_capacity(self.memory_usage());
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Serializes this filter like [`to_bytes`], but compactly if it is sparse.
+    ///
+    /// See [`write_compressed_to`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`write_compressed_to`]: ScalableCuckooFilter::write_compressed_to
+    pub fn to_compressed_bytes(&self) -> io::Result<Vec<u8>>
+    where
+        H: HasherKeys,
+    {
+        let mut bytes = Vec::new();
+        self.write_compressed_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Saves this filter to the file at `path` in the format of [`write_to`].
+    ///
+    /// The file is replaced atomically: the filter is written to a temporary file in the same
+    /// directory, which is synced to the disk and then renamed to `path`. So readers of `path`
+    /// see either the old or the new filter, never a partially written one, even on a crash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing or renaming the file fails, or if the alternative index
+    /// scheme is [`AltIndexScheme::Custom`]. The existing file is left as it is then.
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        persist::save_atomically(path.as_ref(), |w| self.write_to(w))
+    }
+
+    /// Serializes the modifications made to this filter since it was at `baseline_version`
+    /// (a [`version`] returned earlier) into a delta, which [`apply_delta`] applies to a copy
+    /// of the filter at `baseline_version` (or later) to bring it to the current version.
+    ///
+    /// A delta holds the pages (4 KiB each) of the bit arrays written after the baseline,
+    /// as well as the item counts and stashes, so it is much smaller than [`to_bytes`] if
+    /// the filter has changed little. Layers added after the baseline (and those rebuilt by
+    /// [`shrink_to_fit`]) are included whole.
+    ///
+    /// The version is kept by [`to_bytes`] and [`write_to`] but not by serde, and a filter
+    /// read from bytes treats all of its pages as written at the version it was read at.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `baseline_version` is newer than the current version, or if
+    /// a layer is written whole and the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`version`]: ScalableCuckooFilter::version
+    /// [`apply_delta`]: ScalableCuckooFilter::apply_delta
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    /// [`shrink_to_fit`]: ScalableCuckooFilter::shrink_to_fit
+    pub fn delta_since(&self, baseline_version: u64) -> io::Result<Vec<u8>> {
+        delta::write(self, baseline_version)
+    }
+
+    /// Applies a delta made by [`delta_since`], bringing this filter to its version.
+    ///
+    /// The version of this filter must be between the baseline and the version of the delta.
+    /// Nothing is changed if the delta doesn't apply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `delta` is malformed, or if it doesn't apply to this filter
+    /// (because of the version or the shape of the layers).
+    ///
+    /// [`delta_since`]: ScalableCuckooFilter::delta_since
+    pub fn apply_delta(&mut self, delta: &[u8]) -> io::Result<()> {
+        delta::apply(self, delta)
+    }
+
+    /// Returns a summary of the entries of this filter for [`entries_missing_from`] of
+    /// a replica, the first step of reconciling replicas that have been modified independently.
+    ///
+    /// The summary holds a digest (8 bytes) per 64 buckets of each layer, which is about 2% of
+    /// the size of a layer with 4 entries per bucket and 12-bit fingerprints.
+    ///
+    /// Replicas are reconciled in both directions as follows, after which both contain
+    /// the union of their items:
+    ///
+    /// 1. `a` sends `a.reconciliation_summary()` to `b`.
+    /// 2. `b` sends `b.entries_missing_from(&summary)` back to `a`.
+    /// 3. `a` calls `a.merge_entries(&entries)`.
+    /// 4. The same is repeated with `a` and `b` swapped.
+    ///
+    /// Only the entries of the buckets whose digests differ are sent, and only those that
+    /// the receiving replica lacks are merged. Removals aren't propagated, so an item removed
+    /// from only one of the replicas comes back (use [`delta_since`] to replicate removals).
+    ///
+    /// Replicas must be built with the same hasher and configuration, and an item inserted into
+    /// both must be in the same layer (as is the case with [`DefaultGrowthPolicy`]) to be
+    /// recognized as the same item. An item in different layers of the replicas is copied to
+    /// both layers, which wastes entries but doesn't affect lookups.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`entries_missing_from`]: ScalableCuckooFilter::entries_missing_from
+    /// [`delta_since`]: ScalableCuckooFilter::delta_since
+    pub fn reconciliation_summary(&self) -> io::Result<Vec<u8>> {
+        reconcile::write_summary(self)
+    }
+
+    /// Returns the entries of this filter that may be missing from the replica that made
+    /// `peer_summary` (see [`reconciliation_summary`]), for [`merge_entries`] of the replica.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `peer_summary` is malformed, or if the replica has a different
+    /// hasher or configuration or its layers don't match those of this filter.
+    ///
+    /// [`reconciliation_summary`]: ScalableCuckooFilter::reconciliation_summary
+    /// [`merge_entries`]: ScalableCuckooFilter::merge_entries
+    pub fn entries_missing_from(&self, peer_summary: &[u8]) -> io::Result<Vec<u8>> {
+        reconcile::write_entries_missing_from(self, peer_summary)
+    }
+
+    /// Inserts the entries returned by [`entries_missing_from`] of a replica that this filter
+    /// doesn't have, returning the number of inserted entries.
+    ///
+    /// Layers that the replica has and this filter doesn't are added first. Entries are
+    /// inserted into the layers they are in in the replica, and are stashed if they don't fit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `entries` is malformed, or if the replica has a different hasher
+    /// or configuration or its layers don't match those of this filter.
+    /// This filter is left as it is then, including its version and counters, although
+    /// the event listener has been notified of the layers added and dropped again.
+    ///
+    /// [`entries_missing_from`]: ScalableCuckooFilter::entries_missing_from
+    pub fn merge_entries(&mut self, entries: &[u8]) -> io::Result<usize> {
+        reconcile::merge_entries(self, entries)
+    }
+
+    /// Inserts the entries of `other` that this filter doesn't have, so that this filter contains
+    /// the union of the items of both, returning the number of inserted entries.
+    ///
+    /// This is the same as reconciling this filter with `other` in one direction
+    /// (see [`reconciliation_summary`]), and the same requirements apply: `other` must be built
+    /// with the same hasher and configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `other` has a different hasher or configuration or its layers don't
+    /// match those of this filter, or if the alternative index scheme is [`AltIndexScheme::Custom`].
+    /// This filter is left as it is then.
+    ///
+    /// [`reconciliation_summary`]: ScalableCuckooFilter::reconciliation_summary
+    pub fn union_with(&mut self, other: &Self) -> io::Result<usize> {
+        let entries = other.entries_missing_from(&self.reconciliation_summary()?)?;
+        self.merge_entries(&entries)
+    }
+
+    /// Returns the entries of this filter with the parameters of its layers, which can be
+    /// modified and then given to [`restore`] or [`ScalableCuckooFilterBuilder::build_raw_from_dump`].
+    ///
+    /// Unlike the serialized forms of the filter, the dump holds only the fingerprints of the items
+    /// and the bucket indices they are derived from (see [`LayerDump::entries`]), which don't
+    /// depend on where the entries have been placed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`restore`]: ScalableCuckooFilter::restore
+    pub fn dump(&self) -> io::Result<FilterDump> {
+        dump::dump(self)
+    }
+
+    /// Replaces the entries of this filter with those of `dump` made by [`dump`].
+    ///
+    /// The layers are rebuilt as this filter grows, and the entries are placed anew.
+    /// So this filter must be built with the same hasher and configuration as the dumped one
+    /// (including the growth policy), and an equivalent filter is rebuilt.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this filter has a different hasher or configuration, if the layers of
+    /// `dump` don't match those made by this filter, if an entry is out of the range of its layer,
+    /// or if the alternative index scheme is [`AltIndexScheme::Custom`].
+    /// This filter is left as it is then.
+    ///
+    /// [`dump`]: ScalableCuckooFilter::dump
+    pub fn restore(&mut self, dump: &FilterDump) -> io::Result<()> {
+        dump::restore(self, dump)
+    }
+
+    pub(crate) fn rebuild(&mut self, dump: &FilterDump) -> io::Result<()> {
+        dump::rebuild(self, dump)
+    }
+
+    #[cfg(feature = "mmap")]
+    pub(crate) fn layers_ref(&self) -> &[CuckooFilter] {
+        &self.filters
+    }
+
+    pub(crate) fn bucket_index(&self, item_hash: u128) -> usize {
+        self.filters[self.filters.len() - 1].bucket_index(item_hash)
+    }
+
+    pub(crate) fn grow(&mut self) {
+        let spec = self.next_layer_spec();
+        self.push_layer(spec);
+    }
+
+    pub(crate) fn fingerprint_bitwidth(&self, probability: f64) -> usize {
+        // Fingerprints are taken from 64-bit (or 32-bit) hashes, so they can't be any longer.
+        required_fingerprint_bitwidth(self.entries_per_bucket, probability)
+            .min(self.hash_width.max_fingerprint_bitwidth())
+    }
+
+    /// Increments the version for a modification, returning the new one.
+    pub(crate) fn next_version(&mut self) -> u64 {
+        self.version += 1;
+        self.version
+    }
+
+    fn is_last_layer_full(&self) -> bool {
+        let last = &self.filters[self.filters.len() - 1];
+        last.kicked_out_entries() > self.stash_capacity
+            || self
+                .grow_at_load_factor
+                .is_some_and(|load_factor| last.fill_ratio() >= load_factor)
+    }
+
+    fn next_layer_spec(&self) -> LayerSpec {
+        let layers = self.layers();
+        let config = GrowthConfig {
+            initial_capacity: self.initial_capacity,
+            false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
+        };
+        let len = usize::try_from(self.len()).unwrap_or(usize::MAX);
+        self.growth_policy.next_layer(&layers, len, &config)
+    }
+
+    /// Returns the number of buckets of the next layer if it is given by `bucket_counts`.
+    ///
+    /// Layers beyond the given counts have twice as many buckets as their previous layers.
+    fn scheduled_bucket_count(&self) -> Option<usize> {
+        let (&last, _) = self.bucket_counts.split_last()?;
+        let layer = self.filters.len();
+        self.bucket_counts.get(layer).copied().or_else(|| {
+            let doublings = u32::try_from(layer + 1 - self.bucket_counts.len()).ok()?;
+            last.checked_mul(2usize.checked_pow(doublings)?)
+        })
+    }
+
+    /// Makes a layer at the current version, which isn't added to this filter.
+    pub(crate) fn new_layer(
+        &self,
+        fingerprint_bitwidth: usize,
+        capacity: usize,
+        power_of_two_buckets: bool,
+    ) -> CuckooFilter {
+        let mut filter = CuckooFilter::new(
+            fingerprint_bitwidth,
+            self.entries_per_bucket,
+            capacity,
+            self.max_kicks,
+            self.insertion_strategy,
+            self.storage_backend,
+            self.alt_index_scheme,
+            power_of_two_buckets,
+            self.allocator
+                .clone()
+                .with_pages(self.pre_touch_pages, self.transparent_hugepages),
+        );
+        filter.bit_array_mut().reset_versions(self.version);
+        filter
+    }
+
+    fn push_layer(&mut self, spec: LayerSpec) {
+        if !self.filters.is_empty() {
+            self.counters.growths += 1;
+            self.version += 1;
+            telemetry::record_growth();
+        }
+        let (capacity, power_of_two_buckets) = match self.scheduled_bucket_count() {
+            Some(buckets) => (
+                buckets.saturating_mul(self.entries_per_bucket),
+                buckets.is_power_of_two(),
+            ),
+            None => (spec.capacity, self.power_of_two_buckets),
+        };
+
+        // Each layer may use at most `1 - tightening_ratio` of the remaining error budget,
+        // so the sum of the per-layer bounds stays under `false_positive_probability`
+        // regardless of the growth policy, until the fingerprints of a layer are clamped.
+        // A clamped layer exceeds its budget, and so does the sum eventually.
+        let remaining =
+            (self.false_positive_probability - self.false_positive_probability_bound()).max(0.0);
+        let probability = spec
+            .false_positive_probability
+            .min(remaining * (1.0 - self.tightening_ratio));
+
+        let clamped = required_fingerprint_bitwidth(self.entries_per_bucket, probability)
+            > self.hash_width.max_fingerprint_bitwidth();
+        let filter = self.new_layer(
+            self.fingerprint_bitwidth(probability),
+            capacity,
+            power_of_two_buckets,
+        );
+        self.filters.push(filter);
+        telemetry::record_bits(self.metrics_label.as_deref(), self.bits());
+        if (self.filters.len() > 1 || clamped) && self.listener.is_some() {
+            let layer = LayerInfo::new(&self.filters[self.filters.len() - 1]);
+            if self.filters.len() > 1 {
+                self.listener.notify(&FilterEvent::Grow(&layer));
+            }
+            if clamped {
+                self.listener
+                    .notify(&FilterEvent::FingerprintClamped(&layer));
+            }
+        }
+    }
+}
+/// Returns the fingerprint bitwidth that bounds the false positive probability of a layer
+/// by `probability`, which may exceed 64.
+pub(crate) fn required_fingerprint_bitwidth(entries_per_bucket: usize, probability: f64) -> usize {
+    // A lookup compares the fingerprint with at most `2 * entries_per_bucket` entries,
+    // so the false positive probability of a layer is bounded by
+    // `2 * entries_per_bucket / 2^fingerprint_bitwidth` (the union bound).
+    let compared_entries = (2 * entries_per_bucket) as f64;
+    let mut fingerprint_bitwidth = 1;
+    while compared_entries / 2f64.powi(fingerprint_bitwidth as i32) > probability {
+        fingerprint_bitwidth += 1;
+    }
+    fingerprint_bitwidth
+}
+
+/// Parameters of a serialized filter, which precede its layers.
+pub(crate) struct Params {
+    pub encoding: BitsEncoding,
+    hasher_keys: Option<(u64, u64)>,
+    filter_version: u64,
+    initial_capacity: usize,
+    false_positive_probability: f64,
+    tightening_ratio: f64,
+    entries_per_bucket: usize,
+    max_kicks: usize,
+    insertion_strategy: InsertionStrategy,
+    storage_backend: StorageBackend,
+    hash_width: HashWidth,
+    alt_index_scheme: AltIndexScheme,
+    power_of_two_buckets: bool,
+    bucket_counts: Vec<usize>,
+    stash_capacity: usize,
+    stash_enabled: bool,
+    grow_at_load_factor: Option<f64>,
+    pre_touch_pages: bool,
+    transparent_hugepages: bool,
+    /// Number of the layers.
+    pub layers: usize,
+}
+
+impl<H: Hasher + Clone, R: Rng> ScalableCuckooFilter<H, R> {
+    /// Reads a filter written by [`write_to`] (or [`to_bytes`]) from `reader`,
+    /// giving it `hasher` and `rng`.
+    ///
+    /// `hasher` must hash items as the hasher of the written filter did (e.g., be keyed the same),
+    /// otherwise lookups of the read filter give wrong answers. The keys recorded by
+    /// [`write_to`] are ignored.
+    /// The growth policy is the default one.
+    ///
+    /// The layers are read in fixed-size chunks, so `reader` should be buffered
+    /// (e.g., by [`BufReader`]) if it is a file or a socket.
+    ///
+    /// The bit arrays are allocated with the sizes declared in the input,
+    /// so only trusted input should be read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    /// Corrupted input is reported as an [`InvalidData`] error mentioning the checksum mismatch.
+    ///
+    /// [`InvalidData`]: io::ErrorKind::InvalidData
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`BufReader`]: std::io::BufReader
+    pub fn read_from_with<Rd: Read>(reader: Rd, hasher: H, rng: R) -> io::Result<Self> {
+        Self::read_with_hasher(reader, |_| hasher, rng)
+    }
+
+    /// Reads a filter from `reader` like [`read_from_with`], but asynchronously.
+    ///
+    /// The bit arrays are read and converted in chunks of 1 MiB, and the task yields to the
+    /// runtime after each chunk. The other parts are read in small pieces, so `reader` should be
+    /// buffered (e.g., by tokio's `BufReader`) if it is a file or a socket.
+    /// No bytes after the filter are read from `reader`.
+    ///
+    /// This is available only if the `tokio` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`read_from_with`]: ScalableCuckooFilter::read_from_with
+    #[cfg(feature = "tokio")]
+    pub async fn read_from_with_async<Rd: AsyncRead + Unpin>(
+        reader: Rd,
+        hasher: H,
+        rng: R,
+    ) -> io::Result<Self> {
+        async_io::read(reader, |_| hasher, rng).await
+    }
+
+    /// Reads a filter, making its hasher from the recorded keys by `make_hasher`.
+    fn read_with_hasher<Rd: Read>(
+        mut reader: Rd,
+        make_hasher: impl FnOnce(Option<(u64, u64)>) -> H,
+        rng: R,
+    ) -> io::Result<Self> {
+        let r = &mut codec::ChecksumReader::new(&mut reader);
+        let params = Self::read_params(r)?;
+        let filters = (0..params.layers)
+            .map(|_| CuckooFilter::read_from(r, params.encoding))
+            .collect::<io::Result<Vec<_>>>()?;
+        r.verify()?;
+        Self::from_params(params, filters, make_hasher, rng)
+    }
+
+    /// Reads the part written by `write_params_to`.
+    pub(crate) fn read_params<Rd: Read>(r: &mut Rd) -> io::Result<Params> {
+        codec::read_header(r)?;
+        let encoding = BitsEncoding::read_from(r)?;
+        let hasher_keys = codec::read_hasher_keys(r)?;
+        let filter_version = codec::read_u64(r)?;
+        let initial_capacity = codec::read_usize(r)?;
+        let false_positive_probability = codec::read_f64(r)?;
+        let tightening_ratio = codec::read_f64(r)?;
+        let entries_per_bucket = codec::read_usize(r)?;
+        let max_kicks = codec::read_usize(r)?;
+        let insertion_strategy = InsertionStrategy::from_tag(codec::read_u8(r)?)
+            .ok_or_else(|| codec::invalid_data("unknown insertion strategy"))?;
+        let storage_backend = StorageBackend::from_tag(codec::read_u8(r)?)
+            .ok_or_else(|| codec::invalid_data("unknown storage backend"))?;
+        let hash_width = HashWidth::from_tag(codec::read_u8(r)?)
+            .ok_or_else(|| codec::invalid_data("unknown hash width"))?;
+        let alt_index_scheme = AltIndexScheme::from_tag(codec::read_u8(r)?)
+            .ok_or_else(|| codec::invalid_data("unknown alternative index scheme"))?;
+        let power_of_two_buckets = codec::read_bool(r)?;
+        let bucket_counts = (0..codec::read_usize(r)?)
+            .map(|_| codec::read_usize(r))
+            .collect::<io::Result<Vec<_>>>()?;
+        let stash_capacity = codec::read_usize(r)?;
+        let stash_enabled = codec::read_bool(r)?;
+        let has_load_factor = codec::read_bool(r)?;
+        let load_factor = codec::read_f64(r)?;
+        let pre_touch_pages = codec::read_bool(r)?;
+        let transparent_hugepages = codec::read_bool(r)?;
+        let layers = codec::read_usize(r)?;
+        Ok(Params {
+            encoding,
+            hasher_keys,
+            filter_version,
+            initial_capacity,
+            false_positive_probability,
+            tightening_ratio,
+            entries_per_bucket,
+            max_kicks,
+            insertion_strategy,
+            storage_backend,
+            hash_width,
+            alt_index_scheme,
+            power_of_two_buckets,
+            bucket_counts,
+            stash_capacity,
+            stash_enabled,
+            grow_at_load_factor: has_load_factor.then_some(load_factor),
+            pre_touch_pages,
+            transparent_hugepages,
+            layers,
+        })
+    }
+
+    /// Makes a filter from the parameters read by `read_params` and the layers that followed.
+    pub(crate) fn from_params(
+        params: Params,
+        mut filters: Vec<CuckooFilter>,
+        make_hasher: impl FnOnce(Option<(u64, u64)>) -> H,
+        rng: R,
+    ) -> io::Result<Self> {
+        for f in &mut filters {
+            f.bit_array_mut().reset_versions(params.filter_version);
+        }
+        if filters.is_empty() || params.entries_per_bucket == 0 {
+            return Err(codec::invalid_data("invalid filter parameters"));
+        }
+        Ok(ScalableCuckooFilter {
+            hasher: make_hasher(params.hasher_keys),
+            filters,
+            initial_capacity: params.initial_capacity,
+            false_positive_probability: params.false_positive_probability,
+            tightening_ratio: params.tightening_ratio,
+            entries_per_bucket: params.entries_per_bucket,
+            max_kicks: params.max_kicks,
+            insertion_strategy: params.insertion_strategy,
+            storage_backend: params.storage_backend,
+            hash_width: params.hash_width,
+            alt_index_scheme: params.alt_index_scheme,
+            power_of_two_buckets: params.power_of_two_buckets,
+            bucket_counts: params.bucket_counts,
+            stash_capacity: params.stash_capacity,
+            stash_enabled: params.stash_enabled,
+            grow_at_load_factor: params.grow_at_load_factor,
+            pre_touch_pages: params.pre_touch_pages,
+            transparent_hugepages: params.transparent_hugepages,
+            growth_policy: Arc::new(DefaultGrowthPolicy),
+            listener: Listener::default(),
+            metrics_label: None,
+            allocator: Allocator::default(),
+            rng,
+            counters: FilterCounters::default(),
+            version: params.filter_version,
+        })
+    }
+
+    /// Deserializes a filter from bytes made by [`to_bytes`], giving it `hasher` and `rng`.
+    ///
+    /// Bytes written by earlier versions of this crate are accepted.
+    /// See [`read_from_with`] for the requirements of `hasher`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is malformed, of an unknown format version,
+    /// or followed by extra bytes.
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`read_from_with`]: ScalableCuckooFilter::read_from_with
+    pub fn from_bytes_with(bytes: &[u8], hasher: H, rng: R) -> io::Result<Self> {
+        Self::from_bytes_with_hasher(bytes, |_| hasher, rng)
+    }
+
+    fn from_bytes_with_hasher(
+        mut bytes: &[u8],
+        make_hasher: impl FnOnce(Option<(u64, u64)>) -> H,
+        rng: R,
+    ) -> io::Result<Self> {
+        let filter = Self::read_with_hasher(&mut bytes, make_hasher, rng)?;
+        if !bytes.is_empty() {
+            return Err(codec::invalid_data("trailing bytes after the filter"));
+        }
+        Ok(filter)
+    }
+}
+
+impl<H: Hasher + Clone + Default + HasherKeys, R: Rng + Default> ScalableCuckooFilter<H, R> {
+    /// Reads a filter written by [`write_to`] (or [`to_bytes`]) from `reader`.
+    ///
+    /// As with serde, the RNG is made by `Default`, and so is the hasher unless it is keyed,
+    /// in which case it is keyed as the written one was (see [`HasherKeys`]).
+    /// See [`read_from_with`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`read_from_with`]: ScalableCuckooFilter::read_from_with
+    pub fn read_from<Rd: Read>(reader: Rd) -> io::Result<Self> {
+        Self::read_with_hasher(reader, hashers::keyed_hasher, R::default())
+    }
+
+    /// Reads a filter from `reader` like [`read_from`], but asynchronously.
+    ///
+    /// See [`read_from_with_async`] for details.
+    ///
+    /// This is available only if the `tokio` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    /// [`read_from_with_async`]: ScalableCuckooFilter::read_from_with_async
+    #[cfg(feature = "tokio")]
+    pub async fn read_from_async<Rd: AsyncRead + Unpin>(reader: Rd) -> io::Result<Self> {
+        async_io::read(reader, hashers::keyed_hasher, R::default()).await
+    }
+
+    /// Deserializes a filter from bytes made by [`to_bytes`].
+    ///
+    /// The hasher and the RNG are made as by [`read_from`]. See [`from_bytes_with`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is malformed, of an unknown format version,
+    /// or followed by extra bytes.
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    /// [`from_bytes_with`]: ScalableCuckooFilter::from_bytes_with
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        Self::from_bytes_with_hasher(bytes, hashers::keyed_hasher, R::default())
+    }
+
+    /// Loads a filter from the file at `path` saved by [`save_to_path`] (or [`write_to`]).
+    ///
+    /// The hasher and the RNG are made as by [`read_from`].
+    /// Besides the checksum of the contents, the size and the modification time of the file
+    /// are checked not to change while it is read, which catches files being written
+    /// in place by other processes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, if it isn't a regular file,
+    /// if its contents are malformed or followed by extra bytes, or if it changes while read.
+    ///
+    /// [`save_to_path`]: ScalableCuckooFilter::save_to_path
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        persist::load_checked(path.as_ref(), |r| Self::read_from(r))
+    }
+}
+
+#[cfg(any(feature = "serde_support", feature = "python"))]
+impl<H, R> ScalableCuckooFilter<H, R> {
+    /// Replaces the hasher and the RNG.
+    pub(crate) fn with_parts<H2, R2>(self, hasher: H2, rng: R2) -> ScalableCuckooFilter<H2, R2> {
+        ScalableCuckooFilter {
+            hasher,
+            filters: self.filters,
+            initial_capacity: self.initial_capacity,
+            false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
+            entries_per_bucket: self.entries_per_bucket,
+            max_kicks: self.max_kicks,
+            insertion_strategy: self.insertion_strategy,
+            storage_backend: self.storage_backend,
+            hash_width: self.hash_width,
+            alt_index_scheme: self.alt_index_scheme,
+            power_of_two_buckets: self.power_of_two_buckets,
+            bucket_counts: self.bucket_counts,
+            stash_capacity: self.stash_capacity,
+            stash_enabled: self.stash_enabled,
+            grow_at_load_factor: self.grow_at_load_factor,
+            pre_touch_pages: self.pre_touch_pages,
+            transparent_hugepages: self.transparent_hugepages,
+            growth_policy: self.growth_policy,
+            listener: self.listener,
+            metrics_label: self.metrics_label,
+            allocator: self.allocator,
+            rng,
+            counters: self.counters,
+            version: self.version,
+        }
+    }
+}
+
+/// Makes a filter with the defaults of [`ScalableCuckooFilterBuilder`], except that the hasher is
+/// `DefaultHasher::default()` (with fixed keys), so default filters can be merged.
+impl Default for ScalableCuckooFilter {
+    fn default() -> Self {
+        ScalableCuckooFilterBuilder::new()
+            .hasher(DefaultHasher::default())
+            .finish_raw()
+    }
+}
+
+impl<H: Hasher + Clone, R: Rng + Clone> Clone for ScalableCuckooFilter<H, R> {
+    fn clone(&self) -> Self {
+        self.clone_with_rng(self.rng.clone())
+    }
+}
+
+/// Filters are equal if they have the same parameters and layers holding the same entries.
+///
+/// This compares what [`write_to`] writes except for the version, without serializing them:
+/// hashers are compared only by their keys (see [`HasherKeys`]), so unkeyed hashers of the same
+/// type are assumed to hash items the same way.
+/// The growth policy, the event listener, the allocator, the RNG and the counters aren't compared.
+///
+/// [`write_to`]: ScalableCuckooFilter::write_to
+impl<H: HasherKeys, R> PartialEq for ScalableCuckooFilter<H, R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hasher.keys() == other.hasher.keys()
+            && self.initial_capacity == other.initial_capacity
+            && self.false_positive_probability == other.false_positive_probability
+            && self.tightening_ratio == other.tightening_ratio
+            && self.entries_per_bucket == other.entries_per_bucket
+            && self.max_kicks == other.max_kicks
+            && self.insertion_strategy == other.insertion_strategy
+            && self.storage_backend == other.storage_backend
+            && self.hash_width == other.hash_width
+            && self.alt_index_scheme == other.alt_index_scheme
+            && self.power_of_two_buckets == other.power_of_two_buckets
+            && self.bucket_counts == other.bucket_counts
+            && self.stash_capacity == other.stash_capacity
+            && self.stash_enabled == other.stash_enabled
+            && self.grow_at_load_factor == other.grow_at_load_factor
+            && self.pre_touch_pages == other.pre_touch_pages
+            && self.transparent_hugepages == other.transparent_hugepages
+            && self.filters.len() == other.filters.len()
+            && self
+                .filters
+                .iter()
+                .zip(&other.filters)
+                .all(|(a, b)| a.same_contents(b))
+    }
+}
+
+#[cfg(feature = "serde_support")]
+fn default_tightening_ratio() -> f64 {
+    0.5
+}
+
+#[cfg(feature = "serde_support")]
+fn default_stash_enabled() -> bool {
+    true
+}
+
+#[cfg(feature = "serde_support")]
+fn default_power_of_two_buckets() -> bool {
+    true
+}
+
+#[cfg(feature = "serde_support")]
+fn default_growth_policy() -> Arc<dyn GrowthPolicy> {
+    Arc::new(DefaultGrowthPolicy)
+}
+
+#[cfg(test)]
+mod test {
+    use siphasher::sip::SipHasher13;
+
+    use super::*;
+    use crate::MixingHasher;
+
+    #[test]
+    fn it_works() {
+        let mut filter = ScalableCuckooFilter::new(100, 0.001);
+        assert!(filter.is_empty());
+
+        let hashes = (0..10_000u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect::<Vec<_>>();
+        for &h in &hashes {
+            filter.insert(h);
+        }
+        assert_eq!(filter.len(), 10_000);
+        assert!(filter.layers().len() > 1);
+        assert!(hashes.iter().all(|&h| filter.contains(h)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        assert!(filter.remove(hashes[0]));
+        assert_eq!(filter.len(), 9_999);
+    }
+
+    #[test]
+    fn typed_filter_wraps_raw_filter() {
+        let mut typed = crate::ScalableCuckooFilter::<str>::new(100, 0.001);
+        typed.insert("foo");
+
+        let mut raw = typed.into_raw();
+        let width = raw.hash_width();
+        let foo = crate::item_hash(&raw.hasher, "foo", width);
+        let bar = crate::item_hash(&raw.hasher, "bar", width);
+        assert!(raw.contains128(foo));
+        raw.insert128(bar);
+
+        let typed = crate::ScalableCuckooFilter::<str>::from_raw(raw);
+        assert!(typed.contains("foo"));
+        assert!(typed.contains("bar"));
+    }
+
+    #[test]
+    fn write_to_and_read_from_work() {
+        for backend in [
+            StorageBackend::Buckets,
+            StorageBackend::Blocked,
+            StorageBackend::Morton,
+        ] {
+            let mut filter: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .storage_backend(backend)
+                .grow_at_load_factor(0.9)
+                .finish_raw();
+            let hashes = (0..1000u64)
+                .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+                .collect::<Vec<_>>();
+            for &h in &hashes {
+                filter.insert(h);
+            }
+            filter.insert(0);
+            filter.insert(0);
+
+            let mut bytes = Vec::new();
+            filter.write_to(&mut bytes).unwrap();
+            let read: ScalableCuckooFilter = ScalableCuckooFilter::read_from(&bytes[..]).unwrap();
+            assert_eq!(read.len(), filter.len());
+            assert_eq!(read.bits(), filter.bits());
+            assert_eq!(read.storage_backend(), backend);
+            assert_eq!(read.grow_at_load_factor(), Some(0.9));
+            assert!(hashes.iter().all(|&h| read.contains(h)));
+            assert_eq!(read.verify_integrity(), Ok(()));
+
+            let mut rewritten = Vec::new();
+            read.write_to(&mut rewritten).unwrap();
+            assert_eq!(rewritten, bytes);
+
+            let truncated =
+                ScalableCuckooFilter::<DefaultHasher>::read_from(&bytes[..bytes.len() - 1]);
+            assert!(truncated.is_err());
+        }
+    }
+
+    #[test]
+    fn to_bytes_and_from_bytes_work() {
+        let mut filter: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .finish_raw();
+        for i in 0..1000u64 {
+            filter.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+
+        let bytes = filter.to_bytes().unwrap();
+        assert_eq!(&bytes[..7], b"SCKF\x01\x00\x00");
+        let read = ScalableCuckooFilter::<DefaultHasher>::from_bytes(&bytes).unwrap();
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(ScalableCuckooFilter::<DefaultHasher>::from_bytes(&wrong_magic).is_err());
+
+        let mut future_version = bytes.clone();
+        future_version[4] = 6;
+        assert!(ScalableCuckooFilter::<DefaultHasher>::from_bytes(&future_version).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(ScalableCuckooFilter::<DefaultHasher>::from_bytes(&trailing).is_err());
+
+        // Flipping a bit of the last bucket only changes an answer, so it is caught by the checksum.
+        let mut corrupted = bytes;
+        let i = corrupted.len() - 5;
+        corrupted[i] ^= 1;
+        let error = ScalableCuckooFilter::<DefaultHasher>::from_bytes(&corrupted).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn hasher_keys_are_restored() {
+        let hasher = SipHasher13::new_with_keys(1, 2);
+        let mut filter: ScalableCuckooFilter<SipHasher13> = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .hasher(hasher)
+            .finish_raw();
+        for i in 0..1000u64 {
+            filter.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+
+        let bytes = filter.to_bytes().unwrap();
+        let read = ScalableCuckooFilter::<SipHasher13>::from_bytes(&bytes).unwrap();
+        assert_eq!(read.hasher.keys(), hasher.keys());
+        let read = ScalableCuckooFilter::<SipHasher13>::read_from(&bytes[..]).unwrap();
+        assert_eq!(read.hasher.keys(), hasher.keys());
+        assert!((0..1000u64).all(|i| read.contains(i.wrapping_mul(0x9E37_79B9_7F4A_7C15))));
+
+        // The given hasher takes precedence over the recorded keys.
+        let other = SipHasher13::new_with_keys(3, 4);
+        let read =
+            ScalableCuckooFilter::from_bytes_with(&bytes, other, DefaultRng::default()).unwrap();
+        assert_eq!(read.hasher.keys(), other.keys());
+    }
+
+    #[test]
+    fn eq_works() {
+        let make = |keys: (u64, u64)| -> ScalableCuckooFilter<SipHasher13> {
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .hasher(SipHasher13::new_with_keys(keys.0, keys.1))
+                .finish_raw()
+        };
+        let mut filter = make((1, 2));
+        for i in 0..1000u64 {
+            filter.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        assert!(filter == filter.clone());
+        assert!(filter == ScalableCuckooFilter::from_bytes(&filter.to_bytes().unwrap()).unwrap());
+        assert!(filter != make((1, 2)));
+
+        let mut other = filter.clone();
+        other.insert(1000);
+        assert!(filter != other);
+        other.remove(1000);
+        assert!(filter == other);
+
+        // Filters with differently keyed hashers store different fingerprints for the same items.
+        let mut other = make((3, 4));
+        for i in 0..1000u64 {
+            other.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        assert!(filter != other);
+
+        // Hashers are compared by the keys they export, even if the filters are empty.
+        assert!(make((1, 2)) != make((3, 4)));
+        let mixing = |key| -> ScalableCuckooFilter<MixingHasher<SipHasher13>> {
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .hasher(MixingHasher::new(SipHasher13::new_with_keys(key, key)))
+                .finish_raw()
+        };
+        assert!(mixing(1) == mixing(1));
+        assert!(mixing(1) != mixing(2));
+        assert!(ScalableCuckooFilter::<DefaultHasher>::default() == Default::default());
+    }
+
+    #[test]
+    fn save_to_path_and_load_from_path_work() {
+        let mut filter: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .finish_raw();
+        for i in 0..1000u64 {
+            filter.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filter.bin");
+        filter.save_to_path(&path).unwrap();
+        let read = ScalableCuckooFilter::<DefaultHasher>::load_from_path(&path).unwrap();
+        assert_eq!(read.to_bytes().unwrap(), filter.to_bytes().unwrap());
+
+        let mut trailing = std::fs::read(&path).unwrap();
+        trailing.push(0);
+        std::fs::write(&path, trailing).unwrap();
+        assert!(ScalableCuckooFilter::<DefaultHasher>::load_from_path(&path).is_err());
+    }
+
+    #[test]
+    fn delta_works() {
+        for storage_backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+            let mut primary: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100_000)
+                .storage_backend(storage_backend)
+                .finish_raw();
+            for i in 0..50_000u64 {
+                primary.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            }
+            let snapshot = primary.to_bytes().unwrap();
+            let mut replica = ScalableCuckooFilter::<DefaultHasher>::from_bytes(&snapshot).unwrap();
+            assert_eq!(replica.version(), primary.version());
+
+            // Only the written pages are included.
+            let baseline = primary.version();
+            for i in 0..10u64 {
+                primary.insert(i);
+            }
+            assert!(primary.remove(0));
+            let delta = primary.delta_since(baseline).unwrap();
+            assert!(delta.len() < snapshot.len() / 10);
+            replica.apply_delta(&delta).unwrap();
+            assert_eq!(replica.to_bytes().unwrap(), primary.to_bytes().unwrap());
+            assert!(replica.apply_delta(&delta).is_ok());
+
+            // Layers added after the baseline are included whole.
+            let baseline = primary.version();
+            let mut i = 0u64;
+            while primary.layers().len() == 1 {
+                primary.insert(i.wrapping_mul(0x2545_F491_4F6C_DD1D));
+                i += 1;
+            }
+            let delta = primary.delta_since(baseline).unwrap();
+            replica.apply_delta(&delta).unwrap();
+            assert_eq!(replica.to_bytes().unwrap(), primary.to_bytes().unwrap());
+
+            // A delta from an older baseline also applies to newer replicas,
+            // but not to older ones.
+            let stale = ScalableCuckooFilter::<DefaultHasher>::from_bytes(&snapshot).unwrap();
+            primary.insert(12345);
+            let delta = primary.delta_since(stale.version() + 1).unwrap();
+            let mut stale = stale;
+            assert!(stale.apply_delta(&delta).is_err());
+            assert_eq!(stale.to_bytes().unwrap(), snapshot);
+            replica.apply_delta(&delta).unwrap();
+            assert_eq!(replica.to_bytes().unwrap(), primary.to_bytes().unwrap());
+
+            let empty = primary.delta_since(primary.version()).unwrap();
+            replica.apply_delta(&empty).unwrap();
+            assert_eq!(replica.to_bytes().unwrap(), primary.to_bytes().unwrap());
+
+            assert!(primary.delta_since(primary.version() + 1).is_err());
+            let mut corrupted = delta.clone();
+            let last = corrupted.len() - 1;
+            corrupted[last] ^= 1;
+            assert!(replica.apply_delta(&corrupted).is_err());
+        }
+    }
+
+    #[test]
+    fn reconciliation_works() {
+        fn reconcile(a: &mut ScalableCuckooFilter, b: &mut ScalableCuckooFilter) -> usize {
+            let entries = b
+                .entries_missing_from(&a.reconciliation_summary().unwrap())
+                .unwrap();
+            let added = a.merge_entries(&entries).unwrap();
+            let entries = a
+                .entries_missing_from(&b.reconciliation_summary().unwrap())
+                .unwrap();
+            added + b.merge_entries(&entries).unwrap()
+        }
+
+        for storage_backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+            let mut a: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100_000)
+                .storage_backend(storage_backend)
+                .finish_raw();
+            for i in 0..50_000u64 {
+                a.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            }
+            let snapshot = a.to_bytes().unwrap();
+            let mut b = ScalableCuckooFilter::<DefaultHasher>::from_bytes(&snapshot).unwrap();
+
+            // Only the entries of the differing buckets are sent.
+            for i in 0..100u64 {
+                a.insert(i);
+                b.insert(i + 1000);
+                a.insert(i + 2000);
+                b.insert(i + 2000);
+            }
+            let summary = a.reconciliation_summary().unwrap();
+            assert!(summary.len() < snapshot.len() / 10);
+            let entries = b.entries_missing_from(&summary).unwrap();
+            assert!(entries.len() < snapshot.len() / 10);
+
+            assert_eq!(reconcile(&mut a, &mut b), 200);
+            for i in 0..100 {
+                assert!(a.contains(i + 1000));
+                assert!(b.contains(i));
+            }
+            assert_eq!(a.len(), b.len());
+            assert_eq!(
+                a.reconciliation_summary().unwrap(),
+                b.reconciliation_summary().unwrap()
+            );
+            assert_eq!(reconcile(&mut a, &mut b), 0);
+
+            // Layers are added as needed.
+            let mut i = 0u64;
+            while b.layers().len() == 1 {
+                b.insert(i.wrapping_mul(0x2545_F491_4F6C_DD1D));
+                i += 1;
+            }
+            reconcile(&mut a, &mut b);
+            assert_eq!(a.layers().len(), 2);
+            assert_eq!(a.len(), b.len());
+            assert!(a.contains(0x2545_F491_4F6C_DD1D));
+
+            let other: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100_000)
+                .entries_per_bucket(8)
+                .storage_backend(storage_backend)
+                .finish_raw();
+            assert!(other.entries_missing_from(&summary).is_err());
+            let mut corrupted = summary.clone();
+            corrupted[10] ^= 1;
+            assert!(b.entries_missing_from(&corrupted).is_err());
+            let version = a.version();
+            let mut corrupted = b.entries_missing_from(&summary).unwrap();
+            corrupted[10] ^= 1;
+            assert!(a.merge_entries(&corrupted).is_err());
+            assert_eq!(a.version(), version);
+        }
+    }
+
+    #[test]
+    fn failed_merge_entries_keeps_filter() {
+        // The replicas share their first layer, but not the layers added after it.
+        let builder = |counts: [usize; 2]| {
+            ScalableCuckooFilterBuilder::new()
+                .hasher(DefaultHasher::default())
+                .bucket_counts(counts)
+                .finish_raw()
+        };
+        let mut a = builder([256, 512]);
+        let mut b = builder([256, 1024]);
+        b.insert(1u64);
+        b.grow();
+        b.insert(2u64);
+
+        a.insert(3u64);
+        let (version, counters) = (a.version(), a.counters());
+        let entries = b
+            .entries_missing_from(&a.reconciliation_summary().unwrap())
+            .unwrap();
+        assert!(a.merge_entries(&entries).is_err());
+        assert_eq!(a.layers().len(), 1);
+        assert_eq!(a.version(), version);
+        assert_eq!(a.counters(), counters);
+        assert_eq!(a.counters().growths, 0);
+        assert!(a.contains(3u64));
+    }
+
+    #[test]
+    fn to_compressed_bytes_works() {
+        for storage_backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+            let mut filter: ScalableCuckooFilter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(1000)
+                .storage_backend(storage_backend)
+                .finish_raw();
+            // Right after a growth, the last layer (twice as large as the first) is mostly empty.
+            let mut i = 0u64;
+            while filter.layers().len() == 1 {
+                filter.insert(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                i += 1;
+            }
+
+            let bytes = filter.to_bytes().unwrap();
+            let compressed = filter.to_compressed_bytes().unwrap();
+            assert!(compressed.len() < bytes.len() / 2);
+            assert_eq!(&compressed[..7], b"SCKF\x01\x00\x01");
+
+            let read = ScalableCuckooFilter::<DefaultHasher>::from_bytes(&compressed).unwrap();
+            assert_eq!(read.to_bytes().unwrap(), bytes);
+            let truncated = &compressed[..compressed.len() - 5];
+            assert!(ScalableCuckooFilter::<DefaultHasher>::from_bytes(truncated).is_err());
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Formats of the summaries and the entries exchanged to reconcile replicas.
+//!
+//! Entries are compared as pairs of the smaller candidate bucket index and the fingerprint
+//! (see `CuckooFilter::canonical_entries`), which are the same in replicas that have inserted
+//! the same items into the same layer wherever the entries have been placed.
+//! The buckets of each layer are divided into ranges of `RANGE_BUCKETS` buckets, and the digest
+//! of a range is the wrapping sum of the hashes of the entries whose index is in the range.
+//!
+//! A summary starts with `SUMMARY_MAGIC`, the format version (`u16`), and the hash of the
+//! hasher and the configuration of the filter (`u64`), which are followed by the number of
+//! layers and then by the layers. Each layer is its number of buckets, its fingerprint bitwidth
+//! (`u8`), and the digests of its ranges.
+//!
+//! Entries start with `ENTRIES_MAGIC`, the format version, and the hash of the configuration
+//! as well, which are followed by the number of layers and then by the layers. Each layer is
+//! its number of buckets, its fingerprint bitwidth, and the number of ranges, each of which is
+//! its index, the number of entries, and the entries. An entry is the offset of its bucket index
+//! in the range (`u8`) followed by the fingerprint in the fewest bytes that hold the bitwidth.
+//!
+//! Both end with the CRC-32 of all the preceding bytes as a `u32`.
+//! The primitives are encoded as in `codec`.
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+
+use rand::Rng;
+
+use crate::codec;
+use crate::cuckoo_filter::CuckooFilter;
+use crate::hashers::fmix64;
+use crate::raw::ScalableCuckooFilter;
+use crate::telemetry;
+
+/// Bytes that every summary starts with.
+const SUMMARY_MAGIC: [u8; 4] = *b"SCKS";
+
+/// Bytes that every set of entries starts with.
+const ENTRIES_MAGIC: [u8; 4] = *b"SCKE";
+
+/// Version of the formats of summaries and entries written by this crate.
+const RECONCILE_VERSION: u16 = 1;
+
+/// Number of buckets covered by a digest.
+///
+/// Offsets in a range must fit in a `u8`.
+const RANGE_BUCKETS: usize = 64;
+
+/// Salt of the hashes of the configuration and of the entries.
+const SALT: u64 = 0x3C6E_F372_FE94_F82B;
+
+/// Number of buckets and fingerprint bitwidth of a layer.
+type Shape = (usize, usize);
+
+struct Summary {
+    config: u64,
+    layers: Vec<(Shape, Vec<u64>)>,
+}
+
+/// Index of a range and its entries, each of which is the offset of its bucket index in
+/// the range and its fingerprint.
+type RangeEntries = (usize, Vec<(usize, u64)>);
+
+struct Entries {
+    config: u64,
+    layers: Vec<(Shape, Vec<RangeEntries>)>,
+}
+
+pub fn write_summary<H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<H, R>,
+) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let w = &mut codec::ChecksumWriter::new(&mut bytes);
+    write_header(w, SUMMARY_MAGIC, filter)?;
+    for layer in &filter.filters {
+        write_shape(w, shape(layer))?;
+        for digest in range_digests(layer, &filter.hasher) {
+            codec::write_u64(w, digest)?;
+        }
+    }
+    w.finish()?;
+    Ok(bytes)
+}
+
+pub fn write_entries_missing_from<H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<H, R>,
+    peer_summary: &[u8],
+) -> io::Result<Vec<u8>> {
+    let peer = read_summary(peer_summary)?;
+    check_config(filter, peer.config)?;
+    for (layer, (shape, _)) in filter.filters.iter().zip(&peer.layers) {
+        check_shape(layer, *shape)?;
+    }
+
+    let mut bytes = Vec::new();
+    let w = &mut codec::ChecksumWriter::new(&mut bytes);
+    write_header(w, ENTRIES_MAGIC, filter)?;
+    for (i, layer) in filter.filters.iter().enumerate() {
+        let shape = shape(layer);
+        let digests = range_digests(layer, &filter.hasher);
+        let peer_digests = peer.layers.get(i).map_or(&[][..], |(_, d)| &d[..]);
+        // A layer the peer doesn't have is compared with empty ranges.
+        let mut ranges = digests
+            .iter()
+            .enumerate()
+            .map(|(r, &d)| (d != peer_digests.get(r).copied().unwrap_or(0)).then(Vec::new))
+            .collect::<Vec<_>>();
+        for (index, fingerprint) in layer.canonical_entries(&filter.hasher) {
+            if let Some(entries) = &mut ranges[index / RANGE_BUCKETS] {
+                entries.push((index % RANGE_BUCKETS, fingerprint));
+            }
+        }
+
+        write_shape(w, shape)?;
+        codec::write_usize(w, ranges.iter().flatten().count())?;
+        let fingerprint_bytes = shape.1.div_ceil(8);
+        for (r, entries) in ranges.into_iter().enumerate() {
+            let Some(entries) = entries else {
+                continue;
+            };
+            codec::write_usize(w, r)?;
+            codec::write_usize(w, entries.len())?;
+            for (offset, fingerprint) in entries {
+                codec::write_u8(w, offset as u8)?;
+                w.write_all(&fingerprint.to_le_bytes()[..fingerprint_bytes])?;
+            }
+        }
+    }
+    w.finish()?;
+    Ok(bytes)
+}
+
+pub fn merge_entries<H: Hasher + Clone, R: Rng>(
+    filter: &mut ScalableCuckooFilter<H, R>,
+    entries: &[u8],
+) -> io::Result<usize> {
+    let entries = read_entries(entries)?;
+    check_config(filter, entries.config)?;
+    for (layer, (shape, _)) in filter.filters.iter().zip(&entries.layers) {
+        check_shape(layer, *shape)?;
+    }
+
+    // Layers the peer has and this filter doesn't are added the same way as the peer did,
+    // and are dropped again (with the growths counted for them) if they don't match.
+    let (layers, version, counters) = (filter.filters.len(), filter.version, filter.counters);
+    while filter.filters.len() < entries.layers.len() {
+        filter.grow();
+        let i = filter.filters.len() - 1;
+        if let Err(e) = check_shape(&filter.filters[i], entries.layers[i].0) {
+            filter.filters.truncate(layers);
+            filter.version = version;
+            filter.counters = counters;
+            telemetry::record_bits(filter.metrics_label.as_deref(), filter.bits());
+            return Err(e);
+        }
+    }
+
+    let mut added = 0;
+    for (i, (_, ranges)) in entries.layers.into_iter().enumerate() {
+        if ranges.is_empty() {
+            continue;
+        }
+
+        // Entries this filter already has are not added again (as many times as it has them).
+        let mut known = HashMap::<_, usize>::new();
+        let mut requested = vec![false; filter.filters[i].bucket_count().div_ceil(RANGE_BUCKETS)];
+        for &(r, _) in &ranges {
+            requested[r] = true;
+        }
+        for entry in filter.filters[i].canonical_entries(&filter.hasher) {
+            if requested[entry.0 / RANGE_BUCKETS] {
+                *known.entry(entry).or_default() += 1;
+            }
+        }
+
+        let layer = &mut filter.filters[i];
+        layer.set_version(filter.version + 1);
+        for (r, range_entries) in ranges {
+            for (offset, fingerprint) in range_entries {
+                let index = r * RANGE_BUCKETS + offset;
+                match known.get_mut(&(index, fingerprint)) {
+                    Some(count) if *count > 0 => *count -= 1,
+                    _ => {
+                        layer.insert_canonical_entry(
+                            &filter.hasher,
+                            &mut filter.rng,
+                            index,
+                            fingerprint,
+                        );
+                        added += 1;
+                    }
+                }
+            }
+        }
+    }
+    if added > 0 {
+        filter.next_version();
+    }
+    Ok(added)
+}
+
+/// Returns the hash of the hasher and of the parts of the configuration that determine
+/// the canonical entries of items.
+pub fn config_hash<H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<H, R>,
+) -> io::Result<u64> {
+    let config = (
+        SALT,
+        filter.entries_per_bucket as u64,
+        filter.storage_backend.tag(),
+        filter.hash_width.tag(),
+        filter.alt_index_scheme.tag()?,
+    );
+    Ok(crate::hash(&filter.hasher, &config))
+}
+
+fn check_config<H: Hasher + Clone, R: Rng>(
+    filter: &ScalableCuckooFilter<H, R>,
+    config: u64,
+) -> io::Result<()> {
+    if config_hash(filter)? != config {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the replicas have different hashers or configurations",
+        ));
+    }
+    Ok(())
+}
+
+fn shape(layer: &CuckooFilter) -> Shape {
+    (layer.bucket_count(), layer.fingerprint_bitwidth())
+}
+
+fn check_shape(layer: &CuckooFilter, peer_shape: Shape) -> io::Result<()> {
+    if shape(layer) != peer_shape {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the layers of the replicas don't match",
+        ));
+    }
+    Ok(())
+}
+
+fn range_digests<H: Hasher + Clone>(layer: &CuckooFilter, hasher: &H) -> Vec<u64> {
+    let mut digests = vec![0u64; layer.bucket_count().div_ceil(RANGE_BUCKETS)];
+    for (index, fingerprint) in layer.canonical_entries(hasher) {
+        let digest = &mut digests[index / RANGE_BUCKETS];
+        *digest = digest.wrapping_add(fmix64(fmix64(index as u64 ^ SALT) ^ fingerprint));
+    }
+    digests
+}
+
+fn write_header<W: Write, H: Hasher + Clone, R: Rng>(
+    writer: &mut W,
+    magic: [u8; 4],
+    filter: &ScalableCuckooFilter<H, R>,
+) -> io::Result<()> {
+    writer.write_all(&magic)?;
+    writer.write_all(&RECONCILE_VERSION.to_le_bytes())?;
+    codec::write_u64(writer, config_hash(filter)?)?;
+    codec::write_usize(writer, filter.filters.len())
+}
+
+/// Reads the header written by `write_header`, returning the hash of the configuration and
+/// the number of layers.
+fn read_header<Rd: Read>(reader: &mut Rd, magic: [u8; 4]) -> io::Result<(u64, usize)> {
+    let mut actual = [0; 4];
+    reader.read_exact(&mut actual)?;
+    if actual != magic {
+        return Err(codec::invalid_data(
+            "not a reconciliation message of a scalable cuckoo filter",
+        ));
+    }
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    if u16::from_le_bytes(version) != RECONCILE_VERSION {
+        return Err(codec::invalid_data(
+            "unsupported reconciliation format version",
+        ));
+    }
+    Ok((codec::read_u64(reader)?, codec::read_usize(reader)?))
+}
+
+fn write_shape<W: Write>(writer: &mut W, (buckets, fingerprint_bitwidth): Shape) -> io::Result<()> {
+    codec::write_usize(writer, buckets)?;
+    codec::write_u8(writer, fingerprint_bitwidth as u8)
+}
+
+fn read_shape<Rd: Read>(reader: &mut Rd) -> io::Result<Shape> {
+    let buckets = codec::read_usize(reader)?;
+    let fingerprint_bitwidth = usize::from(codec::read_u8(reader)?);
+    if !(1..=64).contains(&fingerprint_bitwidth) {
+        return Err(codec::invalid_data("invalid fingerprint bitwidth"));
+    }
+    Ok((buckets, fingerprint_bitwidth))
+}
+
+fn read_summary(mut bytes: &[u8]) -> io::Result<Summary> {
+    let r = &mut codec::ChecksumReader::new(&mut bytes);
+    let (config, layers) = read_header(r, SUMMARY_MAGIC)?;
+    let layers = (0..layers)
+        .map(|_| {
+            let shape = read_shape(r)?;
+            let digests = (0..shape.0.div_ceil(RANGE_BUCKETS))
+                .map(|_| codec::read_u64(r))
+                .collect::<io::Result<Vec<_>>>()?;
+            Ok((shape, digests))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    r.verify()?;
+    if !bytes.is_empty() {
+        return Err(codec::invalid_data("trailing bytes after the summary"));
+    }
+    Ok(Summary { config, layers })
+}
+
+fn read_entries(mut bytes: &[u8]) -> io::Result<Entries> {
+    let r = &mut codec::ChecksumReader::new(&mut bytes);
+    let (config, layers) = read_header(r, ENTRIES_MAGIC)?;
+    let layers = (0..layers)
+        .map(|_| {
+            let shape @ (buckets, fingerprint_bitwidth) = read_shape(r)?;
+            let ranges = (0..codec::read_usize(r)?)
+                .map(|_| {
+                    let range = codec::read_usize(r)?;
+                    if range >= buckets.div_ceil(RANGE_BUCKETS) {
+                        return Err(codec::invalid_data("range exceeds the buckets"));
+                    }
+                    let entries = (0..codec::read_usize(r)?)
+                        .map(|_| {
+                            let offset = usize::from(codec::read_u8(r)?);
+                            let mut fingerprint = [0; 8];
+                            r.read_exact(&mut fingerprint[..fingerprint_bitwidth.div_ceil(8)])?;
+                            let fingerprint = u64::from_le_bytes(fingerprint);
+                            let index = range * RANGE_BUCKETS + offset;
+                            if offset >= RANGE_BUCKETS
+                                || index >= buckets
+                                || fingerprint >> (fingerprint_bitwidth - 1) > 1
+                            {
+                                return Err(codec::invalid_data("invalid entry"));
+                            }
+                            Ok((offset, fingerprint))
+                        })
+                        .collect::<io::Result<Vec<_>>>()?;
+                    Ok((range, entries))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            Ok((shape, ranges))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    r.verify()?;
+    if !bytes.is_empty() {
+        return Err(codec::invalid_data("trailing bytes after the entries"));
+    }
+    Ok(Entries { config, layers })
+}
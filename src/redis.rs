@@ -0,0 +1,473 @@
+//! Import and export in the chunked format of `CF.SCANDUMP` and `CF.LOADCHUNK` of [RedisBloom],
+//! for migrating cuckoo filters between Redis and this crate.
+//!
+//! `CF.SCANDUMP key <cursor>` returns a header at cursor `0` and then the bytes of the
+//! sub-filters in chunks, each with the cursor to pass next (zero after the last chunk).
+//! Each pair of a cursor and data is passed as is to `CF.LOADCHUNK key <cursor> <data>`,
+//! and to [`ChunkLoader::load_chunk`] here.
+//!
+//! RedisBloom takes the bucket index and the 8-bit fingerprint of an item from the 64-bit
+//! MurmurHash64A of its bytes, and derives the alternative index in the same way as
+//! [`AltIndexScheme::Multiplicative`]. So a loaded filter finds the items added in Redis if they
+//! are hashed by [`item_hash`] and looked up by [`raw::ScalableCuckooFilter::contains128`]
+//! (and items are added by [`raw::ScalableCuckooFilter::insert128`] in the same way).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use scalable_cuckoo_filter::raw::ScalableCuckooFilter;
+//! use scalable_cuckoo_filter::redis::{self, ChunkLoader};
+//! use std::io;
+//!
+//! // `chunks` are the pairs of a cursor and data returned by `CF.SCANDUMP`.
+//! fn import(chunks: &[(i64, Vec<u8>)]) -> io::Result<ScalableCuckooFilter> {
+//!     let mut loader = ChunkLoader::new();
+//!     for (cursor, data) in chunks {
+//!         loader.load_chunk(*cursor, data)?;
+//!     }
+//!     let filter = loader.finish()?;
+//!     assert!(filter.contains128(redis::item_hash(b"foo")));
+//!     Ok(filter)
+//! }
+//!
+//! // Each pair is passed to `CF.LOADCHUNK` in order.
+//! fn export(filter: &ScalableCuckooFilter) -> io::Result<Vec<(i64, Vec<u8>)>> {
+//!     redis::scan_dump(filter)
+//! }
+//! ```
+//!
+//! [RedisBloom]: https://github.com/RedisBloom/RedisBloom
+//! [`AltIndexScheme::Multiplicative`]: crate::AltIndexScheme::Multiplicative
+//! [`raw::ScalableCuckooFilter::contains128`]: crate::raw::ScalableCuckooFilter::contains128
+//! [`raw::ScalableCuckooFilter::insert128`]: crate::raw::ScalableCuckooFilter::insert128
+use std::fmt;
+use std::hash::Hasher;
+use std::io;
+
+use rand::Rng;
+
+use crate::codec;
+use crate::cuckoo_filter::{AltIndexScheme, CuckooFilter, StorageBackend};
+use crate::raw;
+use crate::ScalableCuckooFilterBuilder;
+
+/// Size of the header (`CFHeader` of RedisBloom), which is packed and little-endian:
+/// `numItems`, `numBuckets` (of the first sub-filter), `numDeletes`, and `numFilters` (`u64`s),
+/// followed by `bucketSize`, `maxIterations`, and `expansion` (`u16`s).
+const HEADER_LEN: usize = 38;
+
+/// Maximum number of bytes in a chunk made by `scan_dump`.
+const CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+/// Fingerprint bitwidth of RedisBloom cuckoo filters.
+const FINGERPRINT_BITWIDTH: usize = 8;
+
+/// Returns the item hash of `item` that RedisBloom uses, for
+/// [`raw::ScalableCuckooFilter::contains128`] and the like.
+///
+/// The lower 64 bits are the MurmurHash64A (with seed `0`) of `item`, from which the bucket
+/// indices are taken, and the upper 8 bits are the fingerprint (`hash % 255 + 1`).
+///
+/// [`raw::ScalableCuckooFilter::contains128`]: crate::raw::ScalableCuckooFilter::contains128
+pub fn item_hash(item: &[u8]) -> u128 {
+    let hash = murmur_hash64a(item, 0);
+    let fingerprint = hash % 255 + 1;
+    (u128::from(fingerprint << (64 - FINGERPRINT_BITWIDTH)) << 64) | u128::from(hash)
+}
+
+/// Returns the chunks of `filter` as pairs of a cursor and data, which are the arguments of
+/// the `CF.LOADCHUNK` commands to restore `filter` in Redis (in order).
+///
+/// RedisBloom can only represent filters whose layers have the same layout as its sub-filters,
+/// such as those loaded by [`ChunkLoader`] and extended by items hashed by [`item_hash`] as long
+/// as no layers have been added.
+///
+/// # Errors
+///
+/// Returns an error unless all of the following hold:
+///
+/// - The alternative index scheme is [`AltIndexScheme::Multiplicative`] and the storage backend
+///   is [`StorageBackend::Buckets`].
+/// - Fingerprints are 8 bits in all layers, and no entries are stashed.
+/// - The numbers of buckets are powers of two, each of which is the previous one multiplied by
+///   the same factor (at most `65535`).
+/// - There are at most `255` entries per bucket.
+///
+/// [`AltIndexScheme::Multiplicative`]: crate::AltIndexScheme::Multiplicative
+/// [`StorageBackend::Buckets`]: crate::StorageBackend::Buckets
+pub fn scan_dump<H: Hasher + Clone, R: Rng>(
+    filter: &raw::ScalableCuckooFilter<H, R>,
+) -> io::Result<Vec<(i64, Vec<u8>)>> {
+    let layers = &filter.filters;
+    let compatible = matches!(filter.alt_index_scheme, AltIndexScheme::Multiplicative)
+        && filter.entries_per_bucket <= usize::from(u8::MAX)
+        && layers.iter().all(|layer| {
+            layer.backend() == StorageBackend::Buckets
+                && layer.is_power_of_two()
+                && layer.fingerprint_bitwidth() == FINGERPRINT_BITWIDTH
+                && layer.stash_len() == 0
+        });
+    if !compatible {
+        return Err(incompatible());
+    }
+
+    let buckets = layers[0].bucket_count();
+    let expansion = match layers.get(1) {
+        // This crate doubles the capacity of the next layer by default.
+        None => 2,
+        Some(layer) => layer.bucket_count() / buckets,
+    };
+    if layer_bucket_counts(buckets as u64, layers.len(), expansion as u64).as_deref()
+        != Some(
+            &layers
+                .iter()
+                .map(CuckooFilter::bucket_count)
+                .collect::<Vec<_>>(),
+        )
+        || expansion > usize::from(u16::MAX)
+    {
+        return Err(incompatible());
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(&filter.len().to_le_bytes());
+    header.extend_from_slice(&(buckets as u64).to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&(layers.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(filter.entries_per_bucket as u16).to_le_bytes());
+    let max_iterations = filter.max_kicks.min(usize::from(u16::MAX)) as u16;
+    header.extend_from_slice(&max_iterations.to_le_bytes());
+    header.extend_from_slice(&(expansion as u16).to_le_bytes());
+
+    let mut chunks = vec![(1, header)];
+    let mut cursor = 1;
+    for layer in layers {
+        let entries = (0..layer.bucket_count()).flat_map(|i| {
+            (0..filter.entries_per_bucket).map(move |entry| layer.get_entry(i, entry) as u8)
+        });
+        let bytes = entries.collect::<Vec<_>>();
+        for chunk in bytes.chunks(CHUNK_BYTES) {
+            cursor += chunk.len() as i64;
+            chunks.push((cursor, chunk.to_vec()));
+        }
+    }
+    Ok(chunks)
+}
+
+/// Loader of the chunks returned by `CF.SCANDUMP` of RedisBloom.
+///
+/// The header (the chunk whose cursor is `1`) must be loaded first, and then the other chunks in
+/// any order.
+#[derive(Default)]
+pub struct ChunkLoader {
+    header: Option<Header>,
+    layers: Vec<Vec<u8>>,
+}
+impl ChunkLoader {
+    /// Makes a new `ChunkLoader` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a chunk returned by `CF.SCANDUMP` with the cursor returned along with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header is malformed or describes a filter that this crate can't
+    /// represent, or if a chunk is loaded before the header or doesn't fit into the filter.
+    pub fn load_chunk(&mut self, cursor: i64, data: &[u8]) -> io::Result<()> {
+        if cursor == 1 {
+            let header = Header::parse(data)?;
+            self.layers = header
+                .bucket_counts
+                .iter()
+                .map(|&buckets| vec![0; buckets * header.bucket_size])
+                .collect();
+            self.header = Some(header);
+            return Ok(());
+        }
+        if self.header.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the header must be loaded first",
+            ));
+        }
+
+        // The cursor returned with a chunk is one plus the offset of the end of the chunk.
+        let total = self.layers.iter().map(Vec::len).sum::<usize>();
+        let mut offset = usize::try_from(cursor)
+            .ok()
+            .and_then(|end| end.checked_sub(data.len() + 1))
+            .filter(|offset| !data.is_empty() && offset + data.len() <= total)
+            .ok_or_else(|| codec::invalid_data("chunk doesn't fit into the filter"))?;
+        let mut data = data;
+        for layer in &mut self.layers {
+            if offset >= layer.len() {
+                offset -= layer.len();
+                continue;
+            }
+            let n = (layer.len() - offset).min(data.len());
+            layer[offset..offset + n].copy_from_slice(&data[..n]);
+            data = &data[n..];
+            if data.is_empty() {
+                break;
+            }
+            offset = 0;
+        }
+        Ok(())
+    }
+
+    /// Builds the filter from the loaded chunks.
+    ///
+    /// Layers added to the filter later use a false positive probability that halves at each
+    /// layer from the sum of the bounds of the loaded layers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no header has been loaded.
+    pub fn finish(self) -> io::Result<raw::ScalableCuckooFilter> {
+        let header = self.header.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no header has been loaded")
+        })?;
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(header.bucket_counts[0] * header.bucket_size)
+            .entries_per_bucket(header.bucket_size)
+            .max_kicks(header.max_iterations)
+            .alt_index_scheme(AltIndexScheme::Multiplicative)
+            .bucket_counts(header.bucket_counts.iter().copied())
+            .build_raw()
+            .map_err(|e| codec::invalid_data(&e.to_string()))?;
+
+        filter.filters = (header.bucket_counts.iter().zip(self.layers))
+            .map(|(&buckets, bytes)| {
+                let mut layer = CuckooFilter::new(
+                    FINGERPRINT_BITWIDTH,
+                    header.bucket_size,
+                    buckets * header.bucket_size,
+                    filter.max_kicks,
+                    filter.insertion_strategy,
+                    StorageBackend::Buckets,
+                    AltIndexScheme::Multiplicative,
+                    true,
+                    filter.allocator.clone(),
+                );
+                for (i, &fingerprint) in bytes.iter().enumerate() {
+                    if fingerprint != 0 {
+                        layer.set_entry(
+                            i / header.bucket_size,
+                            i % header.bucket_size,
+                            u64::from(fingerprint),
+                        );
+                    }
+                }
+                layer
+            })
+            .collect();
+        filter.false_positive_probability =
+            (2.0 * filter.false_positive_probability_bound()).min(1.0);
+        Ok(filter)
+    }
+}
+impl fmt::Debug for ChunkLoader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChunkLoader")
+            .field("header", &self.header)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Parts of the header needed to rebuild a filter.
+#[derive(Debug)]
+struct Header {
+    bucket_counts: Vec<usize>,
+    bucket_size: usize,
+    max_iterations: usize,
+}
+impl Header {
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() != HEADER_LEN {
+            return Err(codec::invalid_data(
+                "not a header of a RedisBloom cuckoo filter",
+            ));
+        }
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        let u16_at = |i: usize| u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap());
+        let (buckets, filters) = (u64_at(8), u64_at(24));
+        let (bucket_size, max_iterations, expansion) = (u16_at(32), u16_at(34), u16_at(36));
+
+        let bucket_counts = usize::try_from(filters)
+            .ok()
+            .filter(|&filters| filters > 0 && (1..=255).contains(&bucket_size))
+            .and_then(|filters| layer_bucket_counts(buckets, filters, u64::from(expansion)))
+            .filter(|counts| {
+                let bytes = counts.iter().try_fold(0usize, |sum, n| {
+                    sum.checked_add(n.checked_mul(usize::from(bucket_size))?)
+                });
+                counts.iter().all(|n| n.is_power_of_two())
+                    && bytes.is_some_and(|bytes| bytes <= isize::MAX as usize)
+            });
+        let Some(bucket_counts) = bucket_counts else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the layout of the RedisBloom cuckoo filter isn't supported",
+            ));
+        };
+        Ok(Header {
+            bucket_counts,
+            bucket_size: usize::from(bucket_size),
+            max_iterations: usize::from(max_iterations),
+        })
+    }
+}
+
+/// Returns the numbers of buckets of the sub-filters of a RedisBloom cuckoo filter, which are
+/// `buckets * expansion^i`, or `None` if they overflow.
+fn layer_bucket_counts(buckets: u64, filters: usize, expansion: u64) -> Option<Vec<usize>> {
+    let mut n = buckets;
+    (0..filters)
+        .map(|i| {
+            if i > 0 {
+                n = n.checked_mul(expansion)?;
+            }
+            usize::try_from(n).ok().filter(|&n| n > 0)
+        })
+        .collect()
+}
+
+fn incompatible() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the filter can't be represented as a RedisBloom cuckoo filter",
+    )
+}
+
+/// MurmurHash64A by Austin Appleby, which RedisBloom hashes items with.
+fn murmur_hash64a(bytes: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (bytes.len() as u64).wrapping_mul(M);
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &b) in tail.iter().enumerate() {
+            h ^= u64::from(b) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Makes the chunks of a RedisBloom cuckoo filter with two sub-filters of 64 and 128 buckets,
+    /// adding `items` as `CF.ADD` does (skipping those that would kick other entries).
+    fn redis_chunks(items: &[&[u8]]) -> (Vec<(i64, Vec<u8>)>, Vec<usize>) {
+        let (buckets, bucket_size) = ([64usize, 128], 2);
+        let mut filters = buckets.map(|n| vec![0u8; n * bucket_size]);
+        let mut added = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let hash = murmur_hash64a(item, 0);
+            let fingerprint = (hash % 255 + 1) as u8;
+            let alt_hash = (u64::from(fingerprint) * 0x5bd1_e995) ^ hash;
+            let filter = i % 2;
+            let n = buckets[filter] as u64;
+            let free = [hash % n, alt_hash % n].into_iter().find_map(|b| {
+                let b = b as usize * bucket_size;
+                (b..b + bucket_size).find(|&e| filters[filter][e] == 0)
+            });
+            if let Some(e) = free {
+                filters[filter][e] = fingerprint;
+                added.push(i);
+            }
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(added.len() as u64).to_le_bytes());
+        header.extend_from_slice(&64u64.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&2u64.to_le_bytes());
+        for n in [bucket_size as u16, 20, 2] {
+            header.extend_from_slice(&n.to_le_bytes());
+        }
+        let [first, second] = filters;
+        let chunks = vec![(1, header), (1 + 128, first), (1 + 128 + 256, second)];
+        (chunks, added)
+    }
+
+    #[test]
+    fn murmur_hash64a_works() {
+        assert_eq!(murmur_hash64a(b"", 0), 0);
+        assert_eq!(murmur_hash64a(b"foo", 0), 0xcdde_3835_8fd2_5b01);
+        assert_eq!(murmur_hash64a(b"hello world", 0), 0xd3ba_2368_a832_afce);
+        assert_eq!(
+            murmur_hash64a(b"0123456789abcdef!", 0),
+            0x7866_985d_a0fe_6924
+        );
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let items = (0..200).map(|i| format!("item-{i}")).collect::<Vec<_>>();
+        let items = items.iter().map(|i| i.as_bytes()).collect::<Vec<_>>();
+        let (chunks, added) = redis_chunks(&items);
+
+        // Chunks other than the header may be loaded in any order.
+        let mut loader = ChunkLoader::new();
+        loader.load_chunk(chunks[0].0, &chunks[0].1).unwrap();
+        for (cursor, data) in chunks[1..].iter().rev() {
+            loader.load_chunk(*cursor, data).unwrap();
+        }
+        let mut filter = loader.finish().unwrap();
+        assert_eq!(filter.len(), added.len() as u64);
+        assert_eq!(filter.bucket_counts(), [64, 128]);
+        for &i in &added {
+            assert!(filter.contains128(item_hash(items[i])));
+        }
+        assert_eq!(scan_dump(&filter).unwrap(), chunks);
+
+        // Added items are found by Redis in the same way.
+        filter.insert128(item_hash(b"foo"));
+        assert!(filter.contains128(item_hash(b"foo")));
+        let chunks = scan_dump(&filter).unwrap();
+        let mut loader = ChunkLoader::new();
+        for (cursor, data) in &chunks {
+            loader.load_chunk(*cursor, data).unwrap();
+        }
+        assert!(loader.finish().unwrap().contains128(item_hash(b"foo")));
+    }
+
+    #[test]
+    fn invalid_chunks_are_rejected() {
+        let (chunks, _) = redis_chunks(&[b"foo"]);
+
+        let mut loader = ChunkLoader::new();
+        assert!(loader.load_chunk(chunks[1].0, &chunks[1].1).is_err());
+        assert!(loader.load_chunk(1, &chunks[0].1[1..]).is_err());
+        let mut header = chunks[0].1.clone();
+        header[8] = 63;
+        assert!(loader.load_chunk(1, &header).is_err());
+        assert!(ChunkLoader::new().finish().is_err());
+
+        loader.load_chunk(1, &chunks[0].1).unwrap();
+        assert!(loader.load_chunk(chunks[2].0 + 1, &chunks[2].1).is_err());
+        assert!(loader.load_chunk(chunks[1].0, &[]).is_err());
+        assert!(loader.load_chunk(-1, &chunks[1].1).is_err());
+
+        let filter = ScalableCuckooFilterBuilder::new().finish_raw();
+        assert!(scan_dump(&filter).is_err());
+    }
+}
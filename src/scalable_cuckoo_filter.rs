@@ -1,43 +1,164 @@
-use rand::{rngs::ThreadRng, Rng};
+use rand::rngs::{StdRng, ThreadRng};
+use rand::{Rng, RngCore, SeedableRng};
 use siphasher::sip::SipHasher13;
-use std::hash::{Hash, Hasher};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::ops::{BitOr, BitOrAssign};
+use std::path::Path;
+use std::sync::Arc;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::cuckoo_filter::CuckooFilter;
+#[cfg(feature = "small_rng")]
+use rand::rngs::SmallRng;
+
+use crate::alloc::{AllocBytes, Allocator};
+use crate::bloom::BloomFilter;
+use crate::buffered::BufferedInserter;
+use crate::build_error::BuildError;
+use crate::cuckoo_filter::{
+    AltIndexScheme, CuckooFilter, HashWidth, InsertionStrategy, StorageBackend,
+};
+use crate::dump::FilterDump;
+use crate::events::{EventListener, Listener};
+use crate::frozen::FrozenScalableCuckooFilter;
+use crate::growth::{DefaultGrowthPolicy, GrowthPolicy, LayerInfo};
+use crate::hashers::{BuildHasherAdapter, HasherKeys};
+use crate::integrity::IntegrityError;
+use crate::raw;
+use crate::stats::{FilterCounters, FilterStats, StoredFingerprint};
+#[cfg(feature = "xor_filter")]
+use crate::xor_filter::XorFilter;
 
 /// Default Hasher.
+///
+/// This is `SipHasher13`, which is stable across platforms and versions.
+/// [`ScalableCuckooFilterBuilder::new`] keys it randomly for each filter, so that
+/// an adversary can't precompute items that collide in the filter (hash flooding),
+/// whereas `DefaultHasher::default()` uses fixed keys.
+/// The keys are serialized with the filter (see [`HasherKeys`]).
+/// The `ahash` and `xxhash` features replace it with a faster non-cryptographic hasher
+/// (`ahash::AHasher` and `xxhash_rust::xxh3::Xxh3` respectively; `ahash` takes precedence
+/// if both are enabled), which makes inserting and looking up items notably cheaper.
+/// The trade-off is that their keys can't be serialized, so they are always fixed, and
+/// an adversary who can choose the items can craft many of them that share buckets and
+/// fingerprints, forcing the filter to grow and to report false positives.
+/// Also note that `ahash` may hash differently on other platforms or versions, so filters
+/// built with it shouldn't be deserialized elsewhere.
+#[cfg(not(any(feature = "ahash", feature = "xxhash")))]
 pub type DefaultHasher = SipHasher13;
 
+/// Default Hasher.
+///
+/// This is `ahash::AHasher` because the `ahash` feature is enabled.
+/// It is much faster than SipHash but not designed to resist hash flooding, and
+/// may hash differently on other platforms or versions, so filters built with it
+/// shouldn't be deserialized elsewhere.
+#[cfg(feature = "ahash")]
+pub type DefaultHasher = ahash::AHasher;
+
+/// Default Hasher.
+///
+/// This is `xxhash_rust::xxh3::Xxh3` because the `xxhash` feature is enabled.
+/// It is much faster than SipHash but not designed to resist hash flooding.
+#[cfg(all(feature = "xxhash", not(feature = "ahash")))]
+pub type DefaultHasher = xxhash_rust::xxh3::Xxh3;
+
+/// Makes a `DefaultHasher` keyed randomly by `rng`.
+#[cfg(not(any(feature = "ahash", feature = "xxhash")))]
+pub(crate) fn default_hasher(rng: &mut dyn RngCore) -> DefaultHasher {
+    SipHasher13::new_with_keys(rng.gen(), rng.gen())
+}
+
+/// Makes a `DefaultHasher` with the fixed keys, because its keys can't be serialized.
+#[cfg(any(feature = "ahash", feature = "xxhash"))]
+pub(crate) fn default_hasher(_rng: &mut dyn RngCore) -> DefaultHasher {
+    DefaultHasher::default()
+}
+
 /// Default random number generator.
 pub type DefaultRng = ThreadRng;
 
 /// Builder for `ScalableCuckooFilter`.
+///
+/// The item type `T` of the filter to be built is fixed by [`ScalableCuckooFilter::builder`]
+/// or [`ScalableCuckooFilterBuilder::for_type`], so that [`finish`] needs no type annotation.
+/// Otherwise (`T` is [`Untyped`]), it is given to [`finish`] or inferred from how the filter is used.
+///
+/// [`finish`]: ScalableCuckooFilterBuilder::finish
 #[derive(Debug)]
-pub struct ScalableCuckooFilterBuilder<H = DefaultHasher, R = DefaultRng> {
+pub struct ScalableCuckooFilterBuilder<H = DefaultHasher, R = DefaultRng, T: ?Sized = Untyped> {
     initial_capacity: usize,
     false_positive_probability: f64,
+    tightening_ratio: f64,
     entries_per_bucket: usize,
     max_kicks: usize,
+    insertion_strategy: InsertionStrategy,
+    storage_backend: StorageBackend,
+    hash_width: HashWidth,
+    alt_index_scheme: AltIndexScheme,
+    power_of_two_buckets: bool,
+    bucket_counts: Vec<usize>,
+    stash_capacity: usize,
+    stash_enabled: bool,
+    grow_at_load_factor: Option<f64>,
+    pre_touch_pages: bool,
+    transparent_hugepages: bool,
+    growth_policy: Arc<dyn GrowthPolicy>,
+    listener: Listener,
+    metrics_label: Option<Arc<str>>,
+    allocator: Allocator,
     hasher: H,
+    // Makes the hasher from the final random number generator unless a hasher is given.
+    hasher_from_rng: Option<fn(&mut dyn RngCore) -> H>,
     rng: R,
+    _item: PhantomData<T>,
 }
 impl ScalableCuckooFilterBuilder<DefaultHasher, DefaultRng> {
     /// Makes a new `ScalableCuckooFilterBuilder` instance.
+    ///
+    /// The hasher is keyed randomly by the random number generator when the filter is built,
+    /// so each filter hashes items differently (see [`DefaultHasher`]) unless the generator
+    /// is seeded. Use [`hasher_key`] or [`seed`] to build filters that hash items identically.
+    ///
+    /// [`hasher_key`]: ScalableCuckooFilterBuilder::hasher_key
+    /// [`seed`]: ScalableCuckooFilterBuilder::seed
     pub fn new() -> Self {
         ScalableCuckooFilterBuilder {
             initial_capacity: 100_000,
             false_positive_probability: 0.001,
+            tightening_ratio: 0.5,
             entries_per_bucket: 4,
             max_kicks: 512,
-            hasher: SipHasher13::new(),
+            insertion_strategy: InsertionStrategy::RandomWalk,
+            storage_backend: StorageBackend::Buckets,
+            hash_width: HashWidth::Bits128,
+            alt_index_scheme: AltIndexScheme::Mixed,
+            power_of_two_buckets: true,
+            bucket_counts: Vec::new(),
+            stash_capacity: 0,
+            stash_enabled: true,
+            grow_at_load_factor: None,
+            pre_touch_pages: false,
+            transparent_hugepages: false,
+            growth_policy: Arc::new(DefaultGrowthPolicy),
+            listener: Listener::default(),
+            metrics_label: None,
+            allocator: Allocator::default(),
+            hasher: DefaultHasher::default(),
+            hasher_from_rng: Some(default_hasher),
             rng: rand::thread_rng(),
+            _item: PhantomData,
         }
     }
 }
-impl<H: Hasher + Clone, R: Rng> ScalableCuckooFilterBuilder<H, R> {
+impl<H: Hasher + Clone, R: Rng, T: ?Sized> ScalableCuckooFilterBuilder<H, R, T> {
     /// Sets the initial capacity (i.e., the number of estimated maximum items) of this filter.
     ///
     /// The default value is `100_000`.
+    /// It must be positive.
     #[must_use]
     pub fn initial_capacity(mut self, capacity_hint: usize) -> Self {
         self.initial_capacity = capacity_hint;
@@ -47,20 +168,44 @@ impl<H: Hasher + Clone, R: Rng> ScalableCuckooFilterBuilder<H, R> {
     /// Sets the expected upper bound of the false positive probability of this filter.
     ///
     /// The default value is `0.001`.
+    /// It must be a number in the range `(0.0, 1.0]` that the fingerprints of the first layer
+    /// can achieve (see [`BuildError::FingerprintTooWide`]).
+    /// Later layers whose share of the probability requires fingerprints longer than 64 bits
+    /// (32 bits with [`HashWidth::Bits32`]) use the longest ones possible instead
+    /// (see [`FilterEvent::FingerprintClamped`]), so the probability isn't bounded by this value
+    /// any longer once such layers are added.
     ///
-    /// # Panics
-    ///
-    /// This method panics if `probability` is not a non-negative number smaller than or equal to `1.0`.
+    /// [`FilterEvent::FingerprintClamped`]: crate::FilterEvent::FingerprintClamped
     #[must_use]
     pub fn false_positive_probability(mut self, probability: f64) -> Self {
-        assert!(0.0 < probability && probability <= 1.0);
         self.false_positive_probability = probability;
         self
     }
 
+    /// Sets the tightening ratio of the false positive probability of this filter.
+    ///
+    /// The `i`-th layer (zero origin) of this filter is created with the false positive probability
+    /// `false_positive_probability * (1 - ratio) * ratio^i`, so the sum over all layers stays under
+    /// the configured `false_positive_probability` unless the fingerprints of a layer are clamped
+    /// (see [`false_positive_probability`]).
+    ///
+    /// [`false_positive_probability`]: ScalableCuckooFilterBuilder::false_positive_probability
+    ///
+    /// A smaller ratio makes the fingerprints of later layers grow faster, while a larger ratio
+    /// leaves a smaller part of the error budget to the first layer.
+    ///
+    /// The default value is `0.5`.
+    /// It must be a number in the range `(0.0, 1.0)`.
+    #[must_use]
+    pub fn tightening_ratio(mut self, ratio: f64) -> Self {
+        self.tightening_ratio = ratio;
+        self
+    }
+
     /// Sets the number of entries per bucket of this filter.
     ///
     /// The default value is `4`.
+    /// It must be positive.
     #[must_use]
     pub fn entries_per_bucket(mut self, n: usize) -> Self {
         self.entries_per_bucket = n;
@@ -78,363 +223,3514 @@ impl<H: Hasher + Clone, R: Rng> ScalableCuckooFilterBuilder<H, R> {
         self
     }
 
+    /// Sets the strategy for relocating entries when both candidate buckets of an item are full.
+    ///
+    /// The default value is `InsertionStrategy::RandomWalk`.
+    #[must_use]
+    pub fn insertion_strategy(mut self, strategy: InsertionStrategy) -> Self {
+        self.insertion_strategy = strategy;
+        self
+    }
+
+    /// Sets the layout of the fingerprints of each layer.
+    ///
+    /// The default value is `StorageBackend::Buckets`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilterBuilder, StorageBackend};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(1000)
+    ///     .storage_backend(StorageBackend::Morton)
+    ///     .finish();
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
+    /// }
+    /// assert!(filter.contains(&10));
+    /// ```
+    #[must_use]
+    pub fn storage_backend(mut self, backend: StorageBackend) -> Self {
+        self.storage_backend = backend;
+        self
+    }
+
+    /// Sets the width of the hashes derived from items.
+    ///
+    /// With [`HashWidth::Bits64`], the bucket index and the fingerprint are taken from
+    /// the same 64-bit hash, so they begin to overlap once a layer has more than
+    /// `2^(64 - fingerprint_bitwidth)` buckets, which raises the false positive rate of
+    /// huge filters, and items whose hashes share the index bits are more likely to share
+    /// the fingerprint bits too. [`HashWidth::Bits128`] derives the fingerprint from
+    /// an independently keyed hash of each item to avoid both.
+    /// [`HashWidth::Bits32`] folds the hash into 32 bits for small filters, which limits
+    /// fingerprints to 32 bits but halves the size of the stash entries.
+    ///
+    /// The default value is `HashWidth::Bits128`.
+    /// Filters deserialized from data that predates this option use `HashWidth::Bits64`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{HashWidth, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(1000)
+    ///     .hash_width(HashWidth::Bits128)
+    ///     .finish();
+    /// filter.insert("foo");
+    /// assert!(filter.contains("foo"));
+    /// ```
+    #[must_use]
+    pub fn hash_width(mut self, width: HashWidth) -> Self {
+        self.hash_width = width;
+        self
+    }
+
+    /// Sets the scheme for deriving the alternative bucket index of an entry from its fingerprint.
+    ///
+    /// This is mainly useful to reproduce the bucket layout of another cuckoo filter
+    /// implementation.
+    ///
+    /// The default value is `AltIndexScheme::Mixed`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{AltIndexScheme, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(1000)
+    ///     .alt_index_scheme(AltIndexScheme::Custom(|fingerprint| fingerprint << 1))
+    ///     .finish();
+    /// filter.insert("foo");
+    /// assert!(filter.contains("foo"));
+    /// ```
+    #[must_use]
+    pub fn alt_index_scheme(mut self, scheme: AltIndexScheme) -> Self {
+        self.alt_index_scheme = scheme;
+        self
+    }
+
+    /// Sets whether the number of buckets of each layer is rounded up to a power of two.
+    ///
+    /// Rounding up makes bucket indices cheap to compute (a mask instead of a division),
+    /// but a layer sized just past a power of two nearly doubles its memory.
+    /// If this is `false`, layers have exactly as many buckets as their capacity requires.
+    /// Layers with such bucket counts aren't shrunk by [`ScalableCuckooFilter::shrink_to_fit`]
+    /// (use [`ScalableCuckooFilter::freeze_compacted`] to pack them tightly instead).
+    ///
+    /// This only affects the `StorageBackend::Buckets` and `StorageBackend::Blocked` backends.
+    ///
+    /// The default value is `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilterBuilder;
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(1100)
+    ///     .power_of_two_buckets(false)
+    ///     .finish();
+    /// assert_eq!(filter.capacity(), 1100);
+    /// filter.insert("foo");
+    /// assert!(filter.contains("foo"));
+    /// ```
+    #[must_use]
+    pub fn power_of_two_buckets(mut self, enabled: bool) -> Self {
+        self.power_of_two_buckets = enabled;
+        self
+    }
+
+    /// Sets the exact numbers of buckets of the first layers.
+    ///
+    /// The `i`-th layer has `counts[i]` buckets of `entries_per_bucket` entries, regardless of
+    /// `initial_capacity`, the growth policy and `power_of_two_buckets`.
+    /// Each layer beyond the given counts has twice as many buckets as its previous layer.
+    /// Combined with the fingerprint length derived from the false positive probability,
+    /// this determines the memory usage of each layer exactly.
+    ///
+    /// This only affects the `StorageBackend::Buckets` and `StorageBackend::Blocked` backends.
+    ///
+    /// By default, the numbers of buckets are derived from the capacities of layers.
+    /// None of `counts` may be zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilterBuilder;
+    ///
+    /// let filter = ScalableCuckooFilterBuilder::new()
+    ///     .bucket_counts([1000, 3000])
+    ///     .finish::<str>();
+    /// assert_eq!(filter.capacity(), 4000);
+    /// ```
+    #[must_use]
+    pub fn bucket_counts<I: IntoIterator<Item = usize>>(mut self, counts: I) -> Self {
+        self.bucket_counts = counts.into_iter().collect();
+        self
+    }
+
+    /// Sets the number of kicked out entries the last layer can hold in its stash without growing.
+    ///
+    /// An entry is kicked out to the stash when an insertion fails to find a free entry within
+    /// `max_kicks` relocations. The filter grows when the stash of the last layer holds
+    /// more than `n` such entries, so a larger value prevents small filters from growing
+    /// prematurely on a few unlucky insertions.
+    ///
+    /// The default value is `0`.
+    #[must_use]
+    pub fn stash_capacity(mut self, n: usize) -> Self {
+        self.stash_capacity = n;
+        self
+    }
+
+    /// Disables the stash for kicked out entries.
+    ///
+    /// By default, an entry that can't be placed within `max_kicks` relocations is kept in
+    /// a sorted side vector (the stash) of the layer.
+    /// If the stash is disabled, the relocations are rolled back instead and the item is
+    /// inserted into a newly added layer, which gives pure cuckoo semantics.
+    ///
+    /// Note that items whose fingerprint is zero are still kept aside, because such fingerprints
+    /// can't be represented in buckets. They occur with probability `2^-fingerprint_bitwidth`.
+    #[must_use]
+    pub fn disable_stash(mut self) -> Self {
+        self.stash_enabled = false;
+        self
+    }
+
+    /// Makes this filter grow as soon as the load factor of the last layer reaches `load_factor`.
+    ///
+    /// By default, a new layer is added only when an insertion fails to find a free entry
+    /// within `max_kicks` relocations, so the last layer tends to become very full and
+    /// the insertions just before the growth become slow.
+    /// Setting this option adds a new layer proactively instead.
+    ///
+    /// `load_factor` must be a number in the range `(0.0, 1.0]`.
+    #[must_use]
+    pub fn grow_at_load_factor(mut self, load_factor: f64) -> Self {
+        self.grow_at_load_factor = Some(load_factor);
+        self
+    }
+
+    /// Sets the growth policy of this filter.
+    ///
+    /// The default value is `DefaultGrowthPolicy`.
+    #[must_use]
+    pub fn growth_policy<P: GrowthPolicy + 'static>(mut self, policy: P) -> Self {
+        self.growth_policy = Arc::new(policy);
+        self
+    }
+
+    /// Sets the listener notified of the growth and stash events of this filter.
+    ///
+    /// See [`EventListener`] for an example.
+    #[must_use]
+    pub fn event_listener<L: EventListener + 'static>(mut self, listener: L) -> Self {
+        self.listener = Listener::new(listener);
+        self
+    }
+
+    /// Sets the value of the `filter` label of the `scalable_cuckoo_filter_bits` gauge
+    /// of this filter.
+    ///
+    /// The gauge is recorded only for filters with a label, so that filters don't overwrite
+    /// each other's sizes. This has no effect unless the `metrics` feature is enabled.
+    #[must_use]
+    pub fn metrics_label(mut self, label: &str) -> Self {
+        self.metrics_label = Some(label.into());
+        self
+    }
+
+    /// Sets the allocator of the bit arrays of this filter.
+    ///
+    /// The allocator isn't serialized, and a deserialized filter uses the global allocator.
+    /// See [`AllocBytes`] for an example.
+    ///
+    /// The default is the global allocator.
+    #[must_use]
+    pub fn allocator(mut self, alloc: Arc<dyn AllocBytes>) -> Self {
+        self.allocator = Allocator::new(alloc);
+        self
+    }
+
+    /// Makes this filter write to every page of a layer right after allocating it.
+    ///
+    /// Otherwise the operating system may map the pages lazily, and the first insertions
+    /// into a new layer take page faults, which shows up as latency spikes after each growth.
+    /// Setting this option moves the cost into the insertion that adds the layer.
+    ///
+    /// The default value is `false`.
+    #[must_use]
+    pub fn pre_touch_pages(mut self, enabled: bool) -> Self {
+        self.pre_touch_pages = enabled;
+        self
+    }
+
+    /// Makes this filter ask the kernel to back its layers with transparent hugepages
+    /// (`madvise(MADV_HUGEPAGE)`).
+    ///
+    /// Hugepages reduce TLB misses of lookups in large filters.
+    /// This option has an effect only on Linux with transparent hugepages enabled in
+    /// the `madvise` or `always` mode, and only on the whole hugepages within each layer.
+    ///
+    /// The default value is `false`.
+    #[must_use]
+    pub fn transparent_hugepages(mut self, enabled: bool) -> Self {
+        self.transparent_hugepages = enabled;
+        self
+    }
+
     /// Sets the hasher of this filter.
     ///
-    /// The default value if `DefaultHasher::new()`.
-    pub fn hasher<T: Hasher>(self, hasher: T) -> ScalableCuckooFilterBuilder<T, R> {
+    /// The default value is `DefaultHasher` keyed randomly by the random number generator
+    /// (see [`ScalableCuckooFilterBuilder::new`]).
+    pub fn hasher<X: Hasher>(self, hasher: X) -> ScalableCuckooFilterBuilder<X, R, T> {
         ScalableCuckooFilterBuilder {
             initial_capacity: self.initial_capacity,
             false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
             entries_per_bucket: self.entries_per_bucket,
             max_kicks: self.max_kicks,
+            insertion_strategy: self.insertion_strategy,
+            storage_backend: self.storage_backend,
+            hash_width: self.hash_width,
+            alt_index_scheme: self.alt_index_scheme,
+            power_of_two_buckets: self.power_of_two_buckets,
+            bucket_counts: self.bucket_counts,
+            stash_capacity: self.stash_capacity,
+            stash_enabled: self.stash_enabled,
+            grow_at_load_factor: self.grow_at_load_factor,
+            pre_touch_pages: self.pre_touch_pages,
+            transparent_hugepages: self.transparent_hugepages,
+            growth_policy: self.growth_policy,
+            listener: self.listener,
+            metrics_label: self.metrics_label,
+            allocator: self.allocator,
             hasher,
+            hasher_from_rng: None,
             rng: self.rng,
+            _item: PhantomData,
         }
     }
 
+    /// Sets the hasher of this filter to the one made by `build_hasher`.
+    ///
+    /// See [`BuildHasherAdapter`] for details.
+    pub fn build_hasher<S>(
+        self,
+        build_hasher: S,
+    ) -> ScalableCuckooFilterBuilder<BuildHasherAdapter<S>, R, T>
+    where
+        S: BuildHasher + Clone,
+        S::Hasher: Clone,
+    {
+        self.hasher(BuildHasherAdapter::new(build_hasher))
+    }
+
     /// Sets the random number generator of this filter.
     ///
     /// The default value is `rand::thread_rng()`.
-    pub fn rng<T: Rng>(self, rng: T) -> ScalableCuckooFilterBuilder<H, T> {
+    pub fn rng<X: Rng>(self, rng: X) -> ScalableCuckooFilterBuilder<H, X, T> {
         ScalableCuckooFilterBuilder {
             initial_capacity: self.initial_capacity,
             false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
             entries_per_bucket: self.entries_per_bucket,
             max_kicks: self.max_kicks,
+            insertion_strategy: self.insertion_strategy,
+            storage_backend: self.storage_backend,
+            hash_width: self.hash_width,
+            alt_index_scheme: self.alt_index_scheme,
+            power_of_two_buckets: self.power_of_two_buckets,
+            bucket_counts: self.bucket_counts,
+            stash_capacity: self.stash_capacity,
+            stash_enabled: self.stash_enabled,
+            grow_at_load_factor: self.grow_at_load_factor,
+            pre_touch_pages: self.pre_touch_pages,
+            transparent_hugepages: self.transparent_hugepages,
+            growth_policy: self.growth_policy,
+            listener: self.listener,
+            metrics_label: self.metrics_label,
+            allocator: self.allocator,
             hasher: self.hasher,
+            hasher_from_rng: self.hasher_from_rng,
             rng,
+            _item: PhantomData,
         }
     }
 
-    /// Builds a `ScalableCuckooFilter` instance.
-    pub fn finish<T: Hash + ?Sized>(self) -> ScalableCuckooFilter<T, H, R> {
-        let mut filter = ScalableCuckooFilter {
-            hasher: self.hasher,
-            rng: self.rng,
+    /// Fixes the item type of the filter to be built to `U`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilterBuilder;
+    ///
+    /// let filter = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .initial_capacity(1000)
+    ///     .finish();
+    /// assert!(!filter.contains("foo"));
+    /// ```
+    pub fn for_type<U: Hash + ?Sized>(self) -> ScalableCuckooFilterBuilder<H, R, U> {
+        ScalableCuckooFilterBuilder {
             initial_capacity: self.initial_capacity,
             false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
             entries_per_bucket: self.entries_per_bucket,
             max_kicks: self.max_kicks,
-            filters: Vec::new(),
+            insertion_strategy: self.insertion_strategy,
+            storage_backend: self.storage_backend,
+            hash_width: self.hash_width,
+            alt_index_scheme: self.alt_index_scheme,
+            power_of_two_buckets: self.power_of_two_buckets,
+            bucket_counts: self.bucket_counts,
+            stash_capacity: self.stash_capacity,
+            stash_enabled: self.stash_enabled,
+            grow_at_load_factor: self.grow_at_load_factor,
+            pre_touch_pages: self.pre_touch_pages,
+            transparent_hugepages: self.transparent_hugepages,
+            growth_policy: self.growth_policy,
+            listener: self.listener,
+            metrics_label: self.metrics_label,
+            allocator: self.allocator,
+            hasher: self.hasher,
+            hasher_from_rng: self.hasher_from_rng,
+            rng: self.rng,
             _item: PhantomData,
-        };
-        filter.grow();
-        filter
-    }
-}
-impl Default for ScalableCuckooFilterBuilder {
-    fn default() -> Self {
-        Self::new()
+        }
     }
-}
 
-#[cfg(feature = "serde_support")]
-use serde::{Deserialize, Serialize};
-
-/// Scalable Cuckoo Filter.
-#[derive(Debug)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
-pub struct ScalableCuckooFilter<T: ?Sized, H = DefaultHasher, R = DefaultRng> {
-    #[cfg_attr(feature = "serde_support", serde(skip))]
-    hasher: H,
-    filters: Vec<CuckooFilter>,
-    initial_capacity: usize,
-    false_positive_probability: f64,
-    entries_per_bucket: usize,
-    max_kicks: usize,
-    #[cfg_attr(feature = "serde_support", serde(skip))]
-    rng: R,
-    _item: PhantomData<T>,
-}
-impl<T: Hash + ?Sized> ScalableCuckooFilter<T> {
-    /// Makes a new `ScalableCuckooFilter` instance.
+    /// Uses `SipHasher13` keyed with `key` as the hasher of this filter.
     ///
-    /// This is equivalent to the following expression:
+    /// Filters built with the same key hash items identically on any machine, so
+    /// a serialized filter remains queryable wherever it is deserialized with the same key,
+    /// while items colliding in the filter can't be precomputed without knowing the key.
+    ///
+    /// # Examples
     ///
     /// ```
-    /// # use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
-    /// # let initial_capacity = 10;
-    /// # let false_positive_probability = 0.1;
-    /// # let _: ScalableCuckooFilter<()> =
-    /// ScalableCuckooFilterBuilder::new()
-    ///     .initial_capacity(initial_capacity)
-    ///     .false_positive_probability(false_positive_probability)
-    ///     .finish()
-    /// # ;
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<str>::builder()
+    ///     .hasher_key(*b"0123456789abcdef")
+    ///     .finish();
+    /// filter.insert("foo");
+    /// assert!(filter.contains("foo"));
     /// ```
-    pub fn new(initial_capacity_hint: usize, false_positive_probability: f64) -> Self {
-        ScalableCuckooFilterBuilder::new()
-            .initial_capacity(initial_capacity_hint)
-            .false_positive_probability(false_positive_probability)
-            .finish()
-    }
-}
-impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ScalableCuckooFilter<T, H, R> {
-    /// Returns the approximate number of items inserted in this filter.
-    pub fn len(&self) -> usize {
-        self.filters.iter().map(|f| f.len()).sum()
-    }
-
-    /// Returns `true` if this filter contains no items, otherwise `false`.
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    pub fn hasher_key(self, key: [u8; 16]) -> ScalableCuckooFilterBuilder<SipHasher13, R, T> {
+        self.hasher(SipHasher13::new_with_key(&key))
     }
 
-    /// Returns the capacity (i.e., the upper bound of acceptable items count) of this filter.
+    /// Uses `SipHasher13` keyed randomly by the random number generator as the hasher
+    /// of this filter.
     ///
-    /// "capacity" is upper bound of the number of items can be inserted into the filter without resizing.
-    pub fn capacity(&self) -> usize {
-        self.filters.iter().map(|f| f.capacity()).sum()
+    /// Each filter then hashes items differently, so an adversary who can choose the items
+    /// can't precompute ones that collide in the filter (hash flooding) to force it to grow.
+    /// The keys are serialized with the filter (see [`HasherKeys`]), so a deserialized filter
+    /// hashes items the same way.
+    ///
+    /// The default hasher is keyed in this way as well unless the `ahash` or `xxhash` feature
+    /// is enabled (see [`DefaultHasher`]).
+    ///
+    /// [`HasherKeys`]: crate::HasherKeys
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    /// use siphasher::sip::SipHasher13;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<str>::builder()
+    ///     .random_hasher_key()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let bytes = filter.to_bytes().unwrap();
+    /// let filter = ScalableCuckooFilter::<str, SipHasher13>::from_bytes(&bytes).unwrap();
+    /// assert!(filter.contains("foo"));
+    /// ```
+    pub fn random_hasher_key(mut self) -> ScalableCuckooFilterBuilder<SipHasher13, R, T> {
+        let hasher = SipHasher13::new_with_keys(self.rng.gen(), self.rng.gen());
+        self.hasher(hasher)
     }
 
-    /// Returns the number of bits being used for representing this filter.
-    pub fn bits(&self) -> u64 {
-        self.filters.iter().map(|f| f.bits()).sum()
+    /// Derives both the hasher key and the state of the random number generator from `seed`.
+    ///
+    /// Two filters built with the same seed and parameters end up bit-identical after
+    /// the same sequence of operations (as long as the same versions of this crate and `rand`
+    /// are used). This is useful for replicated builds and golden-file tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilterBuilder;
+    ///
+    /// let mut a = ScalableCuckooFilterBuilder::new().seed([7; 32]).finish();
+    /// let mut b = ScalableCuckooFilterBuilder::new().seed([7; 32]).finish();
+    /// for i in 0..1000 {
+    ///     a.insert(&i);
+    ///     b.insert(&i);
+    /// }
+    /// assert_eq!(a.counters(), b.counters());
+    /// ```
+    pub fn seed(self, seed: [u8; 32]) -> ScalableCuckooFilterBuilder<SipHasher13, StdRng, T> {
+        let mut rng = StdRng::from_seed(seed);
+        let hasher = SipHasher13::new_with_keys(rng.gen(), rng.gen());
+        self.hasher(hasher).rng(rng)
     }
 
-    /// Returns the false positive probability.
-    pub fn false_positive_probability(&self) -> f64 {
-        self.false_positive_probability
+    /// Uses `SmallRng` seeded with `seed` as the random number generator of this filter.
+    ///
+    /// `SmallRng` is cheaper per kick than the default RNG, and a fixed seed makes
+    /// the placement of fingerprints reproducible (together with a fixed [`hasher_key`]).
+    ///
+    /// [`hasher_key`]: ScalableCuckooFilterBuilder::hasher_key
+    ///
+    /// This is available only if the `small_rng` feature is enabled.
+    #[cfg(feature = "small_rng")]
+    pub fn small_rng(self, seed: u64) -> ScalableCuckooFilterBuilder<H, SmallRng, T> {
+        self.rng(SmallRng::seed_from_u64(seed))
     }
 
-    /// Returns the number of elements in each buckets.
-    pub fn entries_per_bucket(&self) -> usize {
-        self.entries_per_bucket
+    /// Uses `SmallRng` seeded from the operating system as the random number generator of this filter.
+    ///
+    /// This is available only if the `small_rng` feature is enabled.
+    #[cfg(feature = "small_rng")]
+    pub fn small_rng_from_entropy(self) -> ScalableCuckooFilterBuilder<H, SmallRng, T> {
+        self.rng(SmallRng::from_entropy())
     }
 
-    /// Returns the number of kicks before the filter grows.
-    pub fn max_kicks(&self) -> usize {
-        self.max_kicks
+    /// Builds a [`raw::ScalableCuckooFilter`] instance that operates on 64-bit item hashes.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the configuration is invalid (see [`build_raw`]).
+    ///
+    /// [`build_raw`]: ScalableCuckooFilterBuilder::build_raw
+    pub fn finish_raw(self) -> raw::ScalableCuckooFilter<H, R> {
+        self.build_raw().unwrap_or_else(|e| panic!("{e}"))
     }
 
-    /// Returns `true` if this filter may contain `item`, otherwise `false`.
-    pub fn contains(&self, item: &T) -> bool {
-        let item_hash = crate::hash(&self.hasher, item);
-        self.filters
-            .iter()
-            .any(|f| f.contains(&self.hasher, item_hash))
+    /// Builds a [`raw::ScalableCuckooFilter`] instance that operates on 64-bit item hashes,
+    /// or returns an error if the configuration is invalid.
+    pub fn build_raw(self) -> Result<raw::ScalableCuckooFilter<H, R>, BuildError> {
+        self.build_raw_filter()
     }
 
-    /// Inserts `item` into this filter.
-    ///
-    /// If the current filter becomes full, it will be expanded automatically.
+    /// Builds a [`raw::ScalableCuckooFilter`] instance that has the layers and entries of `dump`
+    /// made by [`raw::ScalableCuckooFilter::dump`].
     ///
-    /// # Note
+    /// Unlike [`raw::ScalableCuckooFilter::restore`], the layers are made with the parameters
+    /// of `dump` instead of growing the filter, so the growth policy needn't match the dumped one.
     ///
-    /// Cuckoo Filter algorithm is unable to differentiate between two items with
-    /// the same fingerprint, so every [`insert`] method call will add a new entry
-    /// even if the same item is inserted multiple times.
+    /// # Errors
     ///
-    /// This behavior is necessary to avoid false negatives when using the [`remove`] method.
-    /// However, if you do not plan to use the [`remove`] method, you can prevent potential
-    /// duplicate insertions by checking for the existence of the item before insertion,
-    /// as shown below:
+    /// Returns an error if the configuration is invalid, if the hasher or configuration differs
+    /// from the dumped one, if the parameters of a layer of `dump` are invalid, if an entry is out
+    /// of the range of its layer, or if the alternative index scheme is [`AltIndexScheme::Custom`].
+    pub fn build_raw_from_dump(
+        self,
+        dump: &FilterDump,
+    ) -> io::Result<raw::ScalableCuckooFilter<H, R>> {
+        let mut filter = self
+            .build_raw_filter()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        filter.rebuild(dump)?;
+        Ok(filter)
+    }
+
+    fn build_raw_filter(mut self) -> Result<raw::ScalableCuckooFilter<H, R>, BuildError> {
+        self.validate()?;
+        if let Some(hasher_from_rng) = self.hasher_from_rng {
+            self.hasher = hasher_from_rng(&mut self.rng);
+        }
+        let mut filter = raw::ScalableCuckooFilter {
+            hasher: self.hasher,
+            rng: self.rng,
+            initial_capacity: self.initial_capacity,
+            false_positive_probability: self.false_positive_probability,
+            tightening_ratio: self.tightening_ratio,
+            entries_per_bucket: self.entries_per_bucket,
+            max_kicks: self.max_kicks,
+            insertion_strategy: self.insertion_strategy,
+            storage_backend: self.storage_backend,
+            hash_width: self.hash_width,
+            alt_index_scheme: self.alt_index_scheme,
+            power_of_two_buckets: self.power_of_two_buckets,
+            bucket_counts: self.bucket_counts,
+            stash_capacity: self.stash_capacity,
+            stash_enabled: self.stash_enabled,
+            grow_at_load_factor: self.grow_at_load_factor,
+            pre_touch_pages: self.pre_touch_pages,
+            transparent_hugepages: self.transparent_hugepages,
+            growth_policy: self.growth_policy,
+            listener: self.listener,
+            metrics_label: self.metrics_label,
+            allocator: self.allocator,
+            filters: Vec::new(),
+            counters: FilterCounters::default(),
+            version: 0,
+        };
+        filter.grow();
+        Ok(filter)
+    }
+
+    fn validate(&self) -> Result<(), BuildError> {
+        if self.initial_capacity == 0 {
+            return Err(BuildError::ZeroInitialCapacity);
+        }
+        let probability = self.false_positive_probability;
+        if !(0.0 < probability && probability <= 1.0) {
+            return Err(BuildError::InvalidFalsePositiveProbability { probability });
+        }
+        let ratio = self.tightening_ratio;
+        if !(0.0 < ratio && ratio < 1.0) {
+            return Err(BuildError::InvalidTighteningRatio { ratio });
+        }
+        if self.entries_per_bucket == 0 {
+            return Err(BuildError::ZeroEntriesPerBucket);
+        }
+        let bitwidth = raw::required_fingerprint_bitwidth(
+            self.entries_per_bucket,
+            probability * (1.0 - ratio),
+        );
+        let max_bitwidth = self.hash_width.max_fingerprint_bitwidth();
+        if bitwidth > max_bitwidth {
+            return Err(BuildError::FingerprintTooWide {
+                bitwidth,
+                max_bitwidth,
+            });
+        }
+        if let Some(layer) = self.bucket_counts.iter().position(|&n| n == 0) {
+            return Err(BuildError::ZeroBucketCount { layer });
+        }
+        if let Some(load_factor) = self.grow_at_load_factor {
+            if !(0.0 < load_factor && load_factor <= 1.0) {
+                return Err(BuildError::InvalidLoadFactor { load_factor });
+            }
+        }
+        Ok(())
+    }
+}
+impl<H: Hasher + Clone, R: Rng> ScalableCuckooFilterBuilder<H, R> {
+    /// Builds a `ScalableCuckooFilter` instance whose item type is `T`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the configuration is invalid (see [`build`]).
+    ///
+    /// [`build`]: ScalableCuckooFilterBuilder::build
+    pub fn finish<T: Hash + ?Sized>(self) -> ScalableCuckooFilter<T, H, R> {
+        ScalableCuckooFilter::from_raw(self.finish_raw())
+    }
+
+    /// Builds a `ScalableCuckooFilter` instance whose item type is `T`,
+    /// or returns an error if the configuration is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{BuildError, ScalableCuckooFilterBuilder};
+    ///
+    /// let result = ScalableCuckooFilterBuilder::new()
+    ///     .false_positive_probability(1.5)
+    ///     .build::<str>();
+    /// assert_eq!(
+    ///     result.unwrap_err(),
+    ///     BuildError::InvalidFalsePositiveProbability { probability: 1.5 }
+    /// );
+    /// ```
+    pub fn build<T: Hash + ?Sized>(self) -> Result<ScalableCuckooFilter<T, H, R>, BuildError> {
+        self.build_raw().map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Builds a `ScalableCuckooFilter` instance whose item type is `T` from `dump`
+    /// made by [`ScalableCuckooFilter::dump`].
+    ///
+    /// See [`build_raw_from_dump`] for details.
+    ///
+    /// [`build_raw_from_dump`]: ScalableCuckooFilterBuilder::build_raw_from_dump
+    pub fn build_from_dump<T: Hash + ?Sized>(
+        self,
+        dump: &FilterDump,
+    ) -> io::Result<ScalableCuckooFilter<T, H, R>> {
+        self.build_raw_from_dump(dump)
+            .map(ScalableCuckooFilter::from_raw)
+    }
+}
+impl<H: Hasher + Clone, R: Rng, T: Hash + ?Sized> ScalableCuckooFilterBuilder<H, R, T> {
+    /// Builds a `ScalableCuckooFilter` instance.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the configuration is invalid (see [`build`]).
+    ///
+    /// [`build`]: ScalableCuckooFilterBuilder::build
+    pub fn finish(self) -> ScalableCuckooFilter<T, H, R> {
+        ScalableCuckooFilter::from_raw(self.finish_raw())
+    }
+
+    /// Builds a `ScalableCuckooFilter` instance, or returns an error if the configuration is invalid.
+    pub fn build(self) -> Result<ScalableCuckooFilter<T, H, R>, BuildError> {
+        self.build_raw().map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Builds a `ScalableCuckooFilter` instance from `dump` made by [`ScalableCuckooFilter::dump`].
+    ///
+    /// See [`build_raw_from_dump`] for details.
+    ///
+    /// [`build_raw_from_dump`]: ScalableCuckooFilterBuilder::build_raw_from_dump
+    pub fn build_from_dump(self, dump: &FilterDump) -> io::Result<ScalableCuckooFilter<T, H, R>> {
+        self.build_raw_from_dump(dump)
+            .map(ScalableCuckooFilter::from_raw)
+    }
+}
+impl Default for ScalableCuckooFilterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Scalable Cuckoo Filter.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(
+        transparent,
+        bound(
+            serialize = "H: crate::HasherKeys",
+            deserialize = "H: Default + crate::HasherKeys, R: Default"
+        )
+    )
+)]
+pub struct ScalableCuckooFilter<T: ?Sized, H = DefaultHasher, R = DefaultRng> {
+    pub(crate) raw: raw::ScalableCuckooFilter<H, R>,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    _item: PhantomData<T>,
+}
+/// Item type of a [`ScalableCuckooFilterBuilder`] that is not fixed yet.
+///
+/// This type has no values and doesn't implement `Hash`, so filters of this type can't be built.
+#[derive(Debug)]
+pub enum Untyped {}
+
+impl<T: Hash + ?Sized> ScalableCuckooFilter<T> {
+    /// Makes a builder of `ScalableCuckooFilter<T>` instances.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<str>::builder()
+    ///     .initial_capacity(1000)
+    ///     .finish();
+    /// filter.insert("foo");
+    /// assert!(filter.contains("foo"));
+    /// ```
+    pub fn builder() -> ScalableCuckooFilterBuilder<DefaultHasher, DefaultRng, T> {
+        ScalableCuckooFilterBuilder::new().for_type()
+    }
+
+    /// Makes a new `ScalableCuckooFilter` instance.
+    ///
+    /// This is equivalent to the following expression:
+    ///
+    /// ```
+    /// # use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    /// # let initial_capacity = 10;
+    /// # let false_positive_probability = 0.1;
+    /// # let _: ScalableCuckooFilter<()> =
+    /// ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(initial_capacity)
+    ///     .false_positive_probability(false_positive_probability)
+    ///     .finish()
+    /// # ;
+    /// ```
+    pub fn new(initial_capacity_hint: usize, false_positive_probability: f64) -> Self {
+        ScalableCuckooFilterBuilder::new()
+            .initial_capacity(initial_capacity_hint)
+            .false_positive_probability(false_positive_probability)
+            .finish()
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> ScalableCuckooFilter<T, H> {
+    /// Makes a new `ScalableCuckooFilter` instance that uses `hasher`.
+    ///
+    /// This is equivalent to the following expression:
+    ///
+    /// ```
+    /// # use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    /// # use siphasher::sip::SipHasher24;
+    /// # let initial_capacity = 10;
+    /// # let false_positive_probability = 0.1;
+    /// # let hasher = SipHasher24::new();
+    /// # let _: ScalableCuckooFilter<(), SipHasher24> =
+    /// ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(initial_capacity)
+    ///     .false_positive_probability(false_positive_probability)
+    ///     .hasher(hasher)
+    ///     .finish()
+    /// # ;
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    /// use siphasher::sip::SipHasher24;
+    ///
+    /// let mut filter = ScalableCuckooFilter::with_hasher(1000, 0.001, SipHasher24::new());
+    /// filter.insert("foo");
+    /// assert!(filter.contains("foo"));
+    /// ```
+    pub fn with_hasher(
+        initial_capacity_hint: usize,
+        false_positive_probability: f64,
+        hasher: H,
+    ) -> Self {
+        ScalableCuckooFilterBuilder::new()
+            .initial_capacity(initial_capacity_hint)
+            .false_positive_probability(false_positive_probability)
+            .hasher(hasher)
+            .finish()
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ScalableCuckooFilter<T, H, R> {
+    /// Returns the approximate number of items inserted in this filter.
+    pub fn len(&self) -> u64 {
+        self.raw.len()
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity (i.e., the upper bound of acceptable items count) of this filter.
+    ///
+    /// "capacity" is upper bound of the number of items can be inserted into the filter without resizing.
+    pub fn capacity(&self) -> u64 {
+        self.raw.capacity()
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.raw.bits()
+    }
+
+    /// Returns the number of bytes of memory used by this filter.
+    ///
+    /// Unlike [`bits`], this counts the actual allocations including the spare capacity of
+    /// the stashes and the bookkeeping of each layer (see [`raw::ScalableCuckooFilter::memory_usage`]).
+    ///
+    /// [`bits`]: ScalableCuckooFilter::bits
+    ///
+    /// # Examples
     ///
     /// ```
     /// use scalable_cuckoo_filter::ScalableCuckooFilter;
     ///
     /// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
-    /// let items = ["foo", "bar", "foo", "baz"];
+    /// filter.insert("foo");
+    /// assert!(filter.memory_usage() as u64 * 8 > filter.bits());
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        self.raw.memory_usage()
+    }
+
+    /// Returns information about the layers (i.e., internal fixed size cuckoo filters) of this filter.
     ///
-    /// for item in &items {
-    ///     if !filter.contains(item) {
-    ///         filter.insert(item);
-    ///     }
+    /// The first element is the oldest layer and the last one is the layer new items are inserted into.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(100, 0.001);
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
     /// }
+    ///
+    /// let layers = filter.layers();
+    /// assert!(layers.len() > 1);
+    /// assert_eq!(layers.iter().map(|l| l.len() as u64).sum::<u64>(), filter.len());
     /// ```
-    pub fn insert(&mut self, item: &T) {
-        let item_hash = crate::hash(&self.hasher, item);
-        let last = self.filters.len() - 1;
-        self.filters[last].insert(&self.hasher, &mut self.rng, item_hash);
-        if self.filters[last].is_nearly_full() {
-            self.grow();
+    pub fn layers(&self) -> Vec<LayerInfo> {
+        self.raw.layers()
+    }
+
+    /// Returns an iterator over the entries stored in this filter, with the layers and
+    /// the buckets holding them.
+    ///
+    /// Layers are visited from the oldest one. The entries of a layer are yielded in the order of
+    /// their buckets, followed by the entries in the stash of the layer (not necessarily in order).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(100, 0.001);
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
+    /// }
+    ///
+    /// // Number of entries per layer.
+    /// let mut counts = vec![0; filter.layers().len()];
+    /// for entry in filter.fingerprints() {
+    ///     counts[entry.layer] += 1;
+    /// }
+    /// assert_eq!(counts.iter().sum::<u64>(), filter.len());
+    /// ```
+    pub fn fingerprints(&self) -> impl Iterator<Item = StoredFingerprint> + '_ {
+        self.raw.fingerprints()
+    }
+
+    /// Returns the ratio of the occupied bucket entries to the total bucket entries of this filter.
+    ///
+    /// Entries kept in stashes are not counted as occupied.
+    pub fn fill_ratio(&self) -> f64 {
+        self.raw.fill_ratio()
+    }
+
+    /// Returns the upper bound of the false positive probability guaranteed by the current layers.
+    ///
+    /// This is the sum of the per-layer bounds `2 * entries_per_bucket / 2^fingerprint_bitwidth`
+    /// and stays under [`false_positive_probability`] however many times the filter grows,
+    /// until the fingerprints of a layer are clamped (see [`FilterEvent::FingerprintClamped`]).
+    /// A clamped layer takes more than its share, so the bound exceeds the configured
+    /// probability after enough of them.
+    ///
+    /// [`false_positive_probability`]: ScalableCuckooFilter::false_positive_probability
+    /// [`FilterEvent::FingerprintClamped`]: crate::FilterEvent::FingerprintClamped
+    pub fn false_positive_probability_bound(&self) -> f64 {
+        self.raw.false_positive_probability_bound()
+    }
+
+    /// Returns the estimated false positive probability of this filter in its current state.
+    ///
+    /// Unlike [`false_positive_probability`], which returns the configured upper bound,
+    /// this value is computed from the occupancy and the fingerprint length of each layer.
+    ///
+    /// [`false_positive_probability`]: ScalableCuckooFilter::false_positive_probability
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+    /// assert_eq!(filter.estimated_false_positive_probability(), 0.0);
+    ///
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
+    /// }
+    /// let p = filter.estimated_false_positive_probability();
+    /// assert!(0.0 < p && p < filter.false_positive_probability());
+    /// ```
+    pub fn estimated_false_positive_probability(&self) -> f64 {
+        self.raw.estimated_false_positive_probability()
+    }
+
+    /// Sets the listener notified of the growth and stash events of this filter.
+    ///
+    /// This replaces the listener set by [`ScalableCuckooFilterBuilder::event_listener`], if any.
+    pub fn set_event_listener<L: EventListener + 'static>(&mut self, listener: L) {
+        self.raw.set_event_listener(listener);
+    }
+
+    /// Returns the cumulative counters of the operations performed on this filter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(100, 0.001);
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
+    /// }
+    /// assert!(!filter.remove(&1000));
+    ///
+    /// let counters = filter.counters();
+    /// assert!(counters.kicks > 0);
+    /// assert!(counters.growths > 0);
+    /// assert_eq!(counters.failed_removes, 1);
+    /// ```
+    pub fn counters(&self) -> FilterCounters {
+        self.raw.counters()
+    }
+
+    /// Validates the internal invariants of this filter.
+    ///
+    /// This checks, for example, that the item count of each layer matches the number of
+    /// the stored entries, that the stashes are sorted and consistently keyed, and that each
+    /// bucket entry is in a candidate bucket of its fingerprint (which can only be told if
+    /// the fingerprints and the bucket indices are taken from the same 64-bit or 32-bit hash).
+    /// It takes time proportional to the size of the filter, so it is intended for debugging
+    /// (e.g., investigating suspected corruption after deserialization).
+    pub fn verify_integrity(&self) -> Result<(), IntegrityError> {
+        self.raw.verify_integrity()
+    }
+
+    /// Returns the statistics of this filter.
+    pub fn stats(&self) -> FilterStats {
+        self.raw.stats()
+    }
+
+    /// Returns the false positive probability.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.raw.false_positive_probability()
+    }
+
+    /// Returns the tightening ratio of the false positive probability.
+    pub fn tightening_ratio(&self) -> f64 {
+        self.raw.tightening_ratio()
+    }
+
+    /// Returns the number of elements in each buckets.
+    pub fn entries_per_bucket(&self) -> usize {
+        self.raw.entries_per_bucket()
+    }
+
+    /// Returns the number of kicks before the filter grows.
+    pub fn max_kicks(&self) -> usize {
+        self.raw.max_kicks()
+    }
+
+    /// Returns the strategy for relocating entries.
+    pub fn insertion_strategy(&self) -> InsertionStrategy {
+        self.raw.insertion_strategy()
+    }
+
+    /// Returns the layout of the fingerprints of each layer.
+    pub fn storage_backend(&self) -> StorageBackend {
+        self.raw.storage_backend()
+    }
+
+    /// Returns the width of the hashes derived from items.
+    pub fn hash_width(&self) -> HashWidth {
+        self.raw.hash_width()
+    }
+
+    /// Returns the scheme for deriving alternative bucket indices.
+    pub fn alt_index_scheme(&self) -> AltIndexScheme {
+        self.raw.alt_index_scheme()
+    }
+
+    /// Returns `true` if the numbers of buckets of new layers are rounded up to powers of two,
+    /// otherwise `false`.
+    pub fn power_of_two_buckets(&self) -> bool {
+        self.raw.power_of_two_buckets()
+    }
+
+    /// Returns the numbers of buckets of the first layers given by
+    /// [`ScalableCuckooFilterBuilder::bucket_counts`].
+    pub fn bucket_counts(&self) -> &[usize] {
+        self.raw.bucket_counts()
+    }
+
+    /// Returns the number of kicked out entries the last layer can hold without growing.
+    pub fn stash_capacity(&self) -> usize {
+        self.raw.stash_capacity()
+    }
+
+    /// Returns `true` if kicked out entries are kept in stashes, otherwise `false`.
+    pub fn is_stash_enabled(&self) -> bool {
+        self.raw.is_stash_enabled()
+    }
+
+    /// Returns the load factor at which this filter grows proactively, if configured.
+    pub fn grow_at_load_factor(&self) -> Option<f64> {
+        self.raw.grow_at_load_factor()
+    }
+
+    /// Returns a copy of this filter that uses `rng` as the random number generator.
+    ///
+    /// Unlike `Clone`, this doesn't require the current random number generator to be cloneable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+    /// filter.insert("foo");
+    ///
+    /// let copy = filter.clone_with_rng(StdRng::seed_from_u64(0));
+    /// assert!(copy.contains("foo"));
+    /// ```
+    pub fn clone_with_rng<R2: Rng>(&self, rng: R2) -> ScalableCuckooFilter<T, H, R2> {
+        ScalableCuckooFilter::from_raw(self.raw.clone_with_rng(rng))
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type (e.g., `&str` for `String` items),
+    /// as long as it hashes the same as the owned form (as `Borrow` requires).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<String>::new(1000, 0.001);
+    /// filter.insert(&"foo".to_owned());
+    /// assert!(filter.contains("foo"));
+    /// ```
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.raw.contains128(self.item_hash(item))
+    }
+
+    /// Returns `true` if this filter may contain the item whose hash is `item_hash`,
+    /// otherwise `false`.
+    ///
+    /// This is the same as [`contains`] except that the given hash is used as is instead of
+    /// hashing an item with the hasher of this filter.
+    /// See [`insert_hash`] for the requirements of `item_hash`.
+    ///
+    /// [`contains`]: ScalableCuckooFilter::contains
+    /// [`insert_hash`]: ScalableCuckooFilter::insert_hash
+    pub fn contains_hash(&self, item_hash: u64) -> bool {
+        self.raw.contains(item_hash)
+    }
+
+    /// Inserts `item` into this filter.
+    ///
+    /// If the current filter becomes full, it will be expanded automatically.
+    ///
+    /// # Note
+    ///
+    /// Cuckoo Filter algorithm is unable to differentiate between two items with
+    /// the same fingerprint, so every [`insert`] method call will add a new entry
+    /// even if the same item is inserted multiple times.
+    ///
+    /// This behavior is necessary to avoid false negatives when using the [`remove`] method.
+    /// However, if you do not plan to use the [`remove`] method, you can prevent potential
+    /// duplicate insertions by checking for the existence of the item before insertion,
+    /// as shown below:
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+    /// let items = ["foo", "bar", "foo", "baz"];
+    ///
+    /// for item in &items {
+    ///     if !filter.contains(item) {
+    ///         filter.insert(item);
+    ///     }
+    /// }
+    /// ```
+    pub fn insert(&mut self, item: &T) {
+        let item_hash = self.item_hash(item);
+        self.raw.insert128(item_hash);
+    }
+
+    /// Inserts the item whose hash is `item_hash` into this filter.
+    ///
+    /// This bypasses the hasher of this filter, which is useful when items are already
+    /// high-quality hashes such as cryptographic digests.
+    ///
+    /// The upper bits of `item_hash` are used as the fingerprint and the lower bits as
+    /// the bucket index, so `item_hash` should be uniformly distributed over all 64 bits.
+    /// Note that `insert(item)` and `insert_hash(h)` don't match each other unless `h` is
+    /// the hash of `item` computed by the hasher of this filter and the hash width is
+    /// [`HashWidth::Bits64`], so the same kind of keys should be used consistently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let digest = [0x3a, 0x7b, 0xd3, 0xe2, 0x36, 0x0a, 0x3d, 0x29];
+    /// let item_hash = u64::from_le_bytes(digest);
+    ///
+    /// let mut filter = ScalableCuckooFilter::<[u8]>::new(1000, 0.001);
+    /// filter.insert_hash(item_hash);
+    /// assert!(filter.contains_hash(item_hash));
+    /// assert!(filter.remove_hash(item_hash));
+    /// ```
+    pub fn insert_hash(&mut self, item_hash: u64) {
+        self.raw.insert(item_hash);
+    }
+
+    /// Returns an inserter that buffers up to `buffer_size` items and inserts them
+    /// into this filter in bucket order.
+    ///
+    /// This improves the throughput of bulk insertions into large filters.
+    /// See [`BufferedInserter`] for details.
+    pub fn buffered_inserter(&mut self, buffer_size: usize) -> BufferedInserter<'_, T, H, R> {
+        BufferedInserter::new(self, buffer_size)
+    }
+
+    /// Inserts the items yielded by `items` using multiple threads.
+    ///
+    /// Items are hashed in parallel and their fingerprints are placed into disjoint ranges of
+    /// buckets concurrently. Only the items that didn't fit in either candidate bucket are
+    /// inserted sequentially (with kicks).
+    ///
+    /// This is available only if the `rayon` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rayon::prelude::*;
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<usize>::new(1000, 0.001);
+    /// filter.par_extend((0..100_000).into_par_iter());
+    /// assert_eq!(filter.len(), 100_000);
+    /// assert!(filter.contains(&12345));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_extend<I>(&mut self, items: I)
+    where
+        I: rayon::iter::IntoParallelIterator,
+        I::Item: Borrow<T>,
+        H: Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        let hasher = &self.raw.hasher;
+        let width = self.raw.hash_width;
+        let hashes: Vec<u128> = items
+            .into_par_iter()
+            .map(|item| crate::item_hash(hasher, item.borrow(), width))
+            .collect();
+        self.raw.par_extend128(hashes);
+    }
+
+    /// Inserts the items yielded by `stream`, returning when it ends.
+    ///
+    /// Items are hashed as they arrive and inserted in batches of 4096 in bucket order
+    /// (see [`BufferedInserter`]), yielding to the executor after each batch so that ingesting
+    /// a busy stream doesn't starve other tasks. The items buffered when the stream ends
+    /// (or when the future is dropped) are inserted as well.
+    ///
+    /// This is available only if the `stream` feature is enabled.
+    /// The future works with any executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use futures::stream;
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<str>::new(1000, 0.001);
+    /// let keys = stream::iter(["foo".to_owned(), "bar".to_owned()]);
+    /// block_on(filter.extend_from_stream(keys));
+    /// assert!(filter.contains("foo"));
+    /// assert!(filter.contains("bar"));
+    /// ```
+    #[cfg(feature = "stream")]
+    pub async fn extend_from_stream<S>(&mut self, stream: S)
+    where
+        S: futures_core::Stream,
+        S::Item: Borrow<T>,
+    {
+        crate::stream::extend(self, stream).await
+    }
+
+    pub(crate) fn item_hash<Q: Hash + ?Sized>(&self, item: &Q) -> u128 {
+        crate::item_hash(&self.raw.hasher, item, self.raw.hash_width)
+    }
+
+    pub(crate) fn bucket_index(&self, item_hash: u128) -> usize {
+        self.raw.bucket_index(item_hash)
+    }
+
+    /// Shrinks the capacity of this filter as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.raw.shrink_to_fit();
+    }
+
+    /// Converts this filter into a read-only [`FrozenScalableCuckooFilter`].
+    ///
+    /// The layers are shrunk to fit their items, and empty layers are dropped.
+    ///
+    /// Use [`freeze_compacted`] to pack everything into a single layer instead.
+    ///
+    /// [`freeze_compacted`]: ScalableCuckooFilter::freeze_compacted
+    pub fn freeze(mut self) -> FrozenScalableCuckooFilter<T, H> {
+        self.shrink_to_fit();
+        let raw = self.raw;
+        FrozenScalableCuckooFilter::new(
+            raw.hasher,
+            raw.filters,
+            raw.false_positive_probability,
+            raw.hash_width,
+        )
+    }
+
+    /// Converts this filter into a read-only [`FrozenScalableCuckooFilter`] that consists of
+    /// a single densely packed layer.
+    ///
+    /// Lookups of the result touch only one layer, and long-grown filters shrink significantly
+    /// because the whole error budget is spent on one layer.
+    ///
+    /// The layers have fingerprints of different lengths, which can't be moved into one layer,
+    /// so `items` must yield every item inserted into this filter.
+    /// The result contains exactly the yielded items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(100, 0.001);
+    /// for i in 0..10_000 {
+    ///     filter.insert(&i);
+    /// }
+    /// let bits = filter.bits();
+    ///
+    /// let frozen = filter.freeze_compacted(0..10_000);
+    /// assert_eq!(frozen.layers().len(), 1);
+    /// assert!(frozen.bits() < bits);
+    /// assert!(frozen.contains(&123));
+    /// ```
+    pub fn freeze_compacted<I>(self, items: I) -> FrozenScalableCuckooFilter<T, H>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        let hashes = items
+            .into_iter()
+            .map(|item| self.item_hash(item.borrow()))
+            .collect::<Vec<_>>();
+        let mut raw = self.raw;
+
+        // The breadth-first search doesn't use the shared entries of Morton blocks.
+        let strategy = match raw.storage_backend {
+            StorageBackend::Buckets | StorageBackend::Blocked => InsertionStrategy::BreadthFirst,
+            StorageBackend::Morton => InsertionStrategy::RandomWalk,
+        };
+
+        // No further insertions will occur, so the layer is sized for a high load factor.
+        const MAX_LOAD_FACTOR: f64 = 0.95;
+        let capacity = (hashes.len() as f64 / MAX_LOAD_FACTOR).ceil() as usize;
+        let mut filter = CuckooFilter::new(
+            raw.fingerprint_bitwidth(raw.false_positive_probability),
+            raw.entries_per_bucket,
+            capacity.max(1),
+            raw.max_kicks,
+            strategy,
+            raw.storage_backend,
+            raw.alt_index_scheme,
+            raw.power_of_two_buckets,
+            raw.allocator.clone(),
+        );
+        for item_hash in hashes {
+            filter.insert(&raw.hasher, &mut raw.rng, item_hash, true);
+        }
+        filter.shrink_to_fit(&raw.hasher, &mut raw.rng, true);
+        FrozenScalableCuckooFilter::new(
+            raw.hasher,
+            vec![filter],
+            raw.false_positive_probability,
+            raw.hash_width,
+        )
+    }
+
+    /// Builds a classic [`BloomFilter`] from the entries of this filter with `bits_per_item`
+    /// bits per entry.
+    ///
+    /// The same hasher is used, and the bitset layout is documented in [`BloomFilter`] so that
+    /// systems that only understand Bloom filters can query it.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `bits_per_item` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
+    /// }
+    ///
+    /// let bloom = filter.to_bloom(10);
+    /// assert!(bloom.contains(&10));
+    /// assert_eq!(bloom.as_bytes().len(), 1250);
+    /// ```
+    pub fn to_bloom(&self, bits_per_item: usize) -> BloomFilter<T, H> {
+        BloomFilter::new(
+            self.raw.hasher.clone(),
+            self.raw.hash_width,
+            bits_per_item,
+            &self.raw.filters,
+        )
+    }
+
+    /// Builds a static [`XorFilter`] containing `items`.
+    ///
+    /// The fingerprint length of the result is derived from the false positive probability
+    /// of this filter, and the same hasher is used. `items` must yield every item to be
+    /// contained.
+    ///
+    /// This is available only if the `xor_filter` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+    /// for i in 0..1000 {
+    ///     filter.insert(&i);
+    /// }
+    ///
+    /// let xor = filter.to_xor_filter(0..1000);
+    /// assert!(xor.contains(&10));
+    /// assert!(xor.bits() < filter.bits());
+    /// ```
+    #[cfg(feature = "xor_filter")]
+    pub fn to_xor_filter<I>(&self, items: I) -> XorFilter<T, H>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        let fingerprint_bitwidth = (-self.false_positive_probability().log2()).ceil() as usize;
+        XorFilter::new(
+            self.raw.hasher.clone(),
+            fingerprint_bitwidth.clamp(1, 32),
+            items,
+        )
+    }
+
+    /// Reserves capacity for at least `additional` more items to be inserted without growing
+    /// more than once.
+    ///
+    /// If the last layer doesn't have enough free entries, a new layer that can hold
+    /// `additional` items is added up front. This is cheaper than growing several times
+    /// during a bulk load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter: ScalableCuckooFilter<usize> = ScalableCuckooFilter::new(100, 0.001);
+    /// filter.reserve(10_000);
+    /// assert!(filter.capacity() >= 10_000);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.raw.reserve(additional);
+    }
+
+    /// Moves the entries kicked out to the stashes back into buckets if there are free entries.
+    ///
+    /// After removals, buckets often have room for the entries that were previously kicked out.
+    /// [`remove`] leaves them stashed, since re-placing them scans the whole stash, so call
+    /// this as a maintenance step after many removals to make lookups cheaper again.
+    ///
+    /// [`remove`]: ScalableCuckooFilter::remove
+    pub fn defragment(&mut self) {
+        self.raw.defragment();
+    }
+
+    /// Writes this filter to `writer` without building an intermediate buffer or using serde.
+    ///
+    /// The filter can be read back with [`read_from`]. Like serialization with serde,
+    /// this doesn't write the hasher, so the filter must be read with a hasher of the same keys.
+    /// See [`raw::ScalableCuckooFilter::write_to`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` fails, or if the alternative index scheme is
+    /// [`AltIndexScheme::Custom`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let mut bytes = Vec::new();
+    /// filter.write_to(&mut bytes).unwrap();
+    /// let filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::read_from(&bytes[..]).unwrap();
+    /// assert!(filter.contains("foo"));
+    /// ```
+    ///
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        self.raw.write_to(writer)
+    }
+
+    /// Writes this filter to `writer` like [`write_to`], but compactly if it is sparse.
+    ///
+    /// See [`raw::ScalableCuckooFilter::write_compressed_to`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` fails, or if the alternative index scheme is
+    /// [`AltIndexScheme::Custom`].
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    pub fn write_compressed_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        self.raw.write_compressed_to(writer)
+    }
+
+    /// Writes this filter to `writer` like [`write_to`], but asynchronously.
+    ///
+    /// The bit arrays are written in chunks, yielding to the runtime between them.
+    /// See [`raw::ScalableCuckooFilter::write_to_async`] for details.
+    ///
+    /// This is available only if the `tokio` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` fails, or if the alternative index scheme is
+    /// [`AltIndexScheme::Custom`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let mut bytes = Vec::new();
+    /// filter.write_to_async(&mut bytes).await.unwrap();
+    /// let filter: ScalableCuckooFilter<str> =
+    ///     ScalableCuckooFilter::read_from_async(&bytes[..]).await.unwrap();
+    /// assert!(filter.contains("foo"));
+    /// # });
+    /// ```
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    #[cfg(feature = "tokio")]
+    pub async fn write_to_async<W: AsyncWrite + Unpin>(&self, writer: W) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        self.raw.write_to_async(writer).await
+    }
+
+    /// Serializes this filter into a versioned binary format without using serde.
+    ///
+    /// Bytes written by this version of the crate can be read by [`from_bytes`] of any later
+    /// version. See [`raw::ScalableCuckooFilter::to_bytes`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let bytes = filter.to_bytes().unwrap();
+    /// assert!(bytes.starts_with(b"SCKF"));
+    /// let filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::from_bytes(&bytes).unwrap();
+    /// assert!(filter.contains("foo"));
+    /// ```
+    ///
+    /// [`from_bytes`]: ScalableCuckooFilter::from_bytes
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>>
+    where
+        H: HasherKeys,
+    {
+        self.raw.to_bytes()
+    }
+
+    /// Serializes this filter like [`to_bytes`], but compactly if it is sparse.
+    ///
+    /// Long runs of empty buckets, which are common right after the filter grows,
+    /// are stored as their lengths. The bytes are read by [`from_bytes`] as usual.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .initial_capacity(100_000)
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let bytes = filter.to_compressed_bytes().unwrap();
+    /// assert!(bytes.len() * 10 < filter.to_bytes().unwrap().len());
+    /// let filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::from_bytes(&bytes).unwrap();
+    /// assert!(filter.contains("foo"));
+    /// ```
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`from_bytes`]: ScalableCuckooFilter::from_bytes
+    pub fn to_compressed_bytes(&self) -> io::Result<Vec<u8>>
+    where
+        H: HasherKeys,
+    {
+        self.raw.to_compressed_bytes()
+    }
+
+    /// Saves this filter to the file at `path`, atomically replacing any existing file.
+    ///
+    /// See [`raw::ScalableCuckooFilter::save_to_path`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing or renaming the file fails, or if the alternative index
+    /// scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let path = std::env::temp_dir().join(format!("filter-{}.bin", std::process::id()));
+    /// filter.save_to_path(&path).unwrap();
+    /// let filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::load_from_path(&path).unwrap();
+    /// assert!(filter.contains("foo"));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()>
+    where
+        H: HasherKeys,
+    {
+        self.raw.save_to_path(path)
+    }
+
+    /// Returns the version of this filter, which is incremented by every modification.
+    ///
+    /// See [`delta_since`] for its use.
+    ///
+    /// [`delta_since`]: ScalableCuckooFilter::delta_since
+    pub fn version(&self) -> u64 {
+        self.raw.version()
+    }
+
+    /// Serializes the modifications made to this filter since it was at `baseline_version`
+    /// into a delta for [`apply_delta`].
+    ///
+    /// This keeps replicas of a large filter up to date without shipping whole snapshots.
+    /// See [`raw::ScalableCuckooFilter::delta_since`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `baseline_version` is newer than the current version, or if
+    /// a layer is written whole and the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`apply_delta`]: ScalableCuckooFilter::apply_delta
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut primary = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// primary.insert("foo");
+    ///
+    /// let snapshot = primary.to_bytes().unwrap();
+    /// let mut replica: ScalableCuckooFilter<str> = ScalableCuckooFilter::from_bytes(&snapshot).unwrap();
+    ///
+    /// let version = primary.version();
+    /// primary.insert("bar");
+    /// replica.apply_delta(&primary.delta_since(version).unwrap()).unwrap();
+    /// assert!(replica.contains("bar"));
+    /// assert_eq!(replica.version(), primary.version());
+    /// ```
+    pub fn delta_since(&self, baseline_version: u64) -> io::Result<Vec<u8>> {
+        self.raw.delta_since(baseline_version)
+    }
+
+    /// Applies a delta made by [`delta_since`], bringing this filter to its version.
+    ///
+    /// See [`raw::ScalableCuckooFilter::apply_delta`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `delta` is malformed, or if it doesn't apply to this filter.
+    /// This filter is left as it is then.
+    ///
+    /// [`delta_since`]: ScalableCuckooFilter::delta_since
+    pub fn apply_delta(&mut self, delta: &[u8]) -> io::Result<()> {
+        self.raw.apply_delta(delta)
+    }
+
+    /// Returns a summary of the entries of this filter for [`entries_missing_from`] of
+    /// a replica, to reconcile replicas that have been modified independently.
+    ///
+    /// Only the entries that differ are exchanged, so replicas converge on the union of their
+    /// items without transferring whole filters. Removals aren't propagated.
+    /// See [`raw::ScalableCuckooFilter::reconciliation_summary`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// [`entries_missing_from`]: ScalableCuckooFilter::entries_missing_from
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    ///
+    /// let mut a = ScalableCuckooFilterBuilder::new()
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// let mut b: ScalableCuckooFilter<str> =
+    ///     ScalableCuckooFilter::from_bytes(&a.to_bytes().unwrap()).unwrap();
+    /// a.insert("foo");
+    /// b.insert("bar");
+    ///
+    /// let entries = b.entries_missing_from(&a.reconciliation_summary().unwrap()).unwrap();
+    /// a.merge_entries(&entries).unwrap();
+    /// let entries = a.entries_missing_from(&b.reconciliation_summary().unwrap()).unwrap();
+    /// b.merge_entries(&entries).unwrap();
+    /// assert!(a.contains("bar"));
+    /// assert!(b.contains("foo"));
+    /// ```
+    pub fn reconciliation_summary(&self) -> io::Result<Vec<u8>> {
+        self.raw.reconciliation_summary()
+    }
+
+    /// Returns the entries of this filter that may be missing from the replica that made
+    /// `peer_summary`, for [`merge_entries`] of the replica.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `peer_summary` is malformed, or if the replica has a different
+    /// hasher or configuration or its layers don't match those of this filter.
+    ///
+    /// [`merge_entries`]: ScalableCuckooFilter::merge_entries
+    pub fn entries_missing_from(&self, peer_summary: &[u8]) -> io::Result<Vec<u8>> {
+        self.raw.entries_missing_from(peer_summary)
+    }
+
+    /// Inserts the entries returned by [`entries_missing_from`] of a replica that this filter
+    /// doesn't have, returning the number of inserted entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `entries` is malformed, or if the replica has a different hasher
+    /// or configuration or its layers don't match those of this filter.
+    /// This filter is left as it is then.
+    ///
+    /// [`entries_missing_from`]: ScalableCuckooFilter::entries_missing_from
+    pub fn merge_entries(&mut self, entries: &[u8]) -> io::Result<usize> {
+        self.raw.merge_entries(entries)
+    }
+
+    /// Inserts the entries of `other` that this filter doesn't have, so that this filter contains
+    /// the union of the items of both, returning the number of inserted entries.
+    ///
+    /// `other` must be built with the same hasher and configuration.
+    /// See [`raw::ScalableCuckooFilter::union_with`] for details.
+    /// The `|` and `|=` operators do the same but panic on errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `other` has a different hasher or configuration or its layers don't
+    /// match those of this filter, or if the alternative index scheme is [`AltIndexScheme::Custom`].
+    /// This filter is left as it is then.
+    pub fn union_with(&mut self, other: &Self) -> io::Result<usize> {
+        self.raw.union_with(&other.raw)
+    }
+
+    /// Returns the entries of this filter with the parameters of its layers, which can be
+    /// modified and then given to [`restore`] or [`ScalableCuckooFilterBuilder::build_from_dump`].
+    ///
+    /// See [`raw::ScalableCuckooFilter::dump`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alternative index scheme is [`AltIndexScheme::Custom`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// // The filters must hash items identically.
+    /// let key = *b"0123456789abcdef";
+    /// let mut filter = ScalableCuckooFilter::<str>::builder()
+    ///     .hasher_key(key)
+    ///     .finish();
+    /// filter.insert("foo");
+    /// filter.insert("bar");
+    ///
+    /// let mut dump = filter.dump().unwrap();
+    /// assert_eq!(dump.layers[0].entries.len(), 2);
+    ///
+    /// let mut restored = ScalableCuckooFilter::<str>::builder()
+    ///     .hasher_key(key)
+    ///     .build_from_dump(&dump)
+    ///     .unwrap();
+    /// assert!(restored.contains("foo") && restored.contains("bar"));
+    ///
+    /// // Entries can be modified before restored.
+    /// dump.layers[0].entries.clear();
+    /// restored.restore(&dump).unwrap();
+    /// assert!(restored.is_empty());
+    /// ```
+    ///
+    /// [`restore`]: ScalableCuckooFilter::restore
+    pub fn dump(&self) -> io::Result<FilterDump> {
+        self.raw.dump()
+    }
+
+    /// Replaces the entries of this filter with those of `dump` made by [`dump`].
+    ///
+    /// This filter must be built with the same hasher and configuration as the dumped one.
+    /// See [`raw::ScalableCuckooFilter::restore`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this filter has a different hasher or configuration, if the layers of
+    /// `dump` don't match those made by this filter, or if an entry is out of the range of its layer.
+    /// This filter is left as it is then.
+    ///
+    /// [`dump`]: ScalableCuckooFilter::dump
+    pub fn restore(&mut self, dump: &FilterDump) -> io::Result<()> {
+        self.raw.restore(dump)
+    }
+
+    /// Removes `item` from this filter.
+    ///
+    /// This method returns `true` if an entry with the same fingerprint as `item` has been removed, otherwise it returns `false`.
+    ///
+    /// Like [`contains`], `item` may be any borrowed form of the item type.
+    ///
+    /// [`contains`]: ScalableCuckooFilter::contains
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let item_hash = self.item_hash(item);
+        self.raw.remove128(item_hash)
+    }
+
+    /// Removes the item whose hash is `item_hash` from this filter.
+    ///
+    /// See [`insert_hash`] for the requirements of `item_hash`.
+    ///
+    /// [`insert_hash`]: ScalableCuckooFilter::insert_hash
+    pub fn remove_hash(&mut self, item_hash: u64) -> bool {
+        self.raw.remove(item_hash)
+    }
+
+    /// Inserts the item represented by `bytes` into this filter.
+    ///
+    /// The item hash is computed by writing exactly `bytes` into the hasher of this filter
+    /// (`hasher.write(bytes); hasher.finish()`). Unlike hashing `&[u8]` via the `Hash` trait,
+    /// no length prefix is written, so the hash is defined byte for byte and can be
+    /// reproduced by other systems (e.g., SipHash-1-3 with zero keys for the default hasher).
+    /// In [`HashWidth::Bits128`] mode, the fingerprint is derived from a second hash
+    /// computed as described there.
+    ///
+    /// Note that `insert_bytes(b)` and `insert(b)` don't match each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scalable_cuckoo_filter::ScalableCuckooFilter;
+    ///
+    /// let mut filter = ScalableCuckooFilter::<[u8]>::new(1000, 0.001);
+    /// filter.insert_bytes(b"foo");
+    /// assert!(filter.contains_bytes(b"foo"));
+    /// assert!(!filter.contains(b"foo"));
+    /// assert!(filter.remove_bytes(b"foo"));
+    /// ```
+    pub fn insert_bytes(&mut self, bytes: &[u8]) {
+        let item_hash = self.bytes_hash(bytes);
+        self.raw.insert128(item_hash);
+    }
+
+    /// Returns `true` if this filter may contain the item represented by `bytes`,
+    /// otherwise `false`.
+    ///
+    /// See [`insert_bytes`] for how `bytes` is hashed.
+    ///
+    /// [`insert_bytes`]: ScalableCuckooFilter::insert_bytes
+    pub fn contains_bytes(&self, bytes: &[u8]) -> bool {
+        self.raw.contains128(self.bytes_hash(bytes))
+    }
+
+    /// Removes the item represented by `bytes` from this filter.
+    ///
+    /// See [`insert_bytes`] for how `bytes` is hashed.
+    ///
+    /// [`insert_bytes`]: ScalableCuckooFilter::insert_bytes
+    pub fn remove_bytes(&mut self, bytes: &[u8]) -> bool {
+        let item_hash = self.bytes_hash(bytes);
+        self.raw.remove128(item_hash)
+    }
+
+    fn bytes_hash(&self, bytes: &[u8]) -> u128 {
+        let mut hasher = self.raw.hasher.clone();
+        hasher.write(bytes);
+        let lower = hasher.finish();
+        match self.raw.hash_width {
+            HashWidth::Bits64 => crate::widen(lower),
+            HashWidth::Bits128 => {
+                let upper = crate::fingerprint_hash(&self.raw.hasher, |h| h.write(bytes));
+                (u128::from(upper) << 64) | u128::from(lower)
+            }
+            HashWidth::Bits32 => crate::narrow(lower),
+        }
+    }
+
+    /// Makes a typed filter from a [`raw::ScalableCuckooFilter`].
+    ///
+    /// The items of `raw` must have been hashed with the hasher and the hash width of `raw`,
+    /// as the typed filter does.
+    pub fn from_raw(raw: raw::ScalableCuckooFilter<H, R>) -> Self {
+        ScalableCuckooFilter {
+            raw,
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the underlying [`raw::ScalableCuckooFilter`].
+    pub fn as_raw(&self) -> &raw::ScalableCuckooFilter<H, R> {
+        &self.raw
+    }
+
+    /// Converts this filter into the underlying [`raw::ScalableCuckooFilter`].
+    pub fn into_raw(self) -> raw::ScalableCuckooFilter<H, R> {
+        self.raw
+    }
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ScalableCuckooFilter<T, H, R> {
+    /// Reads a filter written by [`write_to`] from `reader`, giving it `hasher` and `rng`.
+    ///
+    /// `hasher` must hash items as the hasher of the written filter did (e.g., be keyed the same),
+    /// otherwise lookups of the read filter give wrong answers.
+    /// See [`raw::ScalableCuckooFilter::read_from_with`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    pub fn read_from_with<Rd: Read>(reader: Rd, hasher: H, rng: R) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::read_from_with(reader, hasher, rng)
+            .map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Reads a filter from `reader` like [`read_from_with`], but asynchronously.
+    ///
+    /// See [`raw::ScalableCuckooFilter::read_from_with_async`] for details.
+    ///
+    /// This is available only if the `tokio` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`read_from_with`]: ScalableCuckooFilter::read_from_with
+    #[cfg(feature = "tokio")]
+    pub async fn read_from_with_async<Rd: AsyncRead + Unpin>(
+        reader: Rd,
+        hasher: H,
+        rng: R,
+    ) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::read_from_with_async(reader, hasher, rng)
+            .await
+            .map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Deserializes a filter from bytes made by [`to_bytes`], giving it `hasher` and `rng`.
+    ///
+    /// See [`read_from_with`] for the requirements of `hasher`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is malformed, of an unknown format version,
+    /// or followed by extra bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+    /// use siphasher::sip::SipHasher13;
+    ///
+    /// let hasher = SipHasher13::new_with_keys(1, 2);
+    /// let mut filter = ScalableCuckooFilterBuilder::new()
+    ///     .hasher(hasher)
+    ///     .rng(StdRng::seed_from_u64(3))
+    ///     .for_type::<str>()
+    ///     .finish();
+    /// filter.insert("foo");
+    ///
+    /// let bytes = filter.to_bytes().unwrap();
+    /// let filter: ScalableCuckooFilter<str, _, _> =
+    ///     ScalableCuckooFilter::from_bytes_with(&bytes, hasher, StdRng::seed_from_u64(4)).unwrap();
+    /// assert!(filter.contains("foo"));
+    /// ```
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`read_from_with`]: ScalableCuckooFilter::read_from_with
+    pub fn from_bytes_with(bytes: &[u8], hasher: H, rng: R) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::from_bytes_with(bytes, hasher, rng)
+            .map(ScalableCuckooFilter::from_raw)
+    }
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone + Default + HasherKeys, R: Rng + Default>
+    ScalableCuckooFilter<T, H, R>
+{
+    /// Reads a filter written by [`write_to`] from `reader`.
+    ///
+    /// The hasher is keyed as the written one was (see [`HasherKeys`]), or made by `Default`
+    /// if it isn't keyed. Only trusted input should be read, because the bit
+    /// arrays are allocated with the sizes declared in it.
+    /// See [`raw::ScalableCuckooFilter::read_from`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`write_to`]: ScalableCuckooFilter::write_to
+    pub fn read_from<Rd: Read>(reader: Rd) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::read_from(reader).map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Reads a filter from `reader` like [`read_from`], but asynchronously.
+    ///
+    /// See [`raw::ScalableCuckooFilter::read_from_with_async`] for details.
+    ///
+    /// This is available only if the `tokio` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` fails or ends early, or if the input is malformed.
+    ///
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    #[cfg(feature = "tokio")]
+    pub async fn read_from_async<Rd: AsyncRead + Unpin>(reader: Rd) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::read_from_async(reader)
+            .await
+            .map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Deserializes a filter from bytes made by [`to_bytes`].
+    ///
+    /// The hasher is made as by [`read_from`].
+    /// See [`raw::ScalableCuckooFilter::from_bytes`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is malformed, of an unknown format version,
+    /// or followed by extra bytes.
+    ///
+    /// [`to_bytes`]: ScalableCuckooFilter::to_bytes
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::from_bytes(bytes).map(ScalableCuckooFilter::from_raw)
+    }
+
+    /// Loads a filter from the file at `path` saved by [`save_to_path`].
+    ///
+    /// The hasher is made as by [`read_from`].
+    /// See [`raw::ScalableCuckooFilter::load_from_path`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, if its contents are malformed,
+    /// or if it changes while read.
+    ///
+    /// [`save_to_path`]: ScalableCuckooFilter::save_to_path
+    /// [`read_from`]: ScalableCuckooFilter::read_from
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        raw::ScalableCuckooFilter::load_from_path(path).map(ScalableCuckooFilter::from_raw)
+    }
+}
+
+/// Makes a filter with the defaults of [`ScalableCuckooFilterBuilder`], except that the hasher is
+/// `DefaultHasher::default()` (with fixed keys), so default filters can be merged.
+///
+/// The filter is sized for 100,000 items initially, so this allocates as `new` does.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilter;
+///
+/// #[derive(Default)]
+/// struct Cache {
+///     seen: ScalableCuckooFilter<str>,
+/// }
+///
+/// let mut cache = Cache::default();
+/// cache.seen.insert("foo");
+/// let seen = std::mem::take(&mut cache.seen);
+/// assert!(seen.contains("foo"));
+/// assert!(cache.seen.is_empty());
+/// ```
+impl<T: Hash + ?Sized> Default for ScalableCuckooFilter<T> {
+    fn default() -> Self {
+        ScalableCuckooFilter::from_raw(raw::ScalableCuckooFilter::default())
+    }
+}
+
+/// Inserts the items of the right-hand filter into the left-hand one (see [`union_with`]).
+///
+/// # Panics
+///
+/// Panics if the filters have different hashers or configurations.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilter;
+///
+/// let mut total = ScalableCuckooFilter::default();
+/// for shard in [["foo", "bar"], ["baz", "qux"]] {
+///     let mut shard_filter = ScalableCuckooFilter::default();
+///     shard.iter().for_each(|item| shard_filter.insert(item));
+///     total |= shard_filter;
+/// }
+/// assert!(["foo", "bar", "baz", "qux"].iter().all(|item| total.contains(item)));
+/// ```
+///
+/// [`union_with`]: ScalableCuckooFilter::union_with
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> BitOrAssign<&Self>
+    for ScalableCuckooFilter<T, H, R>
+{
+    fn bitor_assign(&mut self, other: &Self) {
+        if let Err(e) = self.union_with(other) {
+            panic!("can't take the union of incompatible filters: {e}");
+        }
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> BitOrAssign for ScalableCuckooFilter<T, H, R> {
+    fn bitor_assign(&mut self, other: Self) {
+        *self |= &other;
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> BitOr<&Self> for ScalableCuckooFilter<T, H, R> {
+    type Output = Self;
+
+    fn bitor(mut self, other: &Self) -> Self {
+        self |= other;
+        self
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> BitOr for ScalableCuckooFilter<T, H, R> {
+    type Output = Self;
+
+    fn bitor(mut self, other: Self) -> Self {
+        self |= &other;
+        self
+    }
+}
+
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng + Clone> Clone for ScalableCuckooFilter<T, H, R> {
+    fn clone(&self) -> Self {
+        ScalableCuckooFilter::from_raw(self.raw.clone())
+    }
+}
+
+/// Filters are equal if they have the same parameters and layers holding the same entries.
+///
+/// This compares the underlying [`raw::ScalableCuckooFilter`]s (see its implementation).
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilter;
+///
+/// let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+/// filter.insert("foo");
+///
+/// let mut copy = ScalableCuckooFilter::from_bytes(&filter.to_bytes().unwrap()).unwrap();
+/// assert!(copy == filter);
+/// copy.insert("bar");
+/// assert!(copy != filter);
+/// ```
+impl<T: ?Sized, H: HasherKeys, R> PartialEq for ScalableCuckooFilter<T, H, R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: Hash + ?Sized> arbitrary::Arbitrary<'a> for ScalableCuckooFilter<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let strategy = if u.arbitrary()? {
+            InsertionStrategy::BreadthFirst
+        } else {
+            InsertionStrategy::RandomWalk
+        };
+        let mut builder = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(u.int_in_range(1..=1024)?)
+            .false_positive_probability(*u.choose(&[0.5, 0.1, 0.01, 0.001, 0.0001])?)
+            .tightening_ratio(*u.choose(&[0.1, 0.5, 0.9])?)
+            .entries_per_bucket(u.int_in_range(1..=8)?)
+            .max_kicks(u.int_in_range(0..=512)?)
+            .insertion_strategy(strategy)
+            .stash_capacity(u.int_in_range(0..=4)?);
+        if u.ratio(1, 4)? {
+            builder = builder.disable_stash();
+        }
+        if u.ratio(1, 4)? {
+            builder = builder.grow_at_load_factor(u.int_in_range(50..=100)? as f64 / 100.0);
+        }
+
+        let mut filter = builder.finish();
+        for item_hash in u.arbitrary_iter::<u64>()? {
+            filter.insert_hash(item_hash?);
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::FilterEvent;
+    use crate::growth::{GrowthConfig, LayerSpec};
+
+    #[test]
+    fn it_works() {
+        let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+        assert!(filter.is_empty());
+        assert_eq!(filter.bits(), 14_336);
+
+        assert!(!filter.contains("foo"));
+        filter.insert("foo");
+        assert!(filter.contains("foo"));
+    }
+
+    #[test]
+    fn hash_methods_work() {
+        let mut filter = ScalableCuckooFilter::<str>::new(100, 0.001);
+        let hashes = (0..1000u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect::<Vec<_>>();
+        for &h in &hashes {
+            filter.insert_hash(h);
+        }
+        assert_eq!(filter.len(), 1000);
+        assert!(hashes.iter().all(|&h| filter.contains_hash(h)));
+        assert!(filter.remove_hash(hashes[0]));
+        assert_eq!(filter.len(), 999);
+
+        // In 64-bit mode, the typed methods hash items with the same hasher.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .hash_width(HashWidth::Bits64)
+            .finish::<str>();
+        filter.insert_hash(crate::hash(&filter.raw.hasher, "foo"));
+        assert!(filter.contains("foo"));
+        assert!(filter.remove("foo"));
+    }
+
+    #[test]
+    fn clone_string_and_str() {
+        let mut filter: ScalableCuckooFilter<String> = ScalableCuckooFilter::new(1000, 0.001);
+        let x = "foo".to_owned();
+        filter.insert(&x);
+        let cloned = filter.clone();
+        assert!(filter.contains(&"foo".to_string()));
+        assert!(cloned.contains(&"foo".to_string()));
+
+        let mut filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::new(1000, 0.001);
+        let x = "foo";
+        filter.insert(x);
+        let cloned = filter.clone();
+        assert!(filter.contains("foo"));
+        assert!(cloned.contains("foo"));
+    }
+
+    #[test]
+    fn borrowed_lookups_work() {
+        let mut filter: ScalableCuckooFilter<String> = ScalableCuckooFilter::new(1000, 0.001);
+        filter.insert(&"foo".to_owned());
+        assert!(filter.contains("foo"));
+        assert!(!filter.contains("bar"));
+        assert!(filter.remove("foo"));
+        assert!(!filter.contains("foo"));
+
+        let mut filter: ScalableCuckooFilter<Vec<u8>> = ScalableCuckooFilter::new(1000, 0.001);
+        filter.insert(&vec![1, 2, 3]);
+        assert!(filter.contains(&[1, 2, 3][..]));
+
+        let frozen = filter.freeze();
+        assert!(frozen.contains(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn bytes_methods_work() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1000)
+            .hash_width(HashWidth::Bits64)
+            .hasher(DefaultHasher::default())
+            .finish::<[u8]>();
+        filter.insert_bytes(b"foo");
+        assert!(filter.contains_bytes(b"foo"));
+        assert!(!filter.contains_bytes(b"bar"));
+
+        // In 64-bit mode, the hash is that of exactly the given bytes.
+        let mut hasher = DefaultHasher::default();
+        hasher.write(b"bar");
+        filter.insert_hash(hasher.finish());
+        assert!(filter.contains_bytes(b"bar"));
+
+        assert!(filter.remove_bytes(b"foo"));
+        assert!(!filter.contains_bytes(b"foo"));
+    }
+
+    #[test]
+    fn insert_works() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut seed = [0; 32];
+        for (i, b) in seed.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        let rng: StdRng = SeedableRng::from_seed(seed);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .false_positive_probability(0.00001)
+            .rng(rng)
+            .finish();
+        for i in 0..10_000 {
+            assert!(!filter.contains(&i));
+            filter.insert(&i);
+            assert!(filter.contains(&i));
+        }
+        assert_eq!(filter.len(), 10_000);
+    }
+
+    #[test]
+    fn remove_works() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut seed = [0; 32];
+        for (i, b) in seed.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        let rng: StdRng = SeedableRng::from_seed(seed);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .false_positive_probability(0.00001)
+            .rng(rng)
+            .finish();
+
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        for i in 0..10_000 {
+            assert!(filter.remove(&i));
+            assert!(!filter.contains(&i));
+        }
+        for i in 0..10_000 {
+            assert!(!filter.remove(&i));
+        }
+    }
+
+    #[test]
+    fn fingerprint_collision_remove_works() {
+        let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+        filter.insert("foo");
+        filter.insert("foo");
+        assert!(filter.contains("foo"));
+
+        filter.remove("foo");
+        assert!(filter.contains("foo"));
+
+        filter.remove("foo");
+        assert!(!filter.contains("foo"));
+    }
+
+    #[test]
+    fn zero_fingerprint_remove_works() {
+        // Every item hashes to one, whose fingerprint (the upper bits) is zero.
+        #[derive(Debug, Clone)]
+        struct OneHasher;
+        impl Hasher for OneHasher {
+            fn finish(&self) -> u64 {
+                1
+            }
+
+            fn write(&mut self, _bytes: &[u8]) {}
+        }
+
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .hasher(OneHasher)
+            .finish();
+
+        // Zero fingerprints are kept only in the stash, so an empty bucket entry isn't taken for one.
+        assert!(!filter.remove(&0));
+        assert!(filter.is_empty());
+
+        filter.insert(&0);
+        assert!(filter.remove(&0));
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn memory_usage_works() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1000)
+            .finish();
+        let overhead = std::mem::size_of::<raw::ScalableCuckooFilter>()
+            + std::mem::size_of::<CuckooFilter>() * filter.raw.filters.capacity();
+        assert_eq!(filter.bits(), 14336);
+        assert_eq!(filter.memory_usage(), overhead + 14336 / 8);
+
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        let stashed = filter.layers().iter().map(|l| l.stash_len()).sum::<usize>();
+        assert!(filter.memory_usage() as u64 * 8 >= filter.bits());
+        assert!(filter.memory_usage() >= overhead + stashed * 8);
+    }
+
+    #[test]
+    fn shrink_to_fit_works() {
+        let mut filter = ScalableCuckooFilter::with_hasher(1000, 0.001, DefaultHasher::default());
+        for i in 0..100 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.capacity(), 1024);
+        assert_eq!(filter.bits(), 14336);
+
+        filter.shrink_to_fit();
+        for i in 0..100 {
+            assert!(filter.contains(&i));
+        }
+        assert_eq!(filter.capacity(), 128);
+        assert_eq!(filter.bits(), 1792);
+    }
+
+    #[test]
+    fn shrink_to_fit_keeps_stashed_items() {
+        // Short fingerprints make zero fingerprints (which are always stashed) common.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100_000)
+            .false_positive_probability(0.1)
+            .finish();
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert!(filter.stats().stash_len > 0);
+
+        filter.shrink_to_fit();
+        assert!(filter.capacity() < 100_000);
+        assert!(filter.stats().stash_len > 0);
+        for i in 0..10_000 {
+            assert!(filter.contains(&i));
+        }
+        assert_eq!(filter.verify_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn info_params() {
+        let mut filter = ScalableCuckooFilter::with_hasher(10, 0.001, DefaultHasher::default());
+
+        // constant values
+        assert_eq!(filter.max_kicks(), 512);
+        assert_eq!(filter.entries_per_bucket(), 4);
+        assert_eq!(filter.false_positive_probability(), 0.001);
+        assert_eq!(filter.tightening_ratio(), 0.5);
+        // dynamic values
+        assert_eq!(filter.bits(), 224);
+        assert_eq!(filter.capacity(), 16);
+
+        for i in 0..100 {
+            filter.insert(&i);
+        }
+
+        assert_eq!(filter.bits(), 2240);
+        assert_eq!(filter.capacity(), 114);
+    }
+
+    #[test]
+    fn tightening_ratio_works() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(10)
+            .false_positive_probability(0.001)
+            .tightening_ratio(0.9)
+            .hasher(DefaultHasher::default())
+            .finish();
+        assert_eq!(filter.tightening_ratio(), 0.9);
+        assert_eq!(filter.bits(), 272);
+
+        for i in 0..100 {
+            filter.insert(&i);
+        }
+        for i in 0..100 {
+            assert!(filter.contains(&i));
+        }
+        assert_eq!(filter.capacity(), 114);
+    }
+
+    #[test]
+    fn breadth_first_insertion_works() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1000)
+            .insertion_strategy(InsertionStrategy::BreadthFirst)
+            .finish();
+        assert_eq!(filter.insertion_strategy(), InsertionStrategy::BreadthFirst);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        for i in 0..10_000 {
+            assert!(filter.contains(&i));
+        }
+        for i in 0..10_000 {
+            assert!(filter.remove(&i));
+        }
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn hash_width_works() {
+        // With fixed keys, "foo" isn't a false positive, whose removal would remove another entry.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1000)
+            .hash_width(HashWidth::Bits128)
+            .hasher(DefaultHasher::default())
+            .finish();
+        assert_eq!(filter.hash_width(), HashWidth::Bits128);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let false_positives = (10_000..1_010_000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 2000, "{}", false_positives);
+
+        filter.insert_bytes(b"foo");
+        assert!(filter.contains_bytes(b"foo"));
+        assert!(filter.remove_bytes(b"foo"));
+
+        let frozen = filter.freeze();
+        assert!((0..10_000).all(|i| frozen.contains(&i)));
+    }
+
+    #[test]
+    fn typed_builder_works() {
+        let mut filter = ScalableCuckooFilter::<[u8]>::builder()
+            .seed([3; 32])
+            .finish();
+        filter.insert(b"foo");
+        assert!(filter.contains(b"foo"));
+
+        let filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(10)
+            .for_type::<u64>()
+            .finish();
+        assert!(!filter.contains(&1));
+    }
+
+    #[test]
+    fn bits32_hash_width_works() {
+        let mut filter = ScalableCuckooFilter::<usize>::builder()
+            .initial_capacity(1000)
+            .hash_width(HashWidth::Bits32)
+            .stash_capacity(10)
+            .finish();
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+        assert!(filter
+            .layers()
+            .iter()
+            .all(|l| l.fingerprint_bitwidth() <= 32));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+        for i in 0..10_000 {
+            assert!(filter.remove(&i));
+        }
+        assert!(filter.is_empty());
+
+        assert_eq!(
+            ScalableCuckooFilter::<usize>::builder()
+                .hash_width(HashWidth::Bits32)
+                .false_positive_probability(1e-9)
+                .build()
+                .unwrap_err(),
+            BuildError::FingerprintTooWide {
+                bitwidth: 34,
+                max_bitwidth: 32
+            }
+        );
+    }
+
+    #[test]
+    fn build_works() {
+        let builder = || ScalableCuckooFilter::<str>::builder().initial_capacity(100);
+        assert!(builder().build().is_ok());
+        assert_eq!(
+            builder().initial_capacity(0).build().unwrap_err(),
+            BuildError::ZeroInitialCapacity
+        );
+        assert_eq!(
+            builder()
+                .false_positive_probability(0.0)
+                .build()
+                .unwrap_err(),
+            BuildError::InvalidFalsePositiveProbability { probability: 0.0 }
+        );
+        assert_eq!(
+            builder().tightening_ratio(1.0).build().unwrap_err(),
+            BuildError::InvalidTighteningRatio { ratio: 1.0 }
+        );
+        assert_eq!(
+            builder().entries_per_bucket(0).build().unwrap_err(),
+            BuildError::ZeroEntriesPerBucket
+        );
+        assert_eq!(
+            builder().bucket_counts([10, 0]).build().unwrap_err(),
+            BuildError::ZeroBucketCount { layer: 1 }
+        );
+        assert_eq!(
+            builder()
+                .false_positive_probability(1e-30)
+                .build()
+                .unwrap_err(),
+            BuildError::FingerprintTooWide {
+                bitwidth: 104,
+                max_bitwidth: 64
+            }
+        );
+        assert!(builder()
+            .grow_at_load_factor(f64::NAN)
+            .build()
+            .is_err_and(|e| matches!(e, BuildError::InvalidLoadFactor { .. })));
+    }
+
+    #[test]
+    #[should_panic(expected = "initial capacity must be positive")]
+    fn finish_panics_on_invalid_configuration() {
+        let _ = ScalableCuckooFilter::<str>::new(0, 0.001);
+    }
+
+    #[test]
+    fn hasher_key_works() {
+        let build = |key| {
+            ScalableCuckooFilter::<str>::builder()
+                .hasher_key(key)
+                .finish()
+                .into_raw()
+        };
+        let (a, b, c) = (build([1; 16]), build([1; 16]), build([2; 16]));
+        let hash = |raw: &raw::ScalableCuckooFilter<SipHasher13>| crate::hash(&raw.hasher, "foo");
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&c));
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "ahash", feature = "xxhash")))]
+    fn default_hasher_is_keyed_by_rng() {
+        let hash = |filter: ScalableCuckooFilter<str>| crate::hash(&filter.raw.hasher, "foo");
+        let build = || ScalableCuckooFilter::<str>::builder().finish();
+        assert_ne!(hash(build()), hash(build()));
+
+        let build = || {
+            ScalableCuckooFilterBuilder::new()
+                .rng(StdRng::seed_from_u64(7))
+                .for_type::<str>()
+                .finish()
+        };
+        assert_eq!(build().raw.hasher.keys(), build().raw.hasher.keys());
+        assert_ne!(build().raw.hasher.keys(), DefaultHasher::default().keys());
+    }
+
+    #[test]
+    fn random_hasher_key_works() {
+        let build = || {
+            ScalableCuckooFilter::<str>::builder()
+                .random_hasher_key()
+                .finish()
+                .into_raw()
+        };
+        let (a, b) = (build(), build());
+        assert_ne!(crate::hash(&a.hasher, "foo"), crate::hash(&b.hasher, "foo"));
+    }
+
+    #[test]
+    fn alt_index_scheme_works() {
+        for scheme in [
+            AltIndexScheme::Hashed,
+            AltIndexScheme::Multiplicative,
+            AltIndexScheme::Custom(|fingerprint| fingerprint.rotate_left(17)),
+        ] {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(1000)
+                .alt_index_scheme(scheme)
+                .finish();
+            for i in 0..10_000 {
+                filter.insert(&i);
+            }
+            assert!(filter.layers().len() > 1);
+            assert!((0..10_000).all(|i| filter.contains(&i)));
+            assert_eq!(filter.verify_integrity(), Ok(()));
+            for i in 0..10_000 {
+                assert!(filter.remove(&i));
+            }
+            assert!(filter.is_empty());
+        }
+    }
+
+    #[test]
+    fn non_power_of_two_buckets_work() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1100)
+            .power_of_two_buckets(false)
+            .finish();
+        assert!(!filter.power_of_two_buckets());
+        assert_eq!(filter.capacity(), 1100);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert!(filter.layers().len() > 1);
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let false_positives = (10_000..1_010_000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 2000, "{}", false_positives);
+
+        for i in 0..5_000 {
+            assert!(filter.remove(&i));
+        }
+        filter.shrink_to_fit();
+        assert!((5_000..10_000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn bucket_counts_work() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100_000)
+            .entries_per_bucket(2)
+            .bucket_counts([1000, 3000])
+            .finish();
+        assert_eq!(filter.bucket_counts(), [1000, 3000]);
+        for i in 0..20_000 {
+            filter.insert(&i);
+        }
+        assert!((0..20_000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let buckets = filter
+            .raw
+            .filters
+            .iter()
+            .map(|f| f.entries())
+            .collect::<Vec<_>>();
+        assert_eq!(buckets[..3], [2000, 6000, 12000]);
+    }
+
+    #[test]
+    fn long_fingerprints_work() {
+        for (fpp, fingerprint_bitwidth) in [(1e-16, 58), (1e-17, 61), (1e-18, 64)] {
+            for backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+                let mut filter = ScalableCuckooFilterBuilder::new()
+                    .initial_capacity(1000)
+                    .false_positive_probability(fpp)
+                    .storage_backend(backend)
+                    .finish();
+                assert_eq!(
+                    filter.layers()[0].fingerprint_bitwidth(),
+                    fingerprint_bitwidth
+                );
+                for i in 0..5_000 {
+                    filter.insert(&i);
+                }
+                assert!((0..5_000).all(|i| filter.contains(&i)));
+                assert!(!(5_000..105_000).any(|i| filter.contains(&i)));
+                assert_eq!(filter.verify_integrity(), Ok(()));
+                for i in 0..5_000 {
+                    assert!(filter.remove(&i));
+                }
+                assert!(filter.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn morton_backend_works() {
+        let build = |backend, strategy| {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(1000)
+                .insertion_strategy(strategy)
+                .storage_backend(backend)
+                .finish();
+            for i in 0..10_000 {
+                filter.insert(&i);
+            }
+            filter
+        };
+        for strategy in [
+            InsertionStrategy::RandomWalk,
+            InsertionStrategy::BreadthFirst,
+        ] {
+            let mut filter = build(StorageBackend::Morton, strategy);
+            assert_eq!(filter.storage_backend(), StorageBackend::Morton);
+            assert!((0..10_000).all(|i| filter.contains(&i)));
+            assert_eq!(filter.verify_integrity(), Ok(()));
+
+            let false_positives = (10_000..110_000).filter(|i| filter.contains(i)).count();
+            assert!(false_positives < 100, "{}", false_positives);
+
+            filter.shrink_to_fit();
+            assert!((0..10_000).all(|i| filter.contains(&i)));
+            let removed = (0..10_000).filter(|i| filter.remove(i)).count();
+            assert!(removed > 9_990);
+            assert_eq!(filter.len(), 10_000 - removed as u64);
+        }
+
+        let morton = build(StorageBackend::Morton, InsertionStrategy::RandomWalk);
+        assert!(morton.layers()[0].fill_ratio() > 0.95);
+    }
+
+    #[test]
+    fn blocked_backend_works() {
+        for power_of_two in [true, false] {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(1000)
+                .storage_backend(StorageBackend::Blocked)
+                .power_of_two_buckets(power_of_two)
+                .finish();
+            for i in 0..10_000 {
+                filter.insert(&i);
+            }
+            assert_eq!(filter.storage_backend(), StorageBackend::Blocked);
+            assert!((0..10_000).all(|i| filter.contains(&i)));
+            assert_eq!(filter.verify_integrity(), Ok(()));
+
+            let false_positives = (10_000..110_000).filter(|i| filter.contains(i)).count();
+            assert!(false_positives < 200, "{}", false_positives);
+
+            filter.shrink_to_fit();
+            assert!((0..10_000).all(|i| filter.contains(&i)));
+            let removed = (0..10_000).filter(|i| filter.remove(i)).count();
+            assert!(removed > 9_990);
+        }
+    }
+
+    #[test]
+    fn stash_capacity_works() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .max_kicks(0)
+            .stash_capacity(8)
+            .finish();
+        assert_eq!(filter.stash_capacity(), 8);
+        while filter.raw.filters.len() == 1 {
+            filter.insert(&filter.len());
+        }
+        assert_eq!(filter.raw.filters[0].kicked_out_entries(), 9);
+        for i in 0..filter.len() {
+            assert!(filter.contains(&i));
+        }
+    }
+
+    #[test]
+    fn fingerprints_works() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1000)
+            .max_kicks(0)
+            .stash_capacity(usize::MAX)
+            .finish();
+        for i in 0..1024 {
+            filter.insert(&i);
+        }
+        for i in 0..100 {
+            filter.insert(&i);
+        }
+
+        let entries = filter.fingerprints().collect::<Vec<_>>();
+        assert_eq!(entries.len() as u64, filter.len());
+        let stashed = entries.iter().filter(|e| e.stashed).count();
+        assert!(stashed > 0);
+        assert_eq!(stashed, filter.stats().stash_len);
+        for e in &entries {
+            assert!(e.stashed || e.fingerprint != 0);
+            assert!(e.bucket_index < filter.raw.filters[e.layer].bucket_count());
+        }
+
+        for i in (0..100).chain(0..1024) {
+            assert!(filter.remove(&i));
+        }
+        assert_eq!(filter.fingerprints().count(), 0);
+    }
+
+    #[test]
+    fn defragment_works() {
+        // With fixed keys, every kicked out entry finds a free slot after the removals.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .hasher(DefaultHasher::default())
+            .initial_capacity(1000)
+            .max_kicks(0)
+            .stash_capacity(usize::MAX)
+            .finish();
+        for i in 0..1024 {
+            filter.insert(&i);
+        }
+        let kicked_out = filter.raw.filters[0].kicked_out_entries();
+        assert!(kicked_out > 0);
+
+        // Enough entries are removed so that every kicked out entry likely finds a free slot.
+        for i in 0..768 {
+            assert!(filter.remove(&i));
+        }
+        // Removals leave the remaining kicked out entries stashed.
+        assert!(filter.raw.filters[0].kicked_out_entries() > 0);
+
+        filter.defragment();
+        assert_eq!(filter.raw.filters[0].kicked_out_entries(), 0);
+        for i in 768..1024 {
+            assert!(filter.contains(&i));
+        }
+    }
+
+    #[test]
+    fn disable_stash_works() {
+        for strategy in [
+            InsertionStrategy::RandomWalk,
+            InsertionStrategy::BreadthFirst,
+        ] {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .insertion_strategy(strategy)
+                .disable_stash()
+                .finish();
+            assert!(!filter.is_stash_enabled());
+            for i in 0..10_000 {
+                filter.insert(&i);
+            }
+            assert_eq!(filter.len(), 10_000);
+            assert!(filter
+                .raw
+                .filters
+                .iter()
+                .all(|f| f.kicked_out_entries() == 0));
+            for i in 0..10_000 {
+                assert!(filter.contains(&i));
+            }
+
+            filter.shrink_to_fit();
+            for i in 0..10_000 {
+                assert!(filter.contains(&i));
+            }
         }
     }
 
-    /// Shrinks the capacity of this filter as much as possible.
-    pub fn shrink_to_fit(&mut self) {
-        for f in &mut self.filters {
-            f.shrink_to_fit(&self.hasher, &mut self.rng);
+    #[test]
+    fn layers_works() {
+        let mut filter = ScalableCuckooFilter::new(10, 0.001);
+        let layers = filter.layers();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].capacity(), 16);
+        assert_eq!(layers[0].fingerprint_bitwidth(), 14);
+        assert!(layers[0].is_empty());
+
+        for i in 0..100 {
+            filter.insert(&i);
         }
+        let layers = filter.layers();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers.iter().map(|l| l.len()).sum::<usize>(), 100);
+        assert_eq!(layers.iter().map(|l| l.capacity()).sum::<usize>(), 114);
+        assert_eq!(layers.iter().map(|l| l.bits()).sum::<u64>(), filter.bits());
+        assert!(layers.iter().any(|l| l.stash_len() > 0));
+        assert_eq!(layers[2].fingerprint_bitwidth(), 16);
     }
 
-    /// Removes `item` from this filter.
-    ///
-    /// This method returns `true` if an entry with the same fingerprint as `item` has been removed, otherwise it returns `false`.
-    pub fn remove(&mut self, item: &T) -> bool {
-        let item_hash = crate::hash(&self.hasher, item);
-        for filter in &mut self.filters {
-            let removed = filter.remove(&self.hasher, item_hash);
-            if removed {
-                return true;
+    #[test]
+    fn false_positive_probability_bound_exceeds_once_clamped() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Debug)]
+        struct Additive;
+        impl GrowthPolicy for Additive {
+            fn next_layer(&self, layers: &[LayerInfo], _: usize, c: &GrowthConfig) -> LayerSpec {
+                LayerSpec {
+                    capacity: c.initial_capacity,
+                    false_positive_probability: c.layer_false_positive_probability(layers.len()),
+                }
             }
         }
-        false
-    }
 
-    fn grow(&mut self) {
-        let capacity = self.initial_capacity * 2usize.pow(self.filters.len() as u32);
-        let probability =
-            self.false_positive_probability / 2f64.powi(self.filters.len() as i32 + 1);
-        let fingerprint_bitwidth = ((1.0 / probability).log2()
-            + ((2 * self.entries_per_bucket) as f64).log2())
-        .ceil() as usize;
-        let filter = CuckooFilter::new(
-            fingerprint_bitwidth,
-            self.entries_per_bucket,
-            capacity,
-            self.max_kicks,
-        );
-        self.filters.push(filter);
+        let fpp = 1e-7;
+        let clamped = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&clamped);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(64)
+            .false_positive_probability(fpp)
+            .hash_width(HashWidth::Bits32)
+            .growth_policy(Additive)
+            .event_listener(move |event: &FilterEvent| {
+                if let FilterEvent::FingerprintClamped(_) = event {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .finish::<usize>();
+        for _ in 0..200 {
+            filter.raw.grow();
+            if clamped.load(Ordering::SeqCst) == 0 {
+                assert!(filter.false_positive_probability_bound() <= fpp);
+            }
+        }
+        assert!(clamped.load(Ordering::SeqCst) > 0);
+        assert!(filter
+            .layers()
+            .iter()
+            .all(|l| l.fingerprint_bitwidth() <= 32));
+        assert!(filter.false_positive_probability_bound() > fpp);
     }
-}
-impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng + Clone> Clone for ScalableCuckooFilter<T, H, R> {
-    fn clone(&self) -> Self {
-        Self {
-            hasher: self.hasher.clone(),
-            filters: self.filters.clone(),
-            initial_capacity: self.initial_capacity,
-            false_positive_probability: self.false_positive_probability,
-            entries_per_bucket: self.entries_per_bucket,
-            max_kicks: self.max_kicks,
-            rng: self.rng.clone(),
-            _item: self._item,
+
+    #[test]
+    fn fill_ratio_works() {
+        let mut filter = ScalableCuckooFilter::with_hasher(1000, 0.001, DefaultHasher::default());
+        assert_eq!(filter.fill_ratio(), 0.0);
+        for i in 0..512 {
+            filter.insert(&i);
         }
+        assert_eq!(filter.fill_ratio(), 0.5);
+        assert_eq!(filter.layers()[0].fill_ratio(), 0.5);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn false_positive_probability_bound_holds() {
+        #[derive(Debug)]
+        struct Greedy;
+        impl GrowthPolicy for Greedy {
+            fn next_layer(&self, layers: &[LayerInfo], _: usize, c: &GrowthConfig) -> LayerSpec {
+                LayerSpec {
+                    capacity: c.initial_capacity * (layers.len() + 1),
+                    false_positive_probability: c.false_positive_probability,
+                }
+            }
+        }
+
+        for fpp in [0.1, 0.01, 0.001, 0.0001, 1.0 / 1024.0] {
+            for ratio in [0.1, 0.5, 0.9] {
+                for entries_per_bucket in [1, 2, 4, 8] {
+                    let mut filter = ScalableCuckooFilterBuilder::new()
+                        .initial_capacity(1)
+                        .false_positive_probability(fpp)
+                        .tightening_ratio(ratio)
+                        .entries_per_bucket(entries_per_bucket)
+                        .finish::<usize>();
+                    let mut greedy = ScalableCuckooFilterBuilder::new()
+                        .initial_capacity(1)
+                        .false_positive_probability(fpp)
+                        .tightening_ratio(ratio)
+                        .entries_per_bucket(entries_per_bucket)
+                        .growth_policy(Greedy)
+                        .finish::<usize>();
+                    for _ in 0..10 {
+                        filter.raw.grow();
+                        greedy.raw.grow();
+                        assert!(filter.false_positive_probability_bound() <= fpp);
+                        assert!(greedy.false_positive_probability_bound() <= fpp);
+                    }
+                }
+            }
+        }
+    }
 
     #[test]
-    fn it_works() {
-        let mut filter = ScalableCuckooFilter::new(1000, 0.001);
-        assert!(filter.is_empty());
-        assert_eq!(filter.bits(), 14_336);
+    fn estimated_false_positive_probability_works() {
+        let mut filter = ScalableCuckooFilter::new(1000, 0.01);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        let estimated = filter.estimated_false_positive_probability();
+        assert!(estimated < filter.false_positive_probability());
 
-        assert!(!filter.contains("foo"));
-        filter.insert("foo");
-        assert!(filter.contains("foo"));
+        let trials = 200_000;
+        let false_positives = (10_000..10_000 + trials)
+            .filter(|i| filter.contains(i))
+            .count();
+        let actual = false_positives as f64 / trials as f64;
+        assert!(
+            (actual - estimated).abs() < estimated * 0.2,
+            "actual={actual}, estimated={estimated}"
+        );
     }
 
     #[test]
-    fn clone_string_and_str() {
-        let mut filter: ScalableCuckooFilter<String> = ScalableCuckooFilter::new(1000, 0.001);
-        let x = "foo".to_owned();
-        filter.insert(&x);
-        let cloned = filter.clone();
-        assert!(filter.contains(&"foo".to_string()));
-        assert!(cloned.contains(&"foo".to_string()));
+    fn event_listener_works() {
+        use std::sync::Mutex;
 
-        let mut filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::new(1000, 0.001);
-        let x = "foo";
-        filter.insert(x);
-        let cloned = filter.clone();
-        assert!(filter.contains("foo"));
-        assert!(cloned.contains("foo"));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = Arc::clone(&events);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(10)
+            .event_listener(move |event: &FilterEvent| {
+                let event = match event {
+                    FilterEvent::Grow(layer) => ("grow", layer.fingerprint_bitwidth()),
+                    FilterEvent::Stash(layer) => ("stash", layer.fingerprint_bitwidth()),
+                    FilterEvent::FingerprintClamped(layer) => {
+                        ("clamped", layer.fingerprint_bitwidth())
+                    }
+                };
+                events_clone.lock().unwrap().push(event);
+            })
+            .finish();
+        for i in 0..100 {
+            filter.insert(&i);
+        }
+
+        let events = events.lock().unwrap();
+        let counters = filter.counters();
+        let grows = events.iter().filter(|e| e.0 == "grow").count();
+        let stashes = events.iter().filter(|e| e.0 == "stash").count();
+        assert_eq!(grows as u64, counters.growths);
+        assert_eq!(stashes as u64, counters.stash_insertions);
+        assert!(events.contains(&("grow", 15)));
     }
 
     #[test]
-    fn insert_works() {
-        use rand::{rngs::StdRng, SeedableRng};
+    fn fingerprint_clamped_event_works() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-        let mut seed = [0; 32];
-        for i in 0..seed.len() {
-            seed[i] = i as u8;
+        let clamped = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&clamped);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(10)
+            .false_positive_probability(1e-17)
+            .event_listener(move |event: &FilterEvent| {
+                if let FilterEvent::FingerprintClamped(layer) = event {
+                    assert_eq!(layer.fingerprint_bitwidth(), 64);
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .finish();
+        assert_eq!(filter.layers()[0].fingerprint_bitwidth(), 61);
+        for i in 0..1000 {
+            filter.insert(&i);
         }
+        assert!(filter.layers().len() > 4);
+        assert_eq!(clamped.load(Ordering::SeqCst), filter.layers().len() - 4);
+    }
 
-        let rng: StdRng = SeedableRng::from_seed(seed);
+    #[test]
+    fn verify_integrity_works() {
         let mut filter = ScalableCuckooFilterBuilder::new()
             .initial_capacity(100)
-            .false_positive_probability(0.00001)
-            .rng(rng)
+            .stash_capacity(10)
             .finish();
-        for i in 0..10_000 {
-            assert!(!filter.contains(&i));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+        for i in 0..1000 {
             filter.insert(&i);
-            assert!(filter.contains(&i));
         }
-        assert_eq!(filter.len(), 10_000);
+        assert_eq!(filter.verify_integrity(), Ok(()));
+        for i in 0..500 {
+            filter.remove(&i);
+        }
+        assert_eq!(filter.verify_integrity(), Ok(()));
+        filter.shrink_to_fit();
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        filter.raw.filters.clear();
+        assert_eq!(filter.verify_integrity(), Err(IntegrityError::NoLayers));
+
+        // 64-bit fingerprints of 64-bit hashes determine the bucket indices of the items,
+        // so moving an entry to another bucket is detected (unless it is the alternative one,
+        // which the fixed keys rule out).
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .hasher(DefaultHasher::default())
+            .initial_capacity(100)
+            .false_positive_probability(1e-18)
+            .hash_width(HashWidth::Bits64)
+            .finish();
+        for i in 0..10 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let layer = &mut filter.raw.filters[0];
+        let (i, fingerprint, _) = layer.stored_entries().next().unwrap();
+        let entry = (0..4)
+            .find(|&e| layer.get_entry(i, e) == fingerprint)
+            .unwrap();
+        let j = (i + 1..layer.bucket_count())
+            .find(|&j| layer.get_entry(j, 0) == 0)
+            .unwrap();
+        layer.set_entry(i, entry, 0);
+        layer.set_entry(j, 0, fingerprint);
+        assert_eq!(
+            filter.verify_integrity(),
+            Err(IntegrityError::MisplacedEntry {
+                layer: 0,
+                fingerprint,
+                bucket_index: j,
+            })
+        );
     }
 
     #[test]
-    fn remove_works() {
+    #[cfg(feature = "arbitrary")]
+    fn arbitrary_works() {
+        use arbitrary::{Arbitrary, Unstructured};
         use rand::{rngs::StdRng, SeedableRng};
 
-        let mut seed = [0; 32];
-        for i in 0..seed.len() {
-            seed[i] = i as u8;
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let data = (0..1024).map(|_| rng.gen()).collect::<Vec<u8>>();
+            let mut u = Unstructured::new(&data);
+            let filter = ScalableCuckooFilter::<u64>::arbitrary(&mut u).unwrap();
+            assert_eq!(filter.verify_integrity(), Ok(()));
         }
+    }
 
-        let rng: StdRng = SeedableRng::from_seed(seed);
-        let mut filter = ScalableCuckooFilterBuilder::new()
-            .initial_capacity(100)
-            .false_positive_probability(0.00001)
-            .rng(rng)
-            .finish();
+    #[test]
+    fn seed_works() {
+        let build = |seed| {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .seed(seed)
+                .finish();
+            for i in 0..1000 {
+                filter.insert(&i);
+            }
+            filter
+        };
+        let a = build([1; 32]);
+        let b = build([1; 32]);
+        let c = build([2; 32]);
+        assert_eq!(a.counters(), b.counters());
 
-        for i in 0..10_000 {
-            filter.insert(&i);
+        let false_positives = |f: &ScalableCuckooFilter<usize, _, _>| {
+            (1000..100_000)
+                .filter(|i| f.contains(i))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(false_positives(&a), false_positives(&b));
+        assert_ne!(false_positives(&a), false_positives(&c));
+    }
+
+    #[cfg(feature = "small_rng")]
+    #[test]
+    fn small_rng_works() {
+        let build = || {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .small_rng(7)
+                .finish();
+            for i in 0..1000 {
+                filter.insert(&i);
+            }
+            filter
+        };
+        let a = build();
+        let b = build();
+        assert_eq!(a.len(), 1000);
+        assert!(a.counters().kicks > 0);
+        assert_eq!(a.counters(), b.counters());
+
+        let filter = ScalableCuckooFilterBuilder::new()
+            .small_rng_from_entropy()
+            .finish::<usize>();
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn buffered_inserter_works() {
+        let mut filter = ScalableCuckooFilter::new(100, 0.001);
+        let mut inserter = filter.buffered_inserter(100);
+        for i in 0..150 {
+            inserter.insert(&i);
         }
-        for i in 0..10_000 {
-            assert!(filter.remove(&i));
-            assert!(!filter.contains(&i));
+        assert_eq!(inserter.buffered_len(), 50);
+        inserter.flush();
+        assert_eq!(inserter.buffered_len(), 0);
+        for i in 150..10_000 {
+            inserter.insert(&i);
         }
+        drop(inserter);
+
+        assert_eq!(filter.len(), 10_000);
         for i in 0..10_000 {
-            assert!(!filter.remove(&i));
+            assert!(filter.contains(&i));
         }
     }
 
+    #[cfg(feature = "rayon")]
     #[test]
-    fn fingerprint_collision_remove_works() {
-        let mut filter = ScalableCuckooFilter::new(1000, 0.001);
-        filter.insert("foo");
-        filter.insert("foo");
-        assert!(filter.contains("foo"));
+    fn par_extend_works() {
+        use rayon::prelude::*;
 
-        filter.remove("foo");
-        assert!(filter.contains("foo"));
+        let mut filter = ScalableCuckooFilter::new(100, 0.001);
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        filter.par_extend((1000..200_000).into_par_iter());
+        filter.par_extend(vec![200_000, 200_001]);
 
-        filter.remove("foo");
-        assert!(!filter.contains("foo"));
+        assert_eq!(filter.len(), 200_002);
+        assert!((0..200_002).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
+
+        let false_positives = (200_002..300_002).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 200, "{}", false_positives);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1100)
+            .power_of_two_buckets(false)
+            .finish();
+        filter.par_extend((0..100_000).into_par_iter());
+        assert_eq!(filter.len(), 100_000);
+        assert!((0..100_000).all(|i| filter.contains(&i)));
+        assert_eq!(filter.verify_integrity(), Ok(()));
     }
 
     #[test]
-    fn shrink_to_fit_works() {
-        let mut filter = ScalableCuckooFilter::new(1000, 0.001);
+    fn stats_works() {
+        let mut filter = ScalableCuckooFilter::new(10, 0.001);
         for i in 0..100 {
             filter.insert(&i);
         }
-        assert_eq!(filter.capacity(), 1024);
-        assert_eq!(filter.bits(), 14336);
+        let stats = filter.stats();
+        assert_eq!(stats.len, 100);
+        assert_eq!(stats.capacity, filter.capacity());
+        assert_eq!(stats.bits, filter.bits());
+        assert_eq!(stats.number_of_layers, 3);
+        assert_eq!(
+            stats.stash_len,
+            filter
+                .raw
+                .filters
+                .iter()
+                .map(|f| f.stash_len())
+                .sum::<usize>()
+        );
+        assert_eq!(stats.layers.len(), 3);
+    }
 
-        filter.shrink_to_fit();
-        for i in 0..100 {
+    #[test]
+    fn reserve_works() {
+        let mut filter = ScalableCuckooFilter::new(100, 0.001);
+        filter.reserve(100);
+        assert_eq!(filter.raw.filters.len(), 1);
+
+        filter.reserve(10_000);
+        assert_eq!(filter.raw.filters.len(), 2);
+        assert_eq!(filter.capacity(), 128 + 16384);
+
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.raw.filters.len(), 2);
+        for i in 0..10_000 {
             assert!(filter.contains(&i));
         }
-        assert_eq!(filter.capacity(), 128);
-        assert_eq!(filter.bits(), 1792);
     }
 
     #[test]
-    fn info_params() {
-        let mut filter = ScalableCuckooFilter::new(10, 0.001);
+    fn grow_at_load_factor_works() {
+        // With fixed keys, none of the items has a zero fingerprint, which is stashed and
+        // doesn't count toward the load factor.
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .hasher(DefaultHasher::default())
+            .initial_capacity(1000)
+            .grow_at_load_factor(0.5)
+            .finish();
+        assert_eq!(filter.grow_at_load_factor(), Some(0.5));
+        for i in 0..511 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.raw.filters.len(), 1);
 
-        // constant values
-        assert_eq!(filter.max_kicks(), 512);
-        assert_eq!(filter.entries_per_bucket(), 4);
-        assert_eq!(filter.false_positive_probability(), 0.001);
-        // dynamic values
-        assert_eq!(filter.bits(), 224);
-        assert_eq!(filter.capacity(), 16);
+        filter.insert(&511);
+        assert_eq!(filter.raw.filters.len(), 2);
+        for i in 0..512 {
+            assert!(filter.contains(&i));
+        }
+    }
 
-        for i in 0..100 {
+    #[test]
+    fn allocator_works() {
+        use std::alloc::{GlobalAlloc, Layout, System};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counting(AtomicUsize);
+        unsafe impl GlobalAlloc for Counting {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                self.0.fetch_add(layout.size(), Ordering::SeqCst);
+                GlobalAlloc::alloc(&System, layout)
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                self.0.fetch_sub(layout.size(), Ordering::SeqCst);
+                GlobalAlloc::dealloc(&System, ptr, layout)
+            }
+        }
+
+        let counting = Arc::new(Counting::default());
+        let allocated = || counting.0.load(Ordering::SeqCst) as u64;
+        for backend in [StorageBackend::Buckets, StorageBackend::Morton] {
+            let mut filter: ScalableCuckooFilter<usize> = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .storage_backend(backend)
+                .allocator(counting.clone())
+                .finish();
+            for i in 0..1000 {
+                filter.insert(&i);
+            }
+            assert!(filter.layers().len() > 1);
+            let before = allocated();
+            assert!(before > 0);
+
+            let cloned = filter.clone();
+            assert_eq!(allocated(), before * 2);
+            drop(cloned);
+            assert_eq!(allocated(), before);
+
+            for i in 0..900 {
+                filter.remove(&i);
+            }
+            filter.shrink_to_fit();
+            assert!((900..1000).all(|i| filter.contains(&i)));
+            drop(filter);
+            assert_eq!(allocated(), 0);
+        }
+    }
+
+    #[test]
+    fn bitor_works() {
+        let new_filter = || {
+            ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .hasher(DefaultHasher::default())
+                .for_type::<u64>()
+                .finish()
+        };
+        let mut a = new_filter();
+        let mut b = new_filter();
+        for i in 0..1000 {
+            if i % 2 == 0 {
+                a.insert(&i);
+            } else {
+                b.insert(&i);
+            }
+        }
+
+        let union = a.clone() | &b;
+        assert_eq!(union.len(), 1000);
+        assert!((0..1000).all(|i| union.contains(&i)));
+
+        // Entries the filter already has aren't inserted again.
+        a |= &union;
+        assert_eq!(a.len(), 1000);
+        a |= b;
+        assert_eq!(a.len(), 1000);
+
+        // Default filters are keyed the same, so they can be combined.
+        let mut c = ScalableCuckooFilter::default();
+        c.insert("foo");
+        let union = ScalableCuckooFilter::default() | c;
+        assert!(union.contains("foo"));
+
+        let result = std::panic::catch_unwind(|| {
+            let other = ScalableCuckooFilterBuilder::new()
+                .false_positive_probability(0.1)
+                .for_type::<u64>()
+                .finish();
+            new_filter() | other
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn page_options_work() {
+        let mut filter: ScalableCuckooFilter<usize> = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100_000)
+            .pre_touch_pages(true)
+            .transparent_hugepages(true)
+            .finish();
+        for i in 0..300_000 {
             filter.insert(&i);
         }
+        assert!(filter.layers().len() > 1);
+        assert!((0..300_000).all(|i| filter.contains(&i)));
 
-        assert_eq!(filter.bits(), 2752);
-        assert_eq!(filter.capacity(), 114);
+        let cloned = filter.clone();
+        assert!((0..300_000).all(|i| cloned.contains(&i)));
+    }
+
+    #[test]
+    fn growth_policy_works() {
+        use crate::growth::LayerSpec;
+
+        #[derive(Debug)]
+        struct Additive;
+        impl GrowthPolicy for Additive {
+            fn next_layer(&self, layers: &[LayerInfo], _: usize, c: &GrowthConfig) -> LayerSpec {
+                LayerSpec {
+                    capacity: c.initial_capacity,
+                    false_positive_probability: c.layer_false_positive_probability(layers.len()),
+                }
+            }
+        }
+
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(64)
+            .growth_policy(Additive)
+            .finish();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        for i in 0..1000 {
+            assert!(filter.contains(&i));
+        }
+        assert!(filter.raw.filters.iter().all(|f| f.capacity() < 128));
     }
 
     #[test]
@@ -451,5 +3747,114 @@ mod test {
             assert!(filter.contains(&i));
             assert!(deserialized.contains(&i));
         }
+
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .storage_backend(StorageBackend::Morton)
+            .finish();
+        for i in 0..100usize {
+            filter.insert(&i);
+        }
+        let serialized = serde_json::to_string(&filter).unwrap();
+        let deserialized: ScalableCuckooFilter<usize> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.storage_backend(), StorageBackend::Morton);
+        for i in 0..100 {
+            assert!(deserialized.contains(&i));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde_support")]
+    fn serde_round_trip_covers_every_layer_type() {
+        let backends = [
+            StorageBackend::Buckets,
+            StorageBackend::Blocked,
+            StorageBackend::Morton,
+        ];
+        let hash_widths = [HashWidth::Bits32, HashWidth::Bits64, HashWidth::Bits128];
+        for backend in backends {
+            for hash_width in hash_widths {
+                // Fingerprints wider than 32 bits make the stash store wide entries.
+                let fpp = if hash_width == HashWidth::Bits128 {
+                    1e-12
+                } else {
+                    0.001
+                };
+                let mut filter = ScalableCuckooFilterBuilder::new()
+                    .initial_capacity(100)
+                    .false_positive_probability(fpp)
+                    .storage_backend(backend)
+                    .hash_width(hash_width)
+                    .power_of_two_buckets(false)
+                    .finish();
+                for i in 0..3000usize {
+                    filter.insert(&i);
+                }
+                assert!(filter.layers().iter().any(|l| l.stash_len() > 0));
+
+                let serialized = serde_json::to_string(&filter).unwrap();
+                let deserialized: ScalableCuckooFilter<usize> =
+                    serde_json::from_str(&serialized).unwrap();
+                assert_eq!(deserialized.storage_backend(), backend);
+                assert_eq!(deserialized.hash_width(), hash_width);
+                assert!((0..3000).all(|i| deserialized.contains(&i)));
+                assert_eq!(deserialized.verify_integrity(), Ok(()));
+                assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde_support")]
+    fn bincode_and_postcard_round_trips_work() {
+        // These formats aren't self-describing, so every backend has to be told apart
+        // without looking at the data.
+        for backend in [
+            StorageBackend::Buckets,
+            StorageBackend::Blocked,
+            StorageBackend::Morton,
+        ] {
+            let mut filter = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .storage_backend(backend)
+                .finish();
+            for i in 0..1000usize {
+                filter.insert(&i);
+            }
+
+            let bytes = bincode::serialize(&filter).unwrap();
+            let deserialized: ScalableCuckooFilter<usize> = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(deserialized.storage_backend(), backend);
+            assert!((0..1000).all(|i| deserialized.contains(&i)));
+            assert!(deserialized == filter);
+
+            let bytes = postcard::to_allocvec(&filter).unwrap();
+            let deserialized: ScalableCuckooFilter<usize> = postcard::from_bytes(&bytes).unwrap();
+            assert_eq!(deserialized.storage_backend(), backend);
+            assert!(deserialized == filter);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde_support")]
+    fn randomly_keyed_filters_survive_round_trips() {
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .random_hasher_key()
+            .for_type::<usize>()
+            .finish();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+
+        let json = serde_json::to_string(&filter).unwrap();
+        let deserialized: ScalableCuckooFilter<usize, SipHasher13> =
+            serde_json::from_str(&json).unwrap();
+        assert!((0..1000).all(|i| deserialized.contains(&i)));
+
+        let bytes = filter.to_bytes().unwrap();
+        let read = ScalableCuckooFilter::<usize, SipHasher13>::from_bytes(&bytes).unwrap();
+        assert!((0..1000).all(|i| read.contains(&i)));
+        let view = crate::ScalableCuckooFilterView::<usize, SipHasher13>::new(&bytes).unwrap();
+        assert!((0..1000).all(|i| view.contains(&i)));
     }
 }
@@ -0,0 +1,118 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use rand::Rng;
+use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+
+use crate::{raw, IdentityHasher, ScalableCuckooFilter};
+
+/// [`DeserializeSeed`] that deserializes a [`ScalableCuckooFilter`] with a given hasher and RNG.
+///
+/// The RNG and the hasher aren't serialized (except for the keys exported by [`HasherKeys`]),
+/// so `Deserialize` of the filter makes them by `Default`, which is wrong for keyed hashers
+/// (a filter built with other keys silently answers lookups incorrectly) and impossible for
+/// types without `Default`. This seed takes them from the caller instead.
+/// It reads the same representation as `Deserialize`, ignoring the serialized keys.
+///
+/// [`HasherKeys`]: crate::HasherKeys
+///
+/// # Examples
+///
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use scalable_cuckoo_filter::{ScalableCuckooFilterBuilder, ScalableCuckooFilterSeed};
+/// use serde::de::DeserializeSeed;
+/// use siphasher::sip::SipHasher13;
+///
+/// let hasher = SipHasher13::new_with_keys(1, 2);
+/// let mut filter = ScalableCuckooFilterBuilder::new()
+///     .hasher(hasher)
+///     .rng(StdRng::seed_from_u64(3))
+///     .for_type::<str>()
+///     .finish();
+/// filter.insert("foo");
+/// let json = serde_json::to_string(&filter).unwrap();
+///
+/// let seed = ScalableCuckooFilterSeed::<str, _, _>::new(hasher, StdRng::seed_from_u64(4));
+/// let filter = seed
+///     .deserialize(&mut serde_json::Deserializer::from_str(&json))
+///     .unwrap();
+/// assert!(filter.contains("foo"));
+/// ```
+pub struct ScalableCuckooFilterSeed<T: ?Sized, H, R> {
+    hasher: H,
+    rng: R,
+    _item: PhantomData<fn(&T)>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> ScalableCuckooFilterSeed<T, H, R> {
+    /// Makes a new seed that gives `hasher` and `rng` to the deserialized filter.
+    ///
+    /// `hasher` must hash items as the hasher of the serialized filter did
+    /// (e.g., be keyed the same).
+    pub fn new(hasher: H, rng: R) -> Self {
+        ScalableCuckooFilterSeed {
+            hasher,
+            rng,
+            _item: PhantomData,
+        }
+    }
+}
+impl<'de, T: Hash + ?Sized, H: Hasher + Clone, R: Rng> DeserializeSeed<'de>
+    for ScalableCuckooFilterSeed<T, H, R>
+{
+    type Value = ScalableCuckooFilter<T, H, R>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        // These stand in for the hasher and the RNG, which are given by the seed.
+        let raw = raw::ScalableCuckooFilter::<IdentityHasher, ()>::deserialize(deserializer)?;
+        Ok(ScalableCuckooFilter::from_raw(
+            raw.with_parts(self.hasher, self.rng),
+        ))
+    }
+}
+impl<T: ?Sized, H, R> fmt::Debug for ScalableCuckooFilterSeed<T, H, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScalableCuckooFilterSeed")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use siphasher::sip::SipHasher13;
+
+    use super::*;
+    use crate::ScalableCuckooFilterBuilder;
+
+    #[test]
+    fn it_works() {
+        let hasher = SipHasher13::new_with_keys(1, 2);
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .hasher(hasher)
+            .rng(StdRng::seed_from_u64(3))
+            .for_type::<u64>()
+            .finish();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        let json = serde_json::to_string(&filter).unwrap();
+
+        let seed = ScalableCuckooFilterSeed::<u64, _, _>::new(hasher, StdRng::seed_from_u64(4));
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let mut read = seed.deserialize(&mut deserializer).unwrap();
+        assert_eq!(read.len(), 1000);
+        assert!((0..1000).all(|i| read.contains(&i)));
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+
+        // The read filter keeps working with the given RNG.
+        for i in 1000..2000 {
+            read.insert(&i);
+        }
+        assert!((0..2000).all(|i| read.contains(&i)));
+    }
+}
@@ -0,0 +1,215 @@
+use std::borrow::Borrow;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::{DefaultHasher, HasherKeys, MmapCuckooFilter, ScalableCuckooFilter};
+
+/// Directory in which Linux keeps the POSIX shared memory objects (see `shm_open(3)`).
+const SHM_DIR: &str = "/dev/shm";
+
+/// Read-only filter in a named shared memory segment, which processes on the same host
+/// query without copying.
+///
+/// A writer process [`publish`]es a filter under a name, and any number of reader processes
+/// [`open`] it. Every reader maps the same pages of memory, so the filter takes memory once
+/// however many processes query it. The writer may keep updating its own filter and
+/// publish it again; readers pick up the new generation by [`refresh`], while lookups in the
+/// old one keep working until then.
+///
+/// The segment is the POSIX shared memory object of the name (i.e., a file in `/dev/shm`),
+/// which holds the filter in the format of [`ScalableCuckooFilter::to_bytes`].
+/// It persists until [`unlink`]ed or the host reboots.
+///
+/// [`publish`]: SharedCuckooFilter::publish
+/// [`open`]: SharedCuckooFilter::open
+/// [`refresh`]: SharedCuckooFilter::refresh
+/// [`unlink`]: SharedCuckooFilter::unlink
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{ScalableCuckooFilterBuilder, SharedCuckooFilter};
+///
+/// let name = format!("doc-filter-{}", std::process::id());
+///
+/// // In the writer process.
+/// let mut filter = ScalableCuckooFilterBuilder::new()
+///     .for_type::<str>()
+///     .finish();
+/// filter.insert("foo");
+/// SharedCuckooFilter::publish(&name, &filter).unwrap();
+///
+/// // In reader processes.
+/// let mut shared: SharedCuckooFilter<str> = SharedCuckooFilter::open(&name).unwrap();
+/// assert!(shared.contains("foo"));
+///
+/// filter.insert("bar");
+/// SharedCuckooFilter::publish(&name, &filter).unwrap();
+/// assert!(shared.refresh().unwrap());
+/// assert!(shared.contains("bar"));
+///
+/// SharedCuckooFilter::<str>::unlink(&name).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SharedCuckooFilter<T: ?Sized, H = DefaultHasher> {
+    path: PathBuf,
+    inode: u64,
+    filter: MmapCuckooFilter<T, H>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone + Default + HasherKeys> SharedCuckooFilter<T, H> {
+    /// Publishes `filter` as the segment named `name`, replacing any earlier generation.
+    ///
+    /// The segment is replaced atomically, so readers never see a partially written filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is invalid (empty, or containing `/`), if the segment can't
+    /// be written (e.g., because `/dev/shm` is full), or if the alternative index scheme of
+    /// `filter` is [`AltIndexScheme::Custom`].
+    ///
+    /// [`AltIndexScheme::Custom`]: crate::AltIndexScheme::Custom
+    pub fn publish<R: Rng>(name: &str, filter: &ScalableCuckooFilter<T, H, R>) -> io::Result<()> {
+        filter.save_to_path(segment_path(name)?)
+    }
+
+    /// Opens the segment named `name`, hashing items as [`MmapCuckooFilter::open`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is invalid, if the segment doesn't exist,
+    /// or if its contents are malformed.
+    pub fn open(name: &str) -> io::Result<Self> {
+        let path = segment_path(name)?;
+        let (inode, filter) = map(&path)?;
+        Ok(SharedCuckooFilter {
+            path,
+            inode,
+            filter,
+        })
+    }
+
+    /// Maps the latest generation of the segment if it has been published again since this
+    /// filter was opened (or last refreshed), returning whether it has.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the segment has been unlinked or its contents are malformed.
+    /// This filter keeps the generation it has mapped then.
+    pub fn refresh(&mut self) -> io::Result<bool> {
+        if fs::metadata(&self.path)?.ino() == self.inode {
+            return Ok(false);
+        }
+        (self.inode, self.filter) = map(&self.path)?;
+        Ok(true)
+    }
+
+    /// Removes the segment named `name`.
+    ///
+    /// Processes that have opened it can keep using it, and its memory is freed
+    /// when all of them drop it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is invalid or the segment doesn't exist.
+    pub fn unlink(name: &str) -> io::Result<()> {
+        fs::remove_file(segment_path(name)?)
+    }
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> SharedCuckooFilter<T, H> {
+    /// Returns the mapped filter.
+    pub fn filter(&self) -> &MmapCuckooFilter<T, H> {
+        &self.filter
+    }
+
+    /// Returns the approximate number of items inserted in the filter.
+    pub fn len(&self) -> u64 {
+        self.filter.len()
+    }
+
+    /// Returns `true` if the filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty()
+    }
+
+    /// Returns `true` if the filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.filter.contains(item)
+    }
+}
+
+fn segment_path(name: &str) -> io::Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid shared memory segment name",
+        ));
+    }
+    Ok(Path::new(SHM_DIR).join(name))
+}
+
+fn map<T, H>(path: &Path) -> io::Result<(u64, MmapCuckooFilter<T, H>)>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + Default + HasherKeys,
+{
+    // Looked up before the segment is mapped, so that a generation published in between
+    // is mapped again (rather than missed) by the next refresh.
+    let inode = fs::metadata(path)?.ino();
+    Ok((inode, MmapCuckooFilter::open(path)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ScalableCuckooFilterBuilder;
+
+    #[test]
+    fn it_works() {
+        let name = format!("scalable-cuckoo-filter-test-{}", std::process::id());
+        let mut filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .for_type::<usize>()
+            .finish();
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        SharedCuckooFilter::publish(&name, &filter).unwrap();
+
+        let mut readers = (0..2)
+            .map(|_| SharedCuckooFilter::<usize>::open(&name).unwrap())
+            .collect::<Vec<_>>();
+        for reader in &mut readers {
+            assert_eq!(reader.len(), 1000);
+            assert!((0..1000).all(|i| reader.contains(&i)));
+            assert!(!reader.refresh().unwrap());
+        }
+
+        for i in 1000..2000 {
+            filter.insert(&i);
+        }
+        SharedCuckooFilter::publish(&name, &filter).unwrap();
+        for reader in &mut readers {
+            assert!(reader.refresh().unwrap());
+            assert_eq!(reader.len(), 2000);
+            assert!((0..2000).all(|i| reader.contains(&i)));
+        }
+
+        SharedCuckooFilter::<usize>::unlink(&name).unwrap();
+        assert!(readers[0].contains(&0));
+        assert!(readers[0].refresh().is_err());
+        assert!(SharedCuckooFilter::<usize>::open(&name).is_err());
+
+        assert!(SharedCuckooFilter::<usize>::open("../etc/passwd").is_err());
+        assert!(SharedCuckooFilter::<usize>::open("").is_err());
+    }
+}
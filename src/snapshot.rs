@@ -0,0 +1,181 @@
+use arc_swap::ArcSwap;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::{DefaultHasher, ScalableCuckooFilter};
+
+/// Writer side of a snapshot-based filter for "one writer, many readers" deployments.
+///
+/// The writer mutates a private `ScalableCuckooFilter` and [`publish`] makes a snapshot of it
+/// visible to every [`SnapshotReader`] by atomically swapping an `Arc`.
+/// Readers never wait for the writer, and they don't observe any modification made after
+/// the last publication.
+///
+/// Publishing copies the whole filter, so it is intended to be done periodically
+/// rather than after each modification.
+///
+/// This is available only if the `concurrent` feature is enabled.
+///
+/// [`publish`]: SnapshotWriter::publish
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{ScalableCuckooFilterBuilder, SnapshotWriter};
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use std::thread;
+///
+/// let filter = ScalableCuckooFilterBuilder::new()
+///     .rng(StdRng::seed_from_u64(0))
+///     .finish::<usize>();
+/// let mut writer = SnapshotWriter::new(filter);
+/// let reader = writer.reader();
+///
+/// writer.insert(&1);
+/// assert!(!reader.contains(&1));
+///
+/// writer.publish();
+/// thread::spawn(move || assert!(reader.contains(&1)))
+///     .join()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SnapshotWriter<T: ?Sized, H = DefaultHasher, R = StdRng> {
+    filter: ScalableCuckooFilter<T, H, R>,
+    published: Arc<ArcSwap<ScalableCuckooFilter<T, H, R>>>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng + Clone> SnapshotWriter<T, H, R> {
+    /// Makes a new `SnapshotWriter` instance.
+    ///
+    /// `filter` is published as the initial snapshot.
+    pub fn new(filter: ScalableCuckooFilter<T, H, R>) -> Self {
+        let published = Arc::new(ArcSwap::from_pointee(filter.clone()));
+        SnapshotWriter { filter, published }
+    }
+
+    /// Returns a reader of the snapshots published by this writer.
+    pub fn reader(&self) -> SnapshotReader<T, H, R> {
+        SnapshotReader {
+            published: Arc::clone(&self.published),
+        }
+    }
+
+    /// Returns a reference to the private filter of this writer.
+    ///
+    /// The filter includes the modifications that have not been published yet.
+    pub fn filter(&self) -> &ScalableCuckooFilter<T, H, R> {
+        &self.filter
+    }
+
+    /// Returns `true` if the private filter may contain `item`, otherwise `false`.
+    pub fn contains(&self, item: &T) -> bool {
+        self.filter.contains(item)
+    }
+
+    /// Inserts `item` into the private filter.
+    ///
+    /// The item becomes visible to readers after the next [`publish`](Self::publish).
+    pub fn insert(&mut self, item: &T) {
+        self.filter.insert(item);
+    }
+
+    /// Removes `item` from the private filter.
+    ///
+    /// The removal becomes visible to readers after the next [`publish`](Self::publish).
+    pub fn remove(&mut self, item: &T) -> bool {
+        self.filter.remove(item)
+    }
+
+    /// Publishes a snapshot of the private filter to the readers.
+    pub fn publish(&mut self) {
+        self.published.store(Arc::new(self.filter.clone()));
+    }
+
+    /// Consumes this writer and returns the private filter.
+    pub fn into_inner(self) -> ScalableCuckooFilter<T, H, R> {
+        self.filter
+    }
+}
+
+/// Reader side of a snapshot-based filter.
+///
+/// See [`SnapshotWriter`] for details.
+#[derive(Debug)]
+pub struct SnapshotReader<T: ?Sized, H = DefaultHasher, R = StdRng> {
+    published: Arc<ArcSwap<ScalableCuckooFilter<T, H, R>>>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone, R: Rng> SnapshotReader<T, H, R> {
+    /// Returns `true` if the latest snapshot may contain `item`, otherwise `false`.
+    pub fn contains(&self, item: &T) -> bool {
+        self.published.load().contains(item)
+    }
+
+    /// Returns the number of items in the latest snapshot.
+    pub fn len(&self) -> u64 {
+        self.published.load().len()
+    }
+
+    /// Returns `true` if the latest snapshot contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.published.load().is_empty()
+    }
+
+    /// Returns the latest snapshot.
+    ///
+    /// The returned snapshot is not affected by later publications.
+    pub fn snapshot(&self) -> Arc<ScalableCuckooFilter<T, H, R>> {
+        self.published.load_full()
+    }
+}
+impl<T: ?Sized, H, R> Clone for SnapshotReader<T, H, R> {
+    fn clone(&self) -> Self {
+        SnapshotReader {
+            published: Arc::clone(&self.published),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ScalableCuckooFilterBuilder;
+    use rand::SeedableRng;
+    use std::thread;
+
+    #[test]
+    fn it_works() {
+        let filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .rng(StdRng::seed_from_u64(0))
+            .finish::<usize>();
+        let mut writer = SnapshotWriter::new(filter);
+        let reader = writer.reader();
+
+        for i in 0..1000 {
+            writer.insert(&i);
+        }
+        assert!(reader.is_empty());
+        let old = reader.snapshot();
+
+        writer.publish();
+        assert_eq!(reader.len(), 1000);
+        assert!(old.is_empty());
+
+        let handles = (0..4)
+            .map(|_| {
+                let reader = reader.clone();
+                thread::spawn(move || (500..1000).all(|i| reader.contains(&i)))
+            })
+            .collect::<Vec<_>>();
+        for i in 0..500 {
+            writer.remove(&i);
+        }
+        writer.publish();
+        for h in handles {
+            assert!(h.join().unwrap());
+        }
+        assert_eq!(reader.len(), writer.filter().len());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::growth::LayerInfo;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Statistics of a `ScalableCuckooFilter`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct FilterStats {
+    /// Approximate number of items inserted in the filter.
+    pub len: u64,
+
+    /// Capacity of the filter.
+    pub capacity: u64,
+
+    /// Number of bits being used for representing the filter.
+    pub bits: u64,
+
+    /// Number of layers of the filter.
+    pub number_of_layers: usize,
+
+    /// Total number of entries kept in the stashes of the layers.
+    pub stash_len: usize,
+
+    /// Information about each layer.
+    pub layers: Vec<LayerInfo>,
+}
+impl FilterStats {
+    pub(crate) fn new(layers: Vec<LayerInfo>) -> Self {
+        FilterStats {
+            len: layers.iter().map(|l| l.len() as u64).sum(),
+            capacity: layers.iter().map(|l| l.capacity() as u64).sum(),
+            bits: layers.iter().map(|l| l.bits()).sum(),
+            number_of_layers: layers.len(),
+            stash_len: layers.iter().map(|l| l.stash_len()).sum(),
+            layers,
+        }
+    }
+}
+
+/// Entry stored in a layer of a `ScalableCuckooFilter` (see `ScalableCuckooFilter::fingerprints`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct StoredFingerprint {
+    /// Index of the layer holding the entry (the oldest layer is zero).
+    pub layer: usize,
+
+    /// Index of the bucket holding the entry in the layer.
+    ///
+    /// Stashed entries have the smaller of their two candidate bucket indices.
+    pub bucket_index: usize,
+
+    /// Fingerprint of the entry.
+    pub fingerprint: u64,
+
+    /// Whether the entry is kept in the stash of the layer instead of the bucket.
+    pub stashed: bool,
+}
+
+/// Cumulative counters of the operations performed on a `ScalableCuckooFilter`.
+///
+/// These values are useful for tuning `max_kicks` and the initial capacity of the filter.
+/// Counters are not serialized.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct FilterCounters {
+    /// Total number of entries relocated (kicked) by insertions.
+    pub kicks: u64,
+
+    /// Total number of entries inserted into stashes.
+    pub stash_insertions: u64,
+
+    /// Number of layers added after the first one.
+    pub growths: u64,
+
+    /// Number of removals that didn't find the item.
+    pub failed_removes: u64,
+}
+impl FilterCounters {
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.kicks += other.kicks;
+        self.stash_insertions += other.stash_insertions;
+        self.growths += other.growths;
+        self.failed_removes += other.failed_removes;
+    }
+}
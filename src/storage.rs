@@ -0,0 +1,327 @@
+use rand::Rng;
+use std::io::{self, Read, Write};
+
+use crate::alloc::Allocator;
+use crate::bits::{BitArray, Bits};
+use crate::buckets::Buckets;
+use crate::codec;
+use crate::cuckoo_filter::StorageBackend;
+use crate::morton::MortonBuckets;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Bucket storage of a layer.
+///
+/// Serialized with the variant as a tag (serde's default), because formats that aren't
+/// self-describing (e.g., bincode and postcard) can't tell the variants apart otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    serde(bound(
+        serialize = "Buckets<B>: Serialize, MortonBuckets<B>: Serialize",
+        deserialize = "Buckets<B>: Deserialize<'de>, MortonBuckets<B>: Deserialize<'de>"
+    ))
+)]
+pub enum Storage<B = Bits> {
+    Morton(MortonBuckets<B>),
+    Buckets(Buckets<B>),
+}
+impl Storage {
+    pub fn new(
+        backend: StorageBackend,
+        fingerprint_bitwidth: usize,
+        entries_per_bucket: usize,
+        number_of_items_hint: usize,
+        power_of_two: bool,
+        alloc: Allocator,
+    ) -> Self {
+        match backend {
+            StorageBackend::Buckets => Storage::Buckets(Buckets::new(
+                fingerprint_bitwidth,
+                entries_per_bucket,
+                number_of_items_hint.div_ceil(entries_per_bucket),
+                power_of_two,
+                alloc,
+            )),
+            StorageBackend::Blocked => Storage::Buckets(Buckets::new_blocked(
+                fingerprint_bitwidth,
+                entries_per_bucket,
+                number_of_items_hint.div_ceil(entries_per_bucket),
+                power_of_two,
+                alloc,
+            )),
+            StorageBackend::Morton => Storage::Morton(MortonBuckets::new(
+                fingerprint_bitwidth,
+                entries_per_bucket,
+                number_of_items_hint,
+                alloc,
+            )),
+        }
+    }
+
+    #[inline]
+    pub fn required_number_of_buckets(
+        backend: StorageBackend,
+        entries_per_bucket: usize,
+        number_of_items_hint: usize,
+        power_of_two: bool,
+    ) -> usize {
+        match backend {
+            StorageBackend::Buckets | StorageBackend::Blocked => {
+                Buckets::required_number_of_buckets(
+                    number_of_items_hint.div_ceil(entries_per_bucket),
+                    power_of_two,
+                )
+            }
+            StorageBackend::Morton => {
+                MortonBuckets::required_number_of_buckets(entries_per_bucket, number_of_items_hint)
+            }
+        }
+    }
+
+    /// Writes the kind and the layout of the storage, which precede the bits in
+    /// a serialized layer.
+    pub fn write_head_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Storage::Buckets(b) => {
+                codec::write_u8(writer, 0)?;
+                b.write_head_to(writer)
+            }
+            Storage::Morton(b) => {
+                codec::write_u8(writer, 1)?;
+                b.write_head_to(writer)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            Storage::Buckets(b) => b.heap_bytes(),
+            Storage::Morton(b) => b.heap_bytes(),
+        }
+    }
+
+    #[inline]
+    pub fn allocator(&self) -> &Allocator {
+        match self {
+            Storage::Buckets(b) => b.allocator(),
+            Storage::Morton(b) => b.allocator(),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (usize, u64)> + '_> {
+        match self {
+            Storage::Buckets(b) => Box::new(b.iter()),
+            Storage::Morton(b) => Box::new(b.iter()),
+        }
+    }
+
+    #[inline]
+    pub fn try_insert(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+        match self {
+            Storage::Buckets(b) => b.try_insert(bucket_index, fingerprint),
+            Storage::Morton(b) => b.try_insert(bucket_index, fingerprint),
+        }
+    }
+
+    /// Puts `fingerprint` into the bucket in place of a randomly chosen entry,
+    /// and returns the kicked out entry as `(its_bucket_index, fingerprint)`.
+    #[inline]
+    pub fn random_swap<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        bucket_index: usize,
+        fingerprint: u64,
+    ) -> (usize, u64) {
+        match self {
+            Storage::Buckets(b) => (
+                bucket_index,
+                b.random_swap(rng, bucket_index, fingerprint).1,
+            ),
+            Storage::Morton(b) => b.random_swap(rng, bucket_index, fingerprint),
+        }
+    }
+
+    #[inline]
+    pub fn remove_fingerprint(&mut self, bucket_index: usize, fingerprint: u64) -> bool {
+        match self {
+            Storage::Buckets(b) => b.remove_fingerprint(bucket_index, fingerprint),
+            Storage::Morton(b) => b.remove_fingerprint(bucket_index, fingerprint),
+        }
+    }
+
+    #[inline]
+    pub fn set_fingerprint(&mut self, bucket_index: usize, entry_index: usize, fingerprint: u64) {
+        match self {
+            Storage::Buckets(b) => b.set_fingerprint(bucket_index, entry_index, fingerprint),
+            Storage::Morton(b) => b.set_fingerprint(bucket_index, entry_index, fingerprint),
+        }
+    }
+}
+impl<B: BitArray> Storage<B> {
+    /// Returns the bit array holding the buckets.
+    #[inline]
+    pub fn bit_array(&self) -> &B {
+        match self {
+            Storage::Buckets(b) => b.bit_array(),
+            Storage::Morton(b) => b.bit_array(),
+        }
+    }
+
+    #[inline]
+    pub fn bit_array_mut(&mut self) -> &mut B {
+        match self {
+            Storage::Buckets(b) => b.bit_array_mut(),
+            Storage::Morton(b) => b.bit_array_mut(),
+        }
+    }
+
+    #[inline]
+    pub fn backend(&self) -> StorageBackend {
+        match self {
+            Storage::Buckets(b) if b.is_blocked() => StorageBackend::Blocked,
+            Storage::Buckets(_) => StorageBackend::Buckets,
+            Storage::Morton(_) => StorageBackend::Morton,
+        }
+    }
+
+    /// Returns `true` if the number of buckets is a power of two.
+    ///
+    /// Morton blocks always consist of a power of two buckets.
+    #[inline]
+    pub fn is_power_of_two(&self) -> bool {
+        match self {
+            Storage::Buckets(b) => b.is_power_of_two(),
+            Storage::Morton(_) => true,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            Storage::Buckets(b) => b.len(),
+            Storage::Morton(b) => b.len(),
+        }
+    }
+
+    #[inline]
+    pub fn entries(&self) -> usize {
+        match self {
+            Storage::Buckets(b) => b.entries(),
+            Storage::Morton(b) => b.entries(),
+        }
+    }
+
+    #[inline]
+    pub fn bits(&self) -> u64 {
+        match self {
+            Storage::Buckets(b) => b.bits(),
+            Storage::Morton(b) => b.bits(),
+        }
+    }
+
+    #[inline]
+    pub fn index(&self, hash: u64) -> usize {
+        match self {
+            Storage::Buckets(b) => b.index(hash),
+            Storage::Morton(b) => b.index(hash),
+        }
+    }
+
+    #[inline]
+    pub fn alt_index(&self, i: usize, offset: u64) -> usize {
+        match self {
+            Storage::Buckets(b) => b.alt_index(i, offset),
+            Storage::Morton(b) => b.index(i as u64 ^ offset),
+        }
+    }
+
+    #[inline]
+    pub fn fingerprint(&self, hash: u64) -> u64 {
+        match self {
+            Storage::Buckets(b) => b.fingerprint(hash),
+            Storage::Morton(b) => b.fingerprint(hash),
+        }
+    }
+
+    /// Returns the layout of the buckets without the bits, which indexes and fingerprints
+    /// items the same way.
+    pub fn layout(&self) -> Buckets<()> {
+        match self {
+            Storage::Buckets(b) => b.layout(),
+            Storage::Morton(b) => Buckets::power_of_two_layout(
+                b.fingerprint_bitwidth(),
+                b.entries_per_bucket(),
+                b.len(),
+            ),
+        }
+    }
+
+    /// Returns the maximum number of entries in a bucket.
+    #[inline]
+    pub fn entries_per_bucket(&self) -> usize {
+        match self {
+            Storage::Buckets(b) => b.entries_per_bucket(),
+            Storage::Morton(b) => b.entries_per_bucket(),
+        }
+    }
+
+    #[inline]
+    pub fn fingerprint_bitwidth(&self) -> usize {
+        match self {
+            Storage::Buckets(b) => b.fingerprint_bitwidth(),
+            Storage::Morton(b) => b.fingerprint_bitwidth(),
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, bucket_index: usize, fingerprint: u64) -> bool {
+        match self {
+            Storage::Buckets(b) => b.contains(bucket_index, fingerprint),
+            Storage::Morton(b) => b.contains(bucket_index, fingerprint),
+        }
+    }
+
+    /// Hints the CPU to fetch the bucket into the cache ahead of the actual access.
+    #[inline]
+    pub fn prefetch(&self, bucket_index: usize) {
+        match self {
+            Storage::Buckets(b) => b.prefetch(bucket_index),
+            Storage::Morton(b) => b.prefetch(bucket_index),
+        }
+    }
+
+    #[inline]
+    pub fn contains_either(&self, i0: usize, i1: usize, fingerprint: u64) -> bool {
+        match self {
+            Storage::Buckets(b) => b.contains_either(i0, i1, fingerprint),
+            Storage::Morton(b) => {
+                fingerprint != 0 && (b.contains(i0, fingerprint) | b.contains(i1, fingerprint))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn get_fingerprint(&self, bucket_index: usize, entry_index: usize) -> u64 {
+        match self {
+            Storage::Buckets(b) => b.get_fingerprint(bucket_index, entry_index),
+            Storage::Morton(b) => b.get_fingerprint(bucket_index, entry_index),
+        }
+    }
+
+    /// Reads the storage written by `write_to`, reading the bits with `read_bits`.
+    pub fn read_with<R: Read>(
+        reader: &mut R,
+        read_bits: impl FnOnce(&mut R) -> io::Result<B>,
+    ) -> io::Result<Self> {
+        match codec::read_u8(reader)? {
+            0 => Buckets::read_with(reader, read_bits).map(Storage::Buckets),
+            1 => MortonBuckets::read_with(reader, read_bits).map(Storage::Morton),
+            _ => Err(codec::invalid_data("unknown storage")),
+        }
+    }
+}
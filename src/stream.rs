@@ -0,0 +1,79 @@
+use futures_core::Stream;
+use rand::Rng;
+use std::borrow::Borrow;
+use std::future::{self, Future};
+use std::hash::{Hash, Hasher};
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll};
+
+use crate::ScalableCuckooFilter;
+
+/// Number of items inserted at once by `extend`.
+pub const BATCH_SIZE: usize = 4096;
+
+pub async fn extend<T, H, R, S>(filter: &mut ScalableCuckooFilter<T, H, R>, stream: S)
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone,
+    R: Rng,
+    S: Stream,
+    S::Item: Borrow<T>,
+{
+    let mut stream = pin!(stream);
+    let mut inserter = filter.buffered_inserter(BATCH_SIZE);
+    while let Some(item) = future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+        inserter.insert(item.borrow());
+        if inserter.buffered_len() == 0 {
+            // A batch has just been inserted.
+            YieldNow(false).await;
+        }
+    }
+}
+
+/// Future that is pending once, letting the executor run other tasks.
+struct YieldNow(bool);
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::task::Waker;
+
+    use super::*;
+
+    /// Stream of the items of an iterator, all of which are ready.
+    struct Iter<I>(I);
+    impl<I: Iterator + Unpin> Stream for Iter<I> {
+        type Item = I::Item;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+            Poll::Ready(self.0.next())
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let mut filter = ScalableCuckooFilter::<usize>::new(1000, 0.001);
+        let mut yields = 0;
+        {
+            let mut future = pin!(filter.extend_from_stream(Iter(0..10_000)));
+            let mut cx = Context::from_waker(Waker::noop());
+            while future.as_mut().poll(&mut cx).is_pending() {
+                yields += 1;
+            }
+        }
+        assert_eq!(yields, 10_000 / BATCH_SIZE);
+        assert_eq!(filter.len(), 10_000);
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+    }
+}
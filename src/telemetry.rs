@@ -0,0 +1,50 @@
+//! Integration with the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! All functions are no-ops unless the `metrics` feature is enabled.
+
+#[cfg(feature = "metrics")]
+use metrics::{counter, gauge};
+
+#[inline]
+pub fn record_insert(_kicks: u64) {
+    #[cfg(feature = "metrics")]
+    {
+        counter!("scalable_cuckoo_filter_inserts_total").increment(1);
+        if _kicks > 0 {
+            counter!("scalable_cuckoo_filter_kicks_total").increment(_kicks);
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[inline]
+pub fn record_inserts(_count: u64) {
+    #[cfg(feature = "metrics")]
+    counter!("scalable_cuckoo_filter_inserts_total").increment(_count);
+}
+
+#[inline]
+pub fn record_remove(_removed: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        counter!("scalable_cuckoo_filter_removes_total").increment(1);
+        if !_removed {
+            counter!("scalable_cuckoo_filter_failed_removes_total").increment(1);
+        }
+    }
+}
+
+#[inline]
+pub fn record_growth() {
+    #[cfg(feature = "metrics")]
+    counter!("scalable_cuckoo_filter_growths_total").increment(1);
+}
+
+/// Records the size of the filter labelled `label`, if any.
+#[inline]
+pub fn record_bits(_label: Option<&str>, _bits: u64) {
+    #[cfg(feature = "metrics")]
+    if let Some(label) = _label {
+        gauge!("scalable_cuckoo_filter_bits", "filter" => label.to_owned()).set(_bits as f64);
+    }
+}
@@ -0,0 +1,110 @@
+//! Helpers for validating the parameters of filters empirically.
+//!
+//! This module is available only if the `test_utils` feature is enabled.
+use rand::Rng;
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+
+use crate::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+
+/// Returns the ratio of the items in `negative_samples` that `filter` reports as contained.
+///
+/// `negative_samples` must not contain any items inserted in `filter`.
+/// If it is empty, this function returns `0.0`.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilter;
+/// use scalable_cuckoo_filter::test_utils::measure_false_positive_rate;
+///
+/// let mut filter = ScalableCuckooFilter::new(1000, 0.01);
+/// for i in 0..1000 {
+///     filter.insert(&i);
+/// }
+/// let rate = measure_false_positive_rate(&filter, 1000..101_000);
+/// assert!(rate < 0.01);
+/// ```
+pub fn measure_false_positive_rate<T, H, R, I>(
+    filter: &ScalableCuckooFilter<T, H, R>,
+    negative_samples: I,
+) -> f64
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone,
+    R: Rng,
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+{
+    let mut samples = 0;
+    let mut false_positives = 0;
+    for item in negative_samples {
+        samples += 1;
+        if filter.contains(item.borrow()) {
+            false_positives += 1;
+        }
+    }
+    if samples == 0 {
+        0.0
+    } else {
+        false_positives as f64 / samples as f64
+    }
+}
+
+/// Returns the fill ratio of the first layer of a filter built by `builder` at the moment it grows.
+///
+/// Distinct `u64` items are inserted until the filter adds the second layer.
+/// This is useful for finding the load factor achievable with the given `entries_per_bucket`,
+/// `max_kicks` and insertion strategy.
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::ScalableCuckooFilterBuilder;
+/// use scalable_cuckoo_filter::test_utils::measure_load_factor_at_growth;
+///
+/// let builder = ScalableCuckooFilterBuilder::new().initial_capacity(1000);
+/// let load_factor = measure_load_factor_at_growth(builder);
+/// assert!(0.9 < load_factor && load_factor <= 1.0);
+/// ```
+pub fn measure_load_factor_at_growth<H, R>(builder: ScalableCuckooFilterBuilder<H, R>) -> f64
+where
+    H: Hasher + Clone,
+    R: Rng,
+{
+    let mut filter = builder.finish::<u64>();
+    let mut item = 0;
+    while filter.layers().len() == 1 {
+        filter.insert(&item);
+        item += 1;
+    }
+    filter.layers()[0].fill_ratio()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn measure_false_positive_rate_works() {
+        let mut filter: ScalableCuckooFilter<str> = ScalableCuckooFilter::new(100, 0.1);
+        for i in 0..100 {
+            filter.insert(&i.to_string());
+        }
+
+        let samples = (100..10_100).map(|i| i.to_string()).collect::<Vec<_>>();
+        let rate = measure_false_positive_rate(&filter, samples.iter().map(|s| s.as_str()));
+        assert!(0.0 < rate && rate < 0.1);
+
+        let rate = measure_false_positive_rate(&filter, std::iter::empty::<&str>());
+        assert_eq!(rate, 0.0);
+    }
+
+    #[test]
+    fn measure_load_factor_at_growth_works() {
+        let builder = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(1000)
+            .grow_at_load_factor(0.5);
+        assert_eq!(measure_load_factor_at_growth(builder), 0.5);
+    }
+}
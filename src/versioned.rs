@@ -0,0 +1,123 @@
+//! Serde representation of filters that stays readable across versions of this crate.
+//!
+//! The derived `Serialize` and `Deserialize` of [`ScalableCuckooFilter`] follow its internal
+//! layout. They work with any serde format, but data written by them can only be relied on
+//! to be read by the same version of this crate: non-self-describing formats (e.g., bincode
+//! and postcard) fail on any change of the layout, and self-describing ones (e.g., JSON)
+//! on changes that aren't additions of optional fields.
+//! This module instead (de)serializes a filter as the bytes of
+//! [`ScalableCuckooFilter::to_bytes`], whose format is versioned:
+//! data written by any version of this crate can be read by all later versions.
+//!
+//! Use it via `#[serde(with = "scalable_cuckoo_filter::versioned")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use scalable_cuckoo_filter::{ScalableCuckooFilter, ScalableCuckooFilterBuilder};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Index {
+//!     #[serde(with = "scalable_cuckoo_filter::versioned")]
+//!     words: ScalableCuckooFilter<str>,
+//! }
+//!
+//! let mut words = ScalableCuckooFilterBuilder::new()
+//!     .for_type::<str>()
+//!     .finish();
+//! words.insert("foo");
+//!
+//! let json = serde_json::to_string(&Index { words }).unwrap();
+//! let index: Index = serde_json::from_str(&json).unwrap();
+//! assert!(index.words.contains("foo"));
+//! ```
+use std::hash::{Hash, Hasher};
+
+use rand::Rng;
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserializer, Serializer};
+
+use crate::{HasherKeys, ScalableCuckooFilter};
+
+/// Serializes `filter` as the bytes of [`ScalableCuckooFilter::to_bytes`].
+pub fn serialize<T, H, R, S>(
+    filter: &ScalableCuckooFilter<T, H, R>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + HasherKeys,
+    R: Rng,
+    S: Serializer,
+{
+    let bytes = filter.to_bytes().map_err(S::Error::custom)?;
+    serializer.serialize_bytes(&bytes)
+}
+
+/// Deserializes a filter from the bytes written by [`serialize`].
+///
+/// The hasher and the RNG are made as by [`ScalableCuckooFilter::from_bytes`].
+pub fn deserialize<'de, T, H, R, D>(
+    deserializer: D,
+) -> Result<ScalableCuckooFilter<T, H, R>, D::Error>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + Default + HasherKeys,
+    R: Rng + Default,
+    D: Deserializer<'de>,
+{
+    let bytes: serde_bytes::ByteBuf = serde_bytes::deserialize(deserializer)?;
+    ScalableCuckooFilter::from_bytes(&bytes).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+    use siphasher::sip::SipHasher13;
+
+    use super::*;
+
+    /// The same bytes as `codec::test::GOLDEN_V1`.
+    const GOLDEN_V1: &[u8] = include_bytes!("../testdata/golden_v1.bin");
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "crate::versioned")]
+        filter: ScalableCuckooFilter<u64, SipHasher13>,
+    }
+
+    fn check(wrapper: &Wrapper) {
+        assert_eq!(wrapper.filter.len(), 64);
+        assert!((0..64).all(|i| wrapper.filter.contains(&i)));
+    }
+
+    #[test]
+    fn bincode_layout_is_stable() {
+        let mut blob = (GOLDEN_V1.len() as u64).to_le_bytes().to_vec();
+        blob.extend_from_slice(GOLDEN_V1);
+        let wrapper: Wrapper = bincode::deserialize(&blob).unwrap();
+        check(&wrapper);
+        assert_eq!(bincode::serialize(&wrapper).unwrap(), blob);
+    }
+
+    #[test]
+    fn postcard_layout_is_stable() {
+        // The length is a varint.
+        let len_varint = [0xf1, 0x03];
+        let len = usize::from(len_varint[0] & 0x7f) | usize::from(len_varint[1]) << 7;
+        assert_eq!(len, GOLDEN_V1.len());
+        let blob = [&len_varint, GOLDEN_V1].concat();
+        let wrapper: Wrapper = postcard::from_bytes(&blob).unwrap();
+        check(&wrapper);
+        assert_eq!(postcard::to_allocvec(&wrapper).unwrap(), blob);
+    }
+
+    #[test]
+    fn malformed_bytes_are_rejected() {
+        let blob = bincode::serialize(&serde_bytes::Bytes::new(&GOLDEN_V1[..GOLDEN_V1.len() - 1]))
+            .unwrap();
+        assert!(bincode::deserialize::<Wrapper>(&blob).is_err());
+    }
+}
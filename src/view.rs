@@ -0,0 +1,386 @@
+use std::borrow::Borrow;
+use std::cmp;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::marker::PhantomData;
+#[cfg(feature = "mmap")]
+use std::ops::Range;
+
+use crate::bits::BitsRef;
+use crate::codec::{self, BitsEncoding};
+use crate::cuckoo_filter::{AltIndexScheme, HashWidth};
+use crate::hashers::{self, HasherKeys};
+use crate::storage::Storage;
+use crate::DefaultHasher;
+
+/// Read-only Scalable Cuckoo Filter that borrows its buckets from serialized bytes.
+///
+/// This reads the bytes made by [`ScalableCuckooFilter::to_bytes`] (or `write_to`) in place,
+/// so a large filter can be used right after mapping a file into memory or embedding it
+/// with `include_bytes!`, without copying the buckets.
+/// Only the parameters of the layers are read up front, and lookups don't allocate.
+/// So the checksum of the bytes isn't verified when a view is made either; malformed bytes
+/// are rejected, but corrupted buckets only give wrong answers unless [`verify_checksum`]
+/// is called.
+///
+/// Like [`ScalableCuckooFilter::from_bytes`], [`new`] keys the hasher as the serialized
+/// filter's was (see [`HasherKeys`]). Other hashers aren't part of the bytes and must be
+/// the one the filter was built with.
+///
+/// [`ScalableCuckooFilter::to_bytes`]: crate::ScalableCuckooFilter::to_bytes
+/// [`ScalableCuckooFilter::from_bytes`]: crate::ScalableCuckooFilter::from_bytes
+/// [`new`]: ScalableCuckooFilterView::new
+/// [`verify_checksum`]: ScalableCuckooFilterView::verify_checksum
+/// [`HasherKeys`]: crate::HasherKeys
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{
+///     DefaultHasher, ScalableCuckooFilterBuilder, ScalableCuckooFilterView,
+/// };
+///
+/// let mut filter = ScalableCuckooFilterBuilder::new()
+///     .initial_capacity(100)
+///     .for_type::<usize>()
+///     .finish();
+/// for i in 0..1000 {
+///     filter.insert(&i);
+/// }
+/// let bytes = filter.to_bytes().unwrap();
+///
+/// let view: ScalableCuckooFilterView<usize> = ScalableCuckooFilterView::new(&bytes).unwrap();
+/// view.verify_checksum().unwrap();
+/// assert_eq!(view.len(), 1000);
+/// assert!((0..1000).all(|i| view.contains(&i)));
+/// ```
+#[derive(Debug)]
+pub struct ScalableCuckooFilterView<'a, T: ?Sized, H = DefaultHasher> {
+    hasher: H,
+    bytes: &'a [u8],
+    checksum: u32,
+    layers: Vec<LayerView<'a>>,
+    false_positive_probability: f64,
+    hash_width: HashWidth,
+    _item: PhantomData<fn(&T)>,
+}
+impl<'a, T: Hash + ?Sized, H: Hasher + Clone + Default + HasherKeys>
+    ScalableCuckooFilterView<'a, T, H>
+{
+    /// Makes a view of the filter serialized in `bytes`, hashing items with the hasher keyed by
+    /// the recorded keys, or with `H::default()` if there are none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is malformed, of an unknown format version,
+    /// or followed by extra bytes.
+    pub fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        Self::parse(bytes, hashers::keyed_hasher)
+    }
+}
+impl<'a, T: Hash + ?Sized, H: Hasher + Clone> ScalableCuckooFilterView<'a, T, H> {
+    /// Makes a view of the filter serialized in `bytes`, hashing items with `hasher`.
+    ///
+    /// The keys recorded in `bytes` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is malformed, of an unknown format version,
+    /// compressed (see [`ScalableCuckooFilter::to_compressed_bytes`]), or followed by extra bytes.
+    ///
+    /// [`ScalableCuckooFilter::to_compressed_bytes`]: crate::ScalableCuckooFilter::to_compressed_bytes
+    pub fn with_hasher(bytes: &'a [u8], hasher: H) -> io::Result<Self> {
+        Self::parse(bytes, |_| hasher)
+    }
+
+    fn parse(
+        bytes: &'a [u8],
+        make_hasher: impl FnOnce(Option<(u64, u64)>) -> H,
+    ) -> io::Result<Self> {
+        let parsed = ParsedFilter::parse(bytes)?;
+        Ok(ScalableCuckooFilterView {
+            hasher: make_hasher(parsed.hasher_keys),
+            bytes,
+            checksum: parsed.checksum,
+            layers: parsed.layers,
+            false_positive_probability: parsed.false_positive_probability,
+            hash_width: parsed.hash_width,
+            _item: PhantomData,
+        })
+    }
+
+    /// Verifies the checksum of the bytes of this view, which detects corrupted buckets.
+    ///
+    /// This reads all of the bytes, so it takes time proportional to their length
+    /// (and pages in the whole file for a memory-mapped view).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind `InvalidData` if the checksum doesn't match.
+    pub fn verify_checksum(&self) -> io::Result<()> {
+        let actual = codec::checksum(&self.bytes[..self.bytes.len() - 4]);
+        codec::verify_checksum(self.checksum, actual)
+    }
+
+    /// Returns the approximate number of items inserted in the filter.
+    pub fn len(&self) -> u64 {
+        self.layers.iter().map(|l| l.len as u64).sum()
+    }
+
+    /// Returns `true` if the filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bits of the buckets and stashes borrowed by this view.
+    pub fn bits(&self) -> u64 {
+        self.layers
+            .iter()
+            .map(|l| l.buckets.bits() + l.stash.bytes.len() as u64 * 8)
+            .sum()
+    }
+
+    /// Returns the false positive probability configured for the filter.
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /// Returns `true` if the filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let item_hash = crate::item_hash(&self.hasher, item, self.hash_width);
+        self.layers
+            .iter()
+            .any(|l| l.contains(&self.hasher, item_hash))
+    }
+}
+
+/// Parameters and layers of a serialized filter.
+struct ParsedFilter<'a> {
+    hasher_keys: Option<(u64, u64)>,
+    checksum: u32,
+    false_positive_probability: f64,
+    hash_width: HashWidth,
+    layers: Vec<LayerView<'a>>,
+}
+impl<'a> ParsedFilter<'a> {
+    /// Reads the parameters and the layers without verifying the checksum.
+    fn parse(bytes: &'a [u8]) -> io::Result<Self> {
+        // The parameters are in the order written by `raw::ScalableCuckooFilter::write_to`.
+        let mut rest = bytes;
+        let b = &mut rest;
+        codec::read_header(b)?;
+        if BitsEncoding::read_from(b)? != BitsEncoding::Raw {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "compressed filters can't be viewed in place",
+            ));
+        }
+        let hasher_keys = codec::read_hasher_keys(b)?;
+        let _filter_version = codec::read_u64(b)?;
+        let _initial_capacity = codec::read_usize(b)?;
+        let false_positive_probability = codec::read_f64(b)?;
+        let _tightening_ratio = codec::read_f64(b)?;
+        let _entries_per_bucket = codec::read_usize(b)?;
+        let _max_kicks = codec::read_usize(b)?;
+        let _insertion_strategy = codec::read_u8(b)?;
+        let _storage_backend = codec::read_u8(b)?;
+        let hash_width = HashWidth::from_tag(codec::read_u8(b)?)
+            .ok_or_else(|| codec::invalid_data("unknown hash width"))?;
+        let _alt_index_scheme = codec::read_u8(b)?;
+        let _power_of_two_buckets = codec::read_bool(b)?;
+        for _ in 0..codec::read_usize(b)? {
+            codec::read_usize(b)?;
+        }
+        let _stash_capacity = codec::read_usize(b)?;
+        let _stash_enabled = codec::read_bool(b)?;
+        let _grow_at_load_factor = (codec::read_bool(b)?, codec::read_f64(b)?);
+        let _pre_touch_pages = codec::read_bool(b)?;
+        let _transparent_hugepages = codec::read_bool(b)?;
+        let layers = (0..codec::read_usize(b)?)
+            .map(|_| LayerView::read_from(b))
+            .collect::<io::Result<Vec<_>>>()?;
+        let checksum = codec::read_u32(b)?;
+        if layers.is_empty() {
+            return Err(codec::invalid_data("invalid filter parameters"));
+        }
+        if !b.is_empty() {
+            return Err(codec::invalid_data("trailing bytes after the filter"));
+        }
+        Ok(ParsedFilter {
+            hasher_keys,
+            checksum,
+            false_positive_probability,
+            hash_width,
+            layers,
+        })
+    }
+}
+
+/// Byte ranges of the parts of a serialized layer that insertions and removals change.
+#[cfg(feature = "mmap")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LayerLayout {
+    /// The number of items and the stash (see `CuckooFilter::write_counts_to`).
+    pub counts: Range<usize>,
+
+    /// The bytes of the bit array.
+    pub bits: Range<usize>,
+}
+
+/// Returns the layouts of the layers of the filter serialized in `bytes`,
+/// without verifying its checksum.
+#[cfg(feature = "mmap")]
+pub(crate) fn layer_layouts(bytes: &[u8]) -> io::Result<Vec<LayerLayout>> {
+    let offset = |part: &[u8]| part.as_ptr() as usize - bytes.as_ptr() as usize;
+    let layouts = ParsedFilter::parse(bytes)?
+        .layers
+        .iter()
+        .map(|layer| {
+            let stash = offset(layer.stash.bytes);
+            // The number of items and the number of stash entries precede the entries.
+            let counts = stash - 16..stash + layer.stash.bytes.len();
+            let bits = layer.buckets.bit_array().as_bytes();
+            LayerLayout {
+                counts,
+                bits: offset(bits)..offset(bits) + bits.len(),
+            }
+        })
+        .collect();
+    Ok(layouts)
+}
+
+/// Layer of a `ScalableCuckooFilterView`.
+#[derive(Debug)]
+struct LayerView<'a> {
+    buckets: Storage<BitsRef<'a>>,
+    alt_index_scheme: AltIndexScheme,
+    stash: StashView<'a>,
+    len: usize,
+}
+impl<'a> LayerView<'a> {
+    fn read_from(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        // The fields are in the order written by `CuckooFilter::write_to`.
+        let _max_kicks = codec::read_usize(bytes)?;
+        let _insertion_strategy = codec::read_u8(bytes)?;
+        let alt_index_scheme = AltIndexScheme::from_tag(codec::read_u8(bytes)?)
+            .ok_or_else(|| codec::invalid_data("unknown alternative index scheme"))?;
+        let len = codec::read_usize(bytes)?;
+        let stash = StashView::read_from(bytes)?;
+        let buckets = Storage::read_with(bytes, BitsRef::read_from)?;
+        Ok(LayerView {
+            buckets,
+            alt_index_scheme,
+            stash,
+            len,
+        })
+    }
+
+    /// Same as `CuckooFilter::contains`.
+    #[inline]
+    fn contains<H: Hasher + Clone>(&self, hasher: &H, item_hash: u128) -> bool {
+        let i0 = self.buckets.index(item_hash as u64);
+        let fingerprint = self.buckets.fingerprint((item_hash >> 64) as u64);
+        self.buckets.prefetch(i0);
+        let offset = self.alt_index_scheme.offset(hasher, fingerprint);
+        let i1 = self.buckets.alt_index(i0, offset);
+        let in_buckets = (fingerprint != 0) & self.buckets.contains_either(i0, i1, fingerprint);
+        in_buckets || self.stash.contains(cmp::min(i0, i1), fingerprint)
+    }
+}
+
+/// Stash of a `LayerView`, which consists of `(fingerprint, bucket_index)` pairs
+/// of little-endian `u64`s sorted in ascending order.
+#[derive(Debug)]
+struct StashView<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> StashView<'a> {
+    const ENTRY_LEN: usize = 16;
+
+    fn read_from(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        let len = codec::read_usize(bytes)?;
+        let size = len
+            .checked_mul(Self::ENTRY_LEN)
+            .filter(|&size| size <= bytes.len())
+            .ok_or(io::ErrorKind::UnexpectedEof)?;
+        let (stash, rest) = bytes.split_at(size);
+        *bytes = rest;
+        Ok(StashView { bytes: stash })
+    }
+
+    #[inline]
+    fn contains(&self, bucket_index: usize, fingerprint: u64) -> bool {
+        let (mut low, mut high) = (0, self.bytes.len() / Self::ENTRY_LEN);
+        let target = (fingerprint, bucket_index as u64);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.entry(mid).cmp(&target) {
+                cmp::Ordering::Less => low = mid + 1,
+                cmp::Ordering::Greater => high = mid,
+                cmp::Ordering::Equal => return true,
+            }
+        }
+        false
+    }
+
+    #[inline]
+    fn entry(&self, i: usize) -> (u64, u64) {
+        let entry = &self.bytes[i * Self::ENTRY_LEN..][..Self::ENTRY_LEN];
+        let (fingerprint, bucket_index) = entry.split_at(8);
+        (
+            u64::from_le_bytes(fingerprint.try_into().expect("eight bytes")),
+            u64::from_le_bytes(bucket_index.try_into().expect("eight bytes")),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ScalableCuckooFilter, ScalableCuckooFilterBuilder, StorageBackend};
+
+    #[test]
+    fn it_works() {
+        for backend in [
+            StorageBackend::Buckets,
+            StorageBackend::Blocked,
+            StorageBackend::Morton,
+        ] {
+            let mut filter: ScalableCuckooFilter<u64> = ScalableCuckooFilterBuilder::new()
+                .initial_capacity(100)
+                .storage_backend(backend)
+                .power_of_two_buckets(false)
+                .finish();
+            for i in 0..10_000 {
+                filter.insert(&i);
+            }
+            assert!(filter.stats().stash_len > 0);
+            let bytes = filter.to_bytes().unwrap();
+
+            let view = ScalableCuckooFilterView::<u64>::new(&bytes).unwrap();
+            assert_eq!(view.len(), filter.len());
+            assert!(view.bits() <= bytes.len() as u64 * 8);
+            assert!((0..10_000).all(|i| view.contains(&i)));
+            assert!((10_000..20_000).all(|i| view.contains(&i) == filter.contains(&i)));
+
+            assert!(ScalableCuckooFilterView::<u64>::new(&bytes[..bytes.len() - 1]).is_err());
+
+            // Corrupted buckets are detected only when asked.
+            view.verify_checksum().unwrap();
+            let mut corrupted = bytes.clone();
+            corrupted[bytes.len() / 2] ^= 1;
+            let view = ScalableCuckooFilterView::<u64>::new(&corrupted).unwrap();
+            let error = view.verify_checksum().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+            let compressed = filter.to_compressed_bytes().unwrap();
+            let error = ScalableCuckooFilterView::<u64>::new(&compressed).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        }
+    }
+}
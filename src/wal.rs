@@ -0,0 +1,484 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::{codec, persist, raw};
+use crate::{DefaultHasher, DefaultRng, HasherKeys, ScalableCuckooFilter};
+
+const LOG_MAGIC: [u8; 4] = *b"SCKW";
+const LOG_VERSION: u16 = 1;
+
+/// Operation tag, hash (`u128`), and CRC-32 of the two.
+const RECORD_LEN: usize = 1 + 16 + 4;
+const INSERT: u8 = 1;
+const REMOVE: u8 = 2;
+
+/// Filter that logs its modifications to an append-only file and checkpoints itself
+/// into a snapshot file from time to time.
+///
+/// Every insertion and removal appends a small record (the operation and the hash of the item)
+/// to the log before modifying the filter, and [`sync`] makes the records durable.
+/// [`checkpoint`] saves the whole filter as a new snapshot (in the format of
+/// [`ScalableCuckooFilter::save_to_path`]) and starts an empty log.
+/// After a crash, [`open`] (or [`recover`]) loads the snapshot and replays the log on it,
+/// so the cost of durability is proportional to the modifications rather than to the size
+/// of the filter.
+///
+/// The log records which snapshot it follows, so a log left behind by a crash during
+/// a checkpoint, whose records are already in the new snapshot, is ignored.
+/// A record torn by a crash at the end of the log is ignored as well.
+///
+/// The hashes in the log are only meaningful for the hasher of the snapshot, which is restored
+/// as by [`ScalableCuckooFilter::read_from`] (i.e., keyed hashers have to implement
+/// [`HasherKeys`] or be keyed by the `Default` implementation).
+///
+/// [`sync`]: WalCuckooFilter::sync
+/// [`checkpoint`]: WalCuckooFilter::checkpoint
+/// [`open`]: WalCuckooFilter::open
+/// [`recover`]: WalCuckooFilter::recover
+///
+/// # Examples
+///
+/// ```
+/// use scalable_cuckoo_filter::{ScalableCuckooFilterBuilder, WalCuckooFilter};
+///
+/// let dir = std::env::temp_dir();
+/// let snapshot_path = dir.join(format!("wal-filter-{}.bin", std::process::id()));
+/// let log_path = dir.join(format!("wal-filter-{}.log", std::process::id()));
+///
+/// let filter = ScalableCuckooFilterBuilder::new()
+///     .for_type::<str>()
+///     .finish();
+/// let mut filter = WalCuckooFilter::create(&snapshot_path, &log_path, filter).unwrap();
+/// filter.insert("foo").unwrap();
+/// filter.sync().unwrap();
+/// drop(filter);
+///
+/// let mut filter: WalCuckooFilter<str> = WalCuckooFilter::open(&snapshot_path, &log_path).unwrap();
+/// assert!(filter.contains("foo"));
+/// filter.checkpoint().unwrap();
+/// assert_eq!(filter.log_records(), 0);
+/// # drop(filter);
+/// # std::fs::remove_file(&snapshot_path).unwrap();
+/// # std::fs::remove_file(&log_path).unwrap();
+/// ```
+pub struct WalCuckooFilter<T: ?Sized, H = DefaultHasher, R = DefaultRng> {
+    filter: ScalableCuckooFilter<T, H, R>,
+    snapshot_path: PathBuf,
+    log_path: PathBuf,
+    log: BufWriter<File>,
+    log_records: u64,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone + HasherKeys, R: Rng> WalCuckooFilter<T, H, R> {
+    /// Saves `filter` to the snapshot file at `snapshot_path` and starts an empty log
+    /// at `log_path`, replacing any existing files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files can't be written, or if the alternative index scheme of
+    /// `filter` is [`AltIndexScheme::Custom`].
+    ///
+    /// [`AltIndexScheme::Custom`]: crate::AltIndexScheme::Custom
+    pub fn create<P: AsRef<Path>, Q: AsRef<Path>>(
+        snapshot_path: P,
+        log_path: Q,
+        filter: ScalableCuckooFilter<T, H, R>,
+    ) -> io::Result<Self> {
+        let log = save_snapshot(&filter, snapshot_path.as_ref(), log_path.as_ref())?;
+        Ok(WalCuckooFilter {
+            filter,
+            snapshot_path: snapshot_path.as_ref().to_path_buf(),
+            log_path: log_path.as_ref().to_path_buf(),
+            log,
+            log_records: 0,
+        })
+    }
+
+    /// Returns `true` if the filter may contain `item`, otherwise `false`.
+    ///
+    /// `item` may be any borrowed form of the item type.
+    pub fn contains<Q: Hash + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.filter.contains(item)
+    }
+
+    /// Logs the insertion of `item` and inserts it into the filter.
+    ///
+    /// The record is buffered, and becomes durable by the next [`sync`] or [`checkpoint`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record can't be written, in which case the filter is left
+    /// as it is.
+    ///
+    /// [`sync`]: WalCuckooFilter::sync
+    /// [`checkpoint`]: WalCuckooFilter::checkpoint
+    pub fn insert(&mut self, item: &T) -> io::Result<()> {
+        let item_hash = self.filter.item_hash(item);
+        self.append(INSERT, item_hash)?;
+        self.filter.raw.insert128(item_hash);
+        Ok(())
+    }
+
+    /// Logs the removal of `item` and removes it from the filter, returning `true` if
+    /// it was found.
+    ///
+    /// Nothing is logged if `item` isn't found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record can't be written, in which case the filter is left
+    /// as it is.
+    pub fn remove<Q: Hash + ?Sized>(&mut self, item: &Q) -> io::Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        let item_hash = self.filter.item_hash(item);
+        if !self.filter.raw.contains128(item_hash) {
+            return Ok(false);
+        }
+        self.append(REMOVE, item_hash)?;
+        Ok(self.filter.raw.remove128(item_hash))
+    }
+
+    /// Returns the approximate number of items inserted in the filter.
+    pub fn len(&self) -> u64 {
+        self.filter.len()
+    }
+
+    /// Returns `true` if the filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty()
+    }
+
+    /// Returns the filter.
+    pub fn filter(&self) -> &ScalableCuckooFilter<T, H, R> {
+        &self.filter
+    }
+
+    /// Returns the number of records in the log, i.e., the modifications since the last
+    /// checkpoint.
+    ///
+    /// This helps to decide when to make a checkpoint, as the log grows and takes longer
+    /// to replay by this number.
+    pub fn log_records(&self) -> u64 {
+        self.log_records
+    }
+
+    /// Writes the buffered records to the log, and waits for them to reach the disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing or syncing the log fails.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.log.flush()?;
+        self.log.get_ref().sync_data()
+    }
+
+    /// Saves the filter as a new snapshot and starts an empty log.
+    ///
+    /// Both files are replaced atomically (as by [`ScalableCuckooFilter::save_to_path`]),
+    /// the snapshot first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files can't be written, or if the alternative index scheme
+    /// is [`AltIndexScheme::Custom`]. If the snapshot has been saved then, the current log
+    /// no longer follows it, so retry the checkpoint before relying on later modifications
+    /// being recovered.
+    ///
+    /// [`AltIndexScheme::Custom`]: crate::AltIndexScheme::Custom
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        self.log = save_snapshot(&self.filter, &self.snapshot_path, &self.log_path)?;
+        self.log_records = 0;
+        Ok(())
+    }
+
+    fn append(&mut self, operation: u8, item_hash: u128) -> io::Result<()> {
+        let mut record = [0; RECORD_LEN];
+        record[0] = operation;
+        record[1..17].copy_from_slice(&item_hash.to_le_bytes());
+        let checksum = codec::checksum(&record[..17]);
+        record[17..].copy_from_slice(&checksum.to_le_bytes());
+        self.log.write_all(&record)?;
+        self.log_records += 1;
+        Ok(())
+    }
+}
+impl<T, H, R> WalCuckooFilter<T, H, R>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + Default + HasherKeys,
+    R: Rng + Default,
+{
+    /// Loads the snapshot file at `snapshot_path` and replays the log at `log_path` on it.
+    ///
+    /// A record torn at the end of the log is truncated. If the log doesn't exist or doesn't
+    /// follow the snapshot (see [`recover`]), an empty one is started instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files can't be read or written, or if their contents are
+    /// malformed.
+    ///
+    /// [`recover`]: WalCuckooFilter::recover
+    pub fn open<P: AsRef<Path>, Q: AsRef<Path>>(snapshot_path: P, log_path: Q) -> io::Result<Self> {
+        let snapshot = fs::read(snapshot_path.as_ref())?;
+        let mut filter = ScalableCuckooFilter::from_bytes(&snapshot)?;
+        let id = SnapshotId::of(&snapshot);
+
+        let replayed = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(log_path.as_ref())
+        {
+            Ok(file) => replay(&mut filter.raw, id, BufReader::new(&file))?.map(|r| (file, r)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let (log, log_records) = match replayed {
+            Some((mut file, replayed)) => {
+                file.set_len(replayed.log_len)?;
+                file.seek(SeekFrom::End(0))?;
+                (BufWriter::new(file), replayed.records)
+            }
+            None => (start_log(log_path.as_ref(), id)?, 0),
+        };
+        Ok(WalCuckooFilter {
+            filter,
+            snapshot_path: snapshot_path.as_ref().to_path_buf(),
+            log_path: log_path.as_ref().to_path_buf(),
+            log,
+            log_records,
+        })
+    }
+
+    /// Deserializes a filter from `snapshot` (made by [`ScalableCuckooFilter::to_bytes`])
+    /// and replays the records read from `log` on it.
+    ///
+    /// A log that doesn't follow `snapshot` is ignored, because that happens when a crash
+    /// interrupts a [`checkpoint`] after saving the snapshot, and the records are already
+    /// in the snapshot then. So is a record torn at the end of the log.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `log` fails, or if `snapshot` or `log` is malformed.
+    ///
+    /// [`checkpoint`]: WalCuckooFilter::checkpoint
+    pub fn recover<Rd: Read>(
+        snapshot: &[u8],
+        log: Rd,
+    ) -> io::Result<ScalableCuckooFilter<T, H, R>> {
+        let mut filter = ScalableCuckooFilter::from_bytes(snapshot)?;
+        replay(
+            &mut filter.raw,
+            SnapshotId::of(snapshot),
+            BufReader::new(log),
+        )?;
+        Ok(filter)
+    }
+}
+impl<T: ?Sized, H, R> fmt::Debug for WalCuckooFilter<T, H, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WalCuckooFilter")
+            .field("snapshot_path", &self.snapshot_path)
+            .field("log_path", &self.log_path)
+            .field("log_records", &self.log_records)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Identifies a snapshot by its length and checksum (the last four bytes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SnapshotId {
+    len: u64,
+    checksum: u32,
+}
+impl SnapshotId {
+    fn of(snapshot: &[u8]) -> Self {
+        let mut checksum = [0; 4];
+        let tail = &snapshot[snapshot.len().saturating_sub(4)..];
+        checksum[..tail.len()].copy_from_slice(tail);
+        SnapshotId {
+            len: snapshot.len() as u64,
+            checksum: u32::from_le_bytes(checksum),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Replayed {
+    records: u64,
+    // Length of the log without the torn record, if any.
+    log_len: u64,
+}
+
+fn save_snapshot<T, H, R>(
+    filter: &ScalableCuckooFilter<T, H, R>,
+    snapshot_path: &Path,
+    log_path: &Path,
+) -> io::Result<BufWriter<File>>
+where
+    T: Hash + ?Sized,
+    H: Hasher + Clone + HasherKeys,
+    R: Rng,
+{
+    let snapshot = filter.to_bytes()?;
+    persist::save_atomically(snapshot_path, |w| w.write_all(&snapshot))?;
+    start_log(log_path, SnapshotId::of(&snapshot))
+}
+
+fn start_log(path: &Path, id: SnapshotId) -> io::Result<BufWriter<File>> {
+    persist::save_atomically(path, |w| {
+        w.write_all(&LOG_MAGIC)?;
+        w.write_all(&LOG_VERSION.to_le_bytes())?;
+        codec::write_u64(w, id.len)?;
+        codec::write_u32(w, id.checksum)
+    })?;
+    let file = OpenOptions::new().append(true).open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+/// Replays the records of `log` on `filter`, returning `None` if `log` doesn't follow
+/// the snapshot `id`.
+fn replay<H, R, Rd>(
+    filter: &mut raw::ScalableCuckooFilter<H, R>,
+    id: SnapshotId,
+    mut log: Rd,
+) -> io::Result<Option<Replayed>>
+where
+    H: Hasher + Clone,
+    R: Rng,
+    Rd: BufRead,
+{
+    let mut magic = [0; 4];
+    log.read_exact(&mut magic)?;
+    if magic != LOG_MAGIC {
+        return Err(codec::invalid_data("not a log of a filter"));
+    }
+    let mut version = [0; 2];
+    log.read_exact(&mut version)?;
+    if u16::from_le_bytes(version) != LOG_VERSION {
+        return Err(codec::invalid_data("unknown log version"));
+    }
+    let log_id = SnapshotId {
+        len: codec::read_u64(&mut log)?,
+        checksum: codec::read_u32(&mut log)?,
+    };
+    if log_id != id {
+        return Ok(None);
+    }
+
+    let mut replayed = Replayed {
+        records: 0,
+        log_len: (LOG_MAGIC.len() + 2 + 8 + 4) as u64,
+    };
+    let mut record = [0; RECORD_LEN];
+    while read_record(&mut log, &mut record)? {
+        let checksum = u32::from_le_bytes(record[17..].try_into().expect("unreachable"));
+        if codec::checksum(&record[..17]) != checksum {
+            if log.fill_buf()?.is_empty() {
+                break;
+            }
+            return Err(codec::invalid_data("log record checksum mismatch"));
+        }
+        let item_hash = u128::from_le_bytes(record[1..17].try_into().expect("unreachable"));
+        match record[0] {
+            INSERT => filter.insert128(item_hash),
+            REMOVE => {
+                filter.remove128(item_hash);
+            }
+            _ => return Err(codec::invalid_data("unknown log operation")),
+        }
+        replayed.records += 1;
+        replayed.log_len += RECORD_LEN as u64;
+    }
+    Ok(Some(replayed))
+}
+
+/// Reads a record into `record`, returning `false` at the end of `log` or of a torn record.
+fn read_record<Rd: Read>(log: &mut Rd, record: &mut [u8; RECORD_LEN]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < RECORD_LEN {
+        match log.read(&mut record[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ScalableCuckooFilterBuilder;
+
+    #[test]
+    fn it_works() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot_path = dir.path().join("filter.bin");
+        let log_path = dir.path().join("filter.log");
+        let filter = ScalableCuckooFilterBuilder::new()
+            .initial_capacity(100)
+            .for_type::<usize>()
+            .finish();
+        let mut filter = WalCuckooFilter::create(&snapshot_path, &log_path, filter).unwrap();
+        for i in 0..1000 {
+            filter.insert(&i).unwrap();
+        }
+        for i in 0..100 {
+            assert!(filter.remove(&i).unwrap());
+        }
+        assert!(!filter.remove(&2000).unwrap());
+        assert_eq!(filter.log_records(), 1100);
+        filter.sync().unwrap();
+        drop(filter);
+
+        let mut filter = WalCuckooFilter::<usize>::open(&snapshot_path, &log_path).unwrap();
+        assert_eq!(filter.len(), 900);
+        assert_eq!(filter.log_records(), 1100);
+        assert!((100..1000).all(|i| filter.contains(&i)));
+
+        // A log left by a crash during a checkpoint is ignored.
+        let stale_log = fs::read(&log_path).unwrap();
+        filter.checkpoint().unwrap();
+        assert_eq!(filter.log_records(), 0);
+        filter.insert(&1000).unwrap();
+        filter.sync().unwrap();
+        let log = fs::read(&log_path).unwrap();
+        let snapshot = fs::read(&snapshot_path).unwrap();
+        let recovered = WalCuckooFilter::<usize>::recover(&snapshot, &stale_log[..]).unwrap();
+        assert_eq!(recovered.len(), 900);
+        let recovered = WalCuckooFilter::<usize>::recover(&snapshot, &log[..]).unwrap();
+        assert_eq!(recovered.len(), 901);
+        drop(filter);
+
+        // A torn record is truncated.
+        let mut torn = log.clone();
+        torn.extend_from_slice(&[INSERT, 1, 2]);
+        fs::write(&log_path, &torn).unwrap();
+        let mut filter = WalCuckooFilter::<usize>::open(&snapshot_path, &log_path).unwrap();
+        assert_eq!(filter.len(), 901);
+        filter.insert(&1001).unwrap();
+        filter.sync().unwrap();
+        drop(filter);
+        let filter = WalCuckooFilter::<usize>::open(&snapshot_path, &log_path).unwrap();
+        assert_eq!(filter.log_records(), 2);
+        assert!((100..1002).all(|i| filter.contains(&i)));
+
+        // A corrupted record in the middle is an error.
+        let mut corrupted = fs::read(&log_path).unwrap();
+        let first_record = corrupted.len() - 2 * RECORD_LEN;
+        corrupted[first_record + 5] ^= 1;
+        assert!(WalCuckooFilter::<usize>::recover(&snapshot, &corrupted[..]).is_err());
+    }
+}
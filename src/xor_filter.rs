@@ -0,0 +1,210 @@
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::alloc::Allocator;
+use crate::bits::{BitArray, Bits};
+use crate::DefaultHasher;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Static (read-only) xor filter.
+///
+/// Xor filters need about `1.23 * fingerprint_bitwidth` bits per item, which is smaller than
+/// cuckoo filters for the same false positive probability, but no items can be added after
+/// construction. This is made by [`ScalableCuckooFilter::to_xor_filter`].
+///
+/// This is available only if the `xor_filter` feature is enabled.
+///
+/// [`ScalableCuckooFilter::to_xor_filter`]: crate::ScalableCuckooFilter::to_xor_filter
+///
+/// # References
+///
+/// - [Xor Filters: Faster and Smaller Than Bloom and Cuckoo Filters][xor]
+///
+/// [xor]: https://arxiv.org/abs/1912.08258
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct XorFilter<T: ?Sized, H = DefaultHasher> {
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    hasher: H,
+    seed: u64,
+    block_len: usize,
+    fingerprint_bitwidth: usize,
+    fingerprints: Bits,
+    len: usize,
+    _item: PhantomData<fn(&T)>,
+}
+impl<T: Hash + ?Sized, H: Hasher + Clone> XorFilter<T, H> {
+    /// Builds a `XorFilter` containing `items`.
+    ///
+    /// `hasher` is used for hashing items and `fingerprint_bitwidth` (`1..=32`) determines
+    /// the false positive probability (`2^-fingerprint_bitwidth`).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `fingerprint_bitwidth` is not in the range `1..=32`.
+    pub fn new<I>(hasher: H, fingerprint_bitwidth: usize, items: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        assert!((1..=32).contains(&fingerprint_bitwidth));
+        let mut hashes = items
+            .into_iter()
+            .map(|item| crate::hash(&hasher, item.borrow()))
+            .collect::<Vec<_>>();
+
+        // Duplicate keys can't be peeled.
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let capacity = 32 + (hashes.len() as f64 * 1.23).ceil() as usize;
+        let block_len = capacity.div_ceil(3);
+        let mut filter = XorFilter {
+            hasher,
+            seed: 0,
+            block_len,
+            fingerprint_bitwidth,
+            fingerprints: Bits::new(block_len * 3 * fingerprint_bitwidth, Allocator::default()),
+            len: hashes.len(),
+            _item: PhantomData,
+        };
+        let stack = loop {
+            if let Some(stack) = filter.peel(&hashes) {
+                break stack;
+            }
+            filter.seed = filter.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        };
+
+        // Assigns fingerprints in the reverse order of peeling, so that each key has
+        // a slot that no later assignment touches.
+        for &(slot, key) in stack.iter().rev() {
+            let [i0, i1, i2] = filter.slots(key);
+            let mut fingerprint = filter.fingerprint(key);
+            for i in [i0, i1, i2] {
+                if i != slot {
+                    fingerprint ^= filter.get(i);
+                }
+            }
+            filter.fingerprints.set_uint(
+                slot * fingerprint_bitwidth,
+                fingerprint_bitwidth,
+                fingerprint,
+            );
+        }
+        filter
+    }
+
+    /// Returns the number of (distinct) items in this filter.
+    pub fn len(&self) -> u64 {
+        self.len as u64
+    }
+
+    /// Returns `true` if this filter contains no items, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits being used for representing this filter.
+    pub fn bits(&self) -> u64 {
+        self.fingerprints.len() as u64
+    }
+
+    /// Returns the false positive probability of this filter.
+    pub fn false_positive_probability(&self) -> f64 {
+        0.5f64.powi(self.fingerprint_bitwidth as i32)
+    }
+
+    /// Returns `true` if this filter may contain `item`, otherwise `false`.
+    pub fn contains(&self, item: &T) -> bool {
+        let key = self.key(crate::hash(&self.hasher, item));
+        let [i0, i1, i2] = self.slots(key);
+        self.fingerprint(key) == self.get(i0) ^ self.get(i1) ^ self.get(i2)
+    }
+
+    fn peel(&self, hashes: &[u64]) -> Option<Vec<(usize, u64)>> {
+        let slots = self.block_len * 3;
+        let mut counts = vec![0u32; slots];
+        let mut xor_keys = vec![0u64; slots];
+        for &h in hashes {
+            let key = self.key(h);
+            for i in self.slots(key) {
+                counts[i] += 1;
+                xor_keys[i] ^= key;
+            }
+        }
+
+        let mut queue = (0..slots).filter(|&i| counts[i] == 1).collect::<Vec<_>>();
+        let mut stack = Vec::with_capacity(hashes.len());
+        while let Some(slot) = queue.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+            let key = xor_keys[slot];
+            stack.push((slot, key));
+            for i in self.slots(key) {
+                counts[i] -= 1;
+                xor_keys[i] ^= key;
+                if counts[i] == 1 {
+                    queue.push(i);
+                }
+            }
+        }
+        (stack.len() == hashes.len()).then_some(stack)
+    }
+
+    fn key(&self, item_hash: u64) -> u64 {
+        // splitmix64 finalizer
+        let mut x = item_hash.wrapping_add(self.seed);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^ (x >> 31)
+    }
+
+    fn slots(&self, key: u64) -> [usize; 3] {
+        // Blocks of more than `2^32` slots need all 64 bits of the key to reach every slot.
+        let reduce = |x: u64| {
+            if self.block_len <= u32::MAX as usize {
+                ((u128::from(x as u32) * self.block_len as u128) >> 32) as usize
+            } else {
+                ((u128::from(x) * self.block_len as u128) >> 64) as usize
+            }
+        };
+        [
+            reduce(key),
+            self.block_len + reduce(key.rotate_left(21)),
+            self.block_len * 2 + reduce(key.rotate_left(42)),
+        ]
+    }
+
+    fn fingerprint(&self, key: u64) -> u64 {
+        (key ^ (key >> 32)) & (u64::MAX >> (64 - self.fingerprint_bitwidth))
+    }
+
+    fn get(&self, slot: usize) -> u64 {
+        self.fingerprints
+            .get_uint(slot * self.fingerprint_bitwidth, self.fingerprint_bitwidth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let filter = XorFilter::<usize>::new(DefaultHasher::default(), 8, (0..10_000).chain(0..10));
+        assert_eq!(filter.len(), 10_000);
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+        assert!(filter.bits() <= (10_000.0 * 1.23 * 8.0) as u64 + 32 * 8 + 64);
+
+        let false_positives = (10_000..110_000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 600, "{}", false_positives);
+
+        let empty = XorFilter::<usize>::new(DefaultHasher::default(), 16, None::<usize>);
+        assert!(empty.is_empty());
+        assert!(!empty.contains(&0));
+    }
+}